@@ -2,17 +2,22 @@
 
 struct Input {
 	@location(0) data0: u32, // uv:2 z:10 y:10 x:10
-	@location(1) data1: u32, // tid:24 ao:8
+	@location(1) data1: u32, // tid:16 sky:4 light:4 ao:8
 	@builtin(vertex_index) vertex_index: u32,
+	@builtin(instance_index) instance_index: u32,
 }
 
 struct Output {
-	@builtin(position) pos: vec4f,
+	// the depth has to match the pre-pass' exactly, see depth.wgsl.
+	@invariant @builtin(position) pos: vec4f,
 	@location(1) norm: vec3f,
 	@location(2) col: vec3f,
 	@location(3) tex: vec2f,
 	@location(4) tid: u32,
 	@location(5) @interpolate(flat) qao: vec4f,
+	@location(6) light: f32,
+	@location(7) sky_light: f32,
+	@location(8) world_pos: vec3f,
 }
 
 fn unpack(in: Input, out: ptr<function, Output>) -> vec3f {
@@ -37,8 +42,11 @@ fn unpack(in: Input, out: ptr<function, Output>) -> vec3f {
 		vec2f(0.0, 0.0),
 	);
 
-	// 2^24 values, an 8K screen has just 2x more pixels, should be enough.
-	(*out).tid = in.data1 >> 8u;
+	// 2^16 values, should be enough.
+	(*out).tid = in.data1 >> 16u;
+
+	(*out).light = f32((in.data1 >> 8u) & 15u) / 15.0; // 4 bits
+	(*out).sky_light = f32((in.data1 >> 12u) & 15u) / 15.0; // 4 bits
 
 	(*out).tex = uvs[in.data0 >> 30u]; // 2 bits
 
@@ -49,18 +57,17 @@ fn unpack(in: Input, out: ptr<function, Output>) -> vec3f {
 	);
 }
 
-struct VertPushConsts {
-	chunk_pos: vec3i
-}
-
-var<push_constant> pushed: VertPushConsts;
+// indexed by the instance index, see `ChunkRenderer::set_chunk_draws`.
+@group(0) @binding(2)
+var<storage, read> chunk_origins: array<vec3i>;
 
 @vertex
 fn vs_main(in: Input) -> Output {
 	var out: Output;
 
 	let pos = unpack(in, &out);
-	out.pos = world_camera.view_proj * vec4f(vec3f(pushed.chunk_pos) + pos, 1.0);
+	out.world_pos = vec3f(chunk_origins[in.instance_index]) + pos;
+	out.pos = world_camera.view_proj * vec4f(out.world_pos, 1.0);
 
 	return out;
 }
@@ -73,19 +80,64 @@ var in_tex: texture_2d_array<f32>;
 @group(1) @binding(1)
 var in_samp: sampler;
 
+// offset from a texture to its current animation frame, 0 if it isn't animated.
+@group(1) @binding(2)
+var<storage, read> texture_frames: array<u32>;
+
+@group(2) @binding(0)
+var shadow_map: texture_depth_2d;
+
+@group(2) @binding(1)
+var shadow_samp: sampler_comparison;
+
 const is_black: bool = /*!const(is_black)*/;
 
+/// 1 where the sun is visible, 0 in shadow, filtered over 3x3 texels.
+fn sun_visibility(world_pos: vec3f) -> f32 {
+	let params = world_lighting.shadow_params;
+	if params.x == 0.0 {
+		return 1.0;
+	}
+
+	let light_pos = world_lighting.light_view_proj * vec4f(world_pos, 1.0);
+	let ndc = light_pos.xyz / light_pos.w;
+	let uv = ndc.xy * vec2f(0.5, -0.5) + 0.5;
+	// outside of the shadow map everything is lit.
+	if any(uv < vec2f(0.0)) || any(uv > vec2f(1.0)) || ndc.z > 1.0 {
+		return 1.0;
+	}
+
+	var visibility = 0.0;
+	for (var y = -1; y <= 1; y++) {
+		for (var x = -1; x <= 1; x++) {
+			let offset = vec2f(f32(x), f32(y)) * params.y;
+			visibility += textureSampleCompareLevel(shadow_map, shadow_samp, uv + offset, ndc.z);
+		}
+	}
+	return visibility / 9.0;
+}
+
 @fragment
 fn fs_main(in: Output) -> @location(0) vec4f {
 	if is_black {
 		return vec4f(0.0, 0.0, 0.0, 1.0);
 	} else {
-		var col = textureSample(in_tex, in_samp, in.tex, in.tid);
+		var col = textureSample(in_tex, in_samp, in.tex, in.tid + texture_frames[in.tid]);
 
 		let ao0 = mix(in.qao.x, in.qao.y, in.tex.x);
 		let ao1 = mix(in.qao.z, in.qao.w, in.tex.x);
-		let ao = mix(ao1, ao0, in.tex.y);
+		let ao = mix(1.0, mix(ao1, ao0, in.tex.y), world_lighting.ao_params.x);
+
+		// sky light dims when the sun goes below the horizon, and takes on the sun's color.
+		let shadow = mix(0.6, 1.0, sun_visibility(in.world_pos));
+		let sky = in.sky_light * daylight() * shadow * world_lighting.sun_color.rgb;
+		// the point lights are the emitters that are also in the baked block light, so the brighter of the two is used.
+		// the baked light stops at walls, so the point lights only reach where it does.
+		let point = select(vec3f(0.0), point_lighting(in.world_pos), in.light > 0.0);
+		let block = max(vec3f(in.light) * world_lighting.block_light_color.rgb, point);
+		let light = max(block, sky);
+		let brightness = mix(vec3f(0.05), vec3f(1.0), min(light, vec3f(1.0)));
 		
-		return col * ao;
+		return vec4f(apply_fog(col.rgb * ao * brightness, in.world_pos), col.a);
 	}
 }
@@ -0,0 +1,23 @@
+//!use world
+
+struct Input {
+	@location(0) data0: u32, // uv:2 z:10 y:10 x:10
+	@builtin(instance_index) instance_index: u32,
+}
+
+// same as in block.wgsl.
+@group(0) @binding(2)
+var<storage, read> chunk_origins: array<vec3i>;
+
+// the depth pre-pass, the main pass tests against its depth,
+// so the position has to be computed exactly like in block.wgsl.
+@vertex
+fn vs_main(in: Input) -> @invariant @builtin(position) vec4f {
+	let pos = vec3f(
+		f32(extractBits(i32(in.data0 >>  0u), 0u, 10u)) * 0.5,
+		f32(extractBits(i32(in.data0 >> 10u), 0u, 10u)) * 0.5,
+		f32(extractBits(i32(in.data0 >> 20u), 0u, 10u)) * 0.5,
+	);
+
+	return world_camera.view_proj * vec4f(vec3f(chunk_origins[in.instance_index]) + pos, 1.0);
+}
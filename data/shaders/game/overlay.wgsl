@@ -0,0 +1,43 @@
+//!use world
+
+struct PushConstants {
+	position: vec3<f32>,
+	// layer of the block texture, negative for none.
+	texture: i32,
+	color: vec4<f32>,
+}
+
+var<push_constant> push_constants: PushConstants;
+
+struct Input {
+	@location(0) position: vec3<f32>,
+	@location(1) uv: vec2<f32>,
+}
+
+struct Output {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) uv: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var in_tex: texture_2d_array<f32>;
+
+@group(1) @binding(1)
+var in_samp: sampler;
+
+@vertex
+fn vs_main(in: Input) -> Output {
+	var out: Output;
+	out.clip_position = world_camera.view_proj * vec4<f32>(push_constants.position + in.position, 1.0);
+	out.uv = in.uv;
+	return out;
+}
+
+@fragment
+fn fs_main(in: Output) -> @location(0) vec4<f32> {
+	var col = push_constants.color;
+	if push_constants.texture >= 0 {
+		col *= textureSample(in_tex, in_samp, in.uv, push_constants.texture);
+	}
+	return col;
+}
@@ -0,0 +1,23 @@
+//!use world
+
+struct Input {
+	@location(0) data0: u32, // uv:2 z:10 y:10 x:10
+	@builtin(instance_index) instance_index: u32,
+}
+
+// same as in block.wgsl.
+@group(0) @binding(2)
+var<storage, read> chunk_origins: array<vec3i>;
+
+// the depth is all that's needed, so there's no fragment shader.
+@vertex
+fn vs_main(in: Input) -> @builtin(position) vec4f {
+	// same as in block.wgsl.
+	let pos = vec3f(
+		f32(extractBits(i32(in.data0 >>  0u), 0u, 10u)) * 0.5,
+		f32(extractBits(i32(in.data0 >> 10u), 0u, 10u)) * 0.5,
+		f32(extractBits(i32(in.data0 >> 20u), 0u, 10u)) * 0.5,
+	);
+
+	return world_lighting.light_view_proj * vec4f(vec3f(chunk_origins[in.instance_index]) + pos, 1.0);
+}
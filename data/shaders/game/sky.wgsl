@@ -0,0 +1,45 @@
+//!use world
+
+struct Output {
+	@builtin(position) pos: vec4f,
+	@location(0) ndc: vec2f,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> Output {
+	var out: Output;
+
+	// covers the whole screen: (-1, -1), (3, -1), (-1, 3).
+	let ndc = vec2f(f32(vertex_index & 1u) * 4.0 - 1.0, f32(vertex_index >> 1u) * 4.0 - 1.0);
+	out.pos = vec4f(ndc, 0.0, 1.0);
+	out.ndc = ndc;
+
+	return out;
+}
+
+const zenith_color: vec3f = vec3f(0.20, 0.35, 0.70);
+const ground_color: vec3f = vec3f(0.25, 0.28, 0.32);
+
+@fragment
+fn fs_main(in: Output) -> @location(0) vec4f {
+	// the point on the far plane, minus the eye, is the view direction.
+	let far = world_camera.inv_view_proj * vec4f(in.ndc, 1.0, 1.0);
+	let dir = normalize(far.xyz / far.w - world_camera.position.xyz);
+
+	// the sun direction is the direction the light travels in.
+	let to_sun = -normalize(world_lighting.sun_direction.xyz);
+	let horizon_color = world_lighting.fog_color.rgb;
+
+	var col: vec3f;
+	if dir.y >= 0.0 {
+		col = mix(horizon_color, zenith_color, sqrt(dir.y));
+	} else {
+		col = mix(horizon_color, ground_color, sqrt(-dir.y));
+	}
+
+	// the sky brightens toward the sun.
+	let sun_dot = max(dot(dir, to_sun), 0.0);
+	col += world_lighting.sun_color.rgb * (pow(sun_dot, 8.0) * 0.25 + pow(sun_dot, 512.0) * 4.0);
+
+	return vec4f(mix(night_color, col, daylight()), 1.0);
+}
@@ -3,11 +3,13 @@
 struct Input {
 	@location(0) position: vec2<f32>,
 	@location(1) texcoord: vec2<f32>,
+	@location(2) color: vec4<f32>,
 }
 
 struct Output {
 	@builtin(position) clip_position: vec4<f32>,
-	@location(0) texcoord: vec2<f32>
+	@location(0) texcoord: vec2<f32>,
+	@location(1) color: vec4<f32>,
 }
 
 @vertex
@@ -15,6 +17,7 @@ fn vs_main(in: Input) -> Output {
 	var out: Output;
 	out.clip_position = vec4<f32>(in.position, 0.0, 1.0);
 	out.texcoord = in.texcoord;
+	out.color = in.color;
 	return out;
 }
 
@@ -26,6 +29,6 @@ var in_sampler: sampler;
 
 @fragment
 fn fs_main(in: Output) -> @location(0) vec4<f32> {
-	return textureSample(in_texture, in_sampler, in.texcoord);
+	return textureSample(in_texture, in_sampler, in.texcoord) * in.color;
 }
 
@@ -1,9 +1,25 @@
+struct PointLight {
+	position: vec4<f32>, // xyz, radius
+	color: vec4<f32>,
+}
+
 struct LightingUniform {
 	sun_direction: vec4<f32>,
+	sun_color: vec4<f32>,
+	fog_color: vec4<f32>,
+	fog_range: vec4<f32>, // start, end
+	light_view_proj: mat4x4<f32>,
+	shadow_params: vec4<f32>, // enabled, texel size
+	point_light_count: vec4<u32>,
+	ao_params: vec4<f32>, // strength
+	block_light_color: vec4<f32>,
+	point_lights: array<PointLight, 16>, // MAX_POINT_LIGHTS in renderer/chunk.rs
 }
 
 struct CameraUniform {
 	view_proj: mat4x4<f32>,
+	inv_view_proj: mat4x4<f32>,
+	position: vec4<f32>,
 }
 
 @group(0) @binding(0)
@@ -11,3 +27,35 @@ var<uniform> world_camera: CameraUniform;
 
 @group(0) @binding(1)
 var<uniform> world_lighting: LightingUniform;
+
+const night_color: vec3f = vec3f(0.01, 0.015, 0.04);
+
+/// 1 during the day, fades to 0 as the sun goes below the horizon.
+fn daylight() -> f32 {
+	return clamp(-world_lighting.sun_direction.y * 4.0, 0.0, 1.0);
+}
+
+/// the fog color is also the sky's horizon color, so that far away blocks blend into the sky.
+fn fog_color() -> vec3f {
+	return mix(night_color, world_lighting.fog_color.rgb, daylight());
+}
+
+fn apply_fog(col: vec3f, world_pos: vec3f) -> vec3f {
+	let distance = length(world_pos - world_camera.position.xyz);
+	let range = world_lighting.fog_range;
+	let fog = clamp((distance - range.x) / max(range.y - range.x, 0.001), 0.0, 1.0);
+	return mix(col, fog_color(), fog);
+}
+
+/// the brightest of the point lights at `world_pos`, they fade out linearly up to their radius,
+/// like the baked block light (but by the straight distance, so they're smoother).
+fn point_lighting(world_pos: vec3f) -> vec3f {
+	var brightest = vec3f(0.0);
+	for (var i = 0u; i < world_lighting.point_light_count.x; i++) {
+		let light = world_lighting.point_lights[i];
+		let distance = length(light.position.xyz - world_pos);
+		let falloff = clamp(1.0 - distance / light.position.w, 0.0, 1.0);
+		brightest = max(brightest, light.color.rgb * falloff);
+	}
+	return brightest;
+}
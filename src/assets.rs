@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+
+/// overrides the directory that the assets are loaded from.
+pub const DATA_DIR_VAR: &str = "VOXLE_DATA_DIR";
+const DEFAULT_DATA_DIR: &str = "data";
+
+lazy_static! {
+	static ref DATA_DIR: PathBuf = resolve_data_dir(std::env::var_os(DATA_DIR_VAR).map(PathBuf::from));
+}
+
+/// `data_dir_override` if it's set, otherwise `data` next to the executable if it exists,
+/// falling back to `data` in the working directory.
+pub fn resolve_data_dir(data_dir_override: Option<PathBuf>) -> PathBuf {
+	if let Some(data_dir) = data_dir_override {
+		return data_dir;
+	}
+
+	let next_to_exe = std::env::current_exe().ok()
+		.and_then(|exe| exe.parent().map(|dir| dir.join(DEFAULT_DATA_DIR)))
+		.filter(|dir| dir.is_dir());
+	next_to_exe.unwrap_or_else(|| PathBuf::from(DEFAULT_DATA_DIR))
+}
+
+/// the directory the assets are loaded from, resolved once, see [`resolve_data_dir`].
+pub fn data_dir() -> &'static Path {
+	&DATA_DIR
+}
+
+/// `relative` (e.g. `shaders/game/block.wgsl`) inside [`data_dir`].
+pub fn asset_path(relative: impl AsRef<Path>) -> PathBuf {
+	data_dir().join(relative)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn the_override_is_the_data_dir() {
+		let data_dir = PathBuf::from("/opt/voxle/assets");
+		assert_eq!(resolve_data_dir(Some(data_dir.clone())), data_dir);
+	}
+
+	#[test]
+	fn the_default_data_dir_is_named_data() {
+		// the test binary has no `data` next to it, so it's either that or the working directory one.
+		let data_dir = resolve_data_dir(None);
+		assert_eq!(data_dir.file_name(), Some(DEFAULT_DATA_DIR.as_ref()));
+		assert!(data_dir == Path::new(DEFAULT_DATA_DIR) || data_dir.is_dir());
+	}
+
+	#[test]
+	fn assets_are_inside_the_data_dir() {
+		let path = asset_path("shaders/game/block.wgsl");
+		assert_eq!(path, data_dir().join("shaders").join("game").join("block.wgsl"));
+		assert!(path.starts_with(data_dir()));
+	}
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use winit::keyboard::KeyCode;
+
+use crate::error::VoxleError;
+
+/// logical actions that gameplay code queries instead of physical keys,
+/// see [`crate::Input::action`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+	MoveForward,
+	MoveBackward,
+	MoveLeft,
+	MoveRight,
+	MoveUp,
+	MoveDown,
+	Sprint,
+	ToggleFlight,
+	Zoom,
+	ToggleSmoothMovement,
+	ReleaseCursor,
+	ToggleWireframe,
+	CycleAoMap,
+	/// saves the next frame to `screenshots/`, see [`crate::gfx::Gfx::capture_frame`].
+	Screenshot,
+	ToggleFullscreen,
+	ToggleThirdPerson,
+	/// selects the hotbar slot with the given index.
+	HotbarSlot(u16),
+}
+
+impl Action {
+	pub const HOTBAR_SLOT_COUNT: u16 = 9;
+
+	pub fn all() -> impl Iterator<Item = Action> {
+		[
+			Self::MoveForward,
+			Self::MoveBackward,
+			Self::MoveLeft,
+			Self::MoveRight,
+			Self::MoveUp,
+			Self::MoveDown,
+			Self::Sprint,
+			Self::ToggleFlight,
+			Self::Zoom,
+			Self::ToggleSmoothMovement,
+			Self::ReleaseCursor,
+			Self::ToggleWireframe,
+			Self::CycleAoMap,
+			Self::Screenshot,
+			Self::ToggleFullscreen,
+			Self::ToggleThirdPerson,
+		].into_iter().chain((0..Self::HOTBAR_SLOT_COUNT).map(Self::HotbarSlot))
+	}
+
+	/// the name used in the bindings config.
+	pub fn name(self) -> String {
+		match self {
+			Self::MoveForward => "move_forward".into(),
+			Self::MoveBackward => "move_backward".into(),
+			Self::MoveLeft => "move_left".into(),
+			Self::MoveRight => "move_right".into(),
+			Self::MoveUp => "move_up".into(),
+			Self::MoveDown => "move_down".into(),
+			Self::Sprint => "sprint".into(),
+			Self::ToggleFlight => "toggle_flight".into(),
+			Self::Zoom => "zoom".into(),
+			Self::ToggleSmoothMovement => "toggle_smooth_movement".into(),
+			Self::ReleaseCursor => "release_cursor".into(),
+			Self::ToggleWireframe => "toggle_wireframe".into(),
+			Self::CycleAoMap => "cycle_ao_map".into(),
+			Self::Screenshot => "screenshot".into(),
+			Self::ToggleFullscreen => "toggle_fullscreen".into(),
+			Self::ToggleThirdPerson => "toggle_third_person".into(),
+			Self::HotbarSlot(index) => format!("hotbar_slot_{}", index),
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::all().find(|action| action.name() == name)
+	}
+}
+
+macro_rules! key_code_names {
+	($($key:ident),* $(,)?) => {
+		fn key_code_from_name(name: &str) -> Option<KeyCode> {
+			match name {
+				$(stringify!($key) => Some(KeyCode::$key),)*
+				_ => None
+			}
+		}
+	};
+}
+
+// the keys that can be used in the config, named like the `KeyCode` variants.
+key_code_names!(
+	KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM,
+	KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+	Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+	Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+	F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+	ArrowUp, ArrowDown, ArrowLeft, ArrowRight,
+	ShiftLeft, ShiftRight, ControlLeft, ControlRight, AltLeft, AltRight,
+	Space, Enter, Escape, Tab, Backspace, CapsLock,
+	Minus, Equal, BracketLeft, BracketRight, Backslash, Semicolon, Quote,
+	Backquote, Comma, Period, Slash,
+	Insert, Delete, Home, End, PageUp, PageDown,
+);
+
+pub struct InputBindings {
+	keys: HashMap<Action, KeyCode>,
+}
+
+impl Default for InputBindings {
+	fn default() -> Self {
+		let mut keys = HashMap::from([
+			(Action::MoveForward, KeyCode::KeyW),
+			(Action::MoveBackward, KeyCode::KeyS),
+			(Action::MoveLeft, KeyCode::KeyA),
+			(Action::MoveRight, KeyCode::KeyD),
+			(Action::MoveUp, KeyCode::Space),
+			(Action::MoveDown, KeyCode::ShiftLeft),
+			(Action::Sprint, KeyCode::ControlLeft),
+			(Action::ToggleFlight, KeyCode::KeyF),
+			(Action::Zoom, KeyCode::KeyC),
+			(Action::ToggleSmoothMovement, KeyCode::KeyN),
+			(Action::ReleaseCursor, KeyCode::Escape),
+			(Action::ToggleWireframe, KeyCode::KeyG),
+			(Action::CycleAoMap, KeyCode::KeyL),
+			(Action::Screenshot, KeyCode::F2),
+			(Action::ToggleFullscreen, KeyCode::F11),
+			(Action::ToggleThirdPerson, KeyCode::F5),
+		]);
+
+		for (index, key) in [
+			KeyCode::Digit1,
+			KeyCode::Digit2,
+			KeyCode::Digit3,
+			KeyCode::Digit4,
+			KeyCode::Digit5,
+			KeyCode::Digit6,
+			KeyCode::Digit7,
+			KeyCode::Digit8,
+			KeyCode::Digit9,
+		].into_iter().enumerate() {
+			keys.insert(Action::HotbarSlot(index as u16), key);
+		}
+
+		Self { keys }
+	}
+}
+
+impl InputBindings {
+	/// the defaults, overridden by the config at `json_path` if it exists:
+	/// ```json
+	/// { "move_forward": "KeyW", "toggle_wireframe": "KeyG", "hotbar_slot_0": "Digit1" }
+	/// ```
+	pub fn load(json_path: &std::path::Path) -> Result<Self, VoxleError> {
+		match std::fs::read_to_string(json_path) {
+			Ok(source) => Self::parse(json_path, &source),
+			Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+			Err(error) => Err(VoxleError::io(json_path)(error)),
+		}
+	}
+
+	/// [`InputBindings::load`] with the config already read from `json_path`.
+	fn parse(json_path: &std::path::Path, source: &str) -> Result<Self, VoxleError> {
+		let mut bindings = Self::default();
+		let invalid = |reason: String| VoxleError::invalid_manifest(json_path, reason);
+
+		let config = json::parse(source)
+			.map_err(|error| invalid(format!("bindings should be correct json: {}", error)))?;
+		if !config.is_object() {
+			return Err(invalid("bindings should be an object".to_owned()));
+		}
+
+		for (name, key) in config.entries() {
+			let action = Action::from_name(name).ok_or_else(|| invalid(format!("unknown action in bindings: {}", name)))?;
+			let key = key.as_str().ok_or_else(|| invalid("bindings should map actions to key names".to_owned()))?;
+			let key = key_code_from_name(key).ok_or_else(|| invalid(format!("unknown key in bindings: {}", key)))?;
+			bindings.bind(action, key);
+		}
+
+		Ok(bindings)
+	}
+
+	pub fn bind(&mut self, action: Action, key: KeyCode) {
+		self.keys.insert(action, key);
+	}
+
+	pub fn key(&self, action: Action) -> Option<KeyCode> {
+		self.keys.get(&action).copied()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(source: &str) -> Result<InputBindings, VoxleError> {
+		InputBindings::parse(std::path::Path::new("bindings.json"), source)
+	}
+
+	#[test]
+	fn bindings_override_the_defaults() {
+		let bindings = parse(r#"{ "move_forward": "KeyI" }"#).unwrap();
+		assert_eq!(bindings.key(Action::MoveForward), Some(KeyCode::KeyI));
+		assert_eq!(bindings.key(Action::MoveBackward), InputBindings::default().key(Action::MoveBackward));
+	}
+
+	#[test]
+	fn malformed_bindings_are_invalid() {
+		for source in ["{", r#"["KeyW"]"#, r#"{ "fly": "KeyW" }"#, r#"{ "move_forward": 1 }"#, r#"{ "move_forward": "KeyNone" }"#] {
+			assert!(matches!(parse(source), Err(VoxleError::InvalidManifest { .. })), "{}", source);
+		}
+	}
+}
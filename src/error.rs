@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+/// an error while loading the game's assets.
+#[derive(Debug)]
+pub enum VoxleError {
+	/// `path` couldn't be read.
+	Io { path: PathBuf, error: std::io::Error },
+	/// there's no shader module at `path`, see [`crate::game::renderer::load_shader_module`].
+	ShaderNotFound(PathBuf),
+	/// the shader modules include each other, from the first to the last (which is the first again).
+	ShaderIncludeCycle(Vec<String>),
+	/// the shader references `/*!const(constant)*/`, but it wasn't provided.
+	MissingShaderConst { shader: String, constant: String },
+	/// the manifest (e.g. the block registry) at `path` was read, but its contents are wrong.
+	InvalidManifest { path: PathBuf, reason: String },
+	/// the image at `path` was read, but can't be used as a texture.
+	InvalidTexture { path: PathBuf, reason: String },
+}
+
+impl VoxleError {
+	pub fn io(path: impl Into<PathBuf>) -> impl FnOnce(std::io::Error) -> Self {
+		let path = path.into();
+		move |error| Self::Io { path, error }
+	}
+
+	pub fn invalid_manifest(path: impl Into<PathBuf>, reason: impl Into<String>) -> Self {
+		Self::InvalidManifest { path: path.into(), reason: reason.into() }
+	}
+
+	pub fn invalid_texture(path: impl Into<PathBuf>, reason: impl Into<String>) -> Self {
+		Self::InvalidTexture { path: path.into(), reason: reason.into() }
+	}
+}
+
+impl std::fmt::Display for VoxleError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "couldn't read {:?}: {}", path, error),
+			Self::ShaderNotFound(path) => write!(f, "shader not found: {:?}", path),
+			Self::ShaderIncludeCycle(modules) => write!(f, "shader include cycle: {}", modules.join(" -> ")),
+			Self::MissingShaderConst { shader, constant } => write!(f, "shader {} needs the constant {}", shader, constant),
+			Self::InvalidManifest { path, reason } => write!(f, "invalid manifest {:?}: {}", path, reason),
+			Self::InvalidTexture { path, reason } => write!(f, "invalid texture {:?}: {}", path, reason),
+		}
+	}
+}
+
+impl std::error::Error for VoxleError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io { error, .. } => Some(error),
+			_ => None,
+		}
+	}
+}
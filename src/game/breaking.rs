@@ -0,0 +1,73 @@
+use crate::math::*;
+
+/// how far the targeted block is broken, the block breaks once the button
+/// has been held on it for as long as its hardness.
+#[derive(Debug, Default)]
+pub struct BlockBreaking {
+	/// the global position of the block that's being broken.
+	target: Option<Vec3i32>,
+	/// how long the button has been held on `target`, in seconds.
+	time: f32,
+	hardness: f32,
+}
+
+impl BlockBreaking {
+	/// run every frame, with the targeted block (global) and its hardness, see [`super::registry::BlockInfo::hardness`].
+	/// the progress starts over when the target changes or `held` is released.
+	/// returns whether the block broke.
+	pub fn update(&mut self, target: Option<Vec3i32>, hardness: f32, held: bool, dt: f32) -> bool {
+		if !held || target.is_none() || target != self.target {
+			self.time = 0.0;
+		}
+		self.target = target;
+		self.hardness = hardness;
+
+		if !held || target.is_none() {
+			return false;
+		}
+
+		self.time += dt;
+		if self.time >= hardness {
+			self.time = 0.0;
+			true
+		} else {
+			false
+		}
+	}
+
+	/// the block that's being broken, and how far (0 to 1).
+	/// `None` for unbreakable blocks, so they don't crack.
+	pub fn progress(&self) -> Option<(Vec3i32, f32)> {
+		let target = self.target.filter(|_| self.time > 0.0 && self.hardness.is_finite())?;
+		Some((target, (self.time / self.hardness).min(1.0)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn progress_starts_over_on_a_new_target() {
+		let mut breaking = BlockBreaking::default();
+		let a = vec3(0, 0, 0);
+		let b = vec3(1, 0, 0);
+
+		assert!(!breaking.update(Some(a), 1.0, true, 0.5));
+		assert_eq!(breaking.progress(), Some((a, 0.5)));
+
+		assert!(!breaking.update(Some(b), 1.0, true, 0.25));
+		assert_eq!(breaking.progress(), Some((b, 0.25)));
+
+		assert!(!breaking.update(Some(b), 1.0, true, 0.5));
+		assert!(breaking.update(Some(b), 1.0, true, 0.25));
+	}
+
+	#[test]
+	fn unbreakable_blocks_dont_crack() {
+		let mut breaking = BlockBreaking::default();
+
+		assert!(!breaking.update(Some(vec3(0, 0, 0)), f32::INFINITY, true, 100.0));
+		assert_eq!(breaking.progress(), None);
+	}
+}
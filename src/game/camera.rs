@@ -1,54 +1,238 @@
-use winit::keyboard::KeyCode;
-use crate::{UpdateContext, LoadContext, math::*};
+use std::collections::HashMap;
 
-use super::renderer;
+use crate::{UpdateContext, LoadContext, math::*, bindings::Action};
+
+use super::{renderer, physics, chunk, registry::BlockRegistry};
+
+const GRAVITY: f32 = 28.0;
+const JUMP_SPEED: f32 = 9.0;
+const PLAYER_WIDTH: f32 = 0.6;
+const PLAYER_HEIGHT: f32 = 1.8;
+/// height of the camera above the player's feet.
+const PLAYER_EYE_HEIGHT: f32 = 1.62;
+
+/// field of view limits, in degrees.
+const MIN_FOVY: f32 = 10.0;
+const MAX_FOVY: f32 = 110.0;
+/// how quickly the field of view approaches its target, per second.
+const ZOOM_RATE: f32 = 12.0;
+/// degrees per scrolled line.
+const ZOOM_SCROLL_STEP: f32 = 5.0;
+/// radians turned per pixel of mouse motion, at a sensitivity of 1.
+const LOOK_RADIANS_PER_PIXEL: f32 = 0.002;
+/// how far from a block the eye stays when pulled in front of it in third person.
+const THIRD_PERSON_CLEARANCE: f32 = 0.2;
+
+fn player_aabb(eye: Vec3f32) -> physics::Aabb {
+	physics::Aabb::from_feet(eye - vec3(0.0, PLAYER_EYE_HEIGHT, 0.0), PLAYER_WIDTH, PLAYER_HEIGHT)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlightMode {
+	/// flies freely, up and down with the move up/down keys.
+	Fly,
+	/// applies gravity and collides with the blocks, move up jumps.
+	Walk,
+}
+
+impl FlightMode {
+	pub fn toggled(self) -> Self {
+		match self {
+			Self::Fly => Self::Walk,
+			Self::Walk => Self::Fly,
+		}
+	}
+}
+
+/// the parts of the controller that can be configured, see [`super::config::GameConfig`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraSettings {
+	/// in blocks per second, when not sprinting.
+	pub speed: f32,
+	pub sensitivity: f32,
+	/// field of view when not zooming, in degrees.
+	pub fovy: f32,
+	/// how far behind the player the eye is in third person, in blocks.
+	pub third_person_distance: f32,
+	/// renders with an orthographic projection this many blocks high instead, see [`renderer::chunk::Projection`].
+	pub orthographic_height: Option<f32>,
+}
+
+impl Default for CameraSettings {
+	fn default() -> Self {
+		Self { speed: 10.0, sensitivity: 1.0, fovy: 60.0, third_person_distance: 4.0, orthographic_height: None }
+	}
+}
 
 #[derive(Debug)]
 pub struct CameraController {
-	max_speed: f32,
-	sensitivity: f32,
+	settings: CameraSettings,
 	capturing: bool,
 	velocity: Vec3f32,
 	smooth: bool,
 	time_since_last_forward_press: f32,
 	sprinting_double_press: bool,
 	is_sprinting: bool,
-	acceleration: f32
+	acceleration: f32,
+	flight_mode: FlightMode,
+	on_ground: bool,
+	/// field of view when zooming, in degrees, can be changed by scrolling.
+	zoom_fovy: f32,
+	third_person: bool,
+	/// the camera's position at the last two fixed steps, it's drawn in between them, see [`CameraController::step`].
+	previous_position: Vec3f32,
+	position: Vec3f32,
 }
 
 impl CameraController {
-	pub fn new(speed: f32, sensitivity: f32) -> Self {
+	pub fn new(settings: CameraSettings, position: Vec3f32) -> Self {
 		Self {
-			max_speed: speed,
-			sensitivity,
+			settings,
 			capturing: false,
 			velocity: Vector([0.0, 0.0, 0.0]),
 			smooth: true,
 			time_since_last_forward_press: f32::INFINITY,
 			sprinting_double_press: false,
 			is_sprinting: false,
-			acceleration: 50.0
+			acceleration: 50.0,
+			flight_mode: FlightMode::Fly,
+			on_ground: false,
+			zoom_fovy: 20.0,
+			third_person: false,
+			previous_position: position,
+			position,
 		}
 	}
 
+	pub fn set_settings(&mut self, settings: CameraSettings) {
+		self.settings = settings;
+	}
+
+	pub fn flight_mode(&self) -> FlightMode {
+		self.flight_mode
+	}
+
+	/// the velocity is kept, so that toggling mid-air doesn't stop the camera.
+	fn toggle_flight_mode(&mut self) {
+		self.flight_mode = self.flight_mode.toggled();
+		self.on_ground = false;
+	}
+
+	pub fn third_person(&self) -> bool {
+		self.third_person
+	}
+
 	pub fn load(&mut self, ctx: &mut LoadContext) {
 		ctx.window_mut().capture_cursor(true);
 		self.capturing = true;
 	}
 
-	pub fn update_camera(&mut self, ctx: &mut UpdateContext, camera: &mut renderer::chunk::Camera, dt: f32) -> bool {
+	/// moves with the movement keys, in [`crate::state::State::fixed_update`], so that
+	/// walking is the same at any frame rate. `chunks` are collided with when walking.
+	pub fn step(
+		&mut self,
+		ctx: &UpdateContext,
+		yaw: f32,
+		chunks: &HashMap<Vec3i32, chunk::Chunk>,
+		registry: &BlockRegistry
+	) {
 		let delta = {
 			let mut res = glm::vec3(0.0, 0.0, 0.0);
-			if ctx.input().key(KeyCode::KeyD).held() { res.x += 1.0; }
-			if ctx.input().key(KeyCode::KeyA).held() { res.x -= 1.0; }
-			if ctx.input().key(KeyCode::KeyW).held() { res.z += 1.0; }
-			if ctx.input().key(KeyCode::KeyS).held() { res.z -= 1.0; }
-			if ctx.input().key(KeyCode::Space).held() { res.y += 1.0; }
-			if ctx.input().key(KeyCode::ShiftLeft).held() { res.y -= 1.0; }
-			res
+			if ctx.input().action(Action::MoveRight).held() { res.x += 1.0; }
+			if ctx.input().action(Action::MoveLeft).held() { res.x -= 1.0; }
+			if ctx.input().action(Action::MoveForward).held() { res.z += 1.0; }
+			if ctx.input().action(Action::MoveBackward).held() { res.z -= 1.0; }
+			if ctx.input().action(Action::MoveUp).held() { res.y += 1.0; }
+			if ctx.input().action(Action::MoveDown).held() { res.y -= 1.0; }
+			if ctx.input().gamepad_button(gilrs::Button::South).held() { res.y += 1.0; }
+			if ctx.input().gamepad_button(gilrs::Button::North).held() { res.y -= 1.0; }
+			let stick = ctx.input().gamepad_movement();
+			res.x += stick.x;
+			res.z += stick.y;
+			vec3(res.x, res.y, res.z)
 		};
+		self.move_by(delta, yaw, ctx.dt, chunks, registry);
+	}
+
+	/// `delta` is the movement input, x to the right, y up and z forward.
+	fn move_by(
+		&mut self,
+		delta: Vec3f32,
+		yaw: f32,
+		dt: f32,
+		chunks: &HashMap<Vec3i32, chunk::Chunk>,
+		registry: &BlockRegistry
+	) {
+		self.previous_position = self.position;
+
+		let front = Vector([yaw.cos(), 0.0, yaw.sin()]);
+		let right = front.cross(Vector([0.0, 1.0, 0.0]));
+
+		let mut movement = front * delta.z + right * delta.x + Vector([0.0, delta.y, 0.0]);
+
+		// when walking, the vertical velocity is only affected by gravity and jumping.
+		let fall_speed = self.velocity.y;
+		if self.flight_mode == FlightMode::Walk {
+			movement.0[1] = 0.0;
+			self.velocity.0[1] = 0.0;
+		}
 
-		if ctx.input().key(KeyCode::KeyW).just_pressed() {
+		if self.smooth {
+			let mul = if self.is_sprinting { 5.0 } else { 1.0 };
+
+			self.velocity += movement * mul * self.acceleration * dt;
+
+			if self.velocity.mag_squared() > mul * self.settings.speed * mul * self.settings.speed {
+				self.velocity = self.velocity.normalized() * self.settings.speed * mul;
+			}
+
+			self.velocity = self.velocity.lerp((0.0, 0.0, 0.0).vector(), (self.settings.speed * 0.5 * dt).min(1.0));
+			self.velocity = self.velocity.lerp(Vector([0.0, 0.0, 0.0]), (self.settings.speed * 0.5 * dt).min(1.0));
+			
+			if self.velocity.mag_squared() <= 0.001 {
+				self.velocity = Vector([0.0, 0.0, 0.0]);
+			}
+		} else {
+			self.velocity = movement * self.settings.speed;
+		}
+
+		if self.flight_mode == FlightMode::Walk {
+			self.velocity.0[1] = if self.on_ground && delta.y > 0.0 {
+				JUMP_SPEED
+			} else {
+				fall_speed - GRAVITY * dt
+			};
+
+			let displacement = self.velocity * dt;
+			let moved = physics::sweep_aabb_in_chunks(player_aabb(self.position), displacement, chunks, registry);
+			for axis in 0..3 {
+				if moved.0[axis] != displacement.0[axis] {
+					self.velocity.0[axis] = 0.0;
+				}
+			}
+			self.on_ground = displacement.y < 0.0 && moved.y > displacement.y;
+			self.position += moved;
+		} else {
+			self.position += self.velocity * dt;
+		}
+	}
+
+	/// where the camera is drawn, `alpha` of the way from the previous fixed step to the last one.
+	fn interpolated_position(&self, alpha: f32) -> Vec3f32 {
+		self.previous_position.lerp(self.position, alpha)
+	}
+
+	/// looks around and zooms, and places the camera between the last fixed steps, see [`CameraController::step`].
+	/// `chunks` pull the eye in in third person.
+	pub fn update_camera(
+		&mut self,
+		ctx: &mut UpdateContext,
+		camera: &mut renderer::chunk::Camera,
+		dt: f32,
+		chunks: &HashMap<Vec3i32, chunk::Chunk>,
+		registry: &BlockRegistry
+	) -> bool {
+		if ctx.input().action(Action::MoveForward).just_pressed() {
 			if self.time_since_last_forward_press <= 0.2 {
 				self.is_sprinting = true;
 				self.sprinting_double_press = true;
@@ -56,7 +240,7 @@ impl CameraController {
 			}
 		}
 
-		if ctx.input().key(KeyCode::KeyW).just_released() {
+		if ctx.input().action(Action::MoveForward).just_released() {
 			self.time_since_last_forward_press = 0.0;
 			self.sprinting_double_press = false;
 			self.is_sprinting = false;
@@ -64,21 +248,29 @@ impl CameraController {
 
 		self.time_since_last_forward_press += dt;
 
-		if ctx.input().key(KeyCode::ControlLeft).held() {
+		if ctx.input().action(Action::Sprint).held() {
 			self.is_sprinting = true;
 		}
 
-		if ctx.input().key(KeyCode::ControlLeft).just_released() {
+		if ctx.input().action(Action::Sprint).just_released() {
 			self.is_sprinting = false || self.sprinting_double_press;
 		}
 
-		if ctx.input().key(KeyCode::KeyN).just_pressed() {
+		if ctx.input().action(Action::ToggleSmoothMovement).just_pressed() {
 			self.smooth = !self.smooth;
 		}
 
+		if ctx.input().action(Action::ToggleThirdPerson).just_pressed() {
+			self.third_person = !self.third_person;
+		}
+
+		if ctx.input().action(Action::ToggleFlight).just_pressed() {
+			self.toggle_flight_mode();
+		}
+
 		let last_capturing = self.capturing;
 
-		if self.capturing && ctx.window().input().key(KeyCode::Escape).just_pressed() {
+		if self.capturing && ctx.window().input().action(Action::ReleaseCursor).just_pressed() {
 			ctx.window_mut().capture_cursor(false);
 			self.capturing = false;
 		}
@@ -88,60 +280,174 @@ impl CameraController {
 			self.capturing = true;
 		}
 		
+		camera.position = self.interpolated_position(ctx.fixed_alpha);
+
 		{
-			// let (yaw_sin, yaw_cos) = camera.yaw.sin_cos();
-			// let forward = glm::vec3(-yaw_sin, 0.0, yaw_cos).normalize();
-			// let right = glm::vec3(-yaw_cos, 0.0, -yaw_sin).normalize();
+			let zooming = ctx.input().action(Action::Zoom).held();
+			if zooming {
+				self.zoom_fovy = (self.zoom_fovy - ctx.input().scroll_delta() * ZOOM_SCROLL_STEP).clamp(MIN_FOVY, self.settings.fovy);
+			}
+			let target = if zooming { self.zoom_fovy } else { self.settings.fovy };
+			camera.projection = match (self.settings.orthographic_height, camera.projection) {
+				// an orthographic camera can't zoom by changing the field of view.
+				(Some(height), _) => renderer::chunk::Projection::Orthographic { height },
+				(None, renderer::chunk::Projection::Perspective { fovy }) =>
+					renderer::chunk::Projection::Perspective { fovy: approach_fovy(fovy, target, dt) },
+				(None, renderer::chunk::Projection::Orthographic { .. }) =>
+					renderer::chunk::Projection::Perspective { fovy: target },
+			};
+		}
+		
+		if self.capturing || ctx.input().button(0).held() {
+			// the mouse delta is already the motion during this frame, so it isn't scaled by `dt`.
+			let (yaw, pitch) = look_rotation(ctx.window().input.mouse_delta(), self.settings.sensitivity);
+			camera.yaw += yaw;
+			camera.pitch += pitch;
 
-			let front = Vector([
-				camera.yaw.cos(), // * camera.pitch.cos(),
-				0.0, // camera.pitch.sin(),
-				camera.yaw.sin(), // * camera.pitch.cos(),
-			]);
+			// Keep the camera's angle from going too high/low.
+			let safe_angle: f32 = 3.141592 * 0.5 - 0.01; // glm::epsilon::<f32>()
+	
+			camera.pitch = camera.pitch.clamp(-safe_angle, safe_angle);
+		}
 
-			let right = front.cross(Vector([0.0, 1.0, 0.0]));
+		// the eye is placed after moving and looking around, so that it doesn't lag a frame behind.
+		camera.eye_offset = if self.third_person {
+			let direction = camera.direction();
+			-direction * third_person_distance(camera.position, direction, self.settings.third_person_distance, chunks, registry)
+		} else {
+			Vector([0.0, 0.0, 0.0])
+		};
 
-			let movement = front * delta.z + right * delta.x + Vector([0.0, delta.y, 0.0]);
+		last_capturing
+	}
+}
 
-			if self.smooth {
-				let mul = if self.is_sprinting { 5.0 } else { 1.0 };
+/// moves `fovy` towards `target` over `dt` seconds.
+fn approach_fovy(fovy: f32, target: f32, dt: f32) -> f32 {
+	// exponential decay, so that the transition takes the same time at any frame rate.
+	let t = 1.0 - (-ZOOM_RATE * dt).exp();
+	(fovy + (target - fovy) * t).clamp(MIN_FOVY, MAX_FOVY)
+}
 
-				self.velocity += movement * mul * self.acceleration * dt;
+/// the yaw and pitch change for `mouse_delta` (in pixels, y going down).
+fn look_rotation(mouse_delta: Vec2f32, sensitivity: f32) -> (f32, f32) {
+	let scale = sensitivity * LOOK_RADIANS_PER_PIXEL;
+	(mouse_delta.x * scale, -mouse_delta.y * scale)
+}
 
-				if self.velocity.mag_squared() > mul * self.max_speed * mul * self.max_speed {
-					self.velocity = self.velocity.normalized() * self.max_speed * mul;
-				}
+/// how far back along `-direction` from `focus` the eye can be, up to `max_distance`,
+/// pulled in so that no block is between the eye and `focus`.
+fn third_person_distance(
+	focus: Vec3f32,
+	direction: Vec3f32,
+	max_distance: f32,
+	chunks: &HashMap<Vec3i32, chunk::Chunk>,
+	registry: &BlockRegistry
+) -> f32 {
+	let reach = max_distance + THIRD_PERSON_CLEARANCE;
+	match physics::raycast(focus, -direction, reach, |global| physics::is_solid_at(chunks, registry, global)) {
+		Some(distance) => (distance - THIRD_PERSON_CLEARANCE).clamp(0.0, max_distance),
+		None => max_distance,
+	}
+}
 
-				self.velocity = self.velocity.lerp((0.0, 0.0, 0.0).vector(), (self.max_speed * 0.5 * dt).min(1.0));
-				self.velocity = self.velocity.lerp(Vector([0.0, 0.0, 0.0]), (self.max_speed * 0.5 * dt).min(1.0));
-				
-				if self.velocity.mag_squared() <= 0.001 {
-					self.velocity = Vector([0.0, 0.0, 0.0]);
-				}
-			} else {
-				self.velocity = movement * self.max_speed;
-			}
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-			camera.position += self.velocity * dt;
+	#[test]
+	fn zoom_converges_at_any_frame_rate() {
+		let (mut slow, mut fast) = (60.0, 60.0);
+		for _ in 0..30 {
+			slow = approach_fovy(slow, 20.0, 1.0 / 30.0);
 		}
-		
-		// camera.fovy += ctx.window().input.scroll_diff() * self.speed * self.sensitivity * dt;
-		
-		if self.capturing || ctx.input().button(0).held() {
-			let (
-				rotate_horizontal,
-				rotate_vertical
-			) = ctx.window().input.mouse_delta().into();
+		for _ in 0..240 {
+			fast = approach_fovy(fast, 20.0, 1.0 / 240.0);
+		}
+		// a second later, both are close to the target, and to each other.
+		assert!((slow - 20.0).abs() < 0.01, "{slow}");
+		assert!((slow - fast).abs() < 1e-3, "{slow} != {fast}");
 
-			camera.yaw += rotate_horizontal * self.sensitivity * dt;
-			camera.pitch += -rotate_vertical * self.sensitivity * dt;
+		// it only ever moves towards the target.
+		let mut fovy = 20.0;
+		for _ in 0..10 {
+			let next = approach_fovy(fovy, 60.0, 0.016);
+			assert!(next > fovy && next <= 60.0);
+			fovy = next;
+		}
+		assert_eq!(approach_fovy(60.0, 200.0, 1.0), MAX_FOVY);
+	}
 
-			// Keep the camera's angle from going too high/low.
-			let safe_angle: f32 = 3.141592 * 0.5 - 0.01; // glm::epsilon::<f32>()
-	
-			camera.pitch = camera.pitch.clamp(-safe_angle, safe_angle);
+	#[test]
+	fn toggling_flight_keeps_the_velocity() {
+		let mut controller = CameraController::new(CameraSettings::default(), vec3(0.0, 0.0, 0.0));
+		controller.velocity = vec3(1.0, -2.0, 3.0);
+		controller.toggle_flight_mode();
+		assert_eq!(controller.flight_mode(), FlightMode::Walk);
+		assert_eq!(controller.velocity, vec3(1.0, -2.0, 3.0));
+
+		controller.on_ground = true;
+		controller.toggle_flight_mode();
+		assert_eq!(controller.flight_mode(), FlightMode::Fly);
+		assert!(!controller.on_ground);
+		assert_eq!(controller.velocity, vec3(1.0, -2.0, 3.0));
+	}
+
+	#[test]
+	fn the_camera_is_drawn_between_fixed_steps() {
+		let mut controller = CameraController::new(CameraSettings::default(), vec3(1.0, 2.0, 3.0));
+		controller.smooth = false;
+		assert_eq!(controller.interpolated_position(0.5), vec3(1.0, 2.0, 3.0));
+
+		// forward, along x at a yaw of 0.
+		let (chunks, registry) = (HashMap::new(), BlockRegistry::new());
+		controller.move_by(vec3(0.0, 0.0, 1.0), 0.0, 0.1, &chunks, &registry);
+		assert_eq!(controller.position, vec3(2.0, 2.0, 3.0));
+		assert_eq!(controller.interpolated_position(0.0), vec3(1.0, 2.0, 3.0));
+		assert_eq!(controller.interpolated_position(0.5), vec3(1.5, 2.0, 3.0));
+		assert_eq!(controller.interpolated_position(1.0), vec3(2.0, 2.0, 3.0));
+
+		// it only moves in the steps, however many frames are drawn in between.
+		controller.move_by(vec3(0.0, 0.0, 0.0), 0.0, 0.1, &chunks, &registry);
+		assert_eq!(controller.interpolated_position(0.25), vec3(2.0, 2.0, 3.0));
+	}
+
+	#[test]
+	fn third_person_eye_is_pulled_in_front_of_walls() {
+		let registry = crate::game::registry::tests::registry();
+		let mut chunks = HashMap::new();
+		for position in [vec3(-1, 0, 0), vec3(0, 0, 0)] {
+			chunks.insert(position, chunk::Chunk::new(position, chunk::ChunkData::new()));
 		}
+		let distance = |chunks: &HashMap<_, _>| third_person_distance(vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), 4.0, chunks, &registry);
+		assert_eq!(distance(&chunks), 4.0);
 
-		last_capturing
+		// the eye is behind the focus, so the wall is at -x. its face is at -2.5, blocks are centered on integers.
+		let stone = chunk::Block { id: chunk::BlockId::Stone as u16, state: 0 };
+		let wall = vec3(-3, 0, 0);
+		chunks.get_mut(&chunk::block_global_to_chunk(wall)).unwrap().set_block(chunk::block_global_to_block_local(wall), stone);
+		assert!((distance(&chunks) - (2.5 - THIRD_PERSON_CLEARANCE)).abs() < 1e-5, "{}", distance(&chunks));
+
+		// never in front of the focus.
+		let wall = vec3(-1, 0, 0);
+		chunks.get_mut(&chunk::block_global_to_chunk(wall)).unwrap().set_block(chunk::block_global_to_block_local(wall), stone);
+		assert!((distance(&chunks) - (0.5 - THIRD_PERSON_CLEARANCE)).abs() < 1e-5, "{}", distance(&chunks));
+		chunks.get_mut(&vec3(0, 0, 0)).unwrap().set_block(vec3(0, 0, 0), stone);
+		assert_eq!(distance(&chunks), 0.0);
+	}
+
+	#[test]
+	fn looking_around_doesnt_depend_on_the_frame_rate() {
+		// the same mouse motion, over a second at 30 and at 240 fps.
+		let motion = vec2(300.0, -120.0);
+		let turn = |frames: usize| (0..frames).fold((0.0, 0.0), |(yaw, pitch), _| {
+			let (dyaw, dpitch) = look_rotation(motion / frames as f32, 1.5);
+			(yaw + dyaw, pitch + dpitch)
+		});
+		let (slow, fast) = (turn(30), turn(240));
+		assert!((slow.0 - fast.0).abs() < 1e-5 && (slow.1 - fast.1).abs() < 1e-5, "{:?} != {:?}", slow, fast);
+		assert!((slow.0 - 300.0 * 1.5 * LOOK_RADIANS_PER_PIXEL).abs() < 1e-5);
+		// moving the mouse up looks up.
+		assert!(slow.1 > 0.0);
 	}
 }
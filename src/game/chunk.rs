@@ -1,15 +1,28 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{gfx, math::*};
 
-use super::{texture::{TextureId, LoadedTextures}, Dir};
+use super::{registry::{BlockRegistry, BlockInfo}, Dir};
 
+/// can be changed to any (not necessarily equal) powers of two, as long as the lod mesh
+/// can be built and the mesh vertices fit, so the largest legal size is 128,
+/// see [`crate::game::renderer::chunk::MAX_CHUNK_SIZE`].
+/// e.g. 16³ chunks are cheaper to remesh, but there are 8 times as many.
 pub const CHUNK_SIZE: Vec3<usize> = Vector([32, 32, 32]);
 
 static_assertions::const_assert!(CHUNK_SIZE.0[0].is_power_of_two());
 static_assertions::const_assert!(CHUNK_SIZE.0[1].is_power_of_two());
 static_assertions::const_assert!(CHUNK_SIZE.0[2].is_power_of_two());
+static_assertions::const_assert!(CHUNK_SIZE.0[0] * 2 <= super::renderer::chunk::I10_MAX as usize);
+static_assertions::const_assert!(CHUNK_SIZE.0[1] * 2 <= super::renderer::chunk::I10_MAX as usize);
+static_assertions::const_assert!(CHUNK_SIZE.0[2] * 2 <= super::renderer::chunk::I10_MAX as usize);
 
+/// the level of [`Chunk::lod_mesh`], see [`ChunkData::generate_mesh_lod`].
+pub const LOD_LEVEL: u32 = 1;
+
+static_assertions::const_assert!(CHUNK_SIZE.0[0] >= 1 << LOD_LEVEL);
+static_assertions::const_assert!(CHUNK_SIZE.0[1] >= 1 << LOD_LEVEL);
+static_assertions::const_assert!(CHUNK_SIZE.0[2] >= 1 << LOD_LEVEL);
 pub const CHUNK_BLOCK_COUNT: usize = CHUNK_SIZE.0[0] * CHUNK_SIZE.0[1] * CHUNK_SIZE.0[2];
 
 /// world -> chunk position (in chunks)
@@ -32,13 +45,30 @@ pub fn block_global_to_block_local(global: Vec3i32) -> Vec3i32 {
 	global.zip_map(CHUNK_SIZE, |global, chunk| num::integer::mod_floor(global, chunk as i32))
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(packed)]
 pub struct Block {
 	pub id: u16,
 	pub state: u16
 }
 
+/// as an `(id, state)` tuple, the fields can't be borrowed since the struct is packed.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Block {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let Block { id, state } = *self;
+		serde::Serialize::serialize(&(id, state), serializer)
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Block {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let (id, state) = <(u16, u16) as serde::Deserialize>::deserialize(deserializer)?;
+		Ok(Block { id, state })
+	}
+}
+
 impl Default for Block {
 	fn default() -> Self {
 		Self {
@@ -48,9 +78,60 @@ impl Default for Block {
 	}
 }
 
+/// stored in the lower 3 bits of [`Block::state`], the default state is upright.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRotation {
+	/// the world direction the block's top side is facing.
+	pub up: Dir
+}
+
+impl BlockRotation {
+	const UP_DIRS: [Dir; 6] = [Dir::PY, Dir::NY, Dir::PX, Dir::NX, Dir::PZ, Dir::NZ];
+	const STATE_MASK: u16 = 0b111;
+
+	pub const fn new(up: Dir) -> Self {
+		Self { up }
+	}
+
+	pub fn from_state(state: u16) -> Self {
+		Self {
+			up: Self::UP_DIRS.get((state & Self::STATE_MASK) as usize).copied().unwrap_or(Dir::PY)
+		}
+	}
+
+	pub fn to_state(self) -> u16 {
+		Self::UP_DIRS.iter().position(|dir| *dir == self.up).unwrap() as u16
+	}
+
+	/// the world direction that the (unrotated) `side` of the block faces.
+	pub fn rotate(self, side: Dir) -> Dir {
+		use Dir::*;
+		match (self.up, side) {
+			(PY, side) => side,
+			// 180° around x
+			(NY, PY) => NY, (NY, NY) => PY,
+			(NY, PZ) => NZ, (NY, NZ) => PZ,
+			// around z
+			(PX, PY) => PX, (PX, PX) => NY, (PX, NY) => NX, (PX, NX) => PY,
+			(NX, PY) => NX, (NX, NX) => NY, (NX, NY) => PX, (NX, PX) => PY,
+			// around x
+			(PZ, PY) => PZ, (PZ, PZ) => NY, (PZ, NY) => NZ, (PZ, NZ) => PY,
+			(NZ, PY) => NZ, (NZ, NZ) => NY, (NZ, NY) => PZ, (NZ, PZ) => PY,
+			// the rotation axis stays the same
+			(_, side) => side,
+		}
+	}
+
+	/// which side of the (unrotated) block faces the world direction `dir`.
+	pub fn local_side(self, dir: Dir) -> Dir {
+		*Dir::all().iter().find(|side| self.rotate(**side) == dir).unwrap()
+	}
+}
+
+/// ids of the builtin blocks, these have to match the block registry.
 #[allow(dead_code)]
 #[non_exhaustive]
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum BlockId {
 	Air = 0,
 	Stone = 1,
@@ -58,42 +139,51 @@ pub enum BlockId {
 	Dirt = 3,
 	Snow = 4,
 	SnowGrass = 5,
-	_EndId = 6,
+	Water = 6,
+	Glass = 7,
+	StoneSlab = 8,
+	Lamp = 9,
+	Log = 10,
+	Leaves = 11,
+	Sand = 12,
+	CoalOre = 13,
+	IronOre = 14,
+	GoldOre = 15,
+	Bedrock = 16,
 }
 
-
 impl BlockId {
-	fn from_u16(id: u16) -> Option<Self> {
-		if id >= Self::_EndId as u16 {
-			None
-		} else {
-			Some(unsafe {
-				std::mem::transmute(id as u8)
-			})
-		}
+	pub fn all() -> &'static [BlockId] {
+		&[
+			Self::Air, Self::Stone, Self::Grass, Self::Dirt, Self::Snow,
+			Self::SnowGrass, Self::Water, Self::Glass, Self::StoneSlab, Self::Lamp,
+			Self::Log, Self::Leaves, Self::Sand,
+			Self::CoalOre, Self::IronOre, Self::GoldOre, Self::Bedrock,
+		]
 	}
 
-	fn is_solid(self) -> bool {
+	/// the name in the block registry.
+	pub fn name(self) -> &'static str {
 		match self {
-    	BlockId::Air => false,
-			BlockId::Stone => true,
-			BlockId::Grass => true,
-			BlockId::Dirt => true,
-			BlockId::SnowGrass => true,
-			BlockId::Snow => true,
-			_ => false,
-		}
-	}
-
-	// fn textures(self) -> Option<BlockTextures> {
-	// 	match self {
-  //   	BlockId::Air => None,
-	// 		BlockId::Stone => Some(BlockTextures::same(TextureId(3))),
-	// 		BlockId::Grass => Some(BlockTextures::cylinder(TextureId(1), TextureId(2), TextureId(0))),
-	// 		BlockId::Dirt => Some(BlockTextures::same(TextureId(2))),
-	// 		_ => None,
-	// 	}
-	// }
+			Self::Air => "air",
+			Self::Stone => "stone",
+			Self::Grass => "grass",
+			Self::Dirt => "dirt",
+			Self::Snow => "snow",
+			Self::SnowGrass => "snow_grass",
+			Self::Water => "water",
+			Self::Glass => "glass",
+			Self::StoneSlab => "stone_slab",
+			Self::Lamp => "lamp",
+			Self::Log => "log",
+			Self::Leaves => "leaves",
+			Self::Sand => "sand",
+			Self::CoalOre => "coal_ore",
+			Self::IronOre => "iron_ore",
+			Self::GoldOre => "gold_ore",
+			Self::Bedrock => "bedrock",
+		}
+	}
 }
 
 pub const CUBE_VERTICES: [[f32; 3]; 8] = [
@@ -108,6 +198,70 @@ pub const CUBE_VERTICES: [[f32; 3]; 8] = [
 	[-0.5, -0.5,  0.5], // 7
 ];
 
+pub const SLAB_BOTTOM_VERTICES: [[f32; 3]; 8] = [
+	// X  /  Y  /  Z //
+	[ 0.5,  0.0,  0.5], // 0
+	[ 0.5,  0.0, -0.5], // 1
+	[-0.5,  0.0, -0.5], // 2
+	[-0.5,  0.0,  0.5], // 3
+	[ 0.5, -0.5,  0.5], // 4
+	[ 0.5, -0.5, -0.5], // 5
+	[-0.5, -0.5, -0.5], // 6
+	[-0.5, -0.5,  0.5], // 7
+];
+
+pub const SLAB_TOP_VERTICES: [[f32; 3]; 8] = [
+	// X  /  Y  /  Z //
+	[ 0.5,  0.5,  0.5], // 0
+	[ 0.5,  0.5, -0.5], // 1
+	[-0.5,  0.5, -0.5], // 2
+	[-0.5,  0.5,  0.5], // 3
+	[ 0.5,  0.0,  0.5], // 4
+	[ 0.5,  0.0, -0.5], // 5
+	[-0.5,  0.0, -0.5], // 6
+	[-0.5,  0.0,  0.5], // 7
+];
+
+/// the vertex tables of all shapes use the same order, so they share `CUBE_FACES`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockShape {
+	Cube,
+	SlabBottom,
+	SlabTop,
+}
+
+impl BlockShape {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"cube" => Some(Self::Cube),
+			"slab_bottom" => Some(Self::SlabBottom),
+			"slab_top" => Some(Self::SlabTop),
+			_ => None
+		}
+	}
+
+	pub fn vertices(self) -> &'static [[f32; 3]; 8] {
+		match self {
+			Self::Cube => &CUBE_VERTICES,
+			Self::SlabBottom => &SLAB_BOTTOM_VERTICES,
+			Self::SlabTop => &SLAB_TOP_VERTICES,
+		}
+	}
+
+	/// whether the face in direction `dir` lies on the side of the block,
+	/// faces inside of the block can't be hidden by neighbors.
+	pub fn is_face_on_side(self, dir: Dir) -> bool {
+		match (self, dir) {
+			(Self::SlabBottom, Dir::PY) => false,
+			(Self::SlabTop, Dir::NY) => false,
+			_ => true
+		}
+	}
+}
+
+/// the corners of each face are clockwise when seen from outside of the block,
+/// i.e. `(b - a).cross(c - a)` points against the face's normal,
+/// which the chunk pipelines rely on (`FrontFace::Cw` with back faces culled).
 pub const CUBE_FACES: [(Dir, [usize; 4]); 6] = [
 	(Dir::PX, [5, 4, 0, 1]),
 	(Dir::NX, [7, 6, 2, 3]),
@@ -119,50 +273,214 @@ pub const CUBE_FACES: [(Dir, [usize; 4]); 6] = [
 
 
 impl Block {
-	pub fn is_solid(&self) -> bool {
-		match BlockId::from_u16(self.id) {
-			Some(id) => id.is_solid(),
-			None => true
+	pub fn info<'r>(&self, registry: &'r BlockRegistry) -> &'r BlockInfo {
+		registry.get(self.id)
+	}
+
+	pub fn is_solid(&self, registry: &BlockRegistry) -> bool {
+		self.info(registry).solid
+	}
+
+	pub fn is_transparent(&self, registry: &BlockRegistry) -> bool {
+		self.info(registry).transparent
+	}
+
+	/// opaque blocks hide the faces of all their neighbors.
+	pub fn is_opaque(&self, registry: &BlockRegistry) -> bool {
+		self.info(registry).is_opaque()
+	}
+
+	/// whether the face of this block in direction `dir` that touches `neighbor`
+	/// should be culled. `None` means the neighbor isn't loaded.
+	pub fn is_face_hidden_by(&self, dir: Dir, neighbor: Option<Block>, registry: &BlockRegistry) -> bool {
+		if !self.info(registry).shape.is_face_on_side(dir) {
+			return false;
+		}
+
+		match neighbor {
+			None => true,
+			Some(neighbor) if neighbor.is_opaque(registry) => true,
+			// transparent blocks only cull against the same kind of block,
+			// so that water next to water doesn't render the shared face.
+			Some(neighbor) => self.is_transparent(registry) && { neighbor.id } == { self.id },
 		}
 	}
 }
 
-// 0 1 3 2 best (top face is weird in corners)
-static mut AO_INDEX_MAP_: &'static mut [u32] = &mut [0, 1, 3, 2];
+/// which of the 4 ao slots of a face each of its vertices uses, see [`ChunkData::generate_mesh`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AoIndexMap(pub [u32; 4]);
+
+impl AoIndexMap {
+	// 0 1 3 2 best (top face is weird in corners)
+	pub const DEFAULT: Self = Self([0, 1, 3, 2]);
 
-pub fn ao_index_map() -> &'static [u32] {
-	unsafe {
-		AO_INDEX_MAP_
+	/// the next permutation in lexicographic order, the last one wraps around to the first.
+	pub fn next(self) -> Self {
+		use std::cmp::Ordering;
+		let Self(mut nums) = self;
+		// or use feature(array_windows) on nightly
+		let last_ascending = match nums.windows(2).rposition(|w| w[0] < w[1]) {
+			Some(i) => i,
+			None => {
+				nums.reverse();
+				return Self(nums);
+			}
+		};
+
+		let swap_with = nums[last_ascending + 1..]
+			.binary_search_by(|n| u32::cmp(&nums[last_ascending], n).then(Ordering::Less))
+			.unwrap_err(); // cannot fail because the binary search will never succeed
+		nums.swap(last_ascending, last_ascending + swap_with);
+		nums[last_ascending + 1..].reverse();
+		Self(nums)
+	}
+
+	/// 2 bits per index, so that it fits into an atomic.
+	const fn pack(self) -> u32 {
+		self.0[0] | (self.0[1] << 2) | (self.0[2] << 4) | (self.0[3] << 6)
+	}
+
+	const fn unpack(packed: u32) -> Self {
+		Self([packed & 0b11, (packed >> 2) & 0b11, (packed >> 4) & 0b11, (packed >> 6) & 0b11])
 	}
 }
 
+/// the map used for meshing, shared with the mesher threads. can be cycled for debugging, see [`next_ao_index_map`].
+static AO_INDEX_MAP: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(AoIndexMap::DEFAULT.pack());
+
+pub fn ao_index_map() -> AoIndexMap {
+	AoIndexMap::unpack(AO_INDEX_MAP.load(std::sync::atomic::Ordering::Relaxed))
+}
+
+/// NB: only affects the chunks that are meshed afterwards.
 pub fn next_ao_index_map() {
-	let nums: &'static mut [u32] = unsafe { &mut AO_INDEX_MAP_ };
-	use std::cmp::Ordering;
-	// or use feature(array_windows) on nightly
-	let last_ascending = match nums.windows(2).rposition(|w| w[0] < w[1]) {
-		Some(i) => i,
-		None => {
-			nums.reverse();
-			return;
-		}
+	use std::sync::atomic::Ordering;
+	// can't fail, the closure always returns `Some`.
+	let _ = AO_INDEX_MAP.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |packed| {
+		Some(AoIndexMap::unpack(packed).next().pack())
+	});
+}
+
+/// light levels go from 0 (dark) to `MAX_LIGHT`, and decrease by 1 for every block.
+pub const MAX_LIGHT: u8 = 15;
+
+/// offsets of the chunks (including the chunk itself) that have blocks within `margin`
+/// blocks of `local`, i.e. the ones that have to be updated after an edit at `local`.
+pub fn touched_chunk_offsets(local: Vec3i32, margin: i32) -> Vec<Vec3i32> {
+	let axis_offsets = |c: i32, size: usize| {
+		let low = if c < margin { -1 } else { 0 };
+		let high = if c >= size as i32 - margin { 1 } else { 0 };
+		low..=high
 	};
 
-	let swap_with = nums[last_ascending + 1..]
-		.binary_search_by(|n| u32::cmp(&nums[last_ascending], n).then(Ordering::Less))
-		.unwrap_err(); // cannot fail because the binary search will never succeed
-	nums.swap(last_ascending, last_ascending + swap_with);
-	nums[last_ascending + 1..].reverse();
+	let mut offsets = Vec::new();
+	for y in axis_offsets(local.y, CHUNK_SIZE.y) {
+		for z in axis_offsets(local.z, CHUNK_SIZE.z) {
+			for x in axis_offsets(local.x, CHUNK_SIZE.x) {
+				offsets.push(vec3(x, y, z));
+			}
+		}
+	}
+	offsets
+}
+
+/// a chunk and its (up to) 26 neighbors, for queries that cross chunk boundaries.
+/// positions are relative to the center chunk, and can leave it by up to a chunk on every axis at once
+/// (so the edges and corners work too), anything further or in a chunk that isn't loaded is `None`.
+pub struct ChunkNeighborhood<'a> {
+	chunks: [Option<&'a ChunkData>; 27]
+}
+
+impl<'a> ChunkNeighborhood<'a> {
+	/// index of the center chunk in [`ChunkNeighborhood::from_chunks`].
+	pub const CENTER: usize = 13;
+
+	/// `chunks` are ordered by y, then z, then x, from -1 to 1 relative to the center.
+	/// the center chunk must be present.
+	pub fn from_chunks(chunks: [Option<&'a ChunkData>; 27]) -> Self {
+		assert!(chunks[Self::CENTER].is_some(), "neighborhood without a center chunk");
+		Self { chunks }
+	}
+
+	/// the positions of the chunks in a neighborhood, in the order of [`ChunkNeighborhood::from_chunks`].
+	pub fn positions(chunk_position: Vec3i32) -> [Vec3i32; 27] {
+		let mut positions = [chunk_position; 27];
+		for y in -1..=1 {
+			for z in -1..=1 {
+				for x in -1..=1 {
+					let offset = vec3(x, y, z);
+					positions[Self::chunk_index(offset).unwrap()] = chunk_position + offset;
+				}
+			}
+		}
+		positions
+	}
+
+	pub fn center(&self) -> &'a ChunkData {
+		self.chunks[Self::CENTER].unwrap()
+	}
+
+	pub fn new(center: &'a ChunkData, chunk_position: Vec3i32, chunks: &'a HashMap<Vec3i32, Chunk>) -> Self {
+		let mut neighborhood = Self { chunks: [None; 27] };
+		for y in -1..=1 {
+			for z in -1..=1 {
+				for x in -1..=1 {
+					let offset = vec3(x, y, z);
+					let index = Self::chunk_index(offset).unwrap();
+					neighborhood.chunks[index] = if offset == vec3(0, 0, 0) {
+						Some(center)
+					} else {
+						chunks.get(&(chunk_position + offset)).map(|chunk| &*chunk.data)
+					};
+				}
+			}
+		}
+		neighborhood
+	}
+
+	fn chunk_index(Vector([x, y, z]): Vec3i32) -> Option<usize> {
+		if x.abs() > 1 || y.abs() > 1 || z.abs() > 1 {
+			None
+		} else {
+			Some((((y + 1) * 3 + (z + 1)) * 3 + (x + 1)) as usize)
+		}
+	}
+
+	fn locate(&self, local: Vec3i32) -> Option<(&'a ChunkData, Vec3i32)> {
+		let index = Self::chunk_index(block_global_to_chunk(local))?;
+		self.chunks[index].map(|data| (data, block_global_to_block_local(local)))
+	}
+
+	/// `None` means that the block is in a chunk that isn't loaded.
+	pub fn block(&self, local: Vec3i32) -> Option<Block> {
+		self.locate(local).and_then(|(data, local)| data.get_block(local).copied())
+	}
+
+	pub fn light(&self, local: Vec3i32) -> Option<u8> {
+		self.locate(local).and_then(|(data, local)| data.get_light(local))
+	}
+
+	pub fn skylight(&self, local: Vec3i32) -> Option<u8> {
+		self.locate(local).and_then(|(data, local)| data.get_skylight(local))
+	}
 }
 
+#[derive(Clone)]
 pub struct ChunkData {
-	pub blocks: Box<[Block; CHUNK_BLOCK_COUNT]>
+	pub blocks: Box<[Block; CHUNK_BLOCK_COUNT]>,
+	/// block light, see [`ChunkData::compute_light`].
+	pub light: Box<[u8; CHUNK_BLOCK_COUNT]>,
+	/// sky light, see [`ChunkData::compute_light`].
+	pub skylight: Box<[u8; CHUNK_BLOCK_COUNT]>
 }
 
 impl ChunkData {
 	pub fn new() -> Self {
 		Self {
-			blocks: unsafe { Box::new_zeroed().assume_init() }
+			blocks: unsafe { Box::new_zeroed().assume_init() },
+			light: unsafe { Box::new_zeroed().assume_init() },
+			skylight: unsafe { Box::new_zeroed().assume_init() }
 		}
 	}
 
@@ -188,50 +506,206 @@ impl ChunkData {
 		}
 	}
 
-	pub fn set_block(&mut self, position: Vec3<i32>, block: Block) {
+	/// returns whether the block changed.
+	pub fn set_block(&mut self, position: Vec3<i32>, block: Block) -> bool {
 		if let Some(offset) = self.coords_to_offset(position) {
+			let changed = self.blocks[offset] != block;
 			self.blocks[offset] = block;
+			changed
+		} else {
+			false
 		}
 	}
 
-	pub fn generate_mesh(
+	pub fn get_light(&self, position: Vec3<i32>) -> Option<u8> {
+		self.coords_to_offset(position).map(|offset| self.light[offset])
+	}
+
+	pub fn get_skylight(&self, position: Vec3<i32>) -> Option<u8> {
+		self.coords_to_offset(position).map(|offset| self.skylight[offset])
+	}
+
+	/// the sky light of the lowest layer of blocks, which is what lights the chunk below from above.
+	pub fn bottom_skylight(&self) -> &[u8] {
+		&self.skylight[..CHUNK_SIZE.x * CHUNK_SIZE.z]
+	}
+
+	/// does nothing outside of the chunk.
+	pub fn set_light(&mut self, position: Vec3<i32>, level: u8) {
+		if let Some(offset) = self.coords_to_offset(position) {
+			self.light[offset] = level;
+		}
+	}
+
+	/// does nothing outside of the chunk.
+	pub fn set_skylight(&mut self, position: Vec3<i32>, level: u8) {
+		if let Some(offset) = self.coords_to_offset(position) {
+			self.skylight[offset] = level;
+		}
+	}
+
+	/// computes the block light and the sky light, light can't pass through opaque blocks.
+	/// block light is flood-filled from emissive blocks, sky light goes straight down
+	/// columns that are open to the sky and is then flood-filled under overhangs.
+	/// since light can come from the neighbors, the fill is done over the chunk
+	/// padded by the maximum distance light can travel, only the light inside
+	/// of this chunk is returned. this is for newly loaded chunks, edits are
+	/// relit incrementally by [`super::light::relight_block`].
+	/// where the chunks above aren't loaded, the columns are only open to the sky if `open_above` is set,
+	/// which should be the case if they aren't going to be loaded. otherwise they stay dark until the chunk above
+	/// is loaded and this one is lit again, so that caves don't light up in the meantime.
+	/// NB: the sky light of the chunks above has to be computed first.
+	pub fn compute_light(
 		&self,
 		chunk_position: Vec3i32,
-		chunk: &HashMap<Vec3i32, Chunk>,
-		block_textures: &LoadedTextures
-	) -> (Vec<super::renderer::chunk::BlockVertex>, Vec<u32>) {
-		let mut vertices = Vec::<super::renderer::chunk::BlockVertex>::new();
-		let mut indices = Vec::<u32>::new();
-
-		let is_block_solid_at = |local: Vec3i32, normal: Vec3i32| -> bool {
-			if let Some(offset) = self.coords_to_offset(local + normal) {
-				self.blocks[offset].is_solid()
-			} else if let Some(neighbor_chunk) = &chunk.get(&(chunk_position + normal)) {
-				let pos = normal.map_indexed(|c, i| {
-					if c == 0 { local.0[i] }
-					else if c > 0 { 0 }
-					else { CHUNK_SIZE.0[i] as i32 - 1 }
-				});
-
-				if let Some(offset) = neighbor_chunk.data.coords_to_offset(pos) {
-					neighbor_chunk.data.blocks[offset].is_solid()
-				} else {
-					panic!("😭")
-				}
+		chunks: &HashMap<Vec3i32, Chunk>,
+		registry: &BlockRegistry,
+		open_above: bool
+	) -> (Box<[u8; CHUNK_BLOCK_COUNT]>, Box<[u8; CHUNK_BLOCK_COUNT]>) {
+		let neighborhood = ChunkNeighborhood::new(self, chunk_position, chunks);
+
+		let padding = MAX_LIGHT as i32 - 1;
+		let size = CHUNK_SIZE.each_as::<i32>() + padding * 2;
+		let region_offset = |position: Vec3i32| -> Option<usize> {
+			let Vector([x, y, z]) = position + padding;
+			if x < 0 || y < 0 || z < 0 || x >= size.x || y >= size.y || z >= size.z {
+				None
 			} else {
-				true
+				Some(((y * size.z + z) * size.x + x) as usize)
 			}
 		};
 
+		let region_len = (size.x * size.y * size.z) as usize;
+		let mut light = vec![0u8; region_len];
+		let mut skylight = vec![0u8; region_len];
+		let mut opaque = vec![true; region_len];
+		let mut loaded = vec![false; region_len];
+		let mut queue = std::collections::VecDeque::new();
+		let mut sky_queue = std::collections::VecDeque::new();
+
+		for y in -padding .. CHUNK_SIZE.y as i32 + padding {
+			for z in -padding .. CHUNK_SIZE.z as i32 + padding {
+				for x in -padding .. CHUNK_SIZE.x as i32 + padding {
+					let position = vec3(x, y, z);
+					let offset = region_offset(position).unwrap();
+					// blocks in chunks that aren't loaded stay opaque and dark.
+					if let Some(block) = neighborhood.block(position) {
+						let info = block.info(registry);
+						loaded[offset] = true;
+						opaque[offset] = info.is_opaque();
+						if info.light > 0 {
+							light[offset] = info.light.min(MAX_LIGHT);
+							queue.push_back(position);
+						}
+					}
+				}
+			}
+		}
+
+		let top = CHUNK_SIZE.y as i32 + padding - 1;
+		for z in -padding .. CHUNK_SIZE.z as i32 + padding {
+			for x in -padding .. CHUNK_SIZE.x as i32 + padding {
+				// the column is open if the block above the region gets full sky light.
+				let above = vec3(x, top + 1, z);
+				let mut open = match neighborhood.locate(above) {
+					Some((data, local)) => data.get_skylight(local) == Some(MAX_LIGHT),
+					None => open_above
+				};
+
+				for y in (-padding ..= top).rev() {
+					let position = vec3(x, y, z);
+					let offset = region_offset(position).unwrap();
+					// the column goes through the chunks that aren't loaded, but they stay dark.
+					if !loaded[offset] {
+						continue;
+					}
+					if opaque[offset] {
+						open = false;
+					}
+
+					if !open {
+						break;
+					}
+
+					skylight[offset] = MAX_LIGHT;
+					sky_queue.push_back(position);
+				}
+			}
+		}
+
+		let flood_fill = |light: &mut Vec<u8>, queue: &mut std::collections::VecDeque<Vec3i32>| {
+			while let Some(position) = queue.pop_front() {
+				let level = light[region_offset(position).unwrap()];
+				if level <= 1 {
+					continue;
+				}
+
+				for dir in Dir::all() {
+					let next = position + dir.normal::<i32>();
+					if let Some(offset) = region_offset(next) {
+						if !opaque[offset] && light[offset] < level - 1 {
+							light[offset] = level - 1;
+							queue.push_back(next);
+						}
+					}
+				}
+			}
+		};
+
+		flood_fill(&mut light, &mut queue);
+		flood_fill(&mut skylight, &mut sky_queue);
+
+		let mut chunk_light: Box<[u8; CHUNK_BLOCK_COUNT]> = unsafe { Box::new_zeroed().assume_init() };
+		let mut chunk_skylight: Box<[u8; CHUNK_BLOCK_COUNT]> = unsafe { Box::new_zeroed().assume_init() };
+		for y in 0..CHUNK_SIZE.y as i32 {
+			for z in 0..CHUNK_SIZE.z as i32 {
+				for x in 0..CHUNK_SIZE.x as i32 {
+					let position = vec3(x, y, z);
+					if let Some(offset) = self.coords_to_offset(position) {
+						let region_offset = region_offset(position).unwrap();
+						chunk_light[offset] = light[region_offset];
+						chunk_skylight[offset] = skylight[region_offset];
+					}
+				}
+			}
+		}
+
+		(chunk_light, chunk_skylight)
+	}
+
+	/// the samples can be outside of the chunk on more than one axis
+	/// (AO corners and edges), so the diagonal neighbors are needed too.
+	/// only reads block data, so this can run on any thread (see [`super::mesher`]).
+	pub fn generate_mesh(neighborhood: &ChunkNeighborhood, registry: &BlockRegistry) -> ChunkMeshData {
+		let mut mesh_data = ChunkMeshData::default();
+		let this = neighborhood.center();
+
+		// read once, so that the whole chunk uses the same map even if it's cycled while meshing.
+		let ao_index_map = ao_index_map();
+
+		// blocks in chunks that aren't loaded count as opaque.
+		let is_block_opaque_at = |local: Vec3i32, normal: Vec3i32| -> bool {
+			neighborhood.block(local + normal).map_or(true, |block| block.is_opaque(registry))
+		};
+
+		// average (block, sky) light of the non-opaque blocks around a vertex.
+		let vertex_light_at = |local: Vec3i32, normals: [Vec3i32; 4]| -> (u8, u8) {
+			let (light, skylight, count) = normals.into_iter()
+				.filter(|normal| !is_block_opaque_at(local, *normal))
+				.filter_map(|normal| Some((neighborhood.light(local + normal)?, neighborhood.skylight(local + normal)?)))
+				.fold((0u32, 0u32, 0u32), |(light, skylight, count), (l, s)| (light + l as u32, skylight + s as u32, count + 1));
+			if count == 0 { (0, 0) } else { ((light / count) as u8, (skylight / count) as u8) }
+		};
+
 		'outer: for y in 0..CHUNK_SIZE.y as i32 {
 			for z in 0..CHUNK_SIZE.z as i32 {
 				for x in 0..CHUNK_SIZE.x as i32 {
 					let pos = vec3(x, y, z);
-					let offset = self.coords_to_offset(pos);
+					let offset = this.coords_to_offset(pos);
 					if offset.is_none() { break 'outer }
 					let offset = offset.unwrap();
 
-					let block = self.blocks[offset];
+					let block = this.blocks[offset];
 
 					let block_pos_local =
 						Vector([x, y, z]).each_as();
@@ -240,28 +714,43 @@ impl ChunkData {
 						continue; // don't render air.
 					}
 
+					let info = block.info(registry);
+
+					let MeshData { vertices, indices } = if info.transparent {
+						&mut mesh_data.transparent
+					} else {
+						&mut mesh_data.opaque
+					};
+
 					for (direction, face_vertices) in CUBE_FACES {
 						let normal = direction.normal::<i32>();
-						if is_block_solid_at(pos, normal) {
+						if block.is_face_hidden_by(direction, neighborhood.block(pos + normal), registry) {
 							continue;
 						}
 
 						let start_index = vertices.len() as u32;
 					
-						let texture_id = (BlockId::from_u16(block.id))
-							.and_then(|id| block_textures.blocks.get(&id).map(|tex| tex.in_direction(direction)))
-							.unwrap_or(TextureId(0)).0;
+						let rotation = BlockRotation::from_state(block.state);
+						let texture_id = info.texture(rotation.local_side(direction)).0;
 
 						let mut ao = [0u8; 4];
+						let mut light = [0u8; 4];
+						let mut skylight = [0u8; 4];
 						// let ao_index_map = [0, 1, 2, 3];
 						for (index, vertex_index) in face_vertices.into_iter().enumerate() {
 							let vertex = Vector(CUBE_VERTICES[vertex_index]);
-							ao[ao_index_map()[index] as usize] = {
-								let vertex = vertex * 2.0; // times 2 because vertices are -0.5..=0.5
-								let vertex_cross = direction.exclude_axis(vertex.each_as());
-								let corner = is_block_solid_at(pos, vertex.each_as());
-								let edge1 = is_block_solid_at(pos, direction.with_others(vec2(vertex_cross.x, 0)));
-								let edge2 = is_block_solid_at(pos, direction.with_others(vec2(0, vertex_cross.y)));
+							let vertex = vertex * 2.0; // times 2 because vertices are -0.5..=0.5
+							let vertex_cross = direction.exclude_axis(vertex.each_as());
+							let corner_normal = vertex.each_as();
+							let edge1_normal = direction.with_others(vec2(vertex_cross.x, 0));
+							let edge2_normal = direction.with_others(vec2(0, vertex_cross.y));
+
+							(light[index], skylight[index]) = vertex_light_at(pos, [normal, edge1_normal, edge2_normal, corner_normal]);
+
+							ao[ao_index_map.0[index] as usize] = {
+								let corner = is_block_opaque_at(pos, corner_normal);
+								let edge1 = is_block_opaque_at(pos, edge1_normal);
+								let edge2 = is_block_opaque_at(pos, edge2_normal);
 								
 								if edge1 && edge2 {
 									0
@@ -272,12 +761,14 @@ impl ChunkData {
 						}
 
 						for (index_index, index) in face_vertices.into_iter().enumerate() {
-							let vertex = Vector(CUBE_VERTICES[index]);
+							let vertex = Vector(info.shape.vertices()[index]);
 
 							vertices.push(super::renderer::chunk::BlockVertex::new(
 								vertex + block_pos_local,
 								index_index as u8,
 								&ao,
+								light[index_index],
+								skylight[index_index],
 								texture_id
 							));
 						}
@@ -290,31 +781,509 @@ impl ChunkData {
 			}
 		}
 
-		(vertices, indices)
+		mesh_data
+	}
+
+	/// a coarser mesh of the opaque blocks, for chunks far away. each cell of `2^level` blocks
+	/// on a side is drawn as one big block, the topmost opaque block in the cell, if there's one.
+	/// the faces against the neighbors' cells are culled like in [`ChunkData::generate_mesh`],
+	/// and the cells are lit by the sky light next to their faces, without AO.
+	pub fn generate_mesh_lod(neighborhood: &ChunkNeighborhood, level: u32, registry: &BlockRegistry) -> MeshData {
+		let mut mesh_data = MeshData::default();
+		let size = 1i32 << level;
+		let cells = CHUNK_SIZE.each_as::<i32>() / size;
+
+		// the representative block of the cell at `cell`, `None` for empty cells.
+		// the chunk's size is a multiple of the cell's, so the cells of the neighbors line up.
+		let cell_block = |cell: Vec3i32| -> Option<Block> {
+			let base = cell * size;
+			for y in (0..size).rev() {
+				for z in 0..size {
+					for x in 0..size {
+						let block = neighborhood.block(base + vec3(x, y, z))?;
+						if block.is_opaque(registry) {
+							return Some(block);
+						}
+					}
+				}
+			}
+			None
+		};
+		// cells in chunks that aren't loaded count as opaque.
+		let is_cell_opaque = |cell: Vec3i32| neighborhood.block(cell * size).is_none() || cell_block(cell).is_some();
+
+		for y in 0..cells.y {
+			for z in 0..cells.z {
+				for x in 0..cells.x {
+					let cell = vec3(x, y, z);
+					let Some(block) = cell_block(cell) else { continue };
+					let info = block.info(registry);
+					let base = cell * size;
+					// blocks are centered on integer coordinates, and so is the cell.
+					let center = base.each_as::<f32>() + (size - 1) as f32 * 0.5;
+
+					for (direction, face_vertices) in CUBE_FACES {
+						let normal = direction.normal::<i32>();
+						if is_cell_opaque(cell + normal) {
+							continue;
+						}
+
+						let skylight = neighborhood.skylight(base + normal * size + vec3(0, size - 1, 0)).unwrap_or(MAX_LIGHT);
+						let texture_id = info.texture(BlockRotation::from_state(block.state).local_side(direction)).0;
+						let start_index = mesh_data.vertices.len() as u32;
+						for (index_index, index) in face_vertices.into_iter().enumerate() {
+							let vertex = Vector(CUBE_VERTICES[index]) * size as f32 + center;
+							mesh_data.vertices.push(super::renderer::chunk::BlockVertex::new(
+								vertex,
+								index_index as u8,
+								&[3; 4],
+								0,
+								skylight,
+								texture_id
+							));
+						}
+
+						for index in [0, 1, 2, 2, 3, 0] {
+							mesh_data.indices.push(start_index + index);
+						}
+					}
+				}
+			}
+		}
+
+		mesh_data
+	}
+}
+
+#[derive(Default)]
+pub struct MeshData {
+	pub vertices: Vec<super::renderer::chunk::BlockVertex>,
+	pub indices: Vec<u32>,
+}
+
+/// opaque and transparent geometry are kept apart so that
+/// transparent faces can be drawn after all opaque ones.
+#[derive(Default)]
+pub struct ChunkMeshData {
+	pub opaque: MeshData,
+	pub transparent: MeshData,
+	/// only built for the chunks that are drawn at a distance, see [`ChunkData::generate_mesh_lod`].
+	pub lod: Option<MeshData>,
+}
+
+impl ChunkMeshData {
+	/// of the full detail meshes, the lod mesh isn't counted.
+	pub fn vertex_count(&self) -> usize {
+		self.opaque.vertices.len() + self.transparent.vertices.len()
 	}
 }
 
+/// a chunk was meshed, see [`Chunk::update_mesh`] and [`super::mesher::MeshResult`].
+#[derive(Debug, Clone, Copy)]
+pub struct MeshEvent {
+	pub position: Vec3i32,
+	/// see [`ChunkMeshData::vertex_count`].
+	pub vertex_count: usize,
+	/// how long generating the meshes took, not including the upload.
+	pub duration: std::time::Duration,
+}
+
 pub struct Chunk {
-	pub data: ChunkData,
-	pub mesh: Option<gfx::Mesh<super::renderer::chunk::BlockVertex>>,
-	pub position: Vec3i32
+	/// shared with the mesher threads, see [`Chunk::data_mut`].
+	pub data: Arc<ChunkData>,
+	/// in the [`super::renderer::chunk::ChunkRenderer::mesh_pool`], so all the chunks can be drawn at once.
+	pub mesh: Option<gfx::pool::PooledMesh>,
+	pub transparent_mesh: Option<gfx::pool::PooledMesh>,
+	/// the opaque blocks at [`LOD_LEVEL`], drawn instead of `mesh` when the chunk is far away.
+	/// `None` for the chunks that were meshed close to the camera.
+	pub lod_mesh: Option<gfx::pool::PooledMesh>,
+	pub position: Vec3i32,
+	/// whether the blocks changed since the chunk was generated or last saved.
+	pub dirty: bool,
+	/// local positions and light levels of the emissive blocks, see [`Chunk::update_emitters`].
+	pub emitters: Vec<(Vec3i32, u8)>,
 }
 
 impl Chunk {
 	pub fn new(position: Vec3i32, data: ChunkData) -> Self {
 		Self {
-			data,
+			data: Arc::new(data),
 			position,
 			mesh: None,
+			transparent_mesh: None,
+			lod_mesh: None,
+			dirty: false,
+			emitters: Vec::new(),
 		}
 	}
 
-	pub fn update_mesh(&mut self, gfx: &gfx::Gfx, chunks: &HashMap<Vec3i32, Chunk>, block_textures: &LoadedTextures) {
-		let (vertices, indices) = self.data.generate_mesh(self.position, chunks, block_textures);
-		if let Some(ref mut mesh) = &mut self.mesh {
-			mesh.update(gfx, &vertices, &indices);
-		} else {
-			self.mesh = Some(gfx::Mesh::new(gfx, &vertices, &indices, Some(format!("Chunk {:?}", self.position.0).as_str())));
+	/// returns whether the block changed, in which case the chunk is marked dirty.
+	pub fn set_block(&mut self, position: Vec3i32, block: Block) -> bool {
+		// only copy the data if something actually changes, see [`Chunk::data_mut`].
+		if self.data.get_block(position).is_some_and(|old| *old == block) {
+			return false;
+		}
+		let changed = self.data_mut().set_block(position, block);
+		self.dirty |= changed;
+		changed
+	}
+
+	/// copies the data if a mesher thread still has a reference to it.
+	pub fn data_mut(&mut self) -> &mut ChunkData {
+		Arc::make_mut(&mut self.data)
+	}
+
+	/// the old meshes give their space in the pool back when they're replaced.
+	pub fn upload_mesh(&mut self, gfx: &gfx::Gfx, pool: &mut gfx::pool::MeshPool<super::renderer::chunk::BlockVertex>, mesh_data: &ChunkMeshData) {
+		let mut upload = |data: &MeshData| pool.upload(gfx, &data.vertices, &data.indices);
+		self.mesh = Some(upload(&mesh_data.opaque));
+		self.transparent_mesh = Some(upload(&mesh_data.transparent));
+		// `None` drops the lod mesh, it would be out of date.
+		self.lod_mesh = mesh_data.lod.as_ref().map(upload);
+	}
+
+	/// sets the block and sky light computed by [`ChunkData::compute_light`],
+	/// should be run before [`Chunk::update_mesh`], for this chunk and its neighbors.
+	pub fn replace_light(&mut self, (light, skylight): (Box<[u8; CHUNK_BLOCK_COUNT]>, Box<[u8; CHUNK_BLOCK_COUNT]>), registry: &BlockRegistry) {
+		let data = self.data_mut();
+		(data.light, data.skylight) = (light, skylight);
+		self.update_emitters(registry);
+	}
+
+	/// finds the emissive blocks, should be run after the blocks changed.
+	pub fn update_emitters(&mut self, registry: &BlockRegistry) {
+		self.emitters.clear();
+		for z in 0..CHUNK_SIZE.z as i32 {
+			for y in 0..CHUNK_SIZE.y as i32 {
+				for x in 0..CHUNK_SIZE.x as i32 {
+					let position = vec3(x, y, z);
+					let light = self.data.get_block(position).map_or(0, |block| block.info(registry).light);
+					if light > 0 {
+						self.emitters.push((position, light.min(MAX_LIGHT)));
+					}
+				}
+			}
+		}
+	}
+
+	/// meshes the chunk inline, see [`super::mesher::ChunkMesher`] for the threaded version.
+	/// the [`Chunk::lod_mesh`] is only built with `lod`, otherwise it's dropped.
+	/// `on_meshed` is called afterwards, the meshing is only timed if it's set.
+	pub fn update_mesh(
+		&mut self,
+		gfx: &gfx::Gfx,
+		pool: &mut gfx::pool::MeshPool<super::renderer::chunk::BlockVertex>,
+		chunks: &HashMap<Vec3i32, Chunk>,
+		registry: &BlockRegistry,
+		lod: bool,
+		on_meshed: Option<&mut dyn FnMut(MeshEvent)>
+	) {
+		let mesh_data = self.generate_mesh(chunks, registry, lod, on_meshed);
+		self.upload_mesh(gfx, pool, &mesh_data);
+	}
+
+	/// [`Chunk::update_mesh`] without the upload.
+	fn generate_mesh(
+		&self,
+		chunks: &HashMap<Vec3i32, Chunk>,
+		registry: &BlockRegistry,
+		lod: bool,
+		on_meshed: Option<&mut dyn FnMut(MeshEvent)>
+	) -> ChunkMeshData {
+		let start = on_meshed.is_some().then(std::time::Instant::now);
+		let neighborhood = ChunkNeighborhood::new(&self.data, self.position, chunks);
+		let mut mesh_data = ChunkData::generate_mesh(&neighborhood, registry);
+		mesh_data.lod = lod.then(|| ChunkData::generate_mesh_lod(&neighborhood, LOD_LEVEL, registry));
+		if let (Some(on_meshed), Some(start)) = (on_meshed, start) {
+			on_meshed(MeshEvent { position: self.position, vertex_count: mesh_data.vertex_count(), duration: start.elapsed() });
+		}
+		mesh_data
+	}
+
+	/// bounding box of the chunk in world space, as (min, max).
+	pub fn bounds(&self) -> (Vec3f32, Vec3f32) {
+		// blocks are centered on integer coordinates, hence the `- 0.5`.
+		let min = (self.position * CHUNK_SIZE.each_as()).each_as::<f32>() - 0.5;
+		(min, min + CHUNK_SIZE.each_as::<f32>())
+	}
+
+	/// center of the chunk in world space.
+	pub fn center(&self) -> Vec3f32 {
+		// blocks are centered on integer coordinates, hence the `- 1.0`.
+		(self.position * CHUNK_SIZE.each_as()).each_as::<f32>() + (CHUNK_SIZE.each_as::<f32>() - 1.0) * 0.5
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::{registry::tests::registry, texture::{BlockTextures, TextureId}};
+
+	fn block(id: BlockId) -> Block {
+		Block { id: id as u16, state: 0 }
+	}
+
+	/// the chunks of a neighborhood, in the order of [`ChunkNeighborhood::from_chunks`], all empty.
+	fn empty_chunks() -> Vec<ChunkData> {
+		(0..27).map(|_| ChunkData::new()).collect()
+	}
+
+	fn neighborhood(chunks: &[ChunkData]) -> ChunkNeighborhood {
+		ChunkNeighborhood::from_chunks(std::array::from_fn(|index| Some(&chunks[index])))
+	}
+
+	#[test]
+	fn meshing_samples_diagonal_neighbors() {
+		let registry = registry();
+		let mut chunks = empty_chunks();
+		chunks[ChunkNeighborhood::CENTER].set_block(vec3(0, 0, 0), block(BlockId::Stone));
+		// only sampled for the AO of the corner that the block shares with it.
+		let corner = ChunkNeighborhood::chunk_index(vec3(-1, -1, -1)).unwrap();
+		chunks[corner].set_block(CHUNK_SIZE.each_as::<i32>() - 1, block(BlockId::Stone));
+
+		let mesh = ChunkData::generate_mesh(&neighborhood(&chunks), &registry);
+		assert_eq!(mesh.opaque.vertices.len(), 24);
+		// the three faces touching that corner are darkened by it.
+		let darkened = mesh.opaque.vertices.iter().filter(|vertex| vertex.unpack().ao.contains(&2)).count();
+		assert_eq!(darkened, 12);
+	}
+
+	#[test]
+	fn rotation_moves_the_top_texture() {
+		let mut registry = registry();
+		registry.insert(BlockInfo {
+			id: 100,
+			name: "log".to_owned(),
+			solid: true,
+			transparent: false,
+			light: 0,
+			hardness: 1.0,
+			shape: BlockShape::Cube,
+			textures: Some(BlockTextures::cylinder(TextureId(1), TextureId(1), TextureId(2))),
+		});
+
+		let rotation = BlockRotation::new(Dir::PX);
+		assert_eq!(BlockRotation::from_state(rotation.to_state()), rotation);
+		assert_eq!(rotation.rotate(Dir::PY), Dir::PX);
+		assert_eq!(rotation.local_side(Dir::PX), Dir::PY);
+
+		let mut chunks = empty_chunks();
+		chunks[ChunkNeighborhood::CENTER].set_block(vec3(0, 0, 0), Block { id: 100, state: rotation.to_state() });
+		let mesh = ChunkData::generate_mesh(&neighborhood(&chunks), &registry);
+		// nothing is culled, so the faces are in the order of `CUBE_FACES`.
+		let face_texture = |dir: Dir| {
+			let face = CUBE_FACES.iter().position(|(face, _)| *face == dir).unwrap();
+			mesh.opaque.vertices[face * 4].texture_id()
+		};
+		assert_eq!(face_texture(Dir::PX), 1);
+		assert_eq!(face_texture(Dir::NX), 1);
+		assert_eq!(face_texture(Dir::PY), 2);
+	}
+
+	#[test]
+	fn caves_under_a_roof_get_no_sky_light() {
+		let registry = registry();
+		let mut data = ChunkData::new();
+		for z in 0..CHUNK_SIZE.z as i32 {
+			for x in 0..CHUNK_SIZE.x as i32 {
+				data.set_block(vec3(x, 20, z), block(BlockId::Stone));
+			}
+		}
+
+		// the neighbors aren't loaded, so no light comes in from the sides.
+		let (_, skylight) = data.compute_light(vec3(0, 0, 0), &HashMap::new(), &registry, true);
+		let skylight_at = |position: Vec3i32| skylight[data.coords_to_offset(position).unwrap()];
+		assert_eq!(skylight_at(vec3(4, 21, 4)), MAX_LIGHT);
+		assert_eq!(skylight_at(vec3(4, 31, 30)), MAX_LIGHT);
+		assert_eq!(skylight_at(vec3(4, 20, 4)), 0);
+		assert_eq!(skylight_at(vec3(4, 19, 4)), 0);
+		assert_eq!(skylight_at(vec3(16, 0, 16)), 0);
+	}
+
+	#[test]
+	fn only_changes_make_chunks_dirty() {
+		let mut chunk = Chunk::new(vec3(0, 0, 0), ChunkData::new());
+		assert!(!chunk.set_block(vec3(1, 2, 3), block(BlockId::Air)));
+		assert!(!chunk.dirty);
+		assert!(chunk.set_block(vec3(1, 2, 3), block(BlockId::Stone)));
+		assert!(chunk.dirty);
+
+		chunk.dirty = false;
+		assert!(!chunk.set_block(vec3(1, 2, 3), block(BlockId::Stone)));
+		assert!(!chunk.dirty);
+		// outside of the chunk.
+		assert!(!chunk.set_block(vec3(32, 0, 0), block(BlockId::Stone)));
+		assert!(!chunk.dirty);
+	}
+
+	#[test]
+	fn edits_touch_the_chunks_they_border() {
+		assert_eq!(touched_chunk_offsets(vec3(5, 6, 7), 1), [vec3(0, 0, 0)]);
+
+		let mut face = touched_chunk_offsets(vec3(31, 6, 7), 1);
+		face.sort_by_key(|offset| offset.0);
+		assert_eq!(face, [vec3(0, 0, 0), vec3(1, 0, 0)]);
+
+		// the diagonal chunks share the corner's AO, but only three share its faces.
+		let corner = touched_chunk_offsets(vec3(0, 31, 0), 1);
+		assert_eq!(corner.len(), 8);
+		let mut face_neighbors: Vec<Vec3i32> = corner.into_iter()
+			.filter(|offset| offset.0.iter().filter(|c| **c != 0).count() == 1)
+			.collect();
+		face_neighbors.sort_by_key(|offset| offset.0);
+		assert_eq!(face_neighbors, [vec3(-1, 0, 0), vec3(0, 0, -1), vec3(0, 1, 0)]);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn blocks_round_trip_through_serde() {
+		let block = Block { id: BlockId::Stone as u16, state: 5 };
+		let text = serde_json::to_string(&block).unwrap();
+		assert_eq!(text, format!("[{},5]", BlockId::Stone as u16));
+		assert!(serde_json::from_str::<Block>(&text).unwrap() == block);
+	}
+
+	#[test]
+	fn faces_are_wound_against_their_normals() {
+		for shape in [BlockShape::Cube, BlockShape::SlabBottom, BlockShape::SlabTop] {
+			let vertices = shape.vertices();
+			for (dir, corners) in CUBE_FACES {
+				let [a, b, c, d] = corners.map(|index| Vector(vertices[index]));
+				let normal: Vec3f32 = dir.normal::<i32>().each_as();
+				// both triangles of the quad, see the indices in `generate_mesh`.
+				assert!((b - a).cross(c - a).dot(&normal) < 0.0, "{:?} {:?}", shape, dir);
+				assert!((c - a).cross(d - a).dot(&normal) < 0.0, "{:?} {:?}", shape, dir);
+			}
+		}
+	}
+
+	#[test]
+	fn lod_meshes_have_a_quarter_of_the_faces() {
+		let registry = registry();
+		let mut chunks = empty_chunks();
+		let center = &mut chunks[ChunkNeighborhood::CENTER];
+		for y in 0..CHUNK_SIZE.y as i32 {
+			for z in 0..CHUNK_SIZE.z as i32 {
+				for x in 0..CHUNK_SIZE.x as i32 {
+					center.set_block(vec3(x, y, z), block(BlockId::Stone));
+				}
+			}
+		}
+
+		let full = ChunkData::generate_mesh(&neighborhood(&chunks), &registry).opaque;
+		let lod = ChunkData::generate_mesh_lod(&neighborhood(&chunks), 1, &registry);
+		assert_eq!(full.indices.len() / 6, 2 * (32 * 32 + 32 * 32 + 32 * 32));
+		assert_eq!(lod.indices.len() * 4, full.indices.len());
+	}
+
+	#[test]
+	fn lod_meshes_cull_against_the_neighbors() {
+		let registry = registry();
+		let mut chunks = empty_chunks();
+		chunks[ChunkNeighborhood::CENTER].set_block(vec3(CHUNK_SIZE.x as i32 - 1, 0, 0), block(BlockId::Stone));
+		let faces = |chunks: &[ChunkData]| ChunkData::generate_mesh_lod(&neighborhood(chunks), 1, &registry).indices.len() / 6;
+		assert_eq!(faces(&chunks), 6);
+
+		// the cell next to it, in the +x neighbor.
+		let px = ChunkNeighborhood::chunk_index(vec3(1, 0, 0)).unwrap();
+		chunks[px].set_block(vec3(1, 1, 1), block(BlockId::Stone));
+		assert_eq!(faces(&chunks), 5);
+
+		// chunks that aren't loaded hide the faces, like in the full mesh: only +y, -x and +z are left.
+		let unloaded = ChunkNeighborhood::from_chunks(std::array::from_fn(|index| {
+			(index == ChunkNeighborhood::CENTER).then(|| &chunks[index])
+		}));
+		assert_eq!(ChunkData::generate_mesh_lod(&unloaded, 1, &registry).indices.len() / 6, 3);
+	}
+
+	#[test]
+	fn neighborhoods_sample_across_chunk_boundaries() {
+		let size = CHUNK_SIZE.each_as::<i32>();
+		let mut chunks = empty_chunks();
+		for &dir in Dir::all() {
+			let offset = dir.normal::<i32>();
+			// the block right across the face, e.g. (32, 0, 0) is (0, 0, 0) of the +x neighbor.
+			let across = offset.zip_map(size, |offset, size| if offset > 0 { size } else { offset });
+			let index = ChunkNeighborhood::chunk_index(offset).unwrap();
+			chunks[index].set_block(block_global_to_block_local(across), block(BlockId::Stone));
+			chunks[index].set_light(block_global_to_block_local(across), dir as u8 + 1);
+		}
+		let corner = ChunkNeighborhood::chunk_index(vec3(1, -1, 1)).unwrap();
+		chunks[corner].set_block(vec3(0, size.y - 1, 0), block(BlockId::Dirt));
+		chunks[corner].set_skylight(vec3(0, size.y - 1, 0), 15);
+
+		let neighborhood = neighborhood(&chunks);
+		for &dir in Dir::all() {
+			let offset = dir.normal::<i32>();
+			let across = offset.zip_map(size, |offset, size| if offset > 0 { size } else { offset });
+			assert!(neighborhood.block(across) == Some(block(BlockId::Stone)), "{:?}", dir);
+			assert_eq!(neighborhood.light(across), Some(dir as u8 + 1), "{:?}", dir);
+		}
+		let corner = vec3(size.x, -1, size.z);
+		assert!(neighborhood.block(corner) == Some(block(BlockId::Dirt)));
+		assert_eq!(neighborhood.skylight(corner), Some(15));
+
+		// outside of the neighborhood.
+		assert!(neighborhood.block(vec3(2 * size.x, 0, 0)).is_none());
+		assert!(neighborhood.block(vec3(0, -size.y - 1, 0)).is_none());
+	}
+
+	#[test]
+	fn unloaded_neighbors_have_no_blocks() {
+		let chunks = empty_chunks();
+		let mut loaded: [Option<&ChunkData>; 27] = [None; 27];
+		loaded[ChunkNeighborhood::CENTER] = Some(&chunks[ChunkNeighborhood::CENTER]);
+		let neighborhood = ChunkNeighborhood::from_chunks(loaded);
+
+		let size = CHUNK_SIZE.each_as::<i32>();
+		assert!(neighborhood.block(vec3(0, 0, 0)).is_some());
+		assert!(neighborhood.block(size - vec3(1, 1, 1)).is_some());
+		for dir in Dir::all() {
+			assert!(neighborhood.block(dir.normal::<i32>() * size).is_none(), "{:?}", dir);
+			assert!(neighborhood.light(dir.normal::<i32>() * size).is_none(), "{:?}", dir);
+		}
+		assert!(neighborhood.skylight(vec3(-1, -1, -1)).is_none());
+	}
+
+	#[test]
+	fn ao_index_maps_cycle_through_the_permutations() {
+		assert_eq!(AoIndexMap::DEFAULT.next(), AoIndexMap([0, 2, 1, 3]));
+		assert_eq!(AoIndexMap([3, 2, 1, 0]).next(), AoIndexMap([0, 1, 2, 3]));
+
+		let mut map = AoIndexMap([0, 1, 2, 3]);
+		let mut seen = vec![];
+		for _ in 0..24 {
+			assert_eq!(AoIndexMap::unpack(map.pack()), map);
+			let next = map.next();
+			// in lexicographic order, until it wraps around.
+			assert!(next.0 > map.0 || next == AoIndexMap([0, 1, 2, 3]), "{:?} -> {:?}", map, next);
+			seen.push(map);
+			map = next;
+		}
+		assert_eq!(map, AoIndexMap([0, 1, 2, 3]));
+		seen.sort_by_key(|map| map.0);
+		seen.dedup();
+		assert_eq!(seen.len(), 24);
+	}
+
+	#[test]
+	fn meshing_reports_the_vertex_count() {
+		let registry = registry();
+		let position = vec3(2, -1, 3);
+		let mut chunks = HashMap::new();
+		chunks.insert(position, Chunk::new(position, ChunkData::new()));
+		let chunk = chunks.get_mut(&position).unwrap();
+		chunk.set_block(vec3(5, 5, 5), block(BlockId::Stone));
+		chunk.set_block(vec3(5, 6, 5), block(BlockId::Glass));
+
+		for lod in [false, true] {
+			let mut events = vec![];
+			let mesh_data = chunks[&position].generate_mesh(&chunks, &registry, lod, Some(&mut |event: MeshEvent| events.push(event)));
+			assert_eq!(events.len(), 1);
+			assert_eq!(events[0].position, position);
+			// the stone hides the bottom of the glass, but not the other way around, the lod mesh isn't counted.
+			assert_eq!(events[0].vertex_count, (6 + 5) * 4);
+			assert_eq!(events[0].vertex_count, mesh_data.vertex_count());
 		}
 	}
 }
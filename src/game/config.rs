@@ -0,0 +1,129 @@
+use crate::{math::*, error::VoxleError};
+
+use super::camera::CameraSettings;
+
+/// the settings of a game that are read at startup, see [`GameConfig::load`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameConfig {
+	/// where the camera starts, in blocks.
+	pub camera_position: Vec3f32,
+	pub camera: CameraSettings,
+	/// near and far planes of the camera, in blocks.
+	pub znear: f32,
+	pub zfar: f32,
+	/// horizontal render distance, in chunks.
+	pub render_distance: i32,
+	/// vertical render distance, in chunks.
+	pub vertical_render_distance: i32,
+	/// see [`super::worldgen::WorldGenConfig::sea_level`].
+	pub sea_level: i32,
+}
+
+impl Default for GameConfig {
+	fn default() -> Self {
+		Self {
+			camera_position: Vector([0.0, 128.5, -2.0]),
+			camera: CameraSettings::default(),
+			znear: 0.01,
+			zfar: 1000.0,
+			render_distance: 4,
+			vertical_render_distance: 2,
+			sea_level: 0,
+		}
+	}
+}
+
+impl GameConfig {
+	/// the defaults, overridden by the fields of the config at `json_path` if it exists:
+	/// ```json
+	/// { "camera_position": [0, 128.5, -2], "fovy": 70, "render_distance": 8, "mouse_sensitivity": 1.5 }
+	/// ```
+	/// fails on unknown keys and on values of the wrong type.
+	pub fn load(json_path: &std::path::Path) -> Result<Self, VoxleError> {
+		match std::fs::read_to_string(json_path) {
+			Ok(source) => Self::parse(json_path, &source),
+			Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+			Err(error) => Err(VoxleError::io(json_path)(error)),
+		}
+	}
+
+	/// [`GameConfig::load`] with the config already read from `json_path`.
+	fn parse(json_path: &std::path::Path, source: &str) -> Result<Self, VoxleError> {
+		let mut config = Self::default();
+		let invalid = |reason: String| VoxleError::invalid_manifest(json_path, reason);
+
+		let json = json::parse(source)
+			.map_err(|error| invalid(format!("config should be correct json: {}", error)))?;
+		if !json.is_object() {
+			return Err(invalid("config should be an object".to_owned()));
+		}
+
+		for (name, value) in json.entries() {
+			let number = || value.as_f32().ok_or_else(|| invalid(format!("config {} should be a number", name)));
+			let integer = || value.as_i32().ok_or_else(|| invalid(format!("config {} should be an integer", name)));
+			match name {
+				"camera_position" => {
+					let components = value.members()
+						.map(|c| c.as_f32().ok_or_else(|| invalid("config camera_position should be numbers".to_owned())))
+						.collect::<Result<Vec<f32>, VoxleError>>()?;
+					config.camera_position = Vector::try_from(components.as_slice())
+						.map_err(|error| invalid(format!("config camera_position: {}", error)))?;
+				},
+				"fovy" => config.camera.fovy = number()?,
+				"znear" => config.znear = number()?,
+				"zfar" => config.zfar = number()?,
+				"render_distance" => config.render_distance = integer()?,
+				"vertical_render_distance" => config.vertical_render_distance = integer()?,
+				"sea_level" => config.sea_level = integer()?,
+				"camera_speed" => config.camera.speed = number()?,
+				"mouse_sensitivity" => config.camera.sensitivity = number()?,
+				"third_person_distance" => config.camera.third_person_distance = number()?,
+				"orthographic_height" => config.camera.orthographic_height = Some(number()?),
+				_ => return Err(invalid(format!("unknown setting in config: {}", name))),
+			}
+		}
+
+		Ok(config)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(source: &str) -> Result<GameConfig, VoxleError> {
+		GameConfig::parse(std::path::Path::new("config.json"), source)
+	}
+
+	#[test]
+	fn missing_settings_are_the_defaults() {
+		let config = parse(r#"{ "fovy": 70, "render_distance": 8, "camera_position": [1, 2, 3], "sea_level": 12 }"#).unwrap();
+		assert_eq!(config.camera.fovy, 70.0);
+		assert_eq!(config.sea_level, 12);
+		assert_eq!(config.render_distance, 8);
+		assert_eq!(config.camera_position, Vector([1.0, 2.0, 3.0]));
+
+		let default = GameConfig::default();
+		assert_eq!(config.vertical_render_distance, default.vertical_render_distance);
+		assert_eq!(config.camera.sensitivity, default.camera.sensitivity);
+		assert_eq!(config.zfar, default.zfar);
+
+		assert_eq!(GameConfig::load(std::path::Path::new("does/not/exist.json")).unwrap(), default);
+	}
+
+	#[test]
+	fn invalid_configs_are_errors() {
+		for source in [
+			"{",
+			"[]",
+			r#"{ "fovy": "wide" }"#,
+			r#"{ "render_distance": "far" }"#,
+			r#"{ "sea_level": 1.5 }"#,
+			r#"{ "third_person_distance": null }"#,
+			r#"{ "camera_position": [1, 2] }"#,
+			r#"{ "colour": 1 }"#,
+		] {
+			assert!(matches!(parse(source), Err(VoxleError::InvalidManifest { .. })), "{}", source);
+		}
+	}
+}
@@ -0,0 +1,114 @@
+use std::{collections::{BTreeSet, HashMap}, io::{self, Write}, path::Path};
+
+use crate::math::*;
+
+use super::{chunk::{Chunk, ChunkData, ChunkMeshData, ChunkNeighborhood, MeshData, CHUNK_SIZE}, registry::BlockRegistry};
+
+/// writes the meshes of the chunks at `positions` to a wavefront obj at `path`,
+/// with positions in blocks and a material per block texture (`texture_<id>`),
+/// the materials are listed in a .mtl next to it, without any texture maps.
+/// the chunks are remeshed, so unloaded chunks are skipped, and their neighbors are needed for culling.
+pub fn export_obj(
+	chunks: &HashMap<Vec3i32, Chunk>,
+	positions: impl IntoIterator<Item = Vec3i32>,
+	registry: &BlockRegistry,
+	path: &Path,
+) -> Result<(), io::Error> {
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent)?;
+	}
+
+	let mtl_path = path.with_extension("mtl");
+	let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+	writeln!(out, "# exported from voxle")?;
+	if let Some(mtl_name) = mtl_path.file_name() {
+		writeln!(out, "mtllib {}", mtl_name.to_string_lossy())?;
+	}
+
+	// obj indices are 1-based and global to the file.
+	let mut vertex_count = 0;
+	let mut materials = BTreeSet::new();
+	for position in positions {
+		if !chunks.contains_key(&position) {
+			continue;
+		}
+
+		let neighbors = ChunkNeighborhood::positions(position)
+			.map(|position| chunks.get(&position).map(|chunk| &*chunk.data));
+		let ChunkMeshData { opaque, transparent, .. } = ChunkData::generate_mesh(&ChunkNeighborhood::from_chunks(neighbors), registry);
+
+		writeln!(out, "o chunk_{}_{}_{}", position.x, position.y, position.z)?;
+		let offset = (position * CHUNK_SIZE.each_as()).each_as::<f32>();
+		for mesh in [&opaque, &transparent] {
+			write_mesh(&mut out, mesh, offset, vertex_count, &mut materials)?;
+			vertex_count += mesh.vertices.len();
+		}
+	}
+
+	out.flush()?;
+
+	let mut mtl = io::BufWriter::new(std::fs::File::create(&mtl_path)?);
+	writeln!(mtl, "# exported from voxle")?;
+	for texture_id in materials {
+		writeln!(mtl, "newmtl texture_{}", texture_id)?;
+		writeln!(mtl, "Kd 1 1 1")?;
+	}
+	mtl.flush()
+}
+
+/// the textures the mesh uses are added to `materials`.
+fn write_mesh(out: &mut impl Write, mesh: &MeshData, offset: Vec3f32, first_vertex: usize, materials: &mut BTreeSet<u32>) -> Result<(), io::Error> {
+	for vertex in &mesh.vertices {
+		let vertex = vertex.unpack();
+		let position = vertex.position + offset;
+		let uv = vertex.corner_uv();
+		writeln!(out, "v {} {} {}", position.x, position.y, position.z)?;
+		// obj has v going up, the textures have it going down.
+		writeln!(out, "vt {} {}", uv.x, 1.0 - uv.y)?;
+	}
+
+	let mut material = None;
+	for triangle in mesh.indices.chunks_exact(3) {
+		let texture_id = mesh.vertices[triangle[0] as usize].texture_id();
+		if material != Some(texture_id) {
+			writeln!(out, "usemtl texture_{}", texture_id)?;
+			material = Some(texture_id);
+			materials.insert(texture_id);
+		}
+
+		let [a, b, c] = [0, 1, 2].map(|i| first_vertex + triangle[i] as usize + 1);
+		writeln!(out, "f {0}/{0} {1}/{1} {2}/{2}", a, b, c)?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::{chunk::{Block, BlockId}, registry::tests::registry, world::tests::TestDirectory};
+
+	#[test]
+	fn a_block_exports_as_a_cube() {
+		let mut chunk = Chunk::new(vec3(0, 0, 0), ChunkData::new());
+		chunk.set_block(vec3(4, 4, 4), Block { id: BlockId::Stone as u16, state: 0 });
+		let chunks = HashMap::from([(vec3(0, 0, 0), chunk)]);
+
+		let directory = TestDirectory::new("export_cube");
+		let path = directory.0.join("cube.obj");
+		export_obj(&chunks, [vec3(0, 0, 0), vec3(1, 0, 0)], &registry(), &path).unwrap();
+
+		let obj = std::fs::read_to_string(&path).unwrap();
+		let count = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+		assert_eq!(count("v "), 24);
+		assert_eq!(count("vt "), 24);
+		assert_eq!(count("f "), 12);
+
+		assert!(obj.lines().any(|line| line == "mtllib cube.mtl"));
+		let mtl = std::fs::read_to_string(directory.0.join("cube.mtl")).unwrap();
+		for line in obj.lines().filter(|line| line.starts_with("usemtl ")) {
+			let material = line.trim_start_matches("usemtl ");
+			assert!(mtl.lines().any(|line| line == format!("newmtl {}", material)), "{} isn't in the mtl", material);
+		}
+	}
+}
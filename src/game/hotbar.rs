@@ -0,0 +1,133 @@
+use crate::math::*;
+
+use super::{inventory::Inventory, registry::BlockRegistry, renderer::{icon::IconRenderer, ui::UiBuilder}};
+
+/// the first slots of the [`Inventory`], one of them is selected.
+#[derive(Debug)]
+pub struct Hotbar {
+	slot_count: usize,
+	selected: usize,
+}
+
+impl Hotbar {
+	/// size of a slot on the screen, in pixels.
+	const SLOT_SIZE: i32 = 48;
+	const SLOT_GAP: i32 = 4;
+	/// between the block's icon and the edge of its slot.
+	const ICON_MARGIN: i32 = 6;
+	/// distance from the bottom of the screen, in pixels.
+	const MARGIN: i32 = 12;
+	/// see `ui_spritesheet.png`.
+	const SOLID_SPRITE: Rect<i32> = Rect { x: 16, y: 0, w: 16, h: 16 };
+	const FRAME_SPRITE: Rect<i32> = Rect { x: 0, y: 16, w: 16, h: 16 };
+
+	/// `slot_count` shouldn't be 0.
+	pub fn new(slot_count: usize) -> Self {
+		assert!(slot_count > 0, "hotbar should have at least one slot");
+		Self { slot_count, selected: 0 }
+	}
+
+	pub fn slot_count(&self) -> usize {
+		self.slot_count
+	}
+
+	/// the index of the selected slot, in the inventory.
+	pub fn selected(&self) -> usize {
+		self.selected
+	}
+
+	/// slots past the end are ignored.
+	pub fn select(&mut self, index: usize) {
+		if index < self.slot_count {
+			self.selected = index;
+		}
+	}
+
+	/// moves the selection by `delta` slots, wrapping around at the ends.
+	pub fn scroll(&mut self, delta: i32) {
+		self.selected = (self.selected as i32 + delta).rem_euclid(self.slot_count as i32) as usize;
+	}
+
+	/// draws the slots centered at the bottom of the screen, and the selected block's name above them.
+	/// the blocks' icons should have been requested from `icons`.
+	pub fn draw(&self, ui: &mut UiBuilder, registry: &BlockRegistry, inventory: &Inventory, icons: &IconRenderer, screen_size: Vec2i32) {
+		let count = self.slot_count as i32;
+		let width = count * Self::SLOT_SIZE + (count - 1) * Self::SLOT_GAP;
+		let x = (screen_size.x - width) / 2;
+		let y = screen_size.y - Self::SLOT_SIZE - Self::MARGIN;
+
+		for index in 0..self.slot_count {
+			let slot_x = x + index as i32 * (Self::SLOT_SIZE + Self::SLOT_GAP);
+			let slot_rect = Rect { x: slot_x, y, w: Self::SLOT_SIZE, h: Self::SLOT_SIZE };
+			let frame_color = if index == self.selected { [255, 255, 255, 255] } else { [128, 128, 128, 192] };
+
+			ui.add_rect(slot_rect, Self::SOLID_SPRITE, [0, 0, 0, 128]);
+			let stack = inventory.slot(index);
+			if let Some((texture, uvs)) = stack.and_then(|stack| icons.icon(stack.block)) {
+				ui.add_image(Rect {
+					x: slot_x + Self::ICON_MARGIN,
+					y: y + Self::ICON_MARGIN,
+					w: Self::SLOT_SIZE - Self::ICON_MARGIN * 2,
+					h: Self::SLOT_SIZE - Self::ICON_MARGIN * 2,
+				}, texture, uvs, [255, 255, 255, 255]);
+			}
+			ui.add_rect(slot_rect, Self::FRAME_SPRITE, frame_color);
+			ui.add_text(Rect {
+				x: slot_x + 6,
+				y: y + 6,
+				w: Self::SLOT_SIZE - 12,
+				h: Self::SLOT_SIZE - 12,
+			}, &(index + 1).to_string(), frame_color);
+
+			// the count goes into the bottom-right corner, large counts are cut off at the slot's edge.
+			if let Some(stack) = stack {
+				let text = stack.count.to_string();
+				let text_width = ui.font().scaled_text_width(&text);
+				let text_height = ui.font().scaled_height();
+				ui.set_clip_rect(Some(slot_rect));
+				ui.add_text(Rect {
+					x: slot_x + Self::SLOT_SIZE - 6 - text_width,
+					y: y + Self::SLOT_SIZE - 6 - text_height,
+					w: text_width,
+					h: text_height,
+				}, &text, [255, 255, 255, 255]);
+				ui.set_clip_rect(None);
+			}
+		}
+
+		if let Some(stack) = inventory.slot(self.selected) {
+			let name = &registry.get(stack.block).name;
+			let name_width = ui.font().scaled_text_width(name);
+			let name_height = ui.font().scaled_height();
+			ui.add_text(Rect {
+				x: (screen_size.x - name_width) / 2,
+				y: y - name_height - Self::SLOT_GAP * 2,
+				w: name_width,
+				h: name_height,
+			}, name, [255, 255, 255, 255]);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn scrolling_wraps_around() {
+		let mut hotbar = Hotbar::new(4);
+		hotbar.scroll(-1);
+		assert_eq!(hotbar.selected(), 3);
+		hotbar.scroll(1);
+		assert_eq!(hotbar.selected(), 0);
+		hotbar.scroll(6);
+		assert_eq!(hotbar.selected(), 2);
+		hotbar.scroll(-11);
+		assert_eq!(hotbar.selected(), 3);
+
+		hotbar.select(1);
+		assert_eq!(hotbar.selected(), 1);
+		hotbar.select(4);
+		assert_eq!(hotbar.selected(), 1);
+	}
+}
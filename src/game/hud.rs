@@ -0,0 +1,56 @@
+use crate::math::*;
+
+use super::renderer::ui::UiBuilder;
+
+/// drawn at the center of the screen.
+pub struct Crosshair {
+	/// in logical pixels, see [`Crosshair::scaled_size`].
+	pub size: f32,
+	/// in `ui_spritesheet.png`.
+	pub uvs: Rect<i32>,
+	pub color: [u8; 4],
+	/// used instead of `color` while a block is targeted.
+	pub target_color: [u8; 4],
+}
+
+impl Default for Crosshair {
+	fn default() -> Self {
+		Self {
+			size: 8.5,
+			uvs: Rect { x: 0, y: 0, w: 16, h: 16 },
+			color: [255, 255, 255, 160],
+			target_color: [255, 255, 255, 255],
+		}
+	}
+}
+
+impl Crosshair {
+	/// the size on the screen in physical pixels, for the window's `scale_factor`.
+	pub fn scaled_size(&self, scale_factor: f64) -> i32 {
+		((self.size as f64 * scale_factor).round() as i32).max(1)
+	}
+
+	pub fn draw(&self, ui: &mut UiBuilder, screen_size: Vec2i32, scale_factor: f64, targeting: bool) {
+		let size = self.scaled_size(scale_factor);
+		ui.add_rect(Rect {
+			x: (screen_size.x - size) / 2,
+			y: (screen_size.y - size) / 2,
+			w: size,
+			h: size,
+		}, self.uvs, if targeting { self.target_color } else { self.color });
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn crosshair_scales_with_the_window() {
+		let crosshair = Crosshair { size: 9.0, ..Default::default() };
+		assert_eq!(crosshair.scaled_size(1.0), 9);
+		assert_eq!(crosshair.scaled_size(2.0), 18);
+		assert_eq!(crosshair.scaled_size(1.25), 11);
+		assert_eq!(crosshair.scaled_size(0.01), 1);
+	}
+}
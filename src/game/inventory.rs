@@ -0,0 +1,113 @@
+use super::registry::BlockRegistry;
+
+/// `count` blocks of the same kind in one slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemStack {
+	pub block: u16,
+	pub count: u32,
+}
+
+/// the blocks the player carries, the first slots are the [`super::hotbar::Hotbar`]'s.
+#[derive(Debug)]
+pub struct Inventory {
+	slots: Vec<Option<ItemStack>>,
+}
+
+impl Inventory {
+	/// the most blocks in a slot.
+	pub const MAX_STACK: u32 = 64;
+
+	pub fn new(slot_count: usize) -> Self {
+		Self { slots: vec![None; slot_count] }
+	}
+
+	/// a full stack of each block of the registry that can be placed, in id order, as long as there are slots.
+	pub fn from_registry(registry: &BlockRegistry, slot_count: usize) -> Self {
+		let mut inventory = Self::new(slot_count);
+		for info in registry.iter().filter(|info| info.textures.is_some()).take(slot_count) {
+			inventory.add(info.id, Self::MAX_STACK);
+		}
+		inventory
+	}
+
+	pub fn slots(&self) -> &[Option<ItemStack>] {
+		&self.slots
+	}
+
+	/// `None` if the slot is empty or past the end.
+	pub fn slot(&self, index: usize) -> Option<ItemStack> {
+		self.slots.get(index).copied().flatten()
+	}
+
+	/// fills up the stacks of `block` first, then the empty slots.
+	/// returns how many of the blocks didn't fit.
+	pub fn add(&mut self, block: u16, mut count: u32) -> u32 {
+		for stack in self.slots.iter_mut().flatten().filter(|stack| stack.block == block) {
+			let added = count.min(Self::MAX_STACK - stack.count);
+			stack.count += added;
+			count -= added;
+		}
+
+		for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+			if count == 0 {
+				break;
+			}
+			let added = count.min(Self::MAX_STACK);
+			*slot = Some(ItemStack { block, count: added });
+			count -= added;
+		}
+
+		count
+	}
+
+	/// removes one block from the slot, and returns it. `None` if the slot is empty.
+	pub fn take_one(&mut self, index: usize) -> Option<u16> {
+		let slot = self.slots.get_mut(index)?;
+		let stack = slot.as_mut()?;
+		let block = stack.block;
+		stack.count -= 1;
+		if stack.count == 0 {
+			*slot = None;
+		}
+		Some(block)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn added_blocks_stack() {
+		let mut inventory = Inventory::new(3);
+		assert_eq!(inventory.add(1, 10), 0);
+		assert_eq!(inventory.add(2, 5), 0);
+		assert_eq!(inventory.add(1, 60), 0);
+		// the first stack is filled up before a new one is started.
+		assert_eq!(inventory.slots(), [
+			Some(ItemStack { block: 1, count: Inventory::MAX_STACK }),
+			Some(ItemStack { block: 2, count: 5 }),
+			Some(ItemStack { block: 1, count: 6 }),
+		]);
+
+		// what doesn't fit is left over.
+		assert_eq!(inventory.add(3, 1), 1);
+		assert_eq!(inventory.add(1, 100), 100 - (Inventory::MAX_STACK - 6));
+		assert_eq!(inventory.slot(2), Some(ItemStack { block: 1, count: Inventory::MAX_STACK }));
+	}
+
+	#[test]
+	fn placing_takes_from_the_slot() {
+		let mut inventory = Inventory::new(2);
+		inventory.add(7, 2);
+		assert_eq!(inventory.take_one(0), Some(7));
+		assert_eq!(inventory.slot(0), Some(ItemStack { block: 7, count: 1 }));
+		assert_eq!(inventory.take_one(0), Some(7));
+		assert_eq!(inventory.slot(0), None);
+
+		// empty slots, and slots past the end, can't be placed from.
+		assert_eq!(inventory.take_one(0), None);
+		assert_eq!(inventory.take_one(1), None);
+		assert_eq!(inventory.take_one(5), None);
+	}
+}
@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::math::*;
+
+use super::{chunk::{self, Chunk, Block, MAX_LIGHT}, registry::BlockRegistry, Dir};
+
+/// see [`chunk::ChunkData::compute_light`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channel {
+	Block,
+	Sky,
+}
+
+/// the light of the loaded chunks, by global block position.
+struct LoadedLight<'a> {
+	chunks: &'a mut HashMap<Vec3i32, Chunk>,
+	registry: &'a BlockRegistry,
+	/// chunks that have a block next to one whose light changed, so their meshes are out of date.
+	remesh: HashSet<Vec3i32>,
+}
+
+impl LoadedLight<'_> {
+	fn block(&self, global: Vec3i32) -> Option<Block> {
+		let chunk = self.chunks.get(&chunk::block_global_to_chunk(global))?;
+		chunk.data.get_block(chunk::block_global_to_block_local(global)).copied()
+	}
+
+	/// `None` in chunks that aren't loaded.
+	fn get(&self, channel: Channel, global: Vec3i32) -> Option<u8> {
+		let data = &self.chunks.get(&chunk::block_global_to_chunk(global))?.data;
+		let local = chunk::block_global_to_block_local(global);
+		match channel {
+			Channel::Block => data.get_light(local),
+			Channel::Sky => data.get_skylight(local),
+		}
+	}
+
+	fn set(&mut self, channel: Channel, global: Vec3i32, level: u8) {
+		let position = chunk::block_global_to_chunk(global);
+		let local = chunk::block_global_to_block_local(global);
+		let Some(chunk) = self.chunks.get_mut(&position) else { return };
+		match channel {
+			Channel::Block => chunk.data_mut().set_light(local, level),
+			Channel::Sky => chunk.data_mut().set_skylight(local, level),
+		}
+		// the vertices sample the light of the blocks next to them.
+		self.remesh.extend(chunk::touched_chunk_offsets(local, 1).into_iter().map(|offset| position + offset));
+	}
+
+	/// the light `level` spreads into the next block in direction `dir` with this level.
+	fn spread(channel: Channel, level: u8, dir: Dir) -> u8 {
+		// sky light goes straight down without getting weaker.
+		if channel == Channel::Sky && dir == Dir::NY && level == MAX_LIGHT {
+			MAX_LIGHT
+		} else {
+			level.saturating_sub(1)
+		}
+	}
+
+	/// removes the light that could have come through `global`, then fills the
+	/// darkened blocks in again from the light around them (and from `global` itself).
+	fn relight(&mut self, channel: Channel, global: Vec3i32) {
+		let Some(old_level) = self.get(channel, global) else { return };
+		let mut darkened = VecDeque::from([(global, old_level)]);
+		let mut lit = VecDeque::new();
+		self.set(channel, global, 0);
+
+		while let Some((position, level)) = darkened.pop_front() {
+			for &dir in Dir::all() {
+				let next = position + dir.normal::<i32>();
+				let Some(next_level) = self.get(channel, next) else { continue };
+				if next_level == 0 {
+					continue;
+				}
+
+				if next_level < level || Self::spread(channel, level, dir) == MAX_LIGHT {
+					self.set(channel, next, 0);
+					darkened.push_back((next, next_level));
+				} else {
+					// lit from somewhere else, so it can light the darkened blocks again.
+					lit.push_back(next);
+				}
+			}
+		}
+
+		if channel == Channel::Block {
+			let emitted = self.block(global).map_or(0, |block| block.info(self.registry).light.min(MAX_LIGHT));
+			if emitted > 0 {
+				self.set(channel, global, emitted);
+				lit.push_back(global);
+			}
+		}
+		// a removed block lets the light of its neighbors in.
+		lit.extend(Dir::all().iter().map(|dir| global + dir.normal::<i32>()));
+
+		while let Some(position) = lit.pop_front() {
+			let Some(level) = self.get(channel, position) else { continue };
+			for &dir in Dir::all() {
+				let next = position + dir.normal::<i32>();
+				let next_level = Self::spread(channel, level, dir);
+				if next_level == 0 || self.block(next).map_or(true, |block| block.is_opaque(self.registry)) {
+					continue;
+				}
+
+				if self.get(channel, next).is_some_and(|current| current < next_level) {
+					self.set(channel, next, next_level);
+					lit.push_back(next);
+				}
+			}
+		}
+	}
+}
+
+/// updates the light after the block at `global` changed, only visiting the blocks whose light
+/// changes (which can be any number of chunks below it, for sky light). the light stops at
+/// the chunks that aren't loaded, they're lit when they're loaded.
+/// returns the loaded chunks that have to be remeshed, since the light of their vertices changed.
+pub fn relight_block(chunks: &mut HashMap<Vec3i32, Chunk>, registry: &BlockRegistry, global: Vec3i32) -> HashSet<Vec3i32> {
+	let mut light = LoadedLight { chunks, registry, remesh: HashSet::new() };
+	light.relight(Channel::Block, global);
+	light.relight(Channel::Sky, global);
+
+	let LoadedLight { chunks, remesh, .. } = light;
+	remesh.into_iter().filter(|position| chunks.contains_key(position)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::{chunk::{BlockId, ChunkData, CHUNK_SIZE}, registry::tests::registry};
+
+	fn block(id: BlockId) -> Block {
+		Block { id: id as u16, state: 0 }
+	}
+
+	fn set_block(chunks: &mut HashMap<Vec3i32, Chunk>, global: Vec3i32, block: Block) {
+		let chunk = chunks.get_mut(&chunk::block_global_to_chunk(global)).unwrap();
+		chunk.set_block(chunk::block_global_to_block_local(global), block);
+	}
+
+	/// lights all of the chunks with [`ChunkData::compute_light`], from top to bottom.
+	fn light_from_scratch(chunks: &mut HashMap<Vec3i32, Chunk>, registry: &BlockRegistry, open_above: bool) {
+		let mut positions: Vec<Vec3i32> = chunks.keys().copied().collect();
+		positions.sort_by_key(|position| -position.y);
+		for position in positions {
+			let mut chunk = chunks.remove(&position).unwrap();
+			let light = chunk.data.compute_light(position, chunks, registry, open_above);
+			chunk.replace_light(light, registry);
+			chunks.insert(position, chunk);
+		}
+	}
+
+	fn light_at(chunks: &HashMap<Vec3i32, Chunk>, channel: Channel, global: Vec3i32) -> u8 {
+		let data = &chunks[&chunk::block_global_to_chunk(global)].data;
+		let local = chunk::block_global_to_block_local(global);
+		match channel {
+			Channel::Block => data.get_light(local).unwrap(),
+			Channel::Sky => data.get_skylight(local).unwrap(),
+		}
+	}
+
+	fn single_chunk() -> HashMap<Vec3i32, Chunk> {
+		HashMap::from([(vec3(0, 0, 0), Chunk::new(vec3(0, 0, 0), ChunkData::new()))])
+	}
+
+	#[test]
+	fn lamp_light_fades_with_distance() {
+		let registry = registry();
+		let mut chunks = single_chunk();
+		set_block(&mut chunks, vec3(8, 8, 8), block(BlockId::Lamp));
+		light_from_scratch(&mut chunks, &registry, false);
+
+		assert_eq!(light_at(&chunks, Channel::Block, vec3(8, 8, 8)), 15);
+		assert_eq!(light_at(&chunks, Channel::Block, vec3(11, 8, 8)), 12);
+		assert_eq!(light_at(&chunks, Channel::Block, vec3(9, 10, 6)), 10);
+		// the chunk above isn't loaded, so there's no sky light yet.
+		assert_eq!(light_at(&chunks, Channel::Sky, vec3(8, 15, 8)), 0);
+	}
+
+	#[test]
+	fn walls_block_light() {
+		let registry = registry();
+		let mut chunks = single_chunk();
+		for y in 0..CHUNK_SIZE.y as i32 {
+			for z in 0..CHUNK_SIZE.z as i32 {
+				set_block(&mut chunks, vec3(10, y, z), block(BlockId::Stone));
+			}
+		}
+		set_block(&mut chunks, vec3(8, 8, 8), block(BlockId::Lamp));
+		light_from_scratch(&mut chunks, &registry, false);
+
+		assert_eq!(light_at(&chunks, Channel::Block, vec3(9, 8, 8)), 14);
+		assert_eq!(light_at(&chunks, Channel::Block, vec3(11, 8, 8)), 0);
+	}
+
+	#[test]
+	fn edits_relight_like_from_scratch() {
+		let registry = registry();
+		let mut chunks = HashMap::new();
+		for position in [vec3(0, 0, 0), vec3(0, -1, 0)] {
+			chunks.insert(position, Chunk::new(position, ChunkData::new()));
+		}
+		// the ground is at y = -9.
+		for y in -32..-8 {
+			for z in 0..CHUNK_SIZE.z as i32 {
+				for x in 0..CHUNK_SIZE.x as i32 {
+					set_block(&mut chunks, vec3(x, y, z), block(BlockId::Stone));
+				}
+			}
+		}
+		light_from_scratch(&mut chunks, &registry, true);
+
+		let edits = [
+			// a roof, which darkens the column under it.
+			(vec3(8, -4, 8), BlockId::Stone),
+			(vec3(3, -6, 3), BlockId::Lamp),
+			// a hole in the ground, under the sky.
+			(vec3(5, -9, 5), BlockId::Air),
+			(vec3(3, -6, 3), BlockId::Air),
+			(vec3(8, -4, 8), BlockId::Air),
+		];
+		for (global, id) in edits {
+			set_block(&mut chunks, global, block(id));
+			relight_block(&mut chunks, &registry, global);
+
+			let mut expected: HashMap<Vec3i32, Chunk> = chunks.iter()
+				.map(|(position, chunk)| (*position, Chunk::new(*position, (*chunk.data).clone())))
+				.collect();
+			light_from_scratch(&mut expected, &registry, true);
+			for position in chunks.keys() {
+				assert!(chunks[position].data.light == expected[position].data.light, "block light after {global}");
+				assert!(chunks[position].data.skylight == expected[position].data.skylight, "sky light after {global}");
+			}
+		}
+	}
+
+	#[test]
+	fn roofs_shade_chunks_far_below() {
+		let registry = registry();
+		let mut chunks = HashMap::new();
+		for y in -2..=0 {
+			chunks.insert(vec3(0, y, 0), Chunk::new(vec3(0, y, 0), ChunkData::new()));
+		}
+		light_from_scratch(&mut chunks, &registry, true);
+		assert_eq!(light_at(&chunks, Channel::Sky, vec3(5, -50, 5)), MAX_LIGHT);
+
+		set_block(&mut chunks, vec3(5, 20, 5), block(BlockId::Stone));
+		let remesh = relight_block(&mut chunks, &registry, vec3(5, 20, 5));
+		// lit from the side now.
+		assert_eq!(light_at(&chunks, Channel::Sky, vec3(5, -50, 5)), MAX_LIGHT - 1);
+		assert_eq!(light_at(&chunks, Channel::Sky, vec3(6, -50, 5)), MAX_LIGHT);
+		assert!(remesh.contains(&vec3(0, -2, 0)));
+	}
+}
@@ -0,0 +1,240 @@
+use std::{collections::HashMap, sync::{Arc, Mutex, mpsc}, thread};
+
+use crate::math::*;
+
+use super::{chunk::{Chunk, ChunkData, ChunkMeshData, ChunkNeighborhood, MeshEvent, LOD_LEVEL}, registry::BlockRegistry};
+
+struct MeshJob {
+	position: Vec3i32,
+	generation: u64,
+	/// whether to build the lod mesh too, see [`Chunk::update_mesh`].
+	lod: bool,
+	/// snapshot of the chunk and its neighbors, see [`ChunkNeighborhood::from_chunks`].
+	chunks: [Option<Arc<ChunkData>>; 27],
+}
+
+pub struct MeshResult {
+	pub position: Vec3i32,
+	generation: u64,
+	pub mesh_data: ChunkMeshData,
+	/// how long the worker took to mesh the chunk.
+	pub duration: std::time::Duration,
+}
+
+impl MeshResult {
+	pub fn event(&self) -> MeshEvent {
+		MeshEvent { position: self.position, vertex_count: self.mesh_data.vertex_count(), duration: self.duration }
+	}
+}
+
+/// generates chunk meshes on a pool of worker threads.
+/// the meshes are uploaded on the main thread in [`ChunkMesher::receive`].
+pub struct ChunkMesher {
+	jobs: Option<mpsc::Sender<MeshJob>>,
+	results: mpsc::Receiver<MeshResult>,
+	workers: Vec<thread::JoinHandle<()>>,
+	/// the last generation requested for every chunk, older results are stale.
+	generations: HashMap<Vec3i32, u64>,
+	next_generation: u64,
+	/// finished, but over the budget of [`ChunkMesher::receive`] so far.
+	ready: Vec<MeshResult>,
+	/// the meshes of the chunks nearest to it are handed out first.
+	center: Vec3i32,
+}
+
+impl ChunkMesher {
+	pub fn new(registry: Arc<BlockRegistry>) -> Self {
+		let (jobs, job_receiver) = mpsc::channel::<MeshJob>();
+		let (result_sender, results) = mpsc::channel();
+		let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+		// leave a core for the main thread.
+		let worker_count = thread::available_parallelism().map_or(1, |n| n.get().saturating_sub(1).max(1));
+		let workers = (0..worker_count).map(|i| {
+			let job_receiver = job_receiver.clone();
+			let result_sender = result_sender.clone();
+			let registry = registry.clone();
+			thread::Builder::new()
+				.name(format!("mesher {}", i))
+				.spawn(move || loop {
+					// the lock is only held while waiting for a job, not while meshing.
+					let job = match job_receiver.lock().unwrap().recv() {
+						Ok(job) => job,
+						Err(_) => break, // the mesher was dropped.
+					};
+					let start = std::time::Instant::now();
+					let mesh_data = mesh_job(&job, &registry);
+					let result = MeshResult { position: job.position, generation: job.generation, mesh_data, duration: start.elapsed() };
+					if result_sender.send(result).is_err() {
+						break;
+					}
+				})
+				.expect("failed to spawn a mesher thread")
+		}).collect();
+
+		Self {
+			jobs: Some(jobs),
+			results,
+			workers,
+			generations: HashMap::new(),
+			next_generation: 0,
+			ready: Vec::new(),
+			center: vec3(0, 0, 0),
+		}
+	}
+
+	pub fn set_center(&mut self, center: Vec3i32) {
+		self.center = center;
+	}
+
+	fn bump_generation(&mut self, position: Vec3i32) -> u64 {
+		let generation = self.next_generation;
+		self.next_generation += 1;
+		self.generations.insert(position, generation);
+		generation
+	}
+
+	/// queues the chunk at `position` for meshing, replacing any mesh that's still in flight.
+	/// the lod mesh is only built with `lod`, see [`Chunk::update_mesh`].
+	pub fn request(&mut self, position: Vec3i32, chunks: &HashMap<Vec3i32, Chunk>, lod: bool) {
+		if !chunks.contains_key(&position) { return }
+		let generation = self.bump_generation(position);
+		let chunks = ChunkNeighborhood::positions(position)
+			.map(|position| chunks.get(&position).map(|chunk| chunk.data.clone()));
+		let job = MeshJob { position, generation, lod, chunks };
+		self.jobs.as_ref().unwrap().send(job).expect("all mesher threads have stopped");
+	}
+
+	/// drops the results of any requests for `position` that are still in flight,
+	/// e.g. when the chunk was meshed inline or unloaded.
+	pub fn cancel(&mut self, position: Vec3i32) {
+		self.generations.remove(&position);
+	}
+
+	/// returns up to `budget` of the finished meshes that are still current, the nearest first, without blocking.
+	/// the rest are kept for the next calls.
+	pub fn receive(&mut self, budget: usize) -> Vec<MeshResult> {
+		self.ready.extend(self.results.try_iter());
+		// cancelled or requested again since.
+		let generations = &self.generations;
+		self.ready.retain(|result| generations.get(&result.position) == Some(&result.generation));
+
+		let center = self.center;
+		// furthest first, so that the nearest are split off the end.
+		self.ready.sort_by_key(|result| std::cmp::Reverse(((result.position - center).mag_squared(), result.position.0)));
+		let finished = self.ready.split_off(self.ready.len().saturating_sub(budget));
+		for result in &finished {
+			self.generations.remove(&result.position);
+		}
+		finished
+	}
+
+	/// whether the chunk at `position` is waiting for its mesh.
+	pub fn is_pending(&self, position: Vec3i32) -> bool {
+		self.generations.contains_key(&position)
+	}
+
+	/// number of meshes that haven't been received yet.
+	pub fn pending(&self) -> usize {
+		self.generations.len()
+	}
+}
+
+impl Drop for ChunkMesher {
+	fn drop(&mut self) {
+		// closing the channel stops the workers once they finish their current job.
+		self.jobs = None;
+		for worker in self.workers.drain(..) {
+			let _ = worker.join();
+		}
+	}
+}
+
+fn mesh_job(job: &MeshJob, registry: &BlockRegistry) -> ChunkMeshData {
+	let neighborhood = ChunkNeighborhood::from_chunks(std::array::from_fn(|i| job.chunks[i].as_deref()));
+	let mut mesh_data = ChunkData::generate_mesh(&neighborhood, registry);
+	mesh_data.lod = job.lod.then(|| ChunkData::generate_mesh_lod(&neighborhood, LOD_LEVEL, registry));
+	mesh_data
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::{chunk::{Block, BlockId, MeshData}, registry::tests::registry};
+
+	fn same_mesh(a: &MeshData, b: &MeshData) -> bool {
+		bytemuck::cast_slice::<_, u8>(&a.vertices) == bytemuck::cast_slice::<_, u8>(&b.vertices) && a.indices == b.indices
+	}
+
+	#[test]
+	fn workers_mesh_like_inline() {
+		let registry = Arc::new(registry());
+		let mut chunks = HashMap::new();
+		for position in [vec3(0, 0, 0), vec3(1, 0, 0), vec3(0, -1, 0)] {
+			chunks.insert(position, Chunk::new(position, ChunkData::new()));
+		}
+		let stone = Block { id: BlockId::Stone as u16, state: 0 };
+		let glass = Block { id: BlockId::Glass as u16, state: 0 };
+		let center = chunks.get_mut(&vec3(0, 0, 0)).unwrap();
+		center.set_block(vec3(0, 0, 0), stone);
+		center.set_block(vec3(31, 5, 5), stone);
+		center.set_block(vec3(4, 4, 4), glass);
+		chunks.get_mut(&vec3(1, 0, 0)).unwrap().set_block(vec3(0, 5, 6), stone);
+
+		let data = &chunks[&vec3(0, 0, 0)].data;
+		let inline = ChunkData::generate_mesh(&ChunkNeighborhood::new(data, vec3(0, 0, 0), &chunks), &registry);
+
+		let mut mesher = ChunkMesher::new(registry.clone());
+		mesher.request(vec3(0, 0, 0), &chunks, false);
+		let start = std::time::Instant::now();
+		let result = loop {
+			if let Some(result) = mesher.receive(1).pop() {
+				break result;
+			}
+			assert!(start.elapsed() < std::time::Duration::from_secs(10), "the mesher didn't finish");
+			thread::yield_now();
+		};
+
+		assert_eq!(result.position, vec3(0, 0, 0));
+		assert_eq!(result.event().vertex_count, inline.vertex_count());
+		assert!(!inline.opaque.vertices.is_empty() && !inline.transparent.vertices.is_empty());
+		assert!(same_mesh(&inline.opaque, &result.mesh_data.opaque));
+		assert!(same_mesh(&inline.transparent, &result.mesh_data.transparent));
+		assert!(result.mesh_data.lod.is_none());
+		assert_eq!(mesher.pending(), 0);
+	}
+
+	#[test]
+	fn the_nearest_meshes_are_received_first() {
+		let registry = Arc::new(registry());
+		let positions = [vec3(0, 0, 0), vec3(3, 0, 0), vec3(0, -1, 0), vec3(1, 1, 1), vec3(-2, 0, 0)];
+		let chunks: HashMap<_, _> = positions.iter().map(|&position| (position, Chunk::new(position, ChunkData::new()))).collect();
+
+		let mut mesher = ChunkMesher::new(registry);
+		mesher.set_center(vec3(3, 0, 0));
+		for position in positions {
+			mesher.request(position, &chunks, false);
+		}
+		// cancelled ones aren't received.
+		mesher.cancel(vec3(0, -1, 0));
+
+		let start = std::time::Instant::now();
+		while mesher.ready.len() < positions.len() - 1 {
+			assert!(mesher.receive(0).is_empty());
+			assert!(start.elapsed() < std::time::Duration::from_secs(10), "the mesher didn't finish");
+			thread::yield_now();
+		}
+
+		let mut received = vec![];
+		for _ in 0..2 {
+			let results = mesher.receive(2);
+			assert_eq!(results.len(), 2);
+			let mut positions: Vec<_> = results.iter().map(|result| result.position).collect();
+			positions.sort_by_key(|position| position.0);
+			received.push(positions);
+		}
+		assert_eq!(received, [vec![vec3(1, 1, 1), vec3(3, 0, 0)], vec![vec3(-2, 0, 0), vec3(0, 0, 0)]]);
+		assert!(mesher.receive(2).is_empty());
+		assert_eq!(mesher.pending(), 0);
+	}
+}
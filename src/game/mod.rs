@@ -1,6 +1,5 @@
-use std::collections::{HashMap, HashSet};
-use winit::keyboard::KeyCode;
-use crate::{state::State, gfx, UpdateContext, math::*};
+use std::{collections::{BTreeSet, HashMap, HashSet}, cell::Cell, sync::Arc};
+use crate::{state::State, gfx, UpdateContext, math::*, bindings::Action, error::VoxleError};
 
 use self::{renderer::{GameRenderContext, ui::UiBuilder}, chunk::Block};
 
@@ -9,8 +8,34 @@ mod renderer;
 mod camera;
 mod worldgen;
 mod texture;
+mod registry;
+mod mesher;
+mod world;
+mod physics;
+mod time;
+mod hotbar;
+mod inventory;
+mod hud;
+mod breaking;
+mod export;
+mod config;
+mod light;
 
-#[derive(Debug, Clone, Copy)]
+pub use self::worldgen::seed_from_str;
+
+/// the seed of the world when none is given.
+pub const DEFAULT_SEED: u32 = 69;
+
+/// how the chunks that haven't been saved are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldType {
+	/// the noise terrain, picked by the seed.
+	Default,
+	/// see [`worldgen::FlatWorldGen`], the same for every seed.
+	Flat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Dir {
 	// do not change.
 	PX = 0, NX = 1,
@@ -110,6 +135,178 @@ impl BlockTarget {
 	pub fn to_global(self) -> Vec3i32 {
 		self.chunk * chunk::CHUNK_SIZE.each_as() + self.block
 	}
+
+	/// the chunk and local position of the block placed against `face`,
+	/// which is in the neighboring chunk if the face is on a chunk boundary.
+	pub fn placement(self) -> (Vec3i32, Vec3i32) {
+		let global = self.to_global() + self.face.normal();
+		(chunk::block_global_to_chunk(global), chunk::block_global_to_block_local(global))
+	}
+}
+
+/// chunks are loaded in a cylinder around the current chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RenderDistance {
+	/// radius of the cylinder, in chunks.
+	xz: i32,
+	/// half of the height of the cylinder, in chunks.
+	y: i32,
+}
+
+impl RenderDistance {
+	/// whether a chunk offset from the center should be loaded.
+	fn contains(self, offset: Vec3i32) -> bool {
+		offset.x * offset.x + offset.z * offset.z < self.xz * self.xz && offset.y.abs() <= self.y
+	}
+
+	/// `margin` more chunks in every direction.
+	fn expanded(self, margin: i32) -> Self {
+		Self { xz: self.xz + margin, y: self.y + margin }
+	}
+}
+
+/// distance fog, the range is relative to the render distance,
+/// so that chunks fade out before they're unloaded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Fog {
+	color: [f32; 3],
+	/// fraction of the render distance where the fog starts.
+	start: f32,
+	/// fraction of the render distance where the fog is fully opaque.
+	end: f32,
+}
+
+impl Fog {
+	/// start and end in blocks.
+	fn range(self, render_distance: RenderDistance) -> (f32, f32) {
+		let radius = (render_distance.xz * chunk::CHUNK_SIZE.x as i32) as f32;
+		(self.start * radius, self.end * radius)
+	}
+}
+
+/// the chunks meshed during a frame, inline or on the mesher threads.
+#[derive(Debug, Clone, Copy, Default)]
+struct MeshStats {
+	chunks: usize,
+	vertices: usize,
+	duration: std::time::Duration,
+}
+
+impl MeshStats {
+	fn add(&mut self, event: &chunk::MeshEvent) {
+		self.chunks += 1;
+		self.vertices += event.vertex_count;
+		self.duration += event.duration;
+	}
+}
+
+/// the loaded chunks and what's needed to load more of them, borrowed from the [`GameState`],
+/// so that they can be loaded and edited without a renderer.
+struct LoadedChunks<'a> {
+	chunks: &'a mut HashMap<Vec3i32, chunk::Chunk>,
+	registry: &'a registry::BlockRegistry,
+	world: &'a world::World,
+	worldgen: &'a dyn worldgen::WorldGenerator,
+	worldgen_queue: &'a mut worldgen::WorldGenQueue,
+	/// what the chunks are loaded for, see [`LoadedChunks::update_chunk_light`].
+	render_distance: RenderDistance,
+	center: Vec3i32,
+	/// the chunks changed by the edits, they're meshed again before they're drawn.
+	remesh: &'a mut HashSet<Vec3i32>,
+	/// the newly loaded chunks and their neighbors, they're meshed on the worker threads,
+	/// so that crossing chunk boundaries doesn't hitch.
+	mesh_requests: &'a mut HashSet<Vec3i32>,
+	/// reset when the chunks or their emitters change, see [`GameState::update_point_lights`].
+	point_light_candidates: &'a mut Option<(Vec3i32, Vec<renderer::chunk::PointLight>)>,
+}
+
+impl LoadedChunks<'_> {
+	/// lights a newly loaded chunk, the chunks above it that aren't going to be loaded are open to the sky.
+	fn update_chunk_light(&mut self, pos: Vec3i32) {
+		let open_above = !self.render_distance.contains(pos + vec3(0, 1, 0) - self.center);
+		// the neighbors are read from `chunks`, so the chunk can only be borrowed mutably afterwards.
+		let light = self.chunks[&pos].data.compute_light(pos, self.chunks, self.registry, open_above);
+		self.chunks.get_mut(&pos).unwrap().replace_light(light, self.registry);
+	}
+
+	/// marks the chunks affected by an edit at `local` in the chunk at `pos`:
+	/// the neighbors that share the edited block's faces, edges or corners (for AO),
+	/// and the chunks where the light changed.
+	fn update_chunk(&mut self, pos: Vec3i32, local: Vec3i32) {
+		let global = pos * chunk::CHUNK_SIZE.each_as() + local;
+		self.remesh.extend(light::relight_block(self.chunks, self.registry, global));
+		self.remesh.extend(chunk::touched_chunk_offsets(local, 1).into_iter()
+			.map(|offset| pos + offset)
+			.filter(|pos| self.chunks.contains_key(pos)));
+	}
+
+	/// does nothing if the chunk isn't loaded or the block didn't change.
+	/// returns the block that was replaced, `None` if nothing changed.
+	fn set_block(&mut self, pos: Vec3i32, local: Vec3i32, block: Block) -> Option<Block> {
+		let Some(chunk) = self.chunks.get_mut(&pos) else { return None };
+		let Some(old_block) = chunk.data.get_block(local).copied() else { return None };
+		if !chunk.set_block(local, block) { return None }
+
+		if old_block.info(self.registry).light != block.info(self.registry).light {
+			chunk.update_emitters(self.registry);
+			*self.point_light_candidates = None;
+		}
+		self.update_chunk(pos, local);
+		Some(old_block)
+	}
+
+	/// loads (or generates) the chunk at `pos` on this thread, if it isn't loaded yet,
+	/// e.g. to place a block next to the loaded chunks. returns whether the chunk is loaded.
+	fn load_chunk_now(&mut self, pos: Vec3i32) -> bool {
+		if self.chunks.contains_key(&pos) {
+			return true;
+		}
+
+		let Some(chunk) = worldgen::load_or_generate_chunk(self.world, self.worldgen, pos) else { return false };
+		// the queued copy would replace this one, along with its edits.
+		self.worldgen_queue.retain(|position| position != pos);
+		self.insert_chunks([(pos, chunk)]);
+		true
+	}
+
+	/// relights the new chunks and their neighbors, and requests their meshes.
+	fn insert_chunks(&mut self, chunks: impl IntoIterator<Item = (Vec3i32, chunk::Chunk)>) {
+		let mut to_be_updated = HashSet::new();
+
+		for (abs_pos, chunk) in chunks {
+			self.chunks.insert(abs_pos, chunk);
+			to_be_updated.insert(abs_pos);
+			*self.point_light_candidates = None;
+
+			// update neighbor meshes
+			for dir in Dir::all() {
+				let normal = dir.normal::<i32>();
+				let abs_pos = abs_pos + normal;
+				if self.chunks.contains_key(&abs_pos) {
+					to_be_updated.insert(abs_pos);
+				}
+			}
+		}
+
+		// sky light comes from above, so the chunks are lit from the top down.
+		let mut to_be_lit: BTreeSet<(std::cmp::Reverse<i32>, [i32; 3])> = to_be_updated.iter()
+			.map(|pos| (std::cmp::Reverse(pos.y), pos.0))
+			.collect();
+		while let Some((_, pos)) = to_be_lit.pop_first() {
+			let pos = Vector(pos);
+			let old_bottom = self.chunks[&pos].data.bottom_skylight().to_vec();
+			self.update_chunk_light(pos);
+
+			// the sky light going down changed, e.g. a new chunk above opened up the sky, so it can reach any depth.
+			let below = pos - vec3(0, 1, 0);
+			if self.chunks.contains_key(&below) && self.chunks[&pos].data.bottom_skylight() != old_bottom {
+				to_be_lit.insert((std::cmp::Reverse(below.y), below.0));
+				to_be_updated.insert(below);
+			}
+		}
+
+		self.mesh_requests.extend(to_be_updated);
+	}
 }
 
 pub struct GameState {
@@ -117,113 +314,530 @@ pub struct GameState {
 	chunks: HashMap<Vec3i32, chunk::Chunk>,
 	renderer: renderer::GameRenderer,
 	camera_controller: camera::CameraController,
-	render_distance: i32,
+	/// applied to the camera controller in `update`, a `Cell` so that the debug ui can change it.
+	camera_settings: Cell<camera::CameraSettings>,
+	/// a `Cell` so that the debug ui can change it.
+	render_distance: Cell<RenderDistance>,
+	/// the render distance that the loaded chunks correspond to.
+	loaded_render_distance: RenderDistance,
 	current_chunk_position: Vec3i32,
 	render_wireframe: bool,
-	worldgen: worldgen::WorldGen,
+	/// set by the debug ui, pops the game off of the state stack, back to the menu.
+	quit: Cell<bool>,
+	/// shared with the worldgen queue's threads.
+	worldgen: Arc<dyn worldgen::WorldGenerator>,
+	worldgen_queue: worldgen::WorldGenQueue,
+	world: Arc<world::World>,
+	/// writes the chunks queued in `world` in the background.
+	saver: world::ChunkSaver,
 	target_block: Option<BlockTarget>,
-	block_textures: texture::LoadedTextures
+	breaking: breaking::BlockBreaking,
+	block_registry: Arc<registry::BlockRegistry>,
+	mesher: mesher::ChunkMesher,
+	/// reset at the start of every `update`.
+	mesh_stats: MeshStats,
+	/// when each loaded chunk was last inside of the render distance, in calls to `generate_chunks`.
+	/// the chunks outside of it are kept for a bit (see [`GameState::CHUNK_CACHE_MARGIN`]), the least recently needed are unloaded first.
+	chunk_last_needed: HashMap<Vec3i32, u64>,
+	generate_chunks_count: u64,
+	/// how many generated chunks are inserted, and how many meshes are uploaded per frame at most.
+	/// the rest wait for the next frames, the nearest chunks first.
+	chunk_budget: Cell<usize>,
+	mesh_budget: Cell<usize>,
+	inventory: inventory::Inventory,
+	/// shows the first slots of the inventory.
+	hotbar: hotbar::Hotbar,
+	crosshair: hud::Crosshair,
+	/// chunks inside of the view frustum, updated every frame.
+	/// sorted front-to-back if `sort_chunks` is set.
+	visible_chunks: Vec<Vec3i32>,
+	/// a `Cell` so that the debug ui can toggle it.
+	sort_chunks: Cell<bool>,
+	render_sky: Cell<bool>,
+	/// chunks further than this (horizontally, in chunks) are drawn with their LOD mesh.
+	lod_distance: Cell<f32>,
+	/// draws the depth of the opaque chunks before shading them, see the "depth-prepass" node.
+	depth_prepass: Cell<bool>,
+	/// drawn over the targeted face, rgba (not premultiplied), 0 alpha hides it.
+	face_highlight: Cell<[f32; 4]>,
+	fog: Cell<Fog>,
+	time_of_day: time::TimeOfDay,
+	/// how many times faster than real time the day passes.
+	time_scale: Cell<f32>,
+	time_paused: Cell<bool>,
+	shadows: Cell<bool>,
+	/// applied to the chunk renderer in `update`.
+	texture_filtering: Cell<renderer::chunk::TextureFiltering>,
+	/// the baked ambient occlusion is scaled by `ao_strength`, or disabled if `ao` isn't set.
+	ao: Cell<bool>,
+	ao_strength: Cell<f32>,
+	/// the renderer's shadow map is resized to this in `update`.
+	shadow_map_size: Cell<u32>,
+	/// applied to the surface in `update`, reset to the mode that was actually picked.
+	present_mode: Cell<wgpu::PresentMode>,
+	/// chunks inside of the sun's view, updated every frame.
+	shadow_casters: Vec<Vec3i32>,
+	/// the emitters in the chunks around the chunk they were collected for, the closest are lit every frame.
+	/// reset to `None` when the chunks or their emitters change.
+	point_light_candidates: Option<(Vec3i32, Vec<renderer::chunk::PointLight>)>,
+	/// see [`LoadedChunks::remesh`] and [`LoadedChunks::mesh_requests`], meshed in `update`.
+	remesh: HashSet<Vec3i32>,
+	mesh_requests: HashSet<Vec3i32>,
+	/// cpu time of the parts of `update`, shown in the debug ui.
+	cpu_timings: gfx::profiler::Timings,
+	/// copied from the gpu profiler in `update`, since `ui` doesn't get the `Gfx`.
+	/// `None` if timestamp queries aren't supported.
+	gpu_timings: Option<Vec<(String, f32)>>,
+	/// `None` if the watcher couldn't be started.
+	#[cfg(feature = "hot-reload")]
+	texture_watcher: Option<renderer::reload::DirectoryWatcher>,
 }
 
 impl GameState {
-	pub fn new(gfx: &gfx::Gfx) -> Self {
+	const SHADOW_MAP_SIZES: [u32; 4] = [512, 1024, 2048, 4096];
+	/// the present modes that can be picked in the debug ui, unsupported ones fall back to vsync.
+	const PRESENT_MODES: [(wgpu::PresentMode, &str); 3] = [
+		(wgpu::PresentMode::Fifo, "vsync"),
+		(wgpu::PresentMode::Mailbox, "mailbox"),
+		(wgpu::PresentMode::Immediate, "immediate"),
+	];
+	/// falls back to no msaa if it isn't supported, see [`renderer::GameRenderer::new`].
+	const MSAA_SAMPLES: u32 = 4;
+	/// half of the size of the area around the camera that gets shadows, in blocks.
+	const SHADOW_RADIUS: f32 = 96.0;
+	/// color of the point lights of emissive blocks.
+	const EMITTER_COLOR: Vec3f32 = Vector([1.0, 0.75, 0.45]);
+
+	/// the horizontal render distance, in chunks.
+	pub fn set_render_distance(&mut self, xz: i32) {
+		self.render_distance.set(RenderDistance { xz, ..self.render_distance.get() });
+	}
+
+	/// the first ones are in the hotbar.
+	const INVENTORY_SLOTS: usize = 36;
+	/// chunks up to this many chunks outside of the render distance stay loaded.
+	const CHUNK_CACHE_MARGIN: i32 = 1;
+	/// at most this many chunks are kept outside of the render distance.
+	const MAX_CACHED_CHUNKS: usize = 256;
+
+	/// relative to the data dir, see [`crate::assets::asset_path`].
+	const BLOCK_REGISTRY_PATH: &str = "textures/blocks/blocks.json";
+	const CONFIG_PATH: &str = "config.json";
+	/// where the worlds are saved, see [`GameState::world_directory`].
+	const SAVES_PATH: &str = "saves";
+	/// where the debug ui exports the chunks around the camera to.
+	const EXPORT_PATH: &str = "exports/chunks.obj";
+
+	/// the directory in `saves` that the world is saved in.
+	fn world_directory(saves: &std::path::Path, seed: u32, world_type: WorldType) -> std::path::PathBuf {
+		match world_type {
+			WorldType::Default => {
+				let directory = saves.join(format!("world-{}", seed));
+				// worlds from before the seed could be picked were all generated with the default seed.
+				let old_directory = saves.join("world");
+				if seed == DEFAULT_SEED && !directory.exists() && old_directory.exists() {
+					old_directory
+				} else {
+					directory
+				}
+			}
+			WorldType::Flat => saves.join("flat"),
+		}
+	}
+
+	/// `seed` picks the world, each seed (and the flat world) is saved separately.
+	/// fails if the config, the block registry or the renderer's shaders and textures can't be loaded.
+	pub fn new(gfx: &gfx::Gfx, seed: u32, world_type: WorldType) -> Result<Self, VoxleError> {
 		let _world = shipyard::World::new();
+		let config = config::GameConfig::load(&crate::assets::asset_path(Self::CONFIG_PATH))?;
+		let render_distance = RenderDistance { xz: config.render_distance, y: config.vertical_render_distance };
 		
-		let block_textures = texture::load_block_textures("data/textures/blocks/blocks.json").unwrap();
-		let renderer = renderer::GameRenderer::new(gfx, &block_textures);
+		let mut block_textures = texture::LoadedTextures::new();
+		let block_registry = Arc::new(
+			registry::load_block_registry(&crate::assets::asset_path(Self::BLOCK_REGISTRY_PATH), &mut block_textures)?
+		);
+		let mesher = mesher::ChunkMesher::new(block_registry.clone());
+		let inventory = inventory::Inventory::from_registry(&block_registry, Self::INVENTORY_SLOTS);
+		let hotbar = hotbar::Hotbar::new(Action::HOTBAR_SLOT_COUNT as usize);
+		let worldgen: Arc<dyn worldgen::WorldGenerator> = match world_type {
+			WorldType::Default => Arc::new(worldgen::WorldGen::with_config(seed, worldgen::WorldGenConfig {
+				sea_level: config.sea_level,
+				..Default::default()
+			})),
+			WorldType::Flat => Arc::new(worldgen::FlatWorldGen::default()),
+		};
+		let world = Arc::new(world::World::new(Self::world_directory(std::path::Path::new(Self::SAVES_PATH), seed, world_type)));
+		let shadow_map_size = 2048;
+		let mut renderer = renderer::GameRenderer::new(gfx, &block_textures, shadow_map_size, Self::MSAA_SAMPLES)?;
+		let camera = &mut renderer.chunk_renderer.camera;
+		camera.position = config.camera_position;
+		camera.projection = renderer::chunk::Projection::Perspective { fovy: config.camera.fovy };
+		camera.znear = config.znear;
+		camera.zfar = config.zfar;
+		renderer.chunk_renderer.set_block_light_color(Self::EMITTER_COLOR);
 
-		Self {
+		Ok(Self {
 			_world,
 			chunks: HashMap::new(),
-			block_textures,
+			block_registry,
+			mesher,
+			mesh_stats: MeshStats::default(),
+			chunk_last_needed: HashMap::new(),
+			generate_chunks_count: 0,
+			chunk_budget: Cell::new(8),
+			mesh_budget: Cell::new(32),
+			inventory,
+			hotbar,
+			crosshair: hud::Crosshair::default(),
+			visible_chunks: Vec::new(),
+			sort_chunks: Cell::new(true),
+			render_sky: Cell::new(true),
+			lod_distance: Cell::new(6.0),
+			depth_prepass: Cell::new(true),
+			face_highlight: Cell::new([1.0, 1.0, 1.0, 0.15]),
+			fog: Cell::new(Fog { color: [0.65, 0.75, 0.9], start: 0.6, end: 0.95 }),
+			time_of_day: time::TimeOfDay::new(0.35, 600.0),
+			time_scale: Cell::new(1.0),
+			time_paused: Cell::new(false),
+			shadows: Cell::new(true),
+			texture_filtering: Cell::new(renderer::GameRenderer::TEXTURE_FILTERING),
+			ao: Cell::new(true),
+			ao_strength: Cell::new(1.0),
+			shadow_map_size: Cell::new(shadow_map_size),
+			present_mode: Cell::new(gfx.config.present_mode),
+			shadow_casters: Vec::new(),
+			point_light_candidates: None,
+			remesh: HashSet::new(),
+			mesh_requests: HashSet::new(),
+			cpu_timings: gfx::profiler::Timings::default(),
+			gpu_timings: None,
+			#[cfg(feature = "hot-reload")]
+			texture_watcher: renderer::reload::DirectoryWatcher::new(crate::assets::asset_path(Self::BLOCK_REGISTRY_PATH).parent().unwrap())
+				.map_err(|error| eprintln!("couldn't watch the block textures: {}", error))
+				.ok(),
 			renderer,
-			camera_controller: camera::CameraController::new(10.0, 1.0),
-			render_distance: 4,
+			camera_controller: camera::CameraController::new(config.camera, config.camera_position),
+			camera_settings: Cell::new(config.camera),
+			render_distance: Cell::new(render_distance),
+			loaded_render_distance: render_distance,
 			current_chunk_position: (0, 0, 0).vector(),
 			render_wireframe: false,
-			worldgen: worldgen::WorldGen::new(69),
+			quit: Cell::new(false),
+			worldgen_queue: worldgen::WorldGenQueue::new(worldgen.clone(), world.clone()),
+			saver: world::ChunkSaver::new(world.clone()),
+			worldgen,
+			world,
 			target_block: None,
-		}
+			breaking: breaking::BlockBreaking::default(),
+		})
 	}
 
+	/// meshes the chunk inline, for changes that should be visible on the same frame.
 	fn update_chunk_quick(&mut self, gfx: &gfx::Gfx, pos: Vec3i32) {
+		// an older mesh from the worker threads would overwrite this one.
+		self.mesher.cancel(pos);
+		let lod = self.is_chunk_lod(pos);
 		let chunk: *mut chunk::Chunk = self.chunks.get_mut(&pos).unwrap();
+		let mesh_stats = &mut self.mesh_stats;
 		unsafe {
 			chunk.as_mut().unwrap().update_mesh(
 				gfx,
+				&mut self.renderer.chunk_renderer.mesh_pool,
 				&self.chunks,
-				&self.block_textures,
+				&self.block_registry,
+				lod,
+				Some(&mut |event: chunk::MeshEvent| mesh_stats.add(&event)),
 			);
 		}
 	}
 
-	fn update_chunk(&mut self, gfx: &gfx::Gfx, pos: Vec3i32) {
-		self.update_chunk_quick(gfx, pos);
+	/// see [`registry::BlockInfo::hardness`], 0 without a target.
+	fn target_hardness(&self) -> f32 {
+		self.target_block
+			.and_then(|target| self.chunks.get(&target.chunk)?.data.get_block(target.block).copied())
+			.map_or(0.0, |block| block.info(&self.block_registry).hardness)
+	}
 
-		let to_update: Vec<Vec3i32> = Dir::all().iter().filter_map(|dir| {
-			let normal = dir.normal::<i32>();
-			self.chunks.contains_key(&(pos + normal)).then_some(pos + normal)
-		}).collect();
+	/// the `loaded` chunks that aren't `needed` and are further than [`GameState::CHUNK_CACHE_MARGIN`] outside of `rd`,
+	/// and the least recently needed ones over `max_cached`, see [`GameState::chunk_last_needed`].
+	fn chunks_to_unload(
+		loaded: impl Iterator<Item = Vec3i32>,
+		needed: &HashSet<Vec3i32>,
+		center: Vec3i32,
+		rd: RenderDistance,
+		last_needed: &HashMap<Vec3i32, u64>,
+		max_cached: usize,
+	) -> Vec<Vec3i32> {
+		// chunks just outside of the render distance are kept, so that going back and forth
+		// over a chunk border doesn't reload them every time.
+		let cached = rd.expanded(Self::CHUNK_CACHE_MARGIN);
+		let mut to_be_removed = Vec::new();
+		let mut kept = Vec::new();
+		for position in loaded {
+			if needed.contains(&position) {
+				continue;
+			}
+			if cached.contains(position - center) {
+				kept.push(position);
+			} else {
+				to_be_removed.push(position);
+			}
+		}
 
-		for pos in to_update {
-			self.update_chunk_quick(gfx, pos);
+		if kept.len() > max_cached {
+			let last_needed = |position: &Vec3i32| last_needed.get(position).copied().unwrap_or(0);
+			// most recently needed first.
+			kept.sort_by_key(|position| std::cmp::Reverse((last_needed(position), position.0)));
+			to_be_removed.extend(kept.drain(max_cached..));
 		}
+		to_be_removed
 	}
 
-	fn generate_chunks(&mut self, gfx: &gfx::Gfx) {
-		let mut to_be_updated = HashSet::new();
+	/// queues the missing chunks in the render distance and unloads the ones outside of it.
+	fn generate_chunks(&mut self) {
 		let mut saved_chunks = HashSet::new();
 
-		let half_rd = self.render_distance / 2;
-		let squared_rd = self.render_distance * self.render_distance;
+		let rd = self.render_distance.get();
+		self.loaded_render_distance = rd;
 
-		for x in -half_rd - 1 .. half_rd + 1 {
-			for y in -half_rd - 1 .. half_rd + 1 {
-				for z in -half_rd - 1 .. half_rd + 1 {
+		self.worldgen_queue.set_center(self.current_chunk_position);
+		self.mesher.set_center(self.current_chunk_position);
+
+		for x in -rd.xz ..= rd.xz {
+			for y in -rd.y ..= rd.y {
+				for z in -rd.xz ..= rd.xz {
 					let pos = vec3(x, y, z);
 					let abs_pos = self.current_chunk_position + pos;
-					if x*x + y*y + z*z < squared_rd {
+					if rd.contains(pos) {
 						saved_chunks.insert(abs_pos);
 						if !self.chunks.contains_key(&abs_pos) {
-							if let Some(chunk) = self.worldgen.generate_chunk(abs_pos) {
-								self.chunks.insert(abs_pos, chunk);
-								to_be_updated.insert(abs_pos);
-
-								// update neighbor meshes
-								for dir in Dir::all() {
-									let normal = dir.normal::<i32>();
-									let abs_pos = abs_pos + normal;
-									if self.chunks.contains_key(&abs_pos) {
-										to_be_updated.insert(abs_pos);
-									}
-								}
-							}
+							self.worldgen_queue.push(abs_pos);
 						}
 					}
 				}
 			}	
 		}
 
+		self.worldgen_queue.retain(|position| saved_chunks.contains(&position));
+
 		{
-			let mut to_be_removed = Vec::with_capacity(self.chunks.len() - saved_chunks.len());
-			for (position, _) in &self.chunks {
-				if !saved_chunks.contains(position) {
-					to_be_removed.push(*position);
+			self.generate_chunks_count += 1;
+			for position in &saved_chunks {
+				if self.chunks.contains_key(position) {
+					self.chunk_last_needed.insert(*position, self.generate_chunks_count);
 				}
 			}
 
+			let to_be_removed = Self::chunks_to_unload(
+				self.chunks.keys().copied(),
+				&saved_chunks,
+				self.current_chunk_position,
+				rd,
+				&self.chunk_last_needed,
+				Self::MAX_CACHED_CHUNKS,
+			);
 			for position in to_be_removed {
-				self.chunks.remove(&position);
+				self.chunk_last_needed.remove(&position);
+				if let Some(mut chunk) = self.chunks.remove(&position) {
+					Self::save_chunk(&self.world, &mut chunk);
+					self.point_light_candidates = None;
+				}
+				self.mesher.cancel(position);
 			}
+			self.saver.flush();
+		}
+	}
+
+	/// queues the chunk to be written by the saver thread.
+	/// pristine chunks aren't saved, they can just be generated again.
+	fn save_chunk(world: &world::World, chunk: &mut chunk::Chunk) {
+		if !chunk.dirty { return }
+		world.queue_save(chunk.position, chunk.data.clone());
+		chunk.dirty = false;
+	}
+
+	fn save_all_chunks(&mut self) {
+		for chunk in self.chunks.values_mut() {
+			Self::save_chunk(&self.world, chunk);
 		}
+		self.saver.flush();
+	}
+
+	/// inserts the chunks generated in the background and relights/remeshes them and their neighbors.
+	fn receive_chunks(&mut self) {
+		let chunks: Vec<(Vec3i32, chunk::Chunk)> = self.worldgen_queue.receive(self.chunk_budget.get()).into_iter()
+			.filter_map(|(abs_pos, chunk)| Some((abs_pos, chunk?)))
+			.collect();
+		self.loaded_chunks().insert_chunks(chunks);
+	}
 
-		for pos in to_be_updated {
+	/// the loaded chunks, to load more of them or to edit them.
+	fn loaded_chunks(&mut self) -> LoadedChunks<'_> {
+		LoadedChunks {
+			chunks: &mut self.chunks,
+			registry: &self.block_registry,
+			world: &self.world,
+			worldgen: &*self.worldgen,
+			worldgen_queue: &mut self.worldgen_queue,
+			render_distance: self.loaded_render_distance,
+			center: self.current_chunk_position,
+			remesh: &mut self.remesh,
+			mesh_requests: &mut self.mesh_requests,
+			point_light_candidates: &mut self.point_light_candidates,
+		}
+	}
+
+	/// meshes the chunks that [`LoadedChunks`] changed, the edited ones inline so that the edits are visible on this frame.
+	fn mesh_changed_chunks(&mut self, gfx: &gfx::Gfx) {
+		for pos in std::mem::take(&mut self.mesh_requests) {
+			self.request_mesh(pos);
+		}
+		// after the requests, so that the inline meshes aren't replaced by the ones from the worker threads.
+		for pos in std::mem::take(&mut self.remesh) {
 			if self.chunks.contains_key(&pos) {
 				self.update_chunk_quick(gfx, pos);
 			}
 		}
 	}
 
+	/// meshes the chunk on the worker threads, with a lod mesh if it's far away.
+	fn request_mesh(&mut self, pos: Vec3i32) {
+		let lod = self.is_chunk_lod(pos);
+		self.mesher.request(pos, &self.chunks, lod);
+	}
+
+	/// the chunks that moved past the lod distance since they were meshed are meshed again, with a lod mesh.
+	fn request_lod_meshes(&mut self) {
+		let missing: Vec<Vec3i32> = self.visible_chunks.iter()
+			.copied()
+			.filter(|&pos| self.chunks[&pos].mesh.is_some() && self.chunks[&pos].lod_mesh.is_none())
+			.filter(|&pos| self.is_chunk_lod(pos) && !self.mesher.is_pending(pos))
+			.collect();
+		for pos in missing {
+			self.mesher.request(pos, &self.chunks, true);
+		}
+	}
+
+	fn receive_meshes(&mut self, gfx: &gfx::Gfx) {
+		for result in self.mesher.receive(self.mesh_budget.get()) {
+			self.mesh_stats.add(&result.event());
+			if let Some(chunk) = self.chunks.get_mut(&result.position) {
+				chunk.upload_mesh(gfx, &mut self.renderer.chunk_renderer.mesh_pool, &result.mesh_data);
+			}
+		}
+	}
+
+	fn update_visible_chunks(&mut self) {
+		let frustum = self.renderer.chunk_renderer.camera.frustum();
+		self.visible_chunks.clear();
+		let (rd, center) = (self.loaded_render_distance, self.current_chunk_position);
+		self.visible_chunks.extend(self.chunks.values().filter_map(|chunk| {
+			// the cached chunks outside of the render distance would be drawn past the fog.
+			if !rd.contains(chunk.position - center) {
+				return None;
+			}
+			let (min, max) = chunk.bounds();
+			frustum.intersects_aabb(min, max).then_some(chunk.position)
+		}));
+
+		if self.sort_chunks.get() {
+			let eye = self.renderer.chunk_renderer.camera.eye();
+			let chunks = &self.chunks;
+			// ties are broken by position, so that equidistant chunks don't flicker
+			// between frames (the hashmap's order is arbitrary).
+			self.visible_chunks.sort_by(|a, b| {
+				let da = (chunks[a].center() - eye).mag_squared();
+				let db = (chunks[b].center() - eye).mag_squared();
+				da.total_cmp(&db).then_with(|| a.0.cmp(&b.0))
+			});
+		}
+	}
+
+	/// positions the shadow map around the camera and finds the chunks that cast shadows into it.
+	fn update_shadows(&mut self, gfx: &gfx::Gfx) {
+		if self.shadow_map_size.get() != self.renderer.shadow_map_size() {
+			self.renderer.set_shadow_map_size(gfx, self.shadow_map_size.get());
+		}
+
+		self.shadow_casters.clear();
+		// the shadows would point up at night.
+		let sun_direction = self.time_of_day.sun_direction().xyz();
+		if !self.shadows.get() || sun_direction.y >= 0.0 {
+			self.renderer.chunk_renderer.set_shadow(None, self.renderer.shadow_map_size());
+			return;
+		}
+
+		let light_view_proj = renderer::chunk::sun_view_proj(
+			self.renderer.chunk_renderer.camera.position,
+			sun_direction,
+			Self::SHADOW_RADIUS,
+			self.renderer.shadow_map_size()
+		);
+		self.renderer.chunk_renderer.set_shadow(Some(&light_view_proj), self.renderer.shadow_map_size());
+
+		let frustum = Frustum::from_view_proj(&light_view_proj.data.0);
+		self.shadow_casters.extend(self.chunks.values().filter_map(|chunk| {
+			let (min, max) = chunk.bounds();
+			frustum.intersects_aabb(min, max).then_some(chunk.position)
+		}));
+	}
+
+	/// reloads the block registry and its textures when any of them changed, and remeshes every chunk.
+	/// a broken manifest or texture is reported and the old blocks are kept.
+	#[cfg(feature = "hot-reload")]
+	fn reload_block_textures(&mut self, gfx: &gfx::Gfx) {
+		if self.texture_watcher.as_ref().map_or(true, |watcher| watcher.changed().is_empty()) {
+			return;
+		}
+
+		let mut block_textures = texture::LoadedTextures::new();
+		let loaded = registry::load_block_registry(&crate::assets::asset_path(Self::BLOCK_REGISTRY_PATH), &mut block_textures);
+
+		match loaded {
+			Ok(registry) => {
+				self.renderer.chunk_renderer.set_block_textures(gfx, &block_textures);
+				self.renderer.icon_renderer.clear();
+				self.block_registry = Arc::new(registry);
+				// the workers hold on to the old registry.
+				self.mesher = mesher::ChunkMesher::new(self.block_registry.clone());
+				let positions: Vec<Vec3i32> = self.chunks.keys().copied().collect();
+				for pos in positions {
+					self.request_mesh(pos);
+				}
+			},
+			Err(error) => eprintln!("couldn't reload the block registry, keeping the old one: {}", error),
+		}
+	}
+
+	/// lights the emissive blocks closest to the camera, in the chunks around it.
+	/// the emitters are only collected again when the camera moves to another chunk or the chunks change.
+	fn update_point_lights(&mut self) {
+		let center = self.current_chunk_position;
+		if !matches!(&self.point_light_candidates, Some((position, _)) if *position == center) {
+			let mut lights = Vec::new();
+			for z in -1..=1 {
+				for y in -1..=1 {
+					for x in -1..=1 {
+						if let Some(chunk) = self.chunks.get(&(center + vec3(x, y, z))) {
+							let origin = chunk.position * chunk::CHUNK_SIZE.each_as();
+							lights.extend(chunk.emitters.iter().map(|&(local, light)| renderer::chunk::PointLight {
+								position: (origin + local).each_as(),
+								color: Self::EMITTER_COLOR * (light as f32 / chunk::MAX_LIGHT as f32),
+								radius: light as f32,
+							}));
+						}
+					}
+				}
+			}
+			self.point_light_candidates = Some((center, lights));
+		}
+
+		let Some((_, lights)) = &mut self.point_light_candidates else { unreachable!() };
+		let eye = self.renderer.chunk_renderer.camera.eye();
+		lights.sort_by(|a, b| (a.position - eye).mag_squared().total_cmp(&(b.position - eye).mag_squared()));
+		self.renderer.chunk_renderer.set_point_lights(lights);
+	}
+
 	fn raycast_target(&mut self) {
 		let dir = self.renderer.chunk_renderer.camera.direction().normalized();
 		let step = 0.1;
@@ -235,7 +849,7 @@ impl GameState {
 			let chunk_position = chunk::world_to_chunk(ray);
 			let block_position = chunk::world_to_block_local(ray);
 			if let Some(chunk) = &self.chunks.get(&chunk_position) {
-				if chunk.data.get_block(block_position).is_some_and(|b| b.is_solid()) {
+				if chunk.data.get_block(block_position).is_some_and(|b| b.is_solid(&self.block_registry)) {
 					// https://www.shadertoy.com/view/ld23DV (Inigo Quilez)
 					let ro = (ray - dir * step) - block_position.each_as() - chunk.position.each_as::<f32>() * chunk::CHUNK_SIZE.each_as();
 					let n = one_over_dir * ro;
@@ -270,85 +884,107 @@ impl GameState {
 	}
 }
 
+impl Drop for GameState {
+	fn drop(&mut self) {
+		self.save_all_chunks();
+	}
+}
+
 impl State for GameState {
 	fn load(&mut self, context: &mut crate::LoadContext) {
 		self.camera_controller.load(context);
-		self.generate_chunks(&context.gfx);
-		self.renderer.chunk_renderer.set_sun_direction(vec4::<f32>(4.0, -5.0, 5.0, 1.0).normalized());
+		self.generate_chunks();
+	}
+
+	fn fixed_update(&mut self, context: &mut UpdateContext) {
+		self.camera_controller.step(context, self.renderer.chunk_renderer.camera.yaw, &self.chunks, &self.block_registry);
 	}
 
 	fn update(&mut self, context: &mut UpdateContext) {
-		let allow_input = self.camera_controller.update_camera(context, &mut self.renderer.chunk_renderer.camera, context.dt);
+		let update_start = std::time::Instant::now();
+		self.mesh_stats = MeshStats::default();
+		self.camera_controller.set_settings(self.camera_settings.get());
+		let allow_input = self.camera_controller.update_camera(
+			context,
+			&mut self.renderer.chunk_renderer.camera,
+			context.dt,
+			&self.chunks,
+			&self.block_registry
+		);
 		
 		let last_chunk_position = self.current_chunk_position;
 
 		self.current_chunk_position = chunk::world_to_chunk(self.renderer.chunk_renderer.camera.position);
 
-		if last_chunk_position != self.current_chunk_position {
-			self.generate_chunks(context.gfx);
+		if last_chunk_position != self.current_chunk_position || self.render_distance.get() != self.loaded_render_distance {
+			let start = std::time::Instant::now();
+			self.generate_chunks();
+			self.cpu_timings.record_since("generate chunks", start);
 		}
 
 		if allow_input {
-			if context.input().key(KeyCode::KeyG).just_pressed() {
+			if context.input().action(Action::ToggleWireframe).just_pressed() {
 				self.render_wireframe = !self.render_wireframe;
 			}
 
-			for (id, keycode) in [
-				KeyCode::Digit0,
-				KeyCode::Digit1,
-				KeyCode::Digit2,
-				KeyCode::Digit3,
-				KeyCode::Digit5,
-				KeyCode::Digit6,
-			].into_iter().enumerate() {
-				if context.input().key(keycode).just_pressed() {
-					let loc_block_pos = chunk::world_to_block_local(self.renderer.chunk_renderer.camera.position);
-
-					{
-						let chunk = self.chunks.get_mut(&self.current_chunk_position).unwrap();
-						chunk.data.set_block(loc_block_pos, Block {
-							id: id as u16,
-							state: 0
-						});
-					}
+			for index in 0..Action::HOTBAR_SLOT_COUNT {
+				if context.input().action(Action::HotbarSlot(index)).just_pressed() {
+					self.hotbar.select(index as usize);
+				}
+			}
 
-					self.update_chunk(&context.gfx, self.current_chunk_position);
+			// scrolling zooms while the zoom key is held, see `CameraController::update_camera`.
+			if !context.input().action(Action::Zoom).held() {
+				let scroll = context.input().scroll_delta();
+				if scroll != 0.0 {
+					// scrolling down moves to the right, one slot per line.
+					self.hotbar.scroll(-scroll.signum() as i32 * scroll.abs().ceil() as i32);
 				}
 			}
 
 			self.raycast_target();
 
-			if context.input().button(0).just_pressed() {
+			let breaking = context.input().button(0).held() || context.input().gamepad_button(gilrs::Button::West).held();
+			if self.breaking.update(self.target_block.map(BlockTarget::to_global), self.target_hardness(), breaking, context.dt) {
 				if let Some(target_block) = self.target_block {
-					if let Some(chunk) = self.chunks.get_mut(&target_block.chunk) {
-						chunk.data.set_block(target_block.block, Block {
-							id: 0,
-							state: 0
-						});
+					let broken = self.loaded_chunks().set_block(target_block.chunk, target_block.block, Block {
+						id: 0,
+						state: 0
+					});
+					// blocks without textures (like air) can't be placed, so they aren't picked up.
+					if let Some(broken) = broken.filter(|block| block.info(&self.block_registry).textures.is_some()) {
+						// what doesn't fit is lost.
+						self.inventory.add(broken.id, 1);
 					}
-					self.update_chunk(&context.gfx, target_block.chunk);
 				}
 			}
 
-			if context.input().button(1).just_pressed() {
-				if let Some(target_block) = self.target_block {
-					let global_block_pos = target_block.to_global() + target_block.face.normal();
-					let chunk_pos = chunk::block_global_to_chunk(global_block_pos);
-					let block_pos = chunk::block_global_to_block_local(global_block_pos);
-					if let Some(chunk) = self.chunks.get_mut(&chunk_pos) {
-						chunk.data.set_block(block_pos, Block {
-							id: 1,
-							state: 0
-						});
+			if context.input().button(1).just_pressed() || context.input().gamepad_button(gilrs::Button::East).just_pressed() {
+				let stack = self.inventory.slot(self.hotbar.selected());
+				if let (Some(target_block), Some(stack)) = (self.target_block, stack) {
+					let (chunk_pos, block_pos) = target_block.placement();
+					// the face can be at the edge of the loaded chunks. the chunks on both sides
+					// of a chunk boundary are remeshed after `set_block`.
+					let mut loaded_chunks = self.loaded_chunks();
+					loaded_chunks.load_chunk_now(chunk_pos);
+					let placed = loaded_chunks.set_block(chunk_pos, block_pos, Block {
+						id: stack.block,
+						// directional blocks point away from the face they were placed on.
+						state: chunk::BlockRotation::new(target_block.face).to_state()
+					});
+					if placed.is_some() {
+						self.inventory.take_one(self.hotbar.selected());
 					}
-					self.update_chunk(&context.gfx, chunk_pos);
 				}
 			}
 
-			if context.input().key(KeyCode::KeyL).just_pressed() {
+			if context.input().action(Action::CycleAoMap).just_pressed() {
 				chunk::next_ao_index_map();
+				// the chunks are reloaded from disk, so the edits have to be saved first.
+				self.save_all_chunks();
 				self.chunks.clear();
-				self.generate_chunks(context.gfx);
+				self.chunk_last_needed.clear();
+				self.generate_chunks();
 			}
 		}
 
@@ -356,75 +992,495 @@ impl State for GameState {
 		let screen_height = context.gfx.config.height as i32;
 		let mut ui_builder = UiBuilder::new(
 			vec2(screen_width, screen_height).each_as(),
-			self.renderer.ui_renderer.texture_size()
+			self.renderer.ui_renderer.texture_size(),
+			self.renderer.ui_renderer.font()
+		);
+		self.crosshair.draw(
+			&mut ui_builder,
+			vec2(screen_width, screen_height),
+			context.window().window().scale_factor(),
+			self.target_block.is_some()
+		);
+		for stack in self.inventory.slots()[..self.hotbar.slot_count()].iter().flatten() {
+			self.renderer.icon_renderer.request(context.gfx, stack.block, &self.block_registry);
+		}
+		self.hotbar.draw(
+			&mut ui_builder,
+			&self.block_registry,
+			&self.inventory,
+			&self.renderer.icon_renderer,
+			vec2(screen_width, screen_height)
 		);
 		{
-			ui_builder.add_rect(Rect {
-				x: (screen_width - 17) / 2,
-				y: (screen_height - 17) / 2,
-				w: 17,
-				h: 17,
-			}, Rect {
-				x: 0,
-				y: 0,
-				w: 16,
-				h: 16,
-			});
+			let position = self.renderer.chunk_renderer.camera.position;
+			let lines = [
+				format!("fps: {:.0}", 1.0 / context.dt),
+				format!("xyz: {:.1} {:.1} {:.1}", position.x, position.y, position.z),
+				format!("chunk: {} {} {}", self.current_chunk_position.x, self.current_chunk_position.y, self.current_chunk_position.z),
+			];
+			let line_height = ui_builder.font().scaled_height() + 2;
+			for (i, line) in lines.iter().enumerate() {
+				ui_builder.add_text(Rect {
+					x: 4,
+					y: 4 + i as i32 * line_height,
+					w: screen_width - 8,
+					h: line_height,
+				}, line, [255, 255, 255, 255]);
+			}
 		}
 
-		self.renderer.update(&context.gfx, ui_builder);
+		if !self.time_paused.get() {
+			self.time_of_day.advance(context.dt * self.time_scale.get());
+		}
+		self.renderer.chunk_renderer.set_sun_direction(self.time_of_day.sun_direction());
+		self.renderer.chunk_renderer.set_sun_color(self.time_of_day.sun_color());
+
+		let fog = self.fog.get();
+		let (fog_start, fog_end) = fog.range(self.loaded_render_distance);
+		self.renderer.chunk_renderer.set_fog(self.time_of_day.horizon_color(Vector(fog.color)), fog_start, fog_end);
+		if self.renderer.chunk_renderer.texture_filtering() != self.texture_filtering.get() {
+			self.renderer.chunk_renderer.set_texture_filtering(context.gfx, self.texture_filtering.get());
+		}
+		self.renderer.chunk_renderer.set_ao_strength(if self.ao.get() { self.ao_strength.get() } else { 0.0 });
+
+		if self.present_mode.get() != context.gfx.config.present_mode {
+			self.present_mode.set(context.gfx.set_present_mode(self.present_mode.get()));
+		}
+
+		self.receive_chunks();
+		self.mesh_changed_chunks(context.gfx);
+		let start = std::time::Instant::now();
+		self.receive_meshes(context.gfx);
+		self.cpu_timings.record_since("receive meshes", start);
+		let start = std::time::Instant::now();
+		self.update_visible_chunks();
+		self.request_lod_meshes();
+		self.cpu_timings.record_since("cull chunks", start);
+		self.update_shadows(context.gfx);
+		self.update_chunk_draws(context.gfx);
+		self.update_point_lights();
+		#[cfg(feature = "hot-reload")]
+		self.reload_block_textures(context.gfx);
+		self.renderer.update(&context.gfx, context.dt, ui_builder);
+
+		let profiler = &context.gfx.profiler;
+		self.gpu_timings = profiler.is_supported().then(|| profiler.timings.iter()
+			.map(|(name, milliseconds)| (name.to_owned(), milliseconds))
+			.collect());
+		self.cpu_timings.record_since("update", update_start);
 	}
 	
 	fn render<'a>(&'a self, context: &mut gfx::RenderContext<'a>) {
 		self.renderer.render(context, self);
 	}
 
+	fn resize(&mut self, gfx: &gfx::Gfx) {
+		self.renderer.resize(gfx);
+	}
+
+	/// the state on top (e.g. a menu) may have released the cursor.
+	fn resume(&mut self, context: &mut crate::LoadContext) {
+		self.camera_controller.load(context);
+	}
+
+	/// the chunks are saved when the game is dropped.
+	fn done(&self) -> bool {
+		self.quit.get()
+	}
+
 	fn ui<'a>(&'a self, ctx: &egui::Context) {
 		egui::Window::new("debug").default_open(false).show(ctx, |ui| {
 			ui.label(format!("chunk: {}", self.current_chunk_position));
-			ui.label(format!("eye: {}", self.renderer.chunk_renderer.camera.position));
+			ui.label(format!("drawn chunks: {}/{}", self.visible_chunks.len(), self.chunks.len()));
+			ui.label(format!("pending meshes: {}", self.mesher.pending()));
+			ui.label(format!(
+				"meshed this frame: {} chunks, {} vertices, {:.2} ms",
+				self.mesh_stats.chunks, self.mesh_stats.vertices, self.mesh_stats.duration.as_secs_f64() * 1000.0
+			));
+			ui.label(format!("msaa: {}x", self.renderer.samples()));
+			ui.horizontal(|ui| {
+				let mut present_mode = self.present_mode.get();
+				for (mode, name) in Self::PRESENT_MODES {
+					ui.selectable_value(&mut present_mode, mode, name);
+				}
+				self.present_mode.set(present_mode);
+				ui.label("present mode");
+			});
+			ui.label(format!("pending chunks: {} ({} ready)", self.worldgen_queue.pending(), self.worldgen_queue.ready()));
+			let mut chunk_budget = self.chunk_budget.get();
+			ui.add(egui::Slider::new(&mut chunk_budget, 1..=64).text("chunks per frame"));
+			self.chunk_budget.set(chunk_budget);
+			let mut mesh_budget = self.mesh_budget.get();
+			ui.add(egui::Slider::new(&mut mesh_budget, 1..=256).text("meshes per frame"));
+			self.mesh_budget.set(mesh_budget);
+
+			let mut render_distance = self.render_distance.get();
+			ui.add(egui::Slider::new(&mut render_distance.xz, 1..=16).text("render distance (xz)"));
+			ui.add(egui::Slider::new(&mut render_distance.y, 0..=8).text("render distance (y)"));
+			self.render_distance.set(render_distance);
+
+			let mut camera_settings = self.camera_settings.get();
+			ui.add(egui::Slider::new(&mut camera_settings.fovy, 30.0..=110.0).text("field of view"));
+			ui.add(egui::Slider::new(&mut camera_settings.speed, 1.0..=50.0).text("camera speed"));
+			ui.add(egui::Slider::new(&mut camera_settings.sensitivity, 0.1..=5.0).text("mouse sensitivity"));
+			ui.add(egui::Slider::new(&mut camera_settings.third_person_distance, 1.0..=16.0).text("third person distance"));
+			let mut orthographic = camera_settings.orthographic_height.is_some();
+			if ui.checkbox(&mut orthographic, "orthographic").changed() {
+				camera_settings.orthographic_height = orthographic.then_some(64.0);
+			}
+			if let Some(height) = &mut camera_settings.orthographic_height {
+				ui.add(egui::Slider::new(height, 8.0..=512.0).logarithmic(true).text("orthographic height"));
+			}
+			self.camera_settings.set(camera_settings);
+
+			let mut sort_chunks = self.sort_chunks.get();
+			if ui.checkbox(&mut sort_chunks, "sort chunks front-to-back").changed() {
+				self.sort_chunks.set(sort_chunks);
+			}
+
+			let mut render_sky = self.render_sky.get();
+			if ui.checkbox(&mut render_sky, "render sky").changed() {
+				self.render_sky.set(render_sky);
+			}
+
+			let mut lod_distance = self.lod_distance.get();
+			ui.add(egui::Slider::new(&mut lod_distance, 1.0..=17.0).text("lod distance"));
+			self.lod_distance.set(lod_distance);
+
+			let mut depth_prepass = self.depth_prepass.get();
+			if ui.checkbox(&mut depth_prepass, "depth pre-pass").changed() {
+				self.depth_prepass.set(depth_prepass);
+			}
+
+			let mut face_highlight = self.face_highlight.get();
+			ui.horizontal(|ui| {
+				ui.color_edit_button_rgba_unmultiplied(&mut face_highlight);
+				ui.label("face highlight");
+			});
+			self.face_highlight.set(face_highlight);
+
+			let mut fog = self.fog.get();
+			ui.horizontal(|ui| {
+				ui.color_edit_button_rgb(&mut fog.color);
+				ui.label("fog color");
+			});
+			ui.add(egui::Slider::new(&mut fog.start, 0.0..=1.0).text("fog start"));
+			ui.add(egui::Slider::new(&mut fog.end, 0.0..=1.0).text("fog end"));
+			fog.start = fog.start.min(fog.end);
+			self.fog.set(fog);
+
+			let mut shadows = self.shadows.get();
+			if ui.checkbox(&mut shadows, "shadows").changed() {
+				self.shadows.set(shadows);
+			}
+			ui.horizontal(|ui| {
+				let mut ao = self.ao.get();
+				if ui.checkbox(&mut ao, "ambient occlusion").changed() {
+					self.ao.set(ao);
+				}
+				let mut ao_strength = self.ao_strength.get();
+				ui.add_enabled(ao, egui::Slider::new(&mut ao_strength, 0.0..=1.0).text("strength"));
+				self.ao_strength.set(ao_strength);
+			});
+			ui.horizontal(|ui| {
+				use renderer::chunk::TextureFiltering;
+				let mut filtering = self.texture_filtering.get();
+				ui.selectable_value(&mut filtering, TextureFiltering::Nearest, "nearest");
+				ui.selectable_value(&mut filtering, TextureFiltering::Trilinear, "trilinear");
+				self.texture_filtering.set(filtering);
+				ui.label("texture filtering");
+			});
+			ui.horizontal(|ui| {
+				let mut shadow_map_size = self.shadow_map_size.get();
+				for size in Self::SHADOW_MAP_SIZES {
+					ui.selectable_value(&mut shadow_map_size, size, size.to_string());
+				}
+				self.shadow_map_size.set(shadow_map_size);
+				ui.label("shadow map size");
+			});
+
+			ui.label(format!("time of day: {:.3}", self.time_of_day.time()));
+			let mut time_scale = self.time_scale.get();
+			ui.add(egui::Slider::new(&mut time_scale, 0.0..=100.0).logarithmic(true).text("time scale"));
+			self.time_scale.set(time_scale);
+			let mut time_paused = self.time_paused.get();
+			if ui.checkbox(&mut time_paused, "pause time").changed() {
+				self.time_paused.set(time_paused);
+			}
+
+			ui.label(format!("eye: {}", self.renderer.chunk_renderer.camera.eye()));
+			ui.label(format!("flight mode: {:?}", self.camera_controller.flight_mode()));
+			ui.label(format!("third person: {}", self.camera_controller.third_person()));
+			self.worldgen.debug_ui(ui, self.renderer.chunk_renderer.camera.position);
 			
 			let loc_block_pos = chunk::world_to_block_local(self.renderer.chunk_renderer.camera.position);
 
-			let block = self.chunks[&self.current_chunk_position].data.get_block(loc_block_pos);
+			let block = self.chunks.get(&self.current_chunk_position)
+				.and_then(|chunk| chunk.data.get_block(loc_block_pos).copied());
 
 			ui.label(format!("block: {:?}", block.map(|b| b.id)));
 			ui.label(format!("   at: {:?}", loc_block_pos));
 			ui.label(format!("target: {:?}", self.target_block));
-			ui.label(format!("ao_map: {:?}", chunk::ao_index_map()));
+			ui.label(format!("ao_map: {:?}", chunk::ao_index_map().0));
+
+			if ui.button("export nearby chunks to obj").clicked() {
+				let positions = chunk::ChunkNeighborhood::positions(self.current_chunk_position);
+				match export::export_obj(&self.chunks, positions, &self.block_registry, std::path::Path::new(Self::EXPORT_PATH)) {
+					Ok(()) => println!("exported chunks to {}", Self::EXPORT_PATH),
+					Err(error) => eprintln!("couldn't export chunks: {}", error),
+				}
+			}
+
+			if ui.button("quit to menu").clicked() {
+				self.quit.set(true);
+			}
+
+			egui::CollapsingHeader::new("inventory").show(ui, |ui| {
+				for (index, slot) in self.inventory.slots().iter().enumerate() {
+					if let Some(stack) = slot {
+						ui.label(format!("{}: {} x{}", index + 1, self.block_registry.get(stack.block).name, stack.count));
+					}
+				}
+			});
+
+			egui::CollapsingHeader::new("timings").show(ui, |ui| {
+				for (name, milliseconds) in self.cpu_timings.iter() {
+					ui.label(format!("cpu {}: {:.2} ms", name, milliseconds));
+				}
+				match &self.gpu_timings {
+					Some(gpu_timings) => for (name, milliseconds) in gpu_timings {
+						ui.label(format!("gpu {}: {:.2} ms", name, milliseconds));
+					},
+					None => { ui.label("gpu timings unavailable"); },
+				}
+			});
 		});
 	}
 }
 
 impl GameState {
-	// i have to have the lifetimes like this, otherwise ctx.render_chunk(chunk) doesn't work.
-	// (and there's only one way to have lifetimes in ctx.render_chunk)
-	fn render_chunks<'a, 'b>(&'a self, ctx: &mut renderer::chunk::ChunkRenderContext<'a, 'b>) {
-		for (_, chunk) in &self.chunks {
-			ctx.render_chunk(chunk);
+	/// whether the (loaded) chunk at `pos` is far enough to be drawn with its LOD mesh.
+	fn is_chunk_lod(&self, pos: Vec3i32) -> bool {
+		self.chunks.get(&pos).is_some_and(|chunk| {
+			let offset = (chunk.center() - self.renderer.chunk_renderer.camera.eye()).xz();
+			offset.mag() > self.lod_distance.get() * chunk::CHUNK_SIZE.x as f32
+		})
+	}
+
+	/// the meshes of the visible chunks and the shadow casters, drawn in one batch per pass, see [`renderer::chunk::ChunkBatch`].
+	/// the transparent meshes are drawn back-to-front, since they're blended.
+	fn update_chunk_draws(&mut self, gfx: &gfx::Gfx) {
+		let visible: Vec<&chunk::Chunk> = self.visible_chunks.iter().filter_map(|position| self.chunks.get(position)).collect();
+		let opaque: Vec<_> = visible.iter()
+			.filter_map(|chunk| {
+				let lod_mesh = chunk.lod_mesh.as_ref().filter(|_| self.is_chunk_lod(chunk.position));
+				Some((chunk.position, lod_mesh.or(chunk.mesh.as_ref())?))
+			})
+			.collect();
+
+		let mut back_to_front = visible.clone();
+		if self.sort_chunks.get() {
+			// `visible_chunks` is already sorted front-to-back.
+			back_to_front.reverse();
+		} else {
+			let eye = self.renderer.chunk_renderer.camera.eye();
+			back_to_front.sort_by(|a, b| {
+				let da = (a.center() - eye).mag_squared();
+				let db = (b.center() - eye).mag_squared();
+				db.total_cmp(&da)
+			});
+		}
+		let transparent: Vec<_> = back_to_front.iter()
+			.filter_map(|chunk| Some((chunk.position, chunk.transparent_mesh.as_ref()?)))
+			.collect();
+
+		let shadow: Vec<_> = self.shadow_casters.iter()
+			.filter_map(|position| {
+				let chunk = self.chunks.get(position)?;
+				Some((chunk.position, chunk.mesh.as_ref()?))
+			})
+			.collect();
+
+		self.renderer.chunk_renderer.set_chunk_draws(gfx, [&opaque, &transparent, &shadow]);
+	}
+
+	fn on_render_shadows<'a>(&'a self, ctx: &mut GameRenderContext<'a, '_>) {
+		if !self.shadows.get() {
+			return;
+		}
+
+		let mut chunk_ctx = ctx.begin_chunk_context();
+		chunk_ctx.set_mode(renderer::chunk::ChunkRenderMode::Shadow);
+		chunk_ctx.render_batch(renderer::chunk::ChunkBatch::Shadow);
+	}
+
+	fn on_render_depth_prepass<'a>(&'a self, ctx: &mut GameRenderContext<'a, '_>) {
+		if !self.depth_prepass.get() {
+			return;
 		}
+
+		let mut chunk_ctx = ctx.begin_chunk_context();
+		chunk_ctx.set_mode(renderer::chunk::ChunkRenderMode::DepthPrepass);
+		chunk_ctx.render_batch(renderer::chunk::ChunkBatch::Opaque);
 	}
 
-	fn on_render<'a>(&'a self, gfx: &gfx::Gfx, ctx: &mut GameRenderContext<'a, '_>) {
+	fn on_render<'a>(&'a self, ctx: &mut GameRenderContext<'a, '_>) {
 		{
-			let mut chunk_ctx = ctx.begin_chunk_context(gfx);
+			let mut chunk_ctx = ctx.begin_chunk_context();
 			
-			chunk_ctx.set_mode(renderer::chunk::ChunkRenderMode::Normal);
-			self.render_chunks(&mut chunk_ctx);
+			chunk_ctx.set_mode(if self.depth_prepass.get() {
+				renderer::chunk::ChunkRenderMode::AfterDepthPrepass
+			} else {
+				renderer::chunk::ChunkRenderMode::Normal
+			});
+			chunk_ctx.render_batch(renderer::chunk::ChunkBatch::Opaque);
+
+			chunk_ctx.set_mode(renderer::chunk::ChunkRenderMode::Transparent);
+			chunk_ctx.render_batch(renderer::chunk::ChunkBatch::Transparent);
 
 			if self.render_wireframe {
 				chunk_ctx.set_mode(renderer::chunk::ChunkRenderMode::Wireframe);
-				self.render_chunks(&mut chunk_ctx);
+				chunk_ctx.render_batch(renderer::chunk::ChunkBatch::Opaque);
 			}
 
 			if let Some(position) = self.target_block {
-				chunk_ctx.render_outline(position.to_global().each_as())
+				chunk_ctx.render_outline(position.to_global().each_as());
+				// blocks are placed against the highlighted face.
+				let face_highlight = self.face_highlight.get();
+				if face_highlight[3] > 0.0 {
+					chunk_ctx.render_overlay(position.to_global().each_as(), position.face, None, face_highlight);
+				}
+				let crack = self.breaking.progress()
+					.filter(|(target, _)| *target == position.to_global())
+					.and_then(|(_, progress)| self.block_registry.crack_texture(progress));
+				if let Some(crack) = crack {
+					chunk_ctx.render_overlay(position.to_global().each_as(), position.face, Some(crack), [1.0; 4]);
+				}
 			}
 		}
 
 		{
-			let mut ui_ctx = ctx.begin_ui_context(gfx);
+			let mut ui_ctx = ctx.begin_ui_context();
 			ui_ctx.render();
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn blocks_are_placed_across_chunk_boundaries() {
+		let size: Vec3i32 = chunk::CHUNK_SIZE.each_as();
+		let edge = size.x - 1;
+		let place = |chunk, block, face| BlockTarget { chunk, block, face }.placement();
+
+		assert_eq!(place(vec3(0, 0, 0), vec3(edge, 5, 6), Dir::PX), (vec3(1, 0, 0), vec3(0, 5, 6)));
+		assert_eq!(place(vec3(0, 0, 0), vec3(0, 5, 6), Dir::NX), (vec3(-1, 0, 0), vec3(edge, 5, 6)));
+		assert_eq!(place(vec3(2, -1, 3), vec3(4, 0, 6), Dir::NY), (vec3(2, -2, 3), vec3(4, size.y - 1, 6)));
+		assert_eq!(place(vec3(2, -1, 3), vec3(4, 5, size.z - 1), Dir::PZ), (vec3(2, -1, 4), vec3(4, 5, 0)));
+		// inside of the chunk.
+		assert_eq!(place(vec3(0, 0, 0), vec3(4, 5, 6), Dir::PY), (vec3(0, 0, 0), vec3(4, 6, 6)));
+
+		// the block ends up in the neighbor, next to the targeted one.
+		let target = vec3(0, 0, 0);
+		let (chunk, local) = place(target, vec3(edge, 5, 6), Dir::PX);
+		assert_eq!(BlockTarget { chunk, block: local, face: Dir::PX }.to_global(), vec3(size.x, 5, 6));
+
+		// placed like in `update`, with only the targeted chunk loaded.
+		let registry = registry::tests::registry();
+		let directory = world::tests::TestDirectory::new("border-placement");
+		let world = Arc::new(world::World::new(&directory.0));
+		let worldgen: Arc<dyn worldgen::WorldGenerator> = Arc::new(worldgen::FlatWorldGen::default());
+		let mut worldgen_queue = worldgen::WorldGenQueue::new(worldgen.clone(), world.clone());
+		let (mut chunks, mut remesh, mut mesh_requests, mut point_light_candidates) = (HashMap::new(), HashSet::new(), HashSet::new(), None);
+		let mut loaded = LoadedChunks {
+			chunks: &mut chunks,
+			registry: &registry,
+			world: &world,
+			worldgen: &*worldgen,
+			worldgen_queue: &mut worldgen_queue,
+			render_distance: RenderDistance { xz: 2, y: 1 },
+			center: target,
+			remesh: &mut remesh,
+			mesh_requests: &mut mesh_requests,
+			point_light_candidates: &mut point_light_candidates,
+		};
+		assert!(loaded.load_chunk_now(target));
+		assert!(!loaded.chunks.contains_key(&chunk));
+
+		let stone = Block { id: chunk::BlockId::Stone as u16, state: 0 };
+		assert!(loaded.load_chunk_now(chunk));
+		assert!(loaded.mesh_requests.contains(&chunk));
+		assert_eq!(loaded.set_block(chunk, local, stone).map(|block| block.id), Some(chunk::BlockId::Air as u16));
+		assert_eq!(loaded.chunks[&chunk].data.get_block(local).map(|block| block.id), Some(stone.id));
+		// the targeted chunk is remeshed too, the new block covers its face.
+		assert!(loaded.remesh.contains(&chunk));
+		assert!(loaded.remesh.contains(&target));
+		// placing it again changes nothing.
+		assert!(loaded.set_block(chunk, local, stone).is_none());
+	}
+
+	#[test]
+	fn render_distance_is_a_cylinder() {
+		let rd = RenderDistance { xz: 2, y: 1 };
+		let mut retained = Vec::new();
+		for y in -3..=3 {
+			for z in -3..=3 {
+				for x in -3..=3 {
+					if rd.contains(vec3(x, y, z)) {
+						retained.push(vec3(x, y, z));
+					}
+				}
+			}
+		}
+		// the 3x3 square around the center (x² + z² < 4), in 3 layers.
+		assert_eq!(retained.len(), 27);
+		assert!(retained.iter().all(|offset| offset.x.abs() <= 1 && offset.z.abs() <= 1 && offset.y.abs() <= 1));
+
+		assert!(!rd.contains(vec3(2, 0, 0)));
+		assert!(rd.expanded(1).contains(vec3(2, 0, 0)));
+		assert!(rd.expanded(1).contains(vec3(0, 2, 0)));
+		assert!(!rd.expanded(1).contains(vec3(3, 0, 0)));
+	}
+
+	#[test]
+	fn old_worlds_are_still_loaded() {
+		let saves = world::tests::TestDirectory::new("world_directories");
+		let directory = |seed, world_type| GameState::world_directory(&saves.0, seed, world_type);
+		assert_eq!(directory(DEFAULT_SEED, WorldType::Default), saves.0.join(format!("world-{}", DEFAULT_SEED)));
+
+		std::fs::create_dir_all(saves.0.join("world")).unwrap();
+		assert_eq!(directory(DEFAULT_SEED, WorldType::Default), saves.0.join("world"));
+		// it was generated with the default seed.
+		assert_eq!(directory(5, WorldType::Default), saves.0.join("world-5"));
+		assert_eq!(directory(DEFAULT_SEED, WorldType::Flat), saves.0.join("flat"));
+
+		std::fs::create_dir_all(saves.0.join(format!("world-{}", DEFAULT_SEED))).unwrap();
+		assert_eq!(directory(DEFAULT_SEED, WorldType::Default), saves.0.join(format!("world-{}", DEFAULT_SEED)));
+	}
+
+	#[test]
+	fn chunks_within_the_margin_stay_loaded() {
+		let (rd, center) = (RenderDistance { xz: 4, y: 2 }, vec3(10, 0, -3));
+		let margin = GameState::CHUNK_CACHE_MARGIN;
+		let needed: HashSet<Vec3i32> = [center, center + vec3(3, 0, 0), center + vec3(0, -2, 0)].into();
+		let just_outside = [center + vec3(rd.xz, 0, 0), center + vec3(0, rd.y + margin, 0)];
+		let far_outside = [center + vec3(rd.xz + margin + 1, 0, 0), center + vec3(0, 0, -40), center + vec3(0, rd.y + margin + 1, 0)];
+		assert!(just_outside.iter().chain(&far_outside).all(|&position| !rd.contains(position - center)));
+
+		let loaded = || needed.iter().chain(&just_outside).chain(&far_outside).copied();
+		let mut unloaded = GameState::chunks_to_unload(loaded(), &needed, center, rd, &HashMap::new(), 256);
+		unloaded.sort_by_key(|position| position.0);
+		let mut expected = far_outside.to_vec();
+		expected.sort_by_key(|position| position.0);
+		assert_eq!(unloaded, expected);
+
+		// over the cap, the least recently needed one goes.
+		let last_needed = HashMap::from([(just_outside[0], 5), (just_outside[1], 3)]);
+		let unloaded = GameState::chunks_to_unload(loaded(), &needed, center, rd, &last_needed, 1);
+		assert_eq!(unloaded.len(), far_outside.len() + 1);
+		assert!(unloaded.contains(&just_outside[1]) && !unloaded.contains(&just_outside[0]));
+	}
+}
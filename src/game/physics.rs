@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use crate::math::*;
+
+use super::{chunk::{self, Chunk}, registry::BlockRegistry};
+
+/// gap left between a box and the blocks it collides with, so that
+/// it isn't considered to be intersecting them on the next move.
+const SKIN: f32 = 0.001;
+
+/// an axis-aligned box in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+	pub min: Vec3f32,
+	pub max: Vec3f32,
+}
+
+impl Aabb {
+	pub fn new(min: Vec3f32, max: Vec3f32) -> Self {
+		Self { min, max }
+	}
+
+	/// a box standing on `feet`, i.e. `feet` is the center of the bottom face.
+	pub fn from_feet(feet: Vec3f32, width: f32, height: f32) -> Self {
+		let half = width * 0.5;
+		Self::new(feet - vec3(half, 0.0, half), feet + vec3(half, height, half))
+	}
+
+	pub fn translated(self, offset: Vec3f32) -> Self {
+		Self::new(self.min + offset, self.max + offset)
+	}
+
+	/// the blocks (centered on integer coordinates) that the box overlaps, inclusive.
+	fn block_range(self) -> (Vec3i32, Vec3i32) {
+		let min = self.min.map(|c| (c + 0.5 + SKIN).floor() as i32);
+		let max = self.max.map(|c| (c + 0.5 - SKIN).ceil() as i32 - 1);
+		(min, max)
+	}
+}
+
+/// moves the box by `displacement` on one axis, stopping at the first solid block.
+fn sweep_axis(aabb: Aabb, axis: usize, displacement: f32, is_solid: &impl Fn(Vec3i32) -> bool) -> f32 {
+	if displacement == 0.0 { return 0.0 }
+
+	let mut offset = vec3(0.0, 0.0, 0.0);
+	offset.0[axis] = displacement;
+	let moved = aabb.translated(offset);
+	// all of the blocks the box passes through, so that it can't skip over a wall when it's fast.
+	let swept = Aabb::new(aabb.min.zip_map(moved.min, f32::min), aabb.max.zip_map(moved.max, f32::max));
+	let (min, max) = swept.block_range();
+
+	let mut allowed = displacement;
+	for y in min.y..=max.y {
+		for z in min.z..=max.z {
+			for x in min.x..=max.x {
+				let block = vec3(x, y, z);
+				if !is_solid(block) { continue }
+
+				let block_min = block.0[axis] as f32 - 0.5;
+				let block_max = block.0[axis] as f32 + 0.5;
+				if displacement > 0.0 {
+					let distance = block_min - aabb.max.0[axis] - SKIN;
+					// blocks that the box is already inside of are ignored, so that it can get out.
+					if distance >= -SKIN {
+						allowed = allowed.min(distance.max(0.0));
+					}
+				} else {
+					let distance = block_max - aabb.min.0[axis] + SKIN;
+					if distance <= SKIN {
+						allowed = allowed.max(distance.min(0.0));
+					}
+				}
+			}
+		}
+	}
+	allowed
+}
+
+/// moves `aabb` by `displacement`, resolving collisions with the blocks for which
+/// `is_solid` returns true one axis at a time (y first, so that boxes slide along the ground).
+/// returns the displacement that was actually possible.
+pub fn sweep_aabb(aabb: Aabb, displacement: Vec3f32, is_solid: impl Fn(Vec3i32) -> bool) -> Vec3f32 {
+	let mut aabb = aabb;
+	let mut result = vec3(0.0, 0.0, 0.0);
+	for axis in [1, 0, 2] {
+		let moved = sweep_axis(aabb, axis, displacement.0[axis], &is_solid);
+		result.0[axis] = moved;
+		let mut offset = vec3(0.0, 0.0, 0.0);
+		offset.0[axis] = moved;
+		aabb = aabb.translated(offset);
+	}
+	result
+}
+
+/// blocks in chunks that aren't loaded are solid, so that nothing falls out of the world while it loads.
+pub fn is_solid_at(chunks: &HashMap<Vec3i32, Chunk>, registry: &BlockRegistry, global: Vec3i32) -> bool {
+	chunks.get(&chunk::block_global_to_chunk(global)).map_or(true, |chunk| {
+		chunk.data.get_block(chunk::block_global_to_block_local(global))
+			.is_some_and(|block| block.is_solid(registry))
+	})
+}
+
+/// the distance along `direction` (normalized) from `origin` to the first block that `is_solid`,
+/// if it's within `max_distance`. walks the blocks the ray passes through one by one (dda),
+/// starting with the one `origin` is in, which gives a distance of 0.
+pub fn raycast(origin: Vec3f32, direction: Vec3f32, max_distance: f32, is_solid: impl Fn(Vec3i32) -> bool) -> Option<f32> {
+	// blocks are centered on integer coordinates, this makes their bounds integers instead.
+	let start = origin + 0.5;
+	let mut block = start.map(|c| c.floor() as i32);
+	let step = direction.map(|d| if d > 0.0 { 1 } else if d < 0.0 { -1 } else { 0 });
+	// distance along the ray needed to cross a whole block on each axis.
+	let t_delta = direction.map(|d| if d != 0.0 { 1.0 / d.abs() } else { f32::INFINITY });
+	// distance along the ray to the next block boundary on each axis.
+	let mut t_max = Vec3f32::make(|axis| match step.0[axis] {
+		1 => (block.0[axis] as f32 + 1.0 - start.0[axis]) * t_delta.0[axis],
+		-1 => (start.0[axis] - block.0[axis] as f32) * t_delta.0[axis],
+		_ => f32::INFINITY,
+	});
+
+	if is_solid(block) {
+		return Some(0.0);
+	}
+
+	loop {
+		let axis = (0..3).min_by(|&a, &b| t_max.0[a].total_cmp(&t_max.0[b])).unwrap();
+		let t = t_max.0[axis];
+		if t > max_distance {
+			return None;
+		}
+		block.0[axis] += step.0[axis];
+		t_max.0[axis] += t_delta.0[axis];
+		if is_solid(block) {
+			return Some(t);
+		}
+	}
+}
+
+/// [`sweep_aabb`] against the solid blocks of the loaded chunks.
+pub fn sweep_aabb_in_chunks(
+	aabb: Aabb,
+	displacement: Vec3f32,
+	chunks: &HashMap<Vec3i32, Chunk>,
+	registry: &BlockRegistry
+) -> Vec3f32 {
+	sweep_aabb(aabb, displacement, |global| is_solid_at(chunks, registry, global))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// a wall one block thick at x = 3.
+	fn wall(block: Vec3i32) -> bool {
+		block.x == 3
+	}
+
+	#[test]
+	fn fast_boxes_dont_tunnel() {
+		let aabb = Aabb::from_feet(vec3(0.0, 0.0, 0.0), 0.6, 1.8);
+		// further than the wall is thick, in a single step.
+		let moved = sweep_aabb(aabb, vec3(10.0, 0.0, 0.0), wall);
+		assert!((moved.x - (2.5 - 0.3 - SKIN)).abs() < 1e-4, "moved {}", moved.x);
+
+		let moved = sweep_aabb(aabb.translated(vec3(8.0, 0.0, 0.0)), vec3(-10.0, 0.0, 0.0), wall);
+		assert!((moved.x - (3.5 + 0.3 + SKIN - 8.0)).abs() < 1e-4, "moved {}", moved.x);
+
+		// falling onto a floor at y = -1, from high up.
+		let floor = |block: Vec3i32| block.y == -1;
+		let moved = sweep_aabb(aabb.translated(vec3(0.0, 20.0, 0.0)), vec3(0.0, -40.0, 0.0), floor);
+		assert!((moved.y - (-0.5 + SKIN - 20.0)).abs() < 1e-4, "moved {}", moved.y);
+	}
+
+	#[test]
+	fn boxes_slide_along_walls() {
+		let aabb = Aabb::from_feet(vec3(0.0, 0.0, 0.0), 0.6, 1.8);
+		let moved = sweep_aabb(aabb, vec3(10.0, 0.0, 2.0), wall);
+		assert_eq!(moved.z, 2.0);
+		assert!(moved.x < 2.5);
+		assert_eq!(sweep_aabb(aabb, vec3(1.0, 0.5, -1.0), wall), vec3(1.0, 0.5, -1.0));
+	}
+
+	#[test]
+	fn boxes_stop_at_a_block_from_every_side() {
+		let block = |block: Vec3i32| block == vec3(0, 0, 0);
+		// a unit box, starting 2 blocks away from the block (which spans -0.5..0.5).
+		let unit = Aabb::new(vec3(-0.5, -0.5, -0.5), vec3(0.5, 0.5, 0.5));
+		for axis in 0..3 {
+			for sign in [-1.0, 1.0] {
+				let mut start = vec3(0.0, 0.0, 0.0);
+				start.0[axis] = 2.0 * sign;
+				let mut displacement = vec3(0.0, 0.0, 0.0);
+				displacement.0[axis] = -3.0 * sign;
+
+				let moved = sweep_aabb(unit.translated(start), displacement, block);
+				let mut expected = vec3(0.0, 0.0, 0.0);
+				expected.0[axis] = -(1.0 - SKIN) * sign;
+				assert!((moved - expected).mag() < 1e-4, "axis {axis}, sign {sign}: moved {moved}");
+			}
+		}
+
+		// diagonally towards the corner, the box only overlaps the block on the other axes by the time z is moved.
+		let moved = sweep_aabb(unit.translated(vec3(2.0, 2.0, 2.0)), vec3(-1.5, -1.5, -1.5), block);
+		assert!((moved - vec3(-1.5, -1.5, -(1.0 - SKIN))).mag() < 1e-4, "moved {moved}");
+		// just missing the corner.
+		let moved = sweep_aabb(unit.translated(vec3(1.0, 1.0, 3.0)), vec3(0.0, 0.0, -6.0), block);
+		assert_eq!(moved, vec3(0.0, 0.0, -6.0));
+	}
+}
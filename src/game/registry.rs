@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+
+use crate::error::VoxleError;
+
+use super::{texture::{BlockTextures, LoadedTextures, TextureDesc, TextureId}, chunk::{BlockShape, BlockId}};
+
+/// the keys a block registry entry can have, see [`load_block_registry`].
+const ENTRY_KEYS: &[&str] = &["id", "name", "solid", "transparent", "light", "hardness", "shape", "textures"];
+
+/// drawn over a block that's being broken, from barely to almost broken.
+/// loaded relative to the block registry, like the block textures.
+const CRACK_TEXTURES: &[&str] = &["crack_0.png", "crack_1.png", "crack_2.png", "crack_3.png"];
+
+pub struct BlockInfo {
+	pub id: u16,
+	pub name: String,
+	/// solid blocks can be targeted and collided with.
+	pub solid: bool,
+	/// transparent blocks don't hide the faces of their neighbors.
+	pub transparent: bool,
+	/// light level emitted by the block (0 for none).
+	pub light: u8,
+	/// how long the block takes to break, in seconds. infinite for blocks that can't be broken.
+	pub hardness: f32,
+	pub shape: BlockShape,
+	pub textures: Option<BlockTextures>,
+}
+
+impl BlockInfo {
+	/// opaque blocks hide the faces of all their neighbors.
+	pub fn is_opaque(&self) -> bool {
+		self.solid && !self.transparent && self.shape == BlockShape::Cube
+	}
+
+	pub fn texture(&self, dir: super::Dir) -> TextureId {
+		self.textures.map(|textures| textures.in_direction(dir)).unwrap_or(TextureId(0))
+	}
+}
+
+pub struct BlockRegistry {
+	blocks: Vec<Option<BlockInfo>>,
+	names: HashMap<String, u16>,
+	/// returned for ids that aren't in the registry.
+	unknown: BlockInfo,
+	/// see [`BlockRegistry::crack_texture`].
+	crack_textures: Vec<TextureId>,
+}
+
+impl BlockRegistry {
+	pub fn new() -> Self {
+		Self {
+			blocks: Vec::new(),
+			names: HashMap::new(),
+			unknown: BlockInfo {
+				id: u16::MAX,
+				name: String::from("unknown"),
+				solid: true,
+				transparent: false,
+				light: 0,
+				hardness: 1.0,
+				shape: BlockShape::Cube,
+				textures: None
+			},
+			crack_textures: Vec::new(),
+		}
+	}
+
+	pub fn insert(&mut self, info: BlockInfo) {
+		let index = info.id as usize;
+		if index >= self.blocks.len() {
+			self.blocks.resize_with(index + 1, || None);
+		}
+
+		if let Some(old) = &self.blocks[index] {
+			eprintln!("block id {} ({}) redefined as {}", info.id, old.name, info.name);
+			self.names.remove(&old.name);
+		}
+
+		self.names.insert(info.name.clone(), info.id);
+		self.blocks[index] = Some(info);
+	}
+
+	pub fn get(&self, id: u16) -> &BlockInfo {
+		self.blocks.get(id as usize)
+			.and_then(|info| info.as_ref())
+			.unwrap_or(&self.unknown)
+	}
+
+	pub fn contains(&self, id: u16) -> bool {
+		self.blocks.get(id as usize).is_some_and(|info| info.is_some())
+	}
+
+	pub fn by_name(&self, name: &str) -> Option<&BlockInfo> {
+		self.names.get(name).map(|id| self.get(*id))
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &BlockInfo> {
+		self.blocks.iter().filter_map(|info| info.as_ref())
+	}
+
+	/// the cracks over a block that's `progress` (0 to 1) broken, `None` without crack textures.
+	pub fn crack_texture(&self, progress: f32) -> Option<TextureId> {
+		let last = self.crack_textures.len().checked_sub(1)?;
+		let stage = (progress * self.crack_textures.len() as f32) as usize;
+		Some(self.crack_textures[stage.min(last)])
+	}
+}
+
+/// loads the block registry, the textures are loaded relative to the json file.
+/// ### Example
+/// ```json
+/// [
+/// 	{ "id": 0, "name": "air", "solid": false, "transparent": true },
+/// 	{ "id": 1, "name": "stone", "textures": ["stone.png"] },
+/// 	{ "id": 2, "name": "grass", "textures": ["grass.png", "dirt.png", "grass_side.png"] },
+/// 	{ "id": 3, "name": "lamp", "light": 15, "textures": ["lamp.png"] },
+/// 	{ "id": 4, "name": "stone_slab", "shape": "slab_bottom", "textures": ["stone.png"] },
+/// 	{ "id": 5, "name": "lava", "textures": [{ "path": "lava.png", "frames": 4, "frame_time": 0.25 }] }
+/// ]
+/// ```
+/// `solid` defaults to `true`, `transparent` to `false`, `light` to `0`, `hardness` to `1` and `shape` to `"cube"`.
+/// blocks with a negative `hardness` can't be broken.
+/// animated textures have their frames stacked vertically, see [`super::texture::TextureAnimation`].
+///
+/// fails on unknown keys, on ids or names that are used twice, and if one of the
+/// builtin blocks (see [`BlockId`]) is missing or has a different id.
+pub fn load_block_registry(json_path: &std::path::Path, textures: &mut LoadedTextures) -> Result<BlockRegistry, VoxleError> {
+	let source = std::fs::read_to_string(json_path).map_err(VoxleError::io(json_path))?;
+	parse_block_registry(json_path, &source, textures)
+}
+
+/// [`load_block_registry`] with the manifest already read from `json_path`.
+fn parse_block_registry(json_path: &std::path::Path, source: &str, textures: &mut LoadedTextures) -> Result<BlockRegistry, VoxleError> {
+	let mut registry = BlockRegistry::new();
+	let invalid = |reason: String| VoxleError::invalid_manifest(json_path, reason);
+
+	let manifest = json::parse(source)
+		.map_err(|error| invalid(format!("block registry should be correct json: {}", error)))?;
+
+	if !manifest.is_array() {
+		return Err(invalid("block registry should be an array".to_owned()));
+	}
+
+	let root_dir = json_path.parent()
+		.ok_or_else(|| invalid("block registry should have parent dir".to_owned()))?;
+	for entry in manifest.members() {
+		if !entry.is_object() {
+			return Err(invalid("block registry entries should be objects".to_owned()));
+		}
+
+		let id = entry["id"].as_u16().ok_or_else(|| invalid("block registry entry should have a u16 id".to_owned()))?;
+		let name = entry["name"].as_str().ok_or_else(|| invalid("block registry entry should have a name".to_owned()))?;
+
+		for (key, _) in entry.entries() {
+			if !ENTRY_KEYS.contains(&key) {
+				return Err(invalid(format!("block registry entry {} has an unknown key: {}", name, key)));
+			}
+		}
+		if let Some(other) = registry.by_name(name) {
+			return Err(invalid(format!("block registry entries {} and {} have the same name: {}", other.id, id, name)));
+		}
+		if registry.contains(id) {
+			return Err(invalid(format!("block registry entries {} and {} have the same id: {}", registry.get(id).name, name, id)));
+		}
+
+		let block_textures = if entry["textures"].is_null() {
+			None
+		} else {
+			let paths = entry["textures"].members()
+				.map(|texture| if texture.is_object() {
+					Ok(TextureDesc {
+						path: texture["path"].as_str().ok_or_else(|| invalid("animated textures should have a path".to_owned()))?,
+						frame_count: texture["frames"].as_u32().ok_or_else(|| invalid("animated textures should have a frame count".to_owned()))?,
+						frame_duration: texture["frame_time"].as_f32().ok_or_else(|| invalid("animated textures should have a frame time".to_owned()))?,
+					})
+				} else {
+					Ok(TextureDesc {
+						path: texture.as_str().ok_or_else(|| invalid("block registry entry's textures must be strings or objects".to_owned()))?,
+						frame_count: 1,
+						frame_duration: 1.0,
+					})
+				})
+				.collect::<Result<Vec<TextureDesc>, VoxleError>>()?;
+			Some(textures.load_block_textures(root_dir, &paths)?)
+		};
+
+		let shape = match entry["shape"].as_str() {
+			Some(shape) => BlockShape::from_name(shape)
+				.ok_or_else(|| invalid(format!("block registry entry {} should have a valid shape, not {}", name, shape)))?,
+			None => BlockShape::Cube,
+		};
+
+		registry.insert(BlockInfo {
+			id,
+			name: String::from(name),
+			solid: entry["solid"].as_bool().unwrap_or(true),
+			transparent: entry["transparent"].as_bool().unwrap_or(false),
+			light: entry["light"].as_u8().unwrap_or(0),
+			hardness: match entry["hardness"].as_f32().unwrap_or(1.0) {
+				hardness if hardness < 0.0 => f32::INFINITY,
+				hardness => hardness,
+			},
+			shape,
+			textures: block_textures,
+		});
+	}
+
+	for path in CRACK_TEXTURES {
+		registry.crack_textures.push(textures.load_texture(&root_dir.join(path), 1, 1.0)?);
+	}
+
+	for &builtin in BlockId::all() {
+		match registry.by_name(builtin.name()) {
+			Some(info) if info.id == builtin as u16 => {},
+			Some(info) => return Err(invalid(format!("block {} should have id {}, not {}", info.name, builtin as u16, info.id))),
+			None => return Err(invalid(format!("block registry is missing the block {}", builtin.name()))),
+		}
+	}
+
+	Ok(registry)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::game::Dir;
+
+	/// the builtin blocks, with the properties they have in `blocks.json` but without textures.
+	pub fn registry() -> BlockRegistry {
+		let mut registry = BlockRegistry::new();
+		for &block in BlockId::all() {
+			let (solid, transparent) = match block {
+				BlockId::Air | BlockId::Water => (false, true),
+				BlockId::Glass | BlockId::Leaves => (true, true),
+				_ => (true, false),
+			};
+			registry.insert(BlockInfo {
+				id: block as u16,
+				name: block.name().to_owned(),
+				solid,
+				transparent,
+				light: if block == BlockId::Lamp { 15 } else { 0 },
+				hardness: if block == BlockId::Bedrock { f32::INFINITY } else { 1.0 },
+				shape: if block == BlockId::StoneSlab { BlockShape::SlabBottom } else { BlockShape::Cube },
+				textures: None,
+			});
+		}
+		registry
+	}
+
+	/// a manifest next to the shipped block textures, with all of the builtin blocks and `extra` entries.
+	fn parse_with(extra: &str, textures: &mut LoadedTextures) -> Result<BlockRegistry, VoxleError> {
+		let builtins: Vec<String> = BlockId::all().iter()
+			.map(|&block| format!(r#"{{ "id": {}, "name": "{}" }}"#, block as u16, block.name()))
+			.collect();
+		let source = format!("[{}, {}]", builtins.join(", "), extra);
+		parse_block_registry(std::path::Path::new("data/textures/blocks/fixture.json"), &source, textures)
+	}
+
+	#[test]
+	fn malformed_manifests_are_invalid() {
+		let mut textures = LoadedTextures::new();
+		let path = std::path::Path::new("data/textures/blocks/fixture.json");
+		for source in ["[", "{}"] {
+			let error = parse_block_registry(path, source, &mut textures).err().unwrap();
+			assert!(matches!(error, VoxleError::InvalidManifest { path: ref error_path, .. } if error_path == path), "{}", source);
+		}
+		for extra in [r#"{ "id": 100 }"#, r#"{ "id": 100, "name": "stone" }"#, r#"{ "id": 100, "name": "odd", "shape": "sphere" }"#] {
+			assert!(matches!(parse_with(extra, &mut textures), Err(VoxleError::InvalidManifest { .. })), "{}", extra);
+		}
+	}
+
+	#[test]
+	fn loads_per_face_and_same_face_textures() {
+		let mut textures = LoadedTextures::new();
+		let registry = parse_with(r#"
+			{ "id": 100, "name": "six", "textures": ["stone.png", "dirt.png", "sand.png", "snow.png", "glass.png", "lamp.png"] },
+			{ "id": 101, "name": "same", "solid": false, "light": 7, "textures": ["stone.png"] }
+		"#, &mut textures).unwrap();
+
+		let six = registry.by_name("six").unwrap();
+		assert_eq!(six.id, 100);
+		let ids: Vec<u32> = Dir::all().iter().map(|&dir| six.texture(dir).0).collect();
+		for (i, a) in ids.iter().enumerate() {
+			for b in &ids[i + 1..] {
+				assert_ne!(a, b, "every face of a 6 texture block should have its own texture");
+			}
+		}
+
+		let same = registry.by_name("same").unwrap();
+		assert!(!same.solid);
+		assert_eq!(same.light, 7);
+		assert!(Dir::all().iter().all(|&dir| same.texture(dir).0 == same.texture(Dir::PY).0));
+		// the path was already loaded for the first block.
+		assert_eq!(same.texture(Dir::PY).0, six.texture(Dir::PY).0);
+		assert_eq!(registry.get(BlockId::Stone as u16).name, "stone");
+	}
+
+	#[test]
+	fn loads_the_shipped_blocks() {
+		let mut textures = LoadedTextures::new();
+		let shipped = load_block_registry(std::path::Path::new("data/textures/blocks/blocks.json"), &mut textures).unwrap();
+		let builtin = registry();
+
+		for &block in BlockId::all() {
+			let info = shipped.by_name(block.name()).unwrap_or_else(|| panic!("{} isn't in the manifest", block.name()));
+			assert_eq!(info.id, block as u16);
+			assert_eq!(info.textures.is_some(), block != BlockId::Air, "{}", info.name);
+
+			// the test registry should match the shipped one.
+			let expected = builtin.get(block as u16);
+			assert_eq!((info.solid, info.transparent, info.light), (expected.solid, expected.transparent, expected.light), "{}", info.name);
+			assert_eq!(info.hardness.is_infinite(), expected.hardness.is_infinite(), "{}", info.name);
+			assert!(info.shape == expected.shape, "{}", info.name);
+		}
+
+		let snow = shipped.get(BlockId::Snow as u16);
+		let snow_grass = shipped.get(BlockId::SnowGrass as u16);
+		assert_eq!(snow_grass.texture(Dir::PY).0, snow.texture(Dir::PY).0);
+		assert_ne!(snow_grass.texture(Dir::PX).0, snow.texture(Dir::PX).0);
+		assert_ne!(snow.texture(Dir::PY).0, shipped.get(BlockId::Stone as u16).texture(Dir::PY).0);
+	}
+
+	#[test]
+	fn reloading_with_a_new_texture_adds_a_layer() {
+		let block = r#"{ "id": 100, "name": "a", "textures": ["stone.png"] }"#;
+		let mut textures = LoadedTextures::new();
+		let registry = parse_with(block, &mut textures).unwrap();
+
+		let mut reloaded_textures = LoadedTextures::new();
+		let reloaded = parse_with(&format!(r#"{}, {{ "id": 101, "name": "b", "textures": ["dirt.png"] }}"#, block), &mut reloaded_textures).unwrap();
+		assert_eq!(reloaded_textures.textures.len(), textures.textures.len() + 1);
+		assert_eq!(reloaded_textures.size, textures.size);
+		// the textures are loaded in order, so the earlier blocks keep theirs.
+		let a = registry.by_name("a").unwrap().texture(Dir::PY).0;
+		assert_eq!(reloaded.by_name("a").unwrap().texture(Dir::PY).0, a);
+		assert_eq!(reloaded.by_name("b").unwrap().texture(Dir::PY).0, a + 1);
+	}
+}
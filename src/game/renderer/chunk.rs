@@ -1,6 +1,6 @@
 use wgpu::util::DeviceExt;
 
-use crate::{gfx, math::*, game::{texture, chunk::CHUNK_SIZE}};
+use crate::{gfx, math::*, error::VoxleError, game::{texture, chunk::CHUNK_SIZE, Dir}};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -8,44 +8,129 @@ pub struct OutlineVertex {
 	pub position: [f32; 3],
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OverlayVertex {
+	pub position: [f32; 3],
+	pub uv: [f32; 2],
+}
+
+/// the range of the 10 bit signed ints that [`BlockVertex`] positions are packed into, in half blocks,
+/// so -256..=255.5 blocks.
+pub const I10_MIN: i32 = -512;
+pub const I10_MAX: i32 = 511;
+
+/// the largest chunk side that [`BlockVertex`] can hold: the far faces of a chunk are `CHUNK_SIZE`
+/// blocks from its origin, so twice that has to fit into [`I10_MAX`], and chunk sides are powers of two.
+pub const MAX_CHUNK_SIZE: usize = 128;
+
+static_assertions::const_assert!(MAX_CHUNK_SIZE * 2 <= I10_MAX as usize);
+static_assertions::const_assert!(MAX_CHUNK_SIZE * 4 > I10_MAX as usize);
+
+/// a mesh vertex, packed into two u32s, from the most significant bits:
+/// - `data0`: uv corner index (2 bits), then z, y and x (10 bit signed ints, in half blocks).
+/// - `data1`: texture id (16 bits), sky light (4), block light (4), then the 4 ao values (2 bits each, the first is lowest).
+///
+/// the shader unpacks it in `block.wgsl`, see [`BlockVertex::unpack`].
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct BlockVertex {
-	pub data0: u32, // z:10 y:10 x:10 uv:2
-	pub data1: u32, // tid:24 ao:8
+	pub data0: u32, // uv:2 z:10 y:10 x:10
+	pub data1: u32, // tid:16 sky:4 light:4 ao:8
 }
 
+/// the fields of a [`BlockVertex`], like the arguments of [`BlockVertex::new`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnpackedBlockVertex {
+	/// relative to the chunk, in blocks.
+	pub position: Vec3f32,
+	/// the corner of the face, an index into `uvs` in `block.wgsl`.
+	pub uv: u8,
+	pub ao: [u8; 4],
+	pub light: u8,
+	pub skylight: u8,
+	pub texture_id: u32,
+}
+
+impl UnpackedBlockVertex {
+	/// the texture coordinates of the face corner, see `uvs` in `block.wgsl`.
+	pub fn corner_uv(&self) -> Vec2f32 {
+		const UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+		Vector(UVS[self.uv as usize])
+	}
+}
+
+/// the low 10 bits of `i`, which has to be in -512..512.
 fn i32_to_i10(i: i32) -> u32 {
 	let i = i as u32;
 	((i >> 31) << 9) | (i & 0x1ff)
 }
 
+/// sign-extends the low 10 bits, like `extractBits` in `block.wgsl`.
+fn i10_to_i32(i: u32) -> i32 {
+	((i << 22) as i32) >> 22
+}
+
 impl BlockVertex {
-	pub fn new(pos: Vec3f32, uv: u8, ao: &[u8; 4], tex: u32) -> Self {
+	pub fn new(pos: Vec3f32, uv: u8, ao: &[u8; 4], light: u8, skylight: u8, tex: u32) -> Self {
+		debug_assert!(
+			pos.0.iter().all(|&c| (I10_MIN as f32..=I10_MAX as f32).contains(&(c * 2.0))),
+			"vertex position {:?} doesn't fit into 10 bits", pos
+		);
 		Self {
 			data0: ((uv as u32 & 0b11) << 30)
 				| (i32_to_i10((pos.x * 2.0) as i32) << 00)
 				| (i32_to_i10((pos.y * 2.0) as i32) << 10)
 				| (i32_to_i10((pos.z * 2.0) as i32) << 20),
-			data1: ((tex as u32) << 8)
+			data1: ((tex as u32) << 16)
+				| ((skylight as u32 & 0b1111) << 12)
+				| ((light as u32 & 0b1111) << 8)
 				| ((ao[0] as u32 & 0b11) << 0)
 				| ((ao[1] as u32 & 0b11) << 2)
 				| ((ao[2] as u32 & 0b11) << 4)
 				| ((ao[3] as u32 & 0b11) << 6)
 		}
 	}
+
+	/// the position relative to the chunk, in blocks.
+	pub fn position(&self) -> Vec3f32 {
+		vec3(
+			i10_to_i32(self.data0 >> 00) as f32,
+			i10_to_i32(self.data0 >> 10) as f32,
+			i10_to_i32(self.data0 >> 20) as f32,
+		) * 0.5
+	}
+
+	pub fn texture_id(&self) -> u32 {
+		self.data1 >> 16
+	}
+
+	/// all of the fields, the inverse of [`BlockVertex::new`].
+	pub fn unpack(&self) -> UnpackedBlockVertex {
+		UnpackedBlockVertex {
+			position: self.position(),
+			uv: (self.data0 >> 30) as u8,
+			ao: [0, 2, 4, 6].map(|shift| ((self.data1 >> shift) & 0b11) as u8),
+			light: ((self.data1 >> 8) & 0b1111) as u8,
+			skylight: ((self.data1 >> 12) & 0b1111) as u8,
+			texture_id: self.texture_id(),
+		}
+	}
 }
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct BlockPushConsts {
-	chunk_pos: [i32; 3]
+struct OutlinePushConstants {
+	position: [f32; 3]
 }
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct OutlinePushConstants {
-	position: [f32; 3]
+struct OverlayPushConstants {
+	position: [f32; 3],
+	/// layer of the block texture, negative for none.
+	texture: i32,
+	color: [f32; 4],
 }
 
 fn create_block_pipeline(
@@ -53,7 +138,10 @@ fn create_block_pipeline(
 	layout: &wgpu::PipelineLayout,
 	shader: &wgpu::ShaderModule,
 	polymode: wgpu::PolygonMode,
-	depth_format: wgpu::TextureFormat
+	blend: Option<wgpu::BlendState>,
+	after_depth_prepass: bool,
+	depth_format: wgpu::TextureFormat,
+	samples: u32
 ) -> wgpu::RenderPipeline {
 	gfx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
 		label: Some("Block Pipeline"),
@@ -86,7 +174,7 @@ fn create_block_pipeline(
 			targets: &[
 				Some(wgpu::ColorTargetState {
 					format: gfx.config.format,
-					blend: None,
+					blend,
 					write_mask: wgpu::ColorWrites::ALL
 				})
 			]
@@ -102,9 +190,11 @@ fn create_block_pipeline(
 		},
 		depth_stencil: Some(wgpu::DepthStencilState {
 			format: depth_format,
+			// transparent geometry is sorted instead.
+			// after the depth pre-pass, the depth is already there and only tested against.
 			depth_write_enabled: match polymode {
 				wgpu::PolygonMode::Line => false,
-				_ => true,
+				_ => blend.is_none() && !after_depth_prepass,
 			},
 			depth_compare: match polymode {
 				wgpu::PolygonMode::Line => wgpu::CompareFunction::LessEqual,
@@ -114,7 +204,7 @@ fn create_block_pipeline(
 			bias: wgpu::DepthBiasState::default(),
 		}),
 		multisample: wgpu::MultisampleState {
-			count: super::GameRenderer::SAMPLES,
+			count: samples,
 			mask: !0,
 			alpha_to_coverage_enabled: false
 		},
@@ -122,11 +212,161 @@ fn create_block_pipeline(
 	})
 }
 
-fn create_outline_pipeline(
+/// depth-only, renders the chunks from the sun's point of view.
+fn create_shadow_pipeline(
 	gfx: &gfx::Gfx,
 	layout: &wgpu::PipelineLayout,
 	shader: &wgpu::ShaderModule,
 	depth_format: wgpu::TextureFormat
+) -> wgpu::RenderPipeline {
+	gfx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+		label: Some("Block Shadow Pipeline"),
+		layout: Some(&layout),
+		vertex: wgpu::VertexState {
+			module: &shader,
+			entry_point: "vs_main",
+			buffers: &[wgpu::VertexBufferLayout {
+				array_stride: std::mem::size_of::<BlockVertex>() as wgpu::BufferAddress,
+				step_mode: wgpu::VertexStepMode::Vertex,
+				attributes: &[
+					// data0 (position, ao)
+					wgpu::VertexAttribute {
+						format: wgpu::VertexFormat::Uint32,
+						offset: 0,
+						shader_location: 0
+					},
+				],
+			}]
+		},
+		fragment: None,
+		primitive: wgpu::PrimitiveState {
+			topology: wgpu::PrimitiveTopology::TriangleList,
+			strip_index_format: None,
+			front_face: wgpu::FrontFace::Cw,
+			cull_mode: Some(wgpu::Face::Back),
+			unclipped_depth: false,
+			polygon_mode: wgpu::PolygonMode::Fill,
+			conservative: false
+		},
+		depth_stencil: Some(wgpu::DepthStencilState {
+			format: depth_format,
+			depth_write_enabled: true,
+			depth_compare: wgpu::CompareFunction::LessEqual,
+			stencil: wgpu::StencilState::default(),
+			// pushes the depth back a bit, so that lit faces don't shadow themselves.
+			bias: wgpu::DepthBiasState {
+				constant: 2,
+				slope_scale: 2.0,
+				clamp: 0.0,
+			},
+		}),
+		multisample: wgpu::MultisampleState::default(),
+		multiview: None
+	})
+}
+
+/// depth-only, renders the opaque chunks from the camera before the main pass,
+/// so that the main pass only shades the visible fragments.
+fn create_depth_prepass_pipeline(
+	gfx: &gfx::Gfx,
+	layout: &wgpu::PipelineLayout,
+	shader: &wgpu::ShaderModule,
+	depth_format: wgpu::TextureFormat,
+	samples: u32
+) -> wgpu::RenderPipeline {
+	gfx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+		label: Some("Block Depth Pre-pass Pipeline"),
+		layout: Some(&layout),
+		vertex: wgpu::VertexState {
+			module: &shader,
+			entry_point: "vs_main",
+			buffers: &[wgpu::VertexBufferLayout {
+				array_stride: std::mem::size_of::<BlockVertex>() as wgpu::BufferAddress,
+				step_mode: wgpu::VertexStepMode::Vertex,
+				attributes: &[
+					// data0 (position, ao)
+					wgpu::VertexAttribute {
+						format: wgpu::VertexFormat::Uint32,
+						offset: 0,
+						shader_location: 0
+					},
+				],
+			}]
+		},
+		fragment: None,
+		primitive: wgpu::PrimitiveState {
+			topology: wgpu::PrimitiveTopology::TriangleList,
+			strip_index_format: None,
+			front_face: wgpu::FrontFace::Cw,
+			cull_mode: Some(wgpu::Face::Back),
+			unclipped_depth: false,
+			polygon_mode: wgpu::PolygonMode::Fill,
+			conservative: false
+		},
+		depth_stencil: Some(wgpu::DepthStencilState {
+			format: depth_format,
+			depth_write_enabled: true,
+			depth_compare: wgpu::CompareFunction::LessEqual,
+			stencil: wgpu::StencilState::default(),
+			bias: wgpu::DepthBiasState::default(),
+		}),
+		multisample: wgpu::MultisampleState {
+			count: samples,
+			mask: !0,
+			alpha_to_coverage_enabled: false
+		},
+		multiview: None
+	})
+}
+
+/// the constants of `game/block`, `is_black` is for the wireframe.
+fn block_shader_consts(is_black: bool) -> super::ShaderConsts {
+	super::ShaderConsts::from([("is_black".to_owned(), is_black.into())])
+}
+
+const TRANSPARENT_BLEND: wgpu::BlendState = wgpu::BlendState {
+	color: wgpu::BlendComponent {
+		src_factor: wgpu::BlendFactor::SrcAlpha,
+		dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+		operation: wgpu::BlendOperation::Add,
+	},
+	alpha: wgpu::BlendComponent::OVER
+};
+
+/// the normal, transparent, wireframe and normal-after-the-depth-pre-pass pipelines.
+fn create_block_pipelines(
+	gfx: &gfx::Gfx,
+	layout: &wgpu::PipelineLayout,
+	block_shader: &wgpu::ShaderModule,
+	wf_block_shader: &wgpu::ShaderModule,
+	samples: u32
+) -> [wgpu::RenderPipeline; 4] {
+	[
+		create_block_pipeline(gfx, layout, block_shader, wgpu::PolygonMode::Fill, None, false, super::GameRenderer::DEPTH_FORMAT, samples),
+		create_block_pipeline(gfx, layout, block_shader, wgpu::PolygonMode::Fill, Some(TRANSPARENT_BLEND), false, super::GameRenderer::DEPTH_FORMAT, samples),
+		create_block_pipeline(gfx, layout, wf_block_shader, wgpu::PolygonMode::Line, None, false, super::GameRenderer::DEPTH_FORMAT, samples),
+		create_block_pipeline(gfx, layout, block_shader, wgpu::PolygonMode::Fill, None, true, super::GameRenderer::DEPTH_FORMAT, samples),
+	]
+}
+
+/// opaque and transparent, without msaa, see [`super::icon::IconRenderer`].
+fn create_icon_pipelines(
+	gfx: &gfx::Gfx,
+	layout: &wgpu::PipelineLayout,
+	block_shader: &wgpu::ShaderModule,
+) -> [wgpu::RenderPipeline; 2] {
+	[
+		create_block_pipeline(gfx, layout, block_shader, wgpu::PolygonMode::Fill, None, false, super::GameRenderer::DEPTH_FORMAT, 1),
+		create_block_pipeline(gfx, layout, block_shader, wgpu::PolygonMode::Fill, Some(TRANSPARENT_BLEND), false, super::GameRenderer::DEPTH_FORMAT, 1),
+	]
+}
+
+fn create_outline_pipeline(
+	gfx: &gfx::Gfx,
+	layout: &wgpu::PipelineLayout,
+	shader: &wgpu::ShaderModule,
+	depth_format: wgpu::TextureFormat,
+	samples: u32
 ) -> wgpu::RenderPipeline {
 	gfx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
 		label: Some("Outline Pipeline"),
@@ -175,7 +415,76 @@ fn create_outline_pipeline(
 			bias: wgpu::DepthBiasState::default(),
 		}),
 		multisample: wgpu::MultisampleState {
-			count: super::GameRenderer::SAMPLES,
+			count: samples,
+			mask: !0,
+			alpha_to_coverage_enabled: false
+		},
+		multiview: None
+	})
+}
+
+/// textured quads on the faces of a block, drawn over it.
+fn create_overlay_pipeline(
+	gfx: &gfx::Gfx,
+	layout: &wgpu::PipelineLayout,
+	shader: &wgpu::ShaderModule,
+	depth_format: wgpu::TextureFormat,
+	samples: u32
+) -> wgpu::RenderPipeline {
+	gfx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+		label: Some("Overlay Pipeline"),
+		layout: Some(&layout),
+		vertex: wgpu::VertexState {
+			module: &shader,
+			entry_point: "vs_main",
+			buffers: &[wgpu::VertexBufferLayout {
+				array_stride: std::mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+				step_mode: wgpu::VertexStepMode::Vertex,
+				attributes: &[
+					// position
+					wgpu::VertexAttribute {
+						format: wgpu::VertexFormat::Float32x3,
+						offset: 0,
+						shader_location: 0
+					},
+					// uv
+					wgpu::VertexAttribute {
+						format: wgpu::VertexFormat::Float32x2,
+						offset: 4 * 3,
+						shader_location: 1
+					},
+				],
+			}]
+		},
+		fragment: Some(wgpu::FragmentState {
+			module: &shader,
+			entry_point: "fs_main",
+			targets: &[
+				Some(wgpu::ColorTargetState {
+					format: gfx.config.format,
+					blend: Some(TRANSPARENT_BLEND),
+					write_mask: wgpu::ColorWrites::ALL
+				})
+			]
+		}),
+		primitive: wgpu::PrimitiveState {
+			topology: wgpu::PrimitiveTopology::TriangleList,
+			strip_index_format: None,
+			front_face: wgpu::FrontFace::Cw,
+			cull_mode: Some(wgpu::Face::Back),
+			unclipped_depth: false,
+			polygon_mode: wgpu::PolygonMode::Fill,
+			conservative: false
+		},
+		depth_stencil: Some(wgpu::DepthStencilState {
+			format: depth_format,
+			depth_write_enabled: false,
+			depth_compare: wgpu::CompareFunction::LessEqual,
+			stencil: wgpu::StencilState::default(),
+			bias: wgpu::DepthBiasState::default(),
+		}),
+		multisample: wgpu::MultisampleState {
+			count: samples,
 			mask: !0,
 			alpha_to_coverage_enabled: false
 		},
@@ -183,29 +492,65 @@ fn create_outline_pipeline(
 	})
 }
 
+/// how the block textures are sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFiltering {
+	/// keeps the pixelated look, distant blocks can shimmer.
+	Nearest,
+	/// smooth, blends between the mipmaps.
+	Trilinear,
+}
+
+/// how a [`Camera`] projects the world onto the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+	/// `fovy` is the vertical field of view, in degrees.
+	Perspective { fovy: f32 },
+	/// `height` is how many blocks fit vertically on the screen, e.g. for map previews.
+	Orthographic { height: f32 },
+}
+
 pub struct Camera {
 	pub position: Vec3f32,
 	pub yaw: f32,
 	pub pitch: f32,
 	pub aspect: f32,
-	pub fovy: f32,
+	pub projection: Projection,
 	pub znear: f32,
 	pub zfar: f32,
+	/// moves the eye away from `position` when rendering, e.g. behind the player in third person.
+	pub eye_offset: Vec3f32,
 }
 
 impl Camera {
+	/// where the world is rendered from, `position` moved by `eye_offset`.
+	pub fn eye(&self) -> Vec3f32 {
+		self.position + self.eye_offset
+	}
+
 	fn build_view_proj_matrix(&self) -> glm::Mat4 {
 		let direction = self.direction();
+		let eye = self.eye();
 
 		let view = glm::look_at_rh(
-			&glm::vec3(self.position.x, self.position.y, self.position.z),
-			&glm::TVec::from_column_slice(&(self.position + direction).0),
+			&glm::vec3(eye.x, eye.y, eye.z),
+			&glm::TVec::from_column_slice(&(eye + direction).0),
 			&glm::vec3(0.0, 1.0, 0.0)
 		);
-		
-		let proj = glm::perspective_rh_zo(self.aspect, glm::radians(&glm::vec1(self.fovy)).x, self.znear, self.zfar);
 
-		proj * view
+		self.build_proj_matrix() * view
+	}
+
+	/// the projection matrix, with a 0..1 depth range like [`sun_view_proj`].
+	fn build_proj_matrix(&self) -> glm::Mat4 {
+		match self.projection {
+			Projection::Perspective { fovy } =>
+				glm::perspective_rh_zo(self.aspect, glm::radians(&glm::vec1(fovy)).x, self.znear, self.zfar),
+			Projection::Orthographic { height } => {
+				let (half_width, half_height) = (height * self.aspect * 0.5, height * 0.5);
+				glm::ortho_rh_zo(-half_width, half_width, -half_height, half_height, self.znear, self.zfar)
+			}
+		}
 	}
 
 	pub fn direction(&self) -> Vec3f32 {
@@ -216,22 +561,98 @@ impl Camera {
 		)
 	}
 
+	pub fn frustum(&self) -> Frustum {
+		Frustum::from_view_proj(&self.build_view_proj_matrix().data.0)
+	}
+
 	fn to_uniform(&self) -> CameraUniform {
-		let view_proj = self.build_view_proj_matrix().data.0;
-		CameraUniform { view_proj }
+		let view_proj = self.build_view_proj_matrix();
+		let eye = self.eye();
+		CameraUniform {
+			view_proj: view_proj.data.0,
+			inv_view_proj: glm::inverse(&view_proj).data.0,
+			position: [eye.x, eye.y, eye.z, 1.0],
+		}
 	}
 }
 
+/// an orthographic projection looking along `sun_direction`, covering a cube with
+/// a half-size of `radius` around `center`. the projection is snapped to the shadow map's
+/// texels (of which there are `resolution` along each side), so that shadows don't shimmer as `center` moves.
+pub fn sun_view_proj(center: Vec3f32, sun_direction: Vec3f32, radius: f32, resolution: u32) -> glm::Mat4 {
+	let direction = sun_direction.normalized();
+	let up = if direction.y.abs() > 0.99 { glm::vec3(0.0, 0.0, 1.0) } else { glm::vec3(0.0, 1.0, 0.0) };
+	let view = glm::look_at_rh(
+		&glm::vec3(0.0, 0.0, 0.0),
+		&glm::vec3(direction.x, direction.y, direction.z),
+		&up
+	);
+
+	let light_center = view * glm::vec4(center.x, center.y, center.z, 1.0);
+	let texel = 2.0 * radius / resolution as f32;
+	let x = (light_center.x / texel).round() * texel;
+	let y = (light_center.y / texel).round() * texel;
+	// the view looks down -z, the depth range is larger to include the casters above the cube.
+	let proj = glm::ortho_rh_zo(
+		x - radius, x + radius,
+		y - radius, y + radius,
+		-light_center.z - radius * 2.0, -light_center.z + radius
+	);
+
+	proj * view
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct CameraUniform {
 	view_proj: [[f32; 4]; 4],
+	/// for reconstructing view directions, e.g. for the sky.
+	inv_view_proj: [[f32; 4]; 4],
+	position: [f32; 4],
+}
+
+/// the most point lights that are lit at once, see [`ChunkRenderer::set_point_lights`].
+pub const MAX_POINT_LIGHTS: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PointLight {
+	pub position: Vec3f32,
+	pub color: Vec3f32,
+	/// the light fades to nothing at this distance, in blocks.
+	pub radius: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointLightUniform {
+	/// xyz is the position, w the radius.
+	position: [f32; 4],
+	/// rgb, a is unused.
+	color: [f32; 4],
 }
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct LightingUniform {
-	sun_dir: [f32; 4]
+	sun_dir: [f32; 4],
+	/// rgb, a is unused.
+	sun_color: [f32; 4],
+	/// rgb, a is unused.
+	fog_color: [f32; 4],
+	/// start and end distance from the camera, zw are unused.
+	fog_range: [f32; 4],
+	/// see [`sun_view_proj`].
+	light_view_proj: [[f32; 4]; 4],
+	/// x is 1 if shadows are enabled, y is the size of a shadow map texel in uv, zw are unused.
+	shadow_params: [f32; 4],
+	/// x is the number of used `point_lights`, yzw are unused.
+	point_light_count: [u32; 4],
+	/// x is the ambient occlusion strength, 0 disables it, yzw are unused.
+	ao_params: [f32; 4],
+	/// rgb, the color of the baked block light, a is unused.
+	block_light_color: [f32; 4],
+	/// every member is a multiple of 16 bytes, as required for arrays in uniforms.
+	point_lights: [PointLightUniform; MAX_POINT_LIGHTS],
 }
 
 struct WorldUniforms {
@@ -259,12 +680,11 @@ impl WorldUniforms {
 		}
 	}
 
-	#[allow(dead_code)]
+	#[cfg(test)]
 	fn camera_uniform<'a>(&'a self) -> &'a CameraUniform {
 		bytemuck::from_bytes(&self.data[self.camera_uniform_range()])
 	}
 
-	#[allow(dead_code)]
 	fn lighting_uniform<'a>(&'a self) -> &'a LightingUniform {
 		bytemuck::from_bytes(&self.data[self.lighting_uniform_range()])
 	}
@@ -289,35 +709,175 @@ impl WorldUniforms {
 	const fn lighting_uniform_range(&self) -> std::ops::Range<usize> {
 		self.lighting_uniform_offset() .. self.lighting_uniform_offset() + self.lighting_uniform_size()
 	}
+
+	/// the parts of the buffer that are uploaded separately, so that e.g. moving the camera doesn't rewrite the lighting.
+	const fn ranges(&self) -> [std::ops::Range<usize>; 2] {
+		[self.camera_uniform_range(), self.lighting_uniform_range()]
+	}
+
+	/// the parts of `ranges` that differ from `uploaded`, the copy of the buffer's contents.
+	fn changed_ranges<'a>(&'a self, uploaded: &'a [u8]) -> impl Iterator<Item = std::ops::Range<usize>> + 'a {
+		self.ranges().into_iter().filter(|range| self.data[range.clone()] != uploaded[range.clone()])
+	}
+}
+
+/// the origin of a chunk in blocks, padded to the 16 byte stride of `array<vec3i>`.
+type ChunkOrigin = [i32; 4];
+
+/// the origins of the chunks at `positions`, in the same order.
+pub fn chunk_origins(positions: &[Vec3i32]) -> Vec<ChunkOrigin> {
+	positions.iter().map(|position| {
+		let origin = *position * CHUNK_SIZE.each_as::<i32>();
+		[origin.x, origin.y, origin.z, 0]
+	}).collect()
+}
+
+/// the arguments of an indexed draw, laid out like [`wgpu::util::DrawIndexedIndirect`],
+/// which isn't `Pod`. `first_instance` indexes the chunk origins.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ChunkDraw {
+	pub index_count: u32,
+	pub instance_count: u32,
+	pub first_index: u32,
+	pub base_vertex: i32,
+	pub first_instance: u32,
+}
+
+/// the chunk meshes that are drawn together, see [`ChunkRenderer::set_chunk_draws`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkBatch {
+	/// the opaque (or lod) meshes of the visible chunks, also drawn by the depth pre-pass and the wireframe.
+	Opaque,
+	/// the transparent meshes of the visible chunks, back-to-front.
+	Transparent,
+	/// the opaque meshes of the chunks that cast shadows.
+	Shadow,
+}
+
+/// a chunk's mesh in a batch: its position, the indices in the pool and the base vertex, see [`gfx::pool::PooledMesh`].
+type BatchedMesh = (Vec3i32, std::ops::Range<u32>, i32);
+
+/// one draw per mesh in `batches` (in the order of [`ChunkBatch`]), each with its own origin,
+/// and the range of draws of each batch. the empty meshes are skipped.
+fn chunk_draws(batches: [&[BatchedMesh]; 3]) -> (Vec<ChunkDraw>, Vec<ChunkOrigin>, [std::ops::Range<u32>; 3]) {
+	let mut draws = Vec::new();
+	let mut positions = Vec::new();
+	let ranges = batches.map(|meshes| {
+		let start = draws.len() as u32;
+		for (position, indices, base_vertex) in meshes.iter().filter(|(_, indices, _)| !indices.is_empty()) {
+			draws.push(ChunkDraw {
+				index_count: indices.len() as u32,
+				instance_count: 1,
+				first_index: indices.start,
+				base_vertex: *base_vertex,
+				first_instance: positions.len() as u32,
+			});
+			positions.push(*position);
+		}
+		start..draws.len() as u32
+	});
+	(draws, chunk_origins(&positions), ranges)
+}
+
+/// the corners of the overlay quad on `face` of a block centered at the origin,
+/// slightly larger than the block, so that it isn't hidden by it.
+fn overlay_face_corners(face: Dir) -> [[f32; 3]; 4] {
+	use super::super::chunk::{CUBE_FACES, CUBE_VERTICES};
+	const SCALE: f32 = 1.002;
+	let (_, corners) = CUBE_FACES.into_iter().find(|(dir, _)| *dir == face).unwrap();
+	corners.map(|index| CUBE_VERTICES[index].map(|x| x * SCALE))
 }
 
 pub struct ChunkRenderer {
 	block_render_pipeline: wgpu::RenderPipeline,
+	block_transparent_render_pipeline: wgpu::RenderPipeline,
 	block_wf_render_pipeline: wgpu::RenderPipeline,
+	/// the normal pipeline, without depth writes.
+	block_after_prepass_render_pipeline: wgpu::RenderPipeline,
+	block_shadow_render_pipeline: wgpu::RenderPipeline,
+	block_depth_prepass_render_pipeline: wgpu::RenderPipeline,
+	block_icon_render_pipeline: wgpu::RenderPipeline,
+	block_icon_transparent_render_pipeline: wgpu::RenderPipeline,
 	outline_render_pipeline: wgpu::RenderPipeline,
-	uniform_bind_group: wgpu::BindGroup,
+	overlay_render_pipeline: wgpu::RenderPipeline,
+	/// kept to rebuild the pipelines when the shaders are reloaded.
+	#[cfg(feature = "hot-reload")]
+	block_pipeline_layout: wgpu::PipelineLayout,
+	/// also used by the depth pre-pass.
+	#[cfg(feature = "hot-reload")]
+	shadow_pipeline_layout: wgpu::PipelineLayout,
+	#[cfg(feature = "hot-reload")]
+	outline_pipeline_layout: wgpu::PipelineLayout,
+	#[cfg(feature = "hot-reload")]
+	overlay_pipeline_layout: wgpu::PipelineLayout,
+	shadow_bind_group_layout: wgpu::BindGroupLayout,
+	/// set by [`ChunkRenderer::set_shadow_map`], since the shadow map is owned by the render graph.
+	shadow_bind_group: Option<wgpu::BindGroup>,
+	pub(super) uniform_bind_group: wgpu::BindGroup,
+	/// shared with the other world renderers, e.g. the sky.
+	pub(super) world_bind_group_layout: wgpu::BindGroupLayout,
 	world_uniforms_buffer: wgpu::Buffer,
+	/// what's in `world_uniforms_buffer`, it's only written when the uniforms change, see [`ChunkRenderer::update`].
+	uploaded_world_uniforms: Vec<u8>,
+	/// the origins of the chunks drawn this frame, see [`ChunkRenderer::set_chunk_draws`].
+	chunk_origins_buffer: wgpu::Buffer,
+	/// the [`ChunkDraw`]s of this frame, for [`wgpu::RenderPass::multi_draw_indexed_indirect`].
+	chunk_draws_buffer: wgpu::Buffer,
+	/// what's in `chunk_draws_buffer`, drawn one by one without `multi_draw`.
+	chunk_draws: Vec<ChunkDraw>,
+	/// the draws of each [`ChunkBatch`].
+	chunk_batches: [std::ops::Range<u32>; 3],
+	/// whether the batches are drawn with one indirect draw, which needs the indirect draws to have a first instance.
+	multi_draw: bool,
+	/// the vertices and indices of all the chunk meshes, see [`super::super::chunk::Chunk::upload_mesh`].
+	pub mesh_pool: gfx::pool::MeshPool<BlockVertex>,
 	texture_bind_group: wgpu::BindGroup,
-	_texture: gfx::Texture,
+	texture_bind_group_layout: wgpu::BindGroupLayout,
+	filtering: TextureFiltering,
+	/// msaa sample count of the pipelines, kept to rebuild them.
+	#[cfg(feature = "hot-reload")]
+	samples: u32,
+	block_texture: gfx::Texture,
+	animations: Vec<texture::TextureAnimation>,
+	/// indexed by texture layer, the offset from the layer to the current frame.
+	texture_frames: Vec<u32>,
+	texture_frames_buffer: wgpu::Buffer,
+	/// what's in `texture_frames_buffer`, like `uploaded_world_uniforms`.
+	uploaded_texture_frames: Vec<u32>,
+	/// seconds since the renderer was created, for the animations.
+	time: f32,
 	world_uniforms: WorldUniforms,
 	outline_mesh: gfx::Mesh<OutlineVertex>,
+	/// the faces of a block, 6 indices each, in the order of [`Dir`].
+	overlay_mesh: gfx::Mesh<OverlayVertex>,
 	pub camera: Camera
 }
 
 impl ChunkRenderer {
+	/// the origins and draws buffers grow when more chunks are drawn, see [`ChunkRenderer::set_chunk_draws`].
+	const INITIAL_CHUNK_DRAWS: usize = 256;
+	/// about a render distance of 8 of hilly terrain, the pool grows when it's full.
+	const INITIAL_POOL_VERTICES: u32 = 1 << 20;
+	const INITIAL_POOL_INDICES: u32 = 3 << 19;
+
 	fn create_world_uniforms(gfx: &gfx::Gfx) -> WorldUniforms {
-		WorldUniforms::new(gfx.device.limits().min_uniform_buffer_offset_alignment as usize)
+		let mut uniforms = WorldUniforms::new(gfx.device.limits().min_uniform_buffer_offset_alignment as usize);
+		uniforms.lighting_uniform_mut().ao_params = [1.0, 0.0, 0.0, 0.0];
+		uniforms
 	}
 
-	pub fn new(gfx: &gfx::Gfx, block_textures: &texture::LoadedTextures) -> Self {
+	/// `samples` is the msaa sample count of the main pass, see [`super::GameRenderer::new`].
+	pub fn new(gfx: &gfx::Gfx, block_textures: &texture::LoadedTextures, filtering: TextureFiltering, samples: u32) -> Result<Self, VoxleError> {
 		let camera = Camera {
 			position: Vector([0.0, 128.5, -2.0]),
 			yaw: 3.0 * glm::quarter_pi::<f32>(),
 			pitch: 0.0,
 			aspect: gfx.config.width as f32 / gfx.config.height as f32,
-			fovy: 60.0,
+			projection: Projection::Perspective { fovy: 60.0 },
 			znear: 0.01,
-			zfar: 1000.0
+			zfar: 1000.0,
+			eye_offset: Vector([0.0, 0.0, 0.0]),
 		};
 
 		let world_bind_group_layout = gfx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -326,7 +886,7 @@ impl ChunkRenderer {
 				wgpu::BindGroupLayoutEntry {
 					binding: 0,
 					count: None,
-					visibility: wgpu::ShaderStages::VERTEX,
+					visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
 					ty: wgpu::BindingType::Buffer {
 						ty: wgpu::BufferBindingType::Uniform,
 						has_dynamic_offset: false,
@@ -336,12 +896,24 @@ impl ChunkRenderer {
 				wgpu::BindGroupLayoutEntry {
 					binding: 1,
 					count: None,
-					visibility: wgpu::ShaderStages::FRAGMENT,
+					// the shadow pass transforms vertices with the light's matrix.
+					visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
 					ty: wgpu::BindingType::Buffer {
 						ty: wgpu::BufferBindingType::Uniform,
 						has_dynamic_offset: false,
 						min_binding_size: None
 					},
+				},
+				// the origins of the drawn chunks, see [`ChunkRenderer::set_chunk_draws`].
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					count: None,
+					visibility: wgpu::ShaderStages::VERTEX,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Storage { read_only: true },
+						has_dynamic_offset: false,
+						min_binding_size: None
+					},
 				}
 			]
 		});
@@ -364,23 +936,57 @@ impl ChunkRenderer {
 					visibility: wgpu::ShaderStages::FRAGMENT,
 					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
 					count: None
+				},
+				// the current frame of every texture layer, see [`ChunkRenderer::update`].
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Storage { read_only: true },
+						has_dynamic_offset: false,
+						min_binding_size: None
+					},
+					count: None
 				}
 			],
 		});
 
-		let block_pipeline_layout = gfx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-			label: None,
-			bind_group_layouts: &[&world_bind_group_layout, &texture_bind_group_layout],
-			push_constant_ranges: &[
-				wgpu::PushConstantRange {
-					range: 0..std::mem::size_of::<BlockPushConsts>() as u32,
-					stages: wgpu::ShaderStages::VERTEX
-				}
-			]
-		});
-
-		let outline_pipeline_layout = gfx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-			label: None,
+		let shadow_bind_group_layout = gfx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			label: Some("Shadow Bind Group Layout"),
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						multisampled: false,
+						view_dimension: wgpu::TextureViewDimension::D2,
+						sample_type: wgpu::TextureSampleType::Depth
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+					count: None
+				}
+			],
+		});
+
+		let block_pipeline_layout = gfx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: None,
+			bind_group_layouts: &[&world_bind_group_layout, &texture_bind_group_layout, &shadow_bind_group_layout],
+			push_constant_ranges: &[]
+		});
+
+		let shadow_pipeline_layout = gfx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: None,
+			bind_group_layouts: &[&world_bind_group_layout],
+			push_constant_ranges: &[]
+		});
+
+		let outline_pipeline_layout = gfx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: None,
 			bind_group_layouts: &[&world_bind_group_layout],
 			push_constant_ranges: &[wgpu::PushConstantRange {
 				range: 0..std::mem::size_of::<OutlinePushConstants>() as u32,
@@ -388,27 +994,140 @@ impl ChunkRenderer {
 			}]
 		});
 
-		fn shader_get_const(is_black: bool) -> impl for<'a> Fn(&'a str) -> &'a str {
-			move |name: &str| -> &str {
-				match name {
-					"is_black" => if is_black { "true" } else { "false" },
-					s => s
+		let overlay_pipeline_layout = gfx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: None,
+			bind_group_layouts: &[&world_bind_group_layout, &texture_bind_group_layout],
+			push_constant_ranges: &[wgpu::PushConstantRange {
+				range: 0..std::mem::size_of::<OverlayPushConstants>() as u32,
+				stages: wgpu::ShaderStages::VERTEX_FRAGMENT
+			}]
+		});
+
+		let block_shader = gfx.device.create_shader_module(super::load_shader_consts("game/block", &block_shader_consts(false))?);
+		let wf_block_shader = gfx.device.create_shader_module(super::load_shader_consts("game/block", &block_shader_consts(true))?);
+		let outline_shader = gfx.device.create_shader_module(super::load_shader("game/outline")?);
+		let overlay_shader = gfx.device.create_shader_module(super::load_shader("game/overlay")?);
+		let shadow_shader = gfx.device.create_shader_module(super::load_shader("game/shadow")?);
+		let depth_shader = gfx.device.create_shader_module(super::load_shader("game/depth")?);
+
+		let [
+			block_render_pipeline,
+			block_transparent_render_pipeline,
+			block_wf_render_pipeline,
+			block_after_prepass_render_pipeline
+		] = create_block_pipelines(gfx, &block_pipeline_layout, &block_shader, &wf_block_shader, samples);
+		let [
+			block_icon_render_pipeline,
+			block_icon_transparent_render_pipeline
+		] = create_icon_pipelines(gfx, &block_pipeline_layout, &block_shader);
+		let outline_render_pipeline = create_outline_pipeline(gfx, &outline_pipeline_layout, &outline_shader, super::GameRenderer::DEPTH_FORMAT, samples);
+		let overlay_render_pipeline = create_overlay_pipeline(gfx, &overlay_pipeline_layout, &overlay_shader, super::GameRenderer::DEPTH_FORMAT, samples);
+		let block_shadow_render_pipeline = create_shadow_pipeline(gfx, &shadow_pipeline_layout, &shadow_shader, super::GameRenderer::SHADOW_FORMAT);
+		let block_depth_prepass_render_pipeline = create_depth_prepass_pipeline(gfx, &shadow_pipeline_layout, &depth_shader, super::GameRenderer::DEPTH_FORMAT, samples);
+
+		let block_texture = Self::create_block_texture(gfx, block_textures, filtering);
+		let (texture_frames, texture_frames_buffer, texture_bind_group) =
+			Self::create_texture_bindings(gfx, &texture_bind_group_layout, &block_texture, block_textures);
+
+		let world_uniforms = Self::create_world_uniforms(gfx);
+		let world_uniforms_buffer = Self::create_uniform_buffer(gfx, &world_uniforms.data);
+		let chunk_origins_buffer = Self::create_chunk_origins_buffer(gfx, Self::INITIAL_CHUNK_DRAWS);
+		let chunk_draws_buffer = Self::create_chunk_draws_buffer(gfx, Self::INITIAL_CHUNK_DRAWS);
+		let mesh_pool = gfx::pool::MeshPool::new(gfx, Self::INITIAL_POOL_VERTICES, Self::INITIAL_POOL_INDICES, "Chunk Mesh Pool");
+		let required_features = wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE;
+
+		let uniform_bind_group = Self::create_world_bind_group(
+			gfx,
+			&world_bind_group_layout,
+			&world_uniforms,
+			&world_uniforms_buffer,
+			&chunk_origins_buffer,
+			None
+		);
+
+		let outline_mesh = {
+			let vertices = super::super::chunk::CUBE_VERTICES.map(|v| OutlineVertex { position: v });
+			let indices: [u32; 24] = [
+				0, 1,  1, 2,  2, 3,  3, 0,
+				4, 5,  5, 6,  6, 7,  7, 4,
+				0, 4,  1, 5,  2, 6,  3, 7,
+			];
+			gfx::Mesh::new(gfx, &vertices, &indices, Some("Block Outline Mesh"))
+		};
+
+		let overlay_mesh = {
+			// the same uvs as the block faces, see `block.wgsl`.
+			const UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+			let mut vertices = Vec::with_capacity(24);
+			let mut indices = Vec::with_capacity(36);
+			// in the order of `Dir`, see `ChunkRenderContext::render_overlay`.
+			for &face in Dir::all() {
+				let start = vertices.len() as u32;
+				for (corner, position) in overlay_face_corners(face).into_iter().enumerate() {
+					vertices.push(OverlayVertex { position, uv: UVS[corner] });
 				}
+				indices.extend([0, 1, 2, 2, 3, 0].map(|index| start + index));
 			}
-		}
-		let block_shader = gfx.device.create_shader_module(super::load_shader_consts("game/block", shader_get_const(false)).unwrap());
-		let wf_block_shader = gfx.device.create_shader_module(super::load_shader_consts("game/block", shader_get_const(true)).unwrap());
-		let outline_shader = gfx.device.create_shader_module(super::load_shader("game/outline").unwrap());
+			gfx::Mesh::new(gfx, &vertices, &indices, Some("Block Overlay Mesh"))
+		};
 
-		let block_render_pipeline = create_block_pipeline(gfx, &block_pipeline_layout, &block_shader, wgpu::PolygonMode::Fill, super::GameRenderer::DEPTH_FORMAT);
-		let block_wf_render_pipeline = create_block_pipeline(gfx, &block_pipeline_layout, &wf_block_shader, wgpu::PolygonMode::Line, super::GameRenderer::DEPTH_FORMAT);
-		let outline_render_pipeline = create_outline_pipeline(gfx, &outline_pipeline_layout, &outline_shader, super::GameRenderer::DEPTH_FORMAT);
+		Ok(Self {
+			block_render_pipeline,
+			block_transparent_render_pipeline,
+			block_wf_render_pipeline,
+			block_after_prepass_render_pipeline,
+			block_shadow_render_pipeline,
+			block_depth_prepass_render_pipeline,
+			block_icon_render_pipeline,
+			block_icon_transparent_render_pipeline,
+			outline_render_pipeline,
+			overlay_render_pipeline,
+			#[cfg(feature = "hot-reload")]
+			block_pipeline_layout,
+			#[cfg(feature = "hot-reload")]
+			shadow_pipeline_layout,
+			#[cfg(feature = "hot-reload")]
+			outline_pipeline_layout,
+			#[cfg(feature = "hot-reload")]
+			overlay_pipeline_layout,
+			shadow_bind_group_layout,
+			shadow_bind_group: None,
+			block_texture,
+			texture_bind_group_layout,
+			filtering,
+			#[cfg(feature = "hot-reload")]
+			samples,
+			animations: block_textures.animations.clone(),
+			uploaded_texture_frames: texture_frames.clone(),
+			texture_frames,
+			texture_frames_buffer,
+			time: 0.0,
+			texture_bind_group,
+			uploaded_world_uniforms: world_uniforms.data.clone(),
+			world_uniforms_buffer,
+			chunk_origins_buffer,
+			chunk_draws_buffer,
+			chunk_draws: Vec::new(),
+			chunk_batches: [0..0, 0..0, 0..0],
+			multi_draw: gfx.device.features().contains(required_features),
+			mesh_pool,
+			uniform_bind_group,
+			world_bind_group_layout,
+			world_uniforms,
+			outline_mesh,
+			overlay_mesh,
+			camera
+		})
+	}
 
+	/// the block textures as the layers of an array texture, with mipmaps.
+	fn create_block_texture(gfx: &gfx::Gfx, block_textures: &texture::LoadedTextures, filtering: TextureFiltering) -> gfx::Texture {
+		let mip_level_count = texture::mip_level_count(block_textures.size);
 		let block_texture = gfx.device.create_texture(&wgpu::TextureDescriptor {
 			label: Some("Block Array Texture"),
 			dimension: wgpu::TextureDimension::D2,
 			format: wgpu::TextureFormat::Rgba8UnormSrgb,
-			mip_level_count: 1,
+			mip_level_count,
 			sample_count: 1,
 			size: wgpu::Extent3d {
 				width: block_textures.size.x,
@@ -421,29 +1140,32 @@ impl ChunkRenderer {
 
 		for texture_source in &block_textures.textures {
 			if let Some(ref texture_data) = &texture_source.data {
-				gfx.queue.write_texture(
-					wgpu::ImageCopyTexture {
-						aspect: wgpu::TextureAspect::All,
-						mip_level: 0,
-						origin: wgpu::Origin3d {
-							x: 0,
-							y: 0,
-							z: texture_source.id.0
+				let mips = texture::generate_mips(texture_data);
+				for (mip_level, image) in std::iter::once(texture_data).chain(&mips).enumerate() {
+					gfx.queue.write_texture(
+						wgpu::ImageCopyTexture {
+							aspect: wgpu::TextureAspect::All,
+							mip_level: mip_level as u32,
+							origin: wgpu::Origin3d {
+								x: 0,
+								y: 0,
+								z: texture_source.id.0
+							},
+							texture: &block_texture
 						},
-						texture: &block_texture
-					},
-					&texture_data,
-					wgpu::ImageDataLayout {
-						offset: 0,
-						bytes_per_row: Some(block_texture.width() * 4),
-						rows_per_image: Some(block_texture.height())
-					},
-					wgpu::Extent3d {
-						width: block_texture.width(),
-						height: block_texture.height(),
-						depth_or_array_layers: 1
-					}
-				);
+						&image,
+						wgpu::ImageDataLayout {
+							offset: 0,
+							bytes_per_row: Some(image.width() * 4),
+							rows_per_image: Some(image.height())
+						},
+						wgpu::Extent3d {
+							width: image.width(),
+							height: image.height(),
+							depth_or_array_layers: 1
+						}
+					);
+				}
 			}
 		}
 
@@ -458,26 +1180,65 @@ impl ChunkRenderer {
 			base_array_layer: 0
 		});
 
-		let block_texture = gfx::Texture {
+		gfx::Texture {
 			texture: block_texture,
 			view: block_texture_view,
-			sampler: Some(gfx.device.create_sampler(&wgpu::SamplerDescriptor {
-				address_mode_u: wgpu::AddressMode::ClampToEdge,
-				address_mode_v: wgpu::AddressMode::ClampToEdge,
-				address_mode_w: wgpu::AddressMode::ClampToEdge,
-				mag_filter: wgpu::FilterMode::Nearest,
-				min_filter: wgpu::FilterMode::Nearest,
-				mipmap_filter: wgpu::FilterMode::Nearest,
-				compare: None,
-				lod_min_clamp: 0.0,
-				lod_max_clamp: 0.0,
-				..Default::default()
-			})),
-		};
+			sampler: Some(Self::create_block_sampler(gfx, filtering, mip_level_count)),
+		}
+	}
+
+	fn create_block_sampler(gfx: &gfx::Gfx, filtering: TextureFiltering, mip_level_count: u32) -> wgpu::Sampler {
+		gfx.device.create_sampler(&wgpu::SamplerDescriptor {
+			address_mode_u: wgpu::AddressMode::ClampToEdge,
+			address_mode_v: wgpu::AddressMode::ClampToEdge,
+			address_mode_w: wgpu::AddressMode::ClampToEdge,
+			..match filtering {
+				TextureFiltering::Nearest => wgpu::SamplerDescriptor {
+					mag_filter: wgpu::FilterMode::Nearest,
+					min_filter: wgpu::FilterMode::Nearest,
+					mipmap_filter: wgpu::FilterMode::Nearest,
+					lod_max_clamp: 0.0,
+					..Default::default()
+				},
+				TextureFiltering::Trilinear => wgpu::SamplerDescriptor {
+					mag_filter: wgpu::FilterMode::Linear,
+					min_filter: wgpu::FilterMode::Linear,
+					mipmap_filter: wgpu::FilterMode::Linear,
+					lod_max_clamp: mip_level_count as f32,
+					..Default::default()
+				},
+			}
+		})
+	}
 
-		let texture_bind_group = gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+	/// the animation frame buffer and the bind group for the block texture.
+	fn create_texture_bindings(
+		gfx: &gfx::Gfx,
+		layout: &wgpu::BindGroupLayout,
+		block_texture: &gfx::Texture,
+		block_textures: &texture::LoadedTextures
+	) -> (Vec<u32>, wgpu::Buffer, wgpu::BindGroup) {
+		// the layers that aren't the first frame of an animation stay at 0.
+		let texture_frames = vec![0u32; block_textures.textures.len().max(1)];
+		let texture_frames_buffer = gfx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("Texture Frames Buffer"),
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE,
+			contents: bytemuck::cast_slice(&texture_frames)
+		});
+		let texture_bind_group = Self::create_texture_bind_group(gfx, layout, block_texture, &texture_frames_buffer);
+
+		(texture_frames, texture_frames_buffer, texture_bind_group)
+	}
+
+	fn create_texture_bind_group(
+		gfx: &gfx::Gfx,
+		layout: &wgpu::BindGroupLayout,
+		block_texture: &gfx::Texture,
+		texture_frames_buffer: &wgpu::Buffer
+	) -> wgpu::BindGroup {
+		gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
 			label: None,
-			layout: &texture_bind_group_layout,
+			layout,
 			entries: &[
 				wgpu::BindGroupEntry {
 					binding: 0,
@@ -486,66 +1247,128 @@ impl ChunkRenderer {
 				wgpu::BindGroupEntry {
 					binding: 1,
 					resource: wgpu::BindingResource::Sampler(block_texture.sampler.as_ref().unwrap()),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: texture_frames_buffer.as_entire_binding(),
 				}
 			]
-		});
+		})
+	}
 
-		let world_uniforms = Self::create_world_uniforms(gfx);
-		let world_uniforms_buffer = Self::create_uniform_buffer(gfx, &world_uniforms.data);
+	pub fn texture_filtering(&self) -> TextureFiltering {
+		self.filtering
+	}
+
+	/// only replaces the sampler, the mipmaps are always there.
+	pub fn set_texture_filtering(&mut self, gfx: &gfx::Gfx, filtering: TextureFiltering) {
+		self.filtering = filtering;
+		self.block_texture.sampler = Some(Self::create_block_sampler(gfx, filtering, self.block_texture.texture.mip_level_count()));
+		self.texture_bind_group = Self::create_texture_bind_group(gfx, &self.texture_bind_group_layout, &self.block_texture, &self.texture_frames_buffer);
+	}
+
+	/// replaces the block texture, e.g. after the textures were reloaded.
+	/// NB: the chunks have to be remeshed if the texture ids changed.
+	#[cfg(feature = "hot-reload")]
+	pub fn set_block_textures(&mut self, gfx: &gfx::Gfx, block_textures: &texture::LoadedTextures) {
+		let block_texture = Self::create_block_texture(gfx, block_textures, self.filtering);
+		(self.texture_frames, self.texture_frames_buffer, self.texture_bind_group) =
+			Self::create_texture_bindings(gfx, &self.texture_bind_group_layout, &block_texture, block_textures);
+		self.uploaded_texture_frames = self.texture_frames.clone();
+		self.block_texture = block_texture;
+		self.animations = block_textures.animations.clone();
+	}
 
-		let uniform_bind_group = gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+	fn create_uniform_buffer(gfx: &gfx::Gfx, contents: &[u8]) -> wgpu::Buffer {
+		gfx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
 			label: None,
-			layout: &world_bind_group_layout,
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+			contents
+		})
+	}
+
+	/// room for `capacity` chunks.
+	fn create_chunk_origins_buffer(gfx: &gfx::Gfx, capacity: usize) -> wgpu::Buffer {
+		gfx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Chunk Origins Buffer"),
+			size: (capacity * std::mem::size_of::<ChunkOrigin>()) as u64,
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE,
+			mapped_at_creation: false
+		})
+	}
+
+	fn create_chunk_draws_buffer(gfx: &gfx::Gfx, capacity: usize) -> wgpu::Buffer {
+		gfx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Chunk Draws Buffer"),
+			size: (capacity * std::mem::size_of::<ChunkDraw>()) as u64,
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::INDIRECT,
+			mapped_at_creation: false
+		})
+	}
+
+	fn create_world_bind_group(
+		gfx: &gfx::Gfx,
+		layout: &wgpu::BindGroupLayout,
+		uniforms: &WorldUniforms,
+		uniforms_buffer: &wgpu::Buffer,
+		chunk_origins_buffer: &wgpu::Buffer,
+		label: Option<&str>
+	) -> wgpu::BindGroup {
+		gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label,
+			layout,
 			entries: &[
 				wgpu::BindGroupEntry {
 					binding: 0,
 					resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-						buffer: &world_uniforms_buffer,
-						offset: world_uniforms.camera_uniform_offset() as u64,
-						size: Some(std::num::NonZeroU64::new(world_uniforms.camera_uniform_size() as u64).unwrap())
+						buffer: uniforms_buffer,
+						offset: uniforms.camera_uniform_offset() as u64,
+						size: Some(std::num::NonZeroU64::new(uniforms.camera_uniform_size() as u64).unwrap())
 					})
 				},
 				wgpu::BindGroupEntry {
 					binding: 1,
 					resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-						buffer: &world_uniforms_buffer,
-						offset: world_uniforms.lighting_uniform_offset() as u64,
-						size: Some(std::num::NonZeroU64::new(world_uniforms.lighting_uniform_size() as u64).unwrap())
+						buffer: uniforms_buffer,
+						offset: uniforms.lighting_uniform_offset() as u64,
+						size: Some(std::num::NonZeroU64::new(uniforms.lighting_uniform_size() as u64).unwrap())
 					})
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: chunk_origins_buffer.as_entire_binding()
 				}
 			]
-		});
-
-		let outline_mesh = {
-			let vertices = super::super::chunk::CUBE_VERTICES.map(|v| OutlineVertex { position: v });
-			let indices: [u32; 24] = [
-				0, 1,  1, 2,  2, 3,  3, 0,
-				4, 5,  5, 6,  6, 7,  7, 4,
-				0, 4,  1, 5,  2, 6,  3, 7,
-			];
-			gfx::Mesh::new(gfx, &vertices, &indices, Some("Block Outline Mesh"))
-		};
-
-		Self {
-			block_render_pipeline,
-			block_wf_render_pipeline,
-			outline_render_pipeline,
-			_texture: block_texture,
-			texture_bind_group,
-			world_uniforms_buffer,
-			uniform_bind_group,
-			world_uniforms,
-			outline_mesh,
-			camera
-		}
+		})
 	}
 
-	fn create_uniform_buffer(gfx: &gfx::Gfx, contents: &[u8]) -> wgpu::Buffer {
-		gfx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-			label: None,
-			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
-			contents
-		})
+	/// uploads the draws of the chunk meshes in each [`ChunkBatch`] (the meshes are in [`ChunkRenderer::mesh_pool`]),
+	/// which are then drawn with one indirect draw per batch, see [`ChunkRenderContext::render_batch`].
+	/// every pass (including the shadows) reads the origins of the chunks from one buffer, indexed by the instance.
+	/// NB: run before rendering.
+	pub fn set_chunk_draws(&mut self, gfx: &gfx::Gfx, batches: [&[(Vec3i32, &gfx::pool::PooledMesh)]; 3]) {
+		let batches = batches.map(|meshes| {
+			meshes.iter().map(|(position, mesh)| (*position, mesh.indices(), mesh.base_vertex())).collect::<Vec<_>>()
+		});
+		let (draws, origins, ranges) = chunk_draws(batches.each_ref().map(Vec::as_slice));
+		let capacity = self.chunk_origins_buffer.size() as usize / std::mem::size_of::<ChunkOrigin>();
+		if origins.len() > capacity {
+			self.chunk_origins_buffer = Self::create_chunk_origins_buffer(gfx, origins.len().next_power_of_two());
+			self.chunk_draws_buffer = Self::create_chunk_draws_buffer(gfx, origins.len().next_power_of_two());
+			self.uniform_bind_group = Self::create_world_bind_group(
+				gfx,
+				&self.world_bind_group_layout,
+				&self.world_uniforms,
+				&self.world_uniforms_buffer,
+				&self.chunk_origins_buffer,
+				None
+			);
+		}
+		if !draws.is_empty() {
+			gfx.queue.write_buffer(&self.chunk_origins_buffer, 0, bytemuck::cast_slice(&origins));
+			gfx.queue.write_buffer(&self.chunk_draws_buffer, 0, bytemuck::cast_slice(&draws));
+		}
+		(self.chunk_draws, self.chunk_batches) = (draws, ranges);
 	}
 
 	/// NB: run before rendering.
@@ -558,57 +1381,302 @@ impl ChunkRenderer {
 		Vector(self.world_uniforms.lighting_uniform().sun_dir)
 	}
 
-	pub fn update(&mut self) {
-		*self.world_uniforms.camera_uniform_mut() = self.camera.to_uniform()
+	/// NB: run before rendering.
+	pub fn set_sun_color(&mut self, color: Vec3f32) {
+		self.world_uniforms.lighting_uniform_mut().sun_color = [color.x, color.y, color.z, 1.0];
+	}
+
+	/// geometry fades to `color` between `start` and `end` blocks away from the camera.
+	/// NB: run before rendering.
+	pub fn set_fog(&mut self, color: Vec3f32, start: f32, end: f32) {
+		let lighting = self.world_uniforms.lighting_uniform_mut();
+		lighting.fog_color = [color.x, color.y, color.z, 1.0];
+		lighting.fog_range = [start, end, 0.0, 0.0];
+	}
+
+	/// 1 is the full baked ambient occlusion, 0 disables it.
+	/// NB: run before rendering.
+	pub fn set_ao_strength(&mut self, strength: f32) {
+		self.world_uniforms.lighting_uniform_mut().ao_params = [strength, 0.0, 0.0, 0.0];
+	}
+
+	/// rebuilds the pipelines whose shaders (or their includes) are in `changed`.
+	/// pipelines that fail to compile are kept as they were.
+	#[cfg(feature = "hot-reload")]
+	pub fn reload_shaders(&mut self, gfx: &gfx::Gfx, changed: &[String]) {
+		use super::reload;
+
+		if reload::depends_on("game/block", changed) {
+			let block_shader = reload::create_shader_module(gfx, super::load_shader_consts("game/block", &block_shader_consts(false)));
+			let wf_block_shader = reload::create_shader_module(gfx, super::load_shader_consts("game/block", &block_shader_consts(true)));
+			if let (Some(block_shader), Some(wf_block_shader)) = (block_shader, wf_block_shader) {
+				if let Some([normal, transparent, wireframe, after_prepass]) = reload::validated(gfx, || {
+					create_block_pipelines(gfx, &self.block_pipeline_layout, &block_shader, &wf_block_shader, self.samples)
+				}) {
+					self.block_render_pipeline = normal;
+					self.block_transparent_render_pipeline = transparent;
+					self.block_wf_render_pipeline = wireframe;
+					self.block_after_prepass_render_pipeline = after_prepass;
+				}
+				if let Some([opaque, transparent]) = reload::validated(gfx, || {
+					create_icon_pipelines(gfx, &self.block_pipeline_layout, &block_shader)
+				}) {
+					self.block_icon_render_pipeline = opaque;
+					self.block_icon_transparent_render_pipeline = transparent;
+				}
+			}
+		}
+
+		if reload::depends_on("game/shadow", changed) {
+			if let Some(shader) = reload::create_shader_module(gfx, super::load_shader("game/shadow")) {
+				if let Some(pipeline) = reload::validated(gfx, || {
+					create_shadow_pipeline(gfx, &self.shadow_pipeline_layout, &shader, super::GameRenderer::SHADOW_FORMAT)
+				}) {
+					self.block_shadow_render_pipeline = pipeline;
+				}
+			}
+		}
+
+		if reload::depends_on("game/depth", changed) {
+			if let Some(shader) = reload::create_shader_module(gfx, super::load_shader("game/depth")) {
+				if let Some(pipeline) = reload::validated(gfx, || {
+					create_depth_prepass_pipeline(gfx, &self.shadow_pipeline_layout, &shader, super::GameRenderer::DEPTH_FORMAT, self.samples)
+				}) {
+					self.block_depth_prepass_render_pipeline = pipeline;
+				}
+			}
+		}
+
+		if reload::depends_on("game/outline", changed) {
+			if let Some(shader) = reload::create_shader_module(gfx, super::load_shader("game/outline")) {
+				if let Some(pipeline) = reload::validated(gfx, || {
+					create_outline_pipeline(gfx, &self.outline_pipeline_layout, &shader, super::GameRenderer::DEPTH_FORMAT, self.samples)
+				}) {
+					self.outline_render_pipeline = pipeline;
+				}
+			}
+		}
+
+		if reload::depends_on("game/overlay", changed) {
+			if let Some(shader) = reload::create_shader_module(gfx, super::load_shader("game/overlay")) {
+				if let Some(pipeline) = reload::validated(gfx, || {
+					create_overlay_pipeline(gfx, &self.overlay_pipeline_layout, &shader, super::GameRenderer::DEPTH_FORMAT, self.samples)
+				}) {
+					self.overlay_render_pipeline = pipeline;
+				}
+			}
+		}
+	}
+
+	/// `light_view_proj` is `None` when shadows are disabled, see [`sun_view_proj`].
+	/// NB: run before rendering.
+	pub fn set_shadow(&mut self, light_view_proj: Option<&glm::Mat4>, resolution: u32) {
+		let lighting = self.world_uniforms.lighting_uniform_mut();
+		if let Some(light_view_proj) = light_view_proj {
+			lighting.light_view_proj = light_view_proj.data.0;
+			lighting.shadow_params = [1.0, 1.0 / resolution as f32, 0.0, 0.0];
+		} else {
+			lighting.shadow_params = [0.0, 0.0, 0.0, 0.0];
+		}
+	}
+
+	/// the point lights are expected to be this color too, see [`ChunkRenderer::set_point_lights`].
+	/// NB: run before rendering.
+	pub fn set_block_light_color(&mut self, color: Vec3f32) {
+		self.world_uniforms.lighting_uniform_mut().block_light_color = [color.x, color.y, color.z, 1.0];
+	}
+
+	/// only the first [`MAX_POINT_LIGHTS`] lights are used.
+	/// they're the emissive blocks near the camera, which are also in the baked block light,
+	/// so the shader uses the brighter of the two instead of adding them.
+	/// NB: run before rendering.
+	pub fn set_point_lights(&mut self, lights: &[PointLight]) {
+		let lighting = self.world_uniforms.lighting_uniform_mut();
+		let count = lights.len().min(MAX_POINT_LIGHTS);
+		for (uniform, light) in lighting.point_lights.iter_mut().zip(&lights[..count]) {
+			*uniform = PointLightUniform {
+				position: [light.position.x, light.position.y, light.position.z, light.radius],
+				color: [light.color.x, light.color.y, light.color.z, 1.0],
+			};
+		}
+		lighting.point_light_count = [count as u32, 0, 0, 0];
+	}
+
+	/// the shadow map has to be set again whenever the render graph is rebuilt.
+	pub fn set_shadow_map(&mut self, gfx: &gfx::Gfx, shadow_map: &gfx::Texture) {
+		self.shadow_bind_group = Some(gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("Shadow Bind Group"),
+			layout: &self.shadow_bind_group_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: wgpu::BindingResource::TextureView(&shadow_map.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::Sampler(shadow_map.sampler.as_ref().unwrap()),
+				}
+			]
+		}));
+	}
+
+	/// world uniforms with their own camera, lit by a sun straight above and without fog or shadows.
+	/// for rendering blocks outside of the world, see [`ChunkRenderer::render_icon`], the meshes are offset by `offset` blocks.
+	/// the buffers have to be kept alive with the bind group.
+	pub(super) fn create_icon_bindings(
+		&self,
+		gfx: &gfx::Gfx,
+		view_proj: &glm::Mat4,
+		position: Vec3f32,
+		offset: Vec3i32
+	) -> ([wgpu::Buffer; 2], wgpu::BindGroup) {
+		let mut uniforms = Self::create_world_uniforms(gfx);
+		*uniforms.camera_uniform_mut() = CameraUniform {
+			view_proj: view_proj.data.0,
+			inv_view_proj: glm::inverse(view_proj).data.0,
+			position: [position.x, position.y, position.z, 1.0],
+		};
+		let lighting = uniforms.lighting_uniform_mut();
+		lighting.sun_dir = [0.0, -1.0, 0.0, 0.0];
+		lighting.sun_color = [1.0, 1.0, 1.0, 1.0];
+		lighting.fog_range = [1.0e6, 2.0e6, 0.0, 0.0];
+
+		let buffer = Self::create_uniform_buffer(gfx, &uniforms.data);
+		// a single "chunk" at the offset, drawn with the index 0.
+		let origins_buffer = gfx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("Icon Origins Buffer"),
+			usage: wgpu::BufferUsages::STORAGE,
+			contents: bytemuck::cast_slice(&[[offset.x, offset.y, offset.z, 0]])
+		});
+		let bind_group = Self::create_world_bind_group(
+			gfx,
+			&self.world_bind_group_layout,
+			&uniforms,
+			&buffer,
+			&origins_buffer,
+			Some("Icon World Bind Group")
+		);
+
+		([buffer, origins_buffer], bind_group)
+	}
+
+	/// draws `mesh` in a pass that isn't multisampled.
+	/// `world_bind_group` comes from [`ChunkRenderer::create_icon_bindings`].
+	pub(super) fn render_icon<'a>(
+		&'a self,
+		render_pass: &mut wgpu::RenderPass<'a>,
+		world_bind_group: &'a wgpu::BindGroup,
+		mesh: &'a gfx::Mesh<BlockVertex>,
+		transparent: bool
+	) {
+		render_pass.set_pipeline(if transparent {
+			&self.block_icon_transparent_render_pipeline
+		} else {
+			&self.block_icon_render_pipeline
+		});
+		render_pass.set_bind_group(0, world_bind_group, &[]);
+		render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+		render_pass.set_bind_group(2, self.shadow_bind_group.as_ref().expect("shadow map should be set before rendering"), &[]);
+		mesh.render(render_pass);
+	}
+
+	/// uploads the parts of the uniforms and the animation frames that changed since the last frame, once per frame.
+	/// NB: run after the `set_*` methods and before rendering.
+	pub fn update(&mut self, gfx: &gfx::Gfx, dt: f32) {
+		*self.world_uniforms.camera_uniform_mut() = self.camera.to_uniform();
+
+		self.time += dt;
+		for animation in &self.animations {
+			self.texture_frames[animation.first.0 as usize] = animation.frame(self.time);
+		}
+
+		let changed: Vec<std::ops::Range<usize>> = self.world_uniforms.changed_ranges(&self.uploaded_world_uniforms).collect();
+		for range in changed {
+			let data = &self.world_uniforms.data[range.clone()];
+			gfx.queue.write_buffer(&self.world_uniforms_buffer, range.start as wgpu::BufferAddress, data);
+			self.uploaded_world_uniforms[range].copy_from_slice(data);
+		}
+		if self.texture_frames != self.uploaded_texture_frames {
+			gfx.queue.write_buffer(&self.texture_frames_buffer, 0, bytemuck::cast_slice(&self.texture_frames));
+			self.uploaded_texture_frames.clone_from(&self.texture_frames);
+		}
 	}
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ChunkRenderMode {
 	Normal,
-	Wireframe
+	/// draws the transparent meshes, chunks should be rendered back-to-front.
+	Transparent,
+	Wireframe,
+	/// draws the depth of the opaque meshes from the sun, only in the shadow pass.
+	Shadow,
+	/// draws the depth of the opaque meshes, only in the depth pre-pass.
+	DepthPrepass,
+	/// like `Normal`, but tests against the depth of `DepthPrepass` without writing it.
+	AfterDepthPrepass,
 }
 
 pub struct ChunkRenderContext<'a, 'b> {
 	pub(super) renderer: &'a super::GameRenderer,
-	pub(super) render_pass: &'b mut wgpu::RenderPass<'a>
+	pub(super) render_pass: &'b mut wgpu::RenderPass<'a>,
+	mode: ChunkRenderMode
 }
 
 impl<'a, 'b> ChunkRenderContext<'a, 'b> {
+	/// the uniforms were uploaded in [`ChunkRenderer::update`].
 	pub(super) fn begin(
-		gfx: &gfx::Gfx,
 		renderer: &'a super::GameRenderer,
 		render_pass: &'b mut wgpu::RenderPass<'a>
 	) -> ChunkRenderContext<'a, 'b> {
-		let ctx = ChunkRenderContext {
+		ChunkRenderContext {
 			renderer,
-			render_pass
-		};
-		gfx.queue.write_buffer(
-			&renderer.chunk_renderer.world_uniforms_buffer,
-			0,
-			&renderer.chunk_renderer.world_uniforms.data
-		);
-		ctx
+			render_pass,
+			mode: ChunkRenderMode::Normal
+		}
 	}
 	
 	// TODO: states/game/renderer -> renderer?
 
 	pub fn set_mode(&mut self, mode: ChunkRenderMode) {
+		self.mode = mode;
 		self.render_pass.set_pipeline(match mode {
 			ChunkRenderMode::Normal => &self.renderer.chunk_renderer.block_render_pipeline,
+			ChunkRenderMode::Transparent => &self.renderer.chunk_renderer.block_transparent_render_pipeline,
 			ChunkRenderMode::Wireframe => &self.renderer.chunk_renderer.block_wf_render_pipeline,
+			ChunkRenderMode::Shadow => &self.renderer.chunk_renderer.block_shadow_render_pipeline,
+			ChunkRenderMode::DepthPrepass => &self.renderer.chunk_renderer.block_depth_prepass_render_pipeline,
+			ChunkRenderMode::AfterDepthPrepass => &self.renderer.chunk_renderer.block_after_prepass_render_pipeline,
 		});
 
 		self.render_pass.set_bind_group(0, &self.renderer.chunk_renderer.uniform_bind_group, &[]);
-		self.render_pass.set_bind_group(1, &self.renderer.chunk_renderer.texture_bind_group, &[]); // TODO: make this a GameRenderer thing
+		// the shadow map can't be bound in the pass that renders to it, and the depth-only pipelines don't use the rest.
+		if !matches!(mode, ChunkRenderMode::Shadow | ChunkRenderMode::DepthPrepass) {
+			self.render_pass.set_bind_group(1, &self.renderer.chunk_renderer.texture_bind_group, &[]); // TODO: make this a GameRenderer thing
+			self.render_pass.set_bind_group(2, self.renderer.chunk_renderer.shadow_bind_group.as_ref().expect("shadow map should be set before rendering"), &[]);
+		}
 	}
 
-	pub fn render_chunk(&mut self, chunk: &'a super::super::chunk::Chunk) {
-		if let Some(mesh) = &chunk.mesh {
-			self.render_pass.set_push_constants(wgpu::ShaderStages::VERTEX, 0, bytemuck::bytes_of(&BlockPushConsts {
-				chunk_pos: (chunk.position * CHUNK_SIZE.each_as()).0
-			}));
-			mesh.render(self.render_pass)
+	/// draws the chunks of `batch` set by [`ChunkRenderer::set_chunk_draws`], in one indirect draw if the device supports it.
+	pub fn render_batch(&mut self, batch: ChunkBatch) {
+		let chunk_renderer = &self.renderer.chunk_renderer;
+		let range = chunk_renderer.chunk_batches[batch as usize].clone();
+		if range.is_empty() {
+			return;
+		}
+
+		chunk_renderer.mesh_pool.bind(self.render_pass);
+		if chunk_renderer.multi_draw {
+			let offset = range.start as u64 * std::mem::size_of::<ChunkDraw>() as u64;
+			self.render_pass.multi_draw_indexed_indirect(&chunk_renderer.chunk_draws_buffer, offset, range.len() as u32);
+		} else {
+			for draw in &chunk_renderer.chunk_draws[range.start as usize..range.end as usize] {
+				self.render_pass.draw_indexed(
+					draw.first_index..draw.first_index + draw.index_count,
+					draw.base_vertex,
+					draw.first_instance..draw.first_instance + draw.instance_count,
+				);
+			}
 		}
 	}
 
@@ -621,4 +1689,261 @@ impl<'a, 'b> ChunkRenderContext<'a, 'b> {
 
 		self.renderer.chunk_renderer.outline_mesh.render(self.render_pass);
 	}
+
+	/// draws `texture` (a layer of the block texture) multiplied by `color` on the `face` of the block at `position`.
+	/// without a texture, the face is filled with `color`.
+	pub fn render_overlay(&mut self, position: Vec3f32, face: Dir, texture: Option<texture::TextureId>, color: [f32; 4]) {
+		self.render_pass.set_pipeline(&self.renderer.chunk_renderer.overlay_render_pipeline);
+		self.render_pass.set_bind_group(0, &self.renderer.chunk_renderer.uniform_bind_group, &[]);
+		self.render_pass.set_bind_group(1, &self.renderer.chunk_renderer.texture_bind_group, &[]);
+		self.render_pass.set_push_constants(wgpu::ShaderStages::VERTEX_FRAGMENT, 0, bytemuck::bytes_of(&OverlayPushConstants {
+			position: position.0,
+			texture: texture.map_or(-1, |texture| texture.0 as i32),
+			color,
+		}));
+
+		let buffers = &self.renderer.chunk_renderer.overlay_mesh.buffers;
+		let first_index = face as u32 * 6;
+		self.render_pass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+		self.render_pass.set_index_buffer(buffers.index_buffer.slice(..), buffers.index_format);
+		self.render_pass.draw_indexed(first_index..first_index + 6, 0, 0..1);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// clip space position of `point`, after the perspective divide.
+	fn project(matrix: &glm::Mat4, point: Vec3f32) -> Vec3f32 {
+		let clip = matrix * glm::vec4(point.x, point.y, point.z, 1.0);
+		vec3(clip.x, clip.y, clip.z) / clip.w
+	}
+
+	#[test]
+	fn sun_view_proj_covers_the_cube() {
+		let (center, sun_direction, radius) = (vec3(10.0, 20.0, 30.0), vec3(0.3, -1.0, 0.2), 16.0);
+		let matrix = sun_view_proj(center, sun_direction, radius, 2048);
+		let texel = 2.0 / 2048.0;
+
+		// the center is within a texel of the middle (it's snapped), two thirds of the way into the depth range.
+		let middle = project(&matrix, center);
+		assert!(middle.x.abs() <= texel && middle.y.abs() <= texel, "{middle}");
+		assert!((middle.z - 2.0 / 3.0).abs() < 1e-4, "{middle}");
+
+		for corner in 0..8 {
+			let offset = Vec3f32::make(|axis| if corner & (1 << axis) != 0 { 1.0 } else { -1.0 });
+			// a bit inside, the cube's corners are further than `radius` from the center, along some axes.
+			let ndc = project(&matrix, center + offset * (radius * 0.55));
+			assert!(ndc.0.iter().take(2).all(|c| c.abs() <= 1.0), "{ndc}");
+			assert!((0.0..=1.0).contains(&ndc.z), "{ndc}");
+		}
+
+		// the blocks towards the sun are closer, so they cast shadows on the ones further along.
+		let direction = sun_direction.normalized();
+		let above = project(&matrix, center - direction * 5.0);
+		let below = project(&matrix, center + direction * 5.0);
+		assert!(above.z < middle.z && middle.z < below.z);
+		assert!((above.x - middle.x).abs() < 1e-4 && (above.y - middle.y).abs() < 1e-4);
+		assert!(project(&matrix, center - direction * radius * 2.0).z.abs() < 1e-4);
+	}
+
+	#[test]
+	fn lighting_uniform_matches_the_shader() {
+		// the offsets of the members of `LightingUniform` in world.wgsl.
+		let zeroed = <LightingUniform as bytemuck::Zeroable>::zeroed();
+		assert_eq!(bytemuck::offset_of!(zeroed, LightingUniform, sun_dir), 0);
+		assert_eq!(bytemuck::offset_of!(zeroed, LightingUniform, fog_range), 48);
+		assert_eq!(bytemuck::offset_of!(zeroed, LightingUniform, light_view_proj), 64);
+		assert_eq!(bytemuck::offset_of!(zeroed, LightingUniform, shadow_params), 128);
+		assert_eq!(bytemuck::offset_of!(zeroed, LightingUniform, point_light_count), 144);
+		assert_eq!(bytemuck::offset_of!(zeroed, LightingUniform, ao_params), 160);
+		assert_eq!(bytemuck::offset_of!(zeroed, LightingUniform, block_light_color), 176);
+		assert_eq!(bytemuck::offset_of!(zeroed, LightingUniform, point_lights), 192);
+		assert_eq!(std::mem::size_of::<PointLightUniform>(), 32);
+		assert_eq!(std::mem::size_of::<LightingUniform>(), 192 + 32 * MAX_POINT_LIGHTS);
+
+		// the lighting is bound at a dynamic offset, which has to be aligned.
+		let mut uniforms = WorldUniforms::new(256);
+		assert_eq!(uniforms.lighting_uniform_offset(), 256);
+		assert_eq!(uniforms.data.len(), 256 + std::mem::size_of::<LightingUniform>());
+		assert_eq!(WorldUniforms::new(16).lighting_uniform_offset(), std::mem::size_of::<CameraUniform>());
+
+		// and the parts don't overlap.
+		uniforms.lighting_uniform_mut().block_light_color = [1.0, 0.5, 0.25, 1.0];
+		uniforms.camera_uniform_mut().position = [2.0; 4];
+		assert_eq!(uniforms.lighting_uniform().block_light_color, [1.0, 0.5, 0.25, 1.0]);
+		assert_eq!(uniforms.lighting_uniform().sun_dir, [0.0; 4]);
+		assert_eq!(uniforms.camera_uniform().position, [2.0; 4]);
+	}
+
+	#[test]
+	fn overlay_faces_cover_the_block_face() {
+		for &face in Dir::all() {
+			let normal: Vec3f32 = face.normal::<i32>().each_as();
+			let corners = overlay_face_corners(face);
+			for corner in corners {
+				// on the face's plane, just outside of the block.
+				let along = (0..3).map(|axis| corner[axis] * normal.0[axis]).sum::<f32>();
+				assert!((along - 0.501).abs() < 1e-6, "{face:?} {corner:?}");
+			}
+			// the four distinct corners of the face.
+			let axis = face as usize / 2;
+			let mut others = corners.map(|corner| {
+				let mut other = (0..3).filter(|&other| other != axis).map(|other| corner[other] > 0.0);
+				[other.next().unwrap(), other.next().unwrap()]
+			});
+			others.sort();
+			assert_eq!(others, [[false, false], [false, true], [true, false], [true, true]], "{face:?}");
+		}
+	}
+
+	#[test]
+	fn batches_are_drawn_in_order() {
+		let batched = |position: Vec3i32, indices, base_vertex| (position, indices, base_vertex);
+		let opaque = [batched(vec3(0, 0, 0), 0..36, 0), batched(vec3(1, 0, 0), 36..72, 24)];
+		// the empty transparent meshes don't get draws.
+		let transparent = [batched(vec3(0, 0, 0), 0..0, 0), batched(vec3(1, 0, 0), 72..78, 48)];
+		let shadow = [batched(vec3(0, -1, 0), 78..84, 52)];
+		let (draws, origins, ranges) = chunk_draws([&opaque, &transparent, &shadow]);
+
+		assert_eq!(ranges, [0..2, 2..3, 3..4]);
+		assert_eq!(draws[1], ChunkDraw { index_count: 36, instance_count: 1, first_index: 36, base_vertex: 24, first_instance: 1 });
+		assert_eq!(draws[2], ChunkDraw { index_count: 6, instance_count: 1, first_index: 72, base_vertex: 48, first_instance: 2 });
+		// every draw has its own origin, even when the chunk is in several batches.
+		let first_instances: Vec<u32> = draws.iter().map(|draw| draw.first_instance).collect();
+		assert_eq!(first_instances, [0, 1, 2, 3]);
+		assert_eq!(origins[2], [CHUNK_SIZE.x as i32, 0, 0, 0]);
+		assert_eq!(origins[3], [0, -(CHUNK_SIZE.y as i32), 0, 0]);
+	}
+
+	#[test]
+	fn chunk_origins_are_in_blocks() {
+		let size: Vec3i32 = CHUNK_SIZE.each_as();
+		let origins = chunk_origins(&[vec3(0, 0, 0), vec3(1, -2, 3), vec3(-1, 0, 0)]);
+		assert_eq!(origins, [
+			[0, 0, 0, 0],
+			[size.x, -2 * size.y, 3 * size.z, 0],
+			[-size.x, 0, 0, 0],
+		]);
+		// the stride of `array<vec3i>` in the shaders.
+		assert_eq!(std::mem::size_of::<ChunkOrigin>(), 16);
+	}
+
+	#[test]
+	fn only_the_changed_uniforms_are_uploaded() {
+		let mut uniforms = WorldUniforms::new(256);
+		let mut uploaded = uniforms.data.clone();
+		let mut upload = |uniforms: &WorldUniforms| -> usize {
+			let changed: Vec<_> = uniforms.changed_ranges(&uploaded).collect();
+			for range in &changed {
+				uploaded[range.clone()].copy_from_slice(&uniforms.data[range.clone()]);
+			}
+			changed.len()
+		};
+		assert_eq!(upload(&uniforms), 0);
+
+		uniforms.lighting_uniform_mut().sun_dir = [0.0, -1.0, 0.0, 0.0];
+		assert_eq!(uniforms.changed_ranges(&vec![0; uniforms.data.len()]).collect::<Vec<_>>(), [uniforms.lighting_uniform_range()]);
+		assert_eq!(upload(&uniforms), 1);
+		// e.g. drawing the chunks again for the wireframe doesn't upload anything.
+		assert_eq!(upload(&uniforms), 0);
+
+		uniforms.camera_uniform_mut().view_proj[0][0] = 2.0;
+		assert_eq!(upload(&uniforms), 1);
+		assert_eq!(upload(&uniforms), 0);
+	}
+
+	#[test]
+	fn chunk_sides_fit_into_the_vertices() {
+		for side in [16, 32, 64, MAX_CHUNK_SIZE] {
+			assert!(side * 2 <= I10_MAX as usize, "{}", side);
+			// the near and the far faces of the chunk.
+			for corner in [0.0, side as f32] {
+				let position = Vector([corner; 3]);
+				assert_eq!(BlockVertex::new(position, 0, &[0; 4], 0, 0, 0).position(), position, "{}", side);
+			}
+		}
+
+		// 256³ chunks would wrap around.
+		let side = MAX_CHUNK_SIZE as i32 * 2;
+		assert_ne!(i10_to_i32(i32_to_i10(side * 2)), side * 2);
+	}
+
+	#[test]
+	fn ao_strength_is_at_its_shader_offset() {
+		// the members of `LightingUniform` in world.wgsl, in the order of the offsets above.
+		let source = std::fs::read_to_string("data/shaders/world.wgsl").unwrap();
+		let members: Vec<&str> = source.split("struct LightingUniform {").nth(1).unwrap()
+			.split('}').next().unwrap()
+			.lines()
+			.filter_map(|line| line.trim().split_once(':').map(|(name, _)| name))
+			.collect();
+		assert_eq!(members, [
+			"sun_direction", "sun_color", "fog_color", "fog_range", "light_view_proj",
+			"shadow_params", "point_light_count", "ao_params", "block_light_color", "point_lights",
+		]);
+
+		let mut uniforms = WorldUniforms::new(256);
+		uniforms.lighting_uniform_mut().ao_params = [0.5, 0.0, 0.0, 0.0];
+		let offset = uniforms.lighting_uniform_offset() + 160;
+		assert_eq!(uniforms.data[offset..offset + 4], 0.5f32.to_ne_bytes());
+		assert!(uniforms.data[offset + 4..offset + 16].iter().all(|&byte| byte == 0));
+	}
+
+	#[test]
+	fn block_vertices_round_trip() {
+		let unpacked = |position, uv, ao, light, skylight, texture_id| UnpackedBlockVertex { position, uv, ao, light, skylight, texture_id };
+		let round_trip = |vertex: UnpackedBlockVertex| {
+			let packed = BlockVertex::new(vertex.position, vertex.uv, &vertex.ao, vertex.light, vertex.skylight, vertex.texture_id);
+			assert_eq!(packed.unpack(), vertex);
+		};
+
+		// every half block, with y going the other way, so -256 and 255.5 are on every axis.
+		for i in I10_MIN..=I10_MAX {
+			let position = vec3(i as f32, (I10_MIN + I10_MAX - i) as f32, i as f32) * 0.5;
+			round_trip(unpacked(position, (i & 0b11) as u8, [0; 4], 0, 0, 0));
+		}
+
+		for slot in 0..4 {
+			for value in 0..4 {
+				let mut ao = [3 - value; 4];
+				ao[slot] = value;
+				round_trip(unpacked(vec3(1.0, 2.0, 3.0), 0, ao, 0, 0, 0));
+			}
+		}
+
+		for level in 0..=15 {
+			round_trip(unpacked(vec3(0.0, 0.0, 0.0), 3, [3; 4], level, 15 - level, 1));
+		}
+
+		for texture_id in [0, 1, 0xFF, 0xFFFF] {
+			round_trip(unpacked(vec3(-256.0, 255.5, 32.0), 2, [1, 2, 3, 0], 15, 15, texture_id));
+		}
+	}
+
+	#[test]
+	fn orthographic_cameras_project_to_the_box() {
+		let camera = Camera {
+			position: vec3(1.0, 2.0, 3.0),
+			// looking along +x, so +z is to the right.
+			yaw: 0.0,
+			pitch: 0.0,
+			aspect: 2.0,
+			projection: Projection::Orthographic { height: 10.0 },
+			znear: 1.0,
+			zfar: 101.0,
+			eye_offset: vec3(0.0, 0.0, 0.0),
+		};
+		let matrix = camera.build_view_proj_matrix();
+		let ndc = |offset: Vec3f32| project(&matrix, camera.eye() + offset);
+		let close = |a: Vec3f32, b: Vec3f32| (a - b).iter().all(|c| c.abs() < 1e-5);
+
+		// the corners of the box, 20 wide and 10 high, with a 0..1 depth range.
+		assert!(close(ndc(vec3(1.0, 5.0, 10.0)), vec3(1.0, 1.0, 0.0)), "{}", ndc(vec3(1.0, 5.0, 10.0)));
+		assert!(close(ndc(vec3(101.0, -5.0, -10.0)), vec3(-1.0, -1.0, 1.0)), "{}", ndc(vec3(101.0, -5.0, -10.0)));
+		assert!(close(ndc(vec3(51.0, 0.0, 5.0)), vec3(0.5, 0.0, 0.5)), "{}", ndc(vec3(51.0, 0.0, 5.0)));
+		// unlike a perspective projection, the distance doesn't shrink things.
+		assert!(close(ndc(vec3(2.0, 2.5, 2.5)) - ndc(vec3(90.0, 2.5, 2.5)), vec3(0.0, 0.0, -0.88)));
+	}
 }
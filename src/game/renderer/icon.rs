@@ -0,0 +1,126 @@
+use std::{cell::Cell, collections::HashMap};
+
+use crate::{gfx, math::*, game::{chunk::{Block, ChunkData, ChunkMeshData, ChunkNeighborhood, MAX_LIGHT}, registry::BlockRegistry}};
+
+use super::{chunk::{BlockVertex, ChunkRenderer}, ui::{UiRenderer, UiTexture}};
+
+struct Icon {
+	/// index of the icon's square in the atlas, row by row.
+	slot: u32,
+	opaque: gfx::Mesh<BlockVertex>,
+	transparent: gfx::Mesh<BlockVertex>,
+	/// cleared when the atlas is recreated, see [`IconRenderer::set_atlas`].
+	rendered: Cell<bool>,
+}
+
+/// renders single blocks into the squares of the "icons" attachment (the atlas), so that the ui can draw them.
+/// each block is rendered once, in the frame after it was requested.
+pub struct IconRenderer {
+	/// size of an icon in the atlas, in pixels.
+	icon_size: u32,
+	icons: HashMap<u16, Icon>,
+	_world_buffers: [wgpu::Buffer; 2],
+	world_bind_group: wgpu::BindGroup,
+	ui_texture: UiTexture,
+}
+
+impl IconRenderer {
+	/// the atlas is a square of this many icons on each side.
+	pub const ATLAS_ICONS: u32 = 8;
+	/// the block is meshed away from the chunk's edges, so that all of its faces are drawn.
+	const BLOCK_POSITION: Vec3i32 = Vector([1, 1, 1]);
+	/// half of the size of the view, in blocks, fits a cube seen from a corner.
+	const VIEW_RADIUS: f32 = 0.9;
+
+	/// the size of the atlas for icons of `icon_size` pixels.
+	pub fn atlas_size(icon_size: u32) -> wgpu::Extent3d {
+		wgpu::Extent3d {
+			width: icon_size * Self::ATLAS_ICONS,
+			height: icon_size * Self::ATLAS_ICONS,
+			depth_or_array_layers: 1
+		}
+	}
+
+	/// `atlas` is the graph's attachment, of [`IconRenderer::atlas_size`].
+	pub fn new(gfx: &gfx::Gfx, chunk_renderer: &ChunkRenderer, ui_renderer: &mut UiRenderer, atlas: &gfx::Texture, icon_size: u32) -> Self {
+		// an isometric view of the block, from above.
+		let eye = vec3(-1.0f32, 0.8, -1.0).normalized() * 2.0;
+		let view = glm::look_at_rh(
+			&glm::vec3(eye.x, eye.y, eye.z),
+			&glm::vec3(0.0, 0.0, 0.0),
+			&glm::vec3(0.0, 1.0, 0.0)
+		);
+		let radius = Self::VIEW_RADIUS;
+		let proj = glm::ortho_rh_zo(-radius, radius, -radius, radius, 0.1, 4.0);
+		let (buffers, world_bind_group) = chunk_renderer.create_icon_bindings(gfx, &(proj * view), eye, -Self::BLOCK_POSITION);
+
+		Self {
+			icon_size,
+			icons: HashMap::new(),
+			_world_buffers: buffers,
+			world_bind_group,
+			ui_texture: ui_renderer.add_attachment(gfx, atlas),
+		}
+	}
+
+	/// the atlas has to be set again whenever the render graph is rebuilt, the icons are rendered again.
+	pub fn set_atlas(&mut self, gfx: &gfx::Gfx, ui_renderer: &mut UiRenderer, atlas: &gfx::Texture) {
+		ui_renderer.set_attachment(gfx, self.ui_texture, atlas);
+		for icon in self.icons.values() {
+			icon.rendered.set(false);
+		}
+	}
+
+	/// forgets all icons, e.g. after the block textures changed.
+	pub fn clear(&mut self) {
+		self.icons.clear();
+	}
+
+	/// meshes the block if it doesn't have an icon yet, it's rendered in the next frame.
+	/// blocks are ignored once the atlas is full.
+	pub fn request(&mut self, gfx: &gfx::Gfx, id: u16, registry: &BlockRegistry) {
+		let slot = self.icons.len() as u32;
+		if self.icons.contains_key(&id) || slot >= Self::ATLAS_ICONS * Self::ATLAS_ICONS {
+			return;
+		}
+
+		let mut data = ChunkData::new();
+		data.set_block(Self::BLOCK_POSITION, Block { id, state: 0 });
+		data.skylight.fill(MAX_LIGHT);
+		let mut chunks = [None; 27];
+		chunks[ChunkNeighborhood::CENTER] = Some(&data);
+		let ChunkMeshData { opaque, transparent, .. } = ChunkData::generate_mesh(&ChunkNeighborhood::from_chunks(chunks), registry);
+
+		self.icons.insert(id, Icon {
+			slot,
+			opaque: gfx::Mesh::new(gfx, &opaque.vertices, &opaque.indices, Some("Icon Mesh")),
+			transparent: gfx::Mesh::new(gfx, &transparent.vertices, &transparent.indices, Some("Icon Transparent Mesh")),
+			rendered: Cell::new(false),
+		});
+	}
+
+	/// the atlas and the icon's normalized uvs in it, `None` until the icon has been rendered.
+	pub fn icon(&self, id: u16) -> Option<(UiTexture, Rect<f32>)> {
+		let icon = self.icons.get(&id).filter(|icon| icon.rendered.get())?;
+		let size = 1.0 / Self::ATLAS_ICONS as f32;
+		Some((self.ui_texture, Rect {
+			x: (icon.slot % Self::ATLAS_ICONS) as f32 * size,
+			y: (icon.slot / Self::ATLAS_ICONS) as f32 * size,
+			w: size,
+			h: size,
+		}))
+	}
+
+	/// renders the icons that haven't been rendered yet, the pass should load the atlas.
+	pub fn render<'a>(&'a self, chunk_renderer: &'a ChunkRenderer, render_pass: &mut wgpu::RenderPass<'a>) {
+		for icon in self.icons.values().filter(|icon| !icon.rendered.get()) {
+			let x = (icon.slot % Self::ATLAS_ICONS) * self.icon_size;
+			let y = (icon.slot / Self::ATLAS_ICONS) * self.icon_size;
+			render_pass.set_viewport(x as f32, y as f32, self.icon_size as f32, self.icon_size as f32, 0.0, 1.0);
+			for (mesh, transparent) in [(&icon.opaque, false), (&icon.transparent, true)] {
+				chunk_renderer.render_icon(render_pass, &self.world_bind_group, mesh, transparent);
+			}
+			icon.rendered.set(true);
+		}
+	}
+}
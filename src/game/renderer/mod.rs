@@ -1,57 +1,165 @@
-use crate::gfx::{self, graph};
+use crate::{gfx::{self, graph}, error::VoxleError};
 use self::{chunk::ChunkRenderContext, ui::{UiRenderContext, UiBuilder}};
+use std::collections::HashMap;
+
 use lazy_static::lazy_static;
 
 pub mod chunk;
+pub mod icon;
 pub mod ui;
+pub mod sky;
+#[cfg(feature = "hot-reload")]
+pub mod reload;
+
+/// the directory of the shader modules, in the data dir.
+pub fn shaders_dir() -> std::path::PathBuf {
+	crate::assets::asset_path("shaders")
+}
+
+/// the file of the shader module `name` (e.g. `game/block`) in `root`, usually [`shaders_dir`].
+fn shader_path(root: &std::path::Path, name: &str) -> std::path::PathBuf {
+	root.join(name).with_extension("wgsl")
+}
+
+/// the modules that `line` includes, if it's a `//!use a b c` line.
+fn line_includes(line: &str) -> Option<impl Iterator<Item = &str>> {
+	let mut words = line.split_whitespace();
+	(words.next() == Some("//!use")).then_some(words)
+}
+
+/// the modules that `source` includes with `//!use` lines, in order.
+#[cfg(feature = "hot-reload")]
+fn shader_includes(source: &str) -> Vec<&str> {
+	source.lines().filter_map(line_includes).flatten().collect()
+}
 
-pub fn load_shader_module<F: Fn(&str) -> &str>(name: &str, get_const: Option<&F>) -> Result<String, std::io::Error> {
-	use std::io::Read;
+/// while loading a shader, see [`include_shader_module`].
+#[derive(Default)]
+struct ShaderIncludes {
+	/// the modules that are being included, to detect cycles.
+	stack: Vec<String>,
+	/// the modules that already were included, so that e.g. a diamond only includes the shared module once.
+	included: Vec<String>,
+}
 
-	let base_path = std::path::PathBuf::from("data/shaders");
-	let module_path = base_path.join(name).with_extension("wgsl");
+/// `name` with its `//!use` lines preceded by the included modules, except the ones in `includes.included`.
+fn include_shader_module(root: &std::path::Path, name: &str, includes: &mut ShaderIncludes) -> Result<String, VoxleError> {
+	if includes.stack.iter().any(|module| module == name) {
+		let mut cycle = includes.stack.clone();
+		cycle.push(name.to_owned());
+		return Err(VoxleError::ShaderIncludeCycle(cycle));
+	}
+	if includes.included.iter().any(|module| module == name) {
+		return Ok(String::new());
+	}
+
+	let module_path = shader_path(root, name);
 	if !module_path.is_file() {
-		panic!("Shader not found: {:?}", module_path);
+		return Err(VoxleError::ShaderNotFound(module_path));
 	}
 
-	let mut module_source = String::new();
-	std::io::BufReader::new(std::fs::File::open(&module_path)?).read_to_string(&mut module_source)?;
+	let module_source = std::fs::read_to_string(&module_path).map_err(VoxleError::io(&module_path))?;
 	let mut module_string = String::new();
 
-	let first_line = module_source.lines().next().unwrap();
-	if first_line.starts_with("//!use") {
-		for include in first_line.split_whitespace().skip(1) {
-			module_string.push_str(&*load_shader_module(include, get_const).unwrap());
+	includes.stack.push(name.to_owned());
+	for line in module_source.lines() {
+		if let Some(line_includes) = line_includes(line) {
+			for include in line_includes {
+				module_string.push_str(&include_shader_module(root, include, includes)?);
+			}
 		}
+		module_string.push_str(line);
+		module_string.push('\n');
 	}
+	includes.stack.pop();
+	includes.included.push(name.to_owned());
 
-	module_string.push_str(&module_source);
-	
-	if let Some(get_const) = get_const.as_ref() {
-	lazy_static! {
-			static ref RE: regex::Regex = regex::Regex::new(
-				r"/\*!const\(([\w_]+)\)\*/"
-			).unwrap();
+	Ok(module_string)
+}
+
+/// a value for a `/*!const(name)*/` in a shader, see [`load_shader_consts`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstValue {
+	Bool(bool),
+	Int(i32),
+	Uint(u32),
+	Float(f32),
+}
+
+impl std::fmt::Display for ConstValue {
+	/// as a wgsl literal.
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Bool(value) => write!(f, "{}", value),
+			Self::Int(value) => write!(f, "{}i", value),
+			Self::Uint(value) => write!(f, "{}u", value),
+			Self::Float(value) => write!(f, "{:?}f", value),
 		}
+	}
+}
 
-		let mut offset: usize = 0;
-		loop {
-			if let Some(caps) = RE.captures_at(&module_string, offset) {
-				offset += caps.get(0).unwrap().len();
-				let range = caps.get(0).unwrap().range();
-				let c = get_const(caps.get(1).unwrap().as_str());
-				module_string.replace_range(range, &c.to_owned());
-			} else {
-				break;
-			}
+impl From<bool> for ConstValue { fn from(value: bool) -> Self { Self::Bool(value) } }
+impl From<i32> for ConstValue { fn from(value: i32) -> Self { Self::Int(value) } }
+impl From<u32> for ConstValue { fn from(value: u32) -> Self { Self::Uint(value) } }
+impl From<f32> for ConstValue { fn from(value: f32) -> Self { Self::Float(value) } }
+
+pub type ShaderConsts = HashMap<String, ConstValue>;
+
+/// the source of the module `name`, with its includes (each only once) and the `/*!const(name)*/`s replaced.
+/// every referenced constant has to be in `consts`.
+pub fn load_shader_module(name: &str, consts: &ShaderConsts) -> Result<String, VoxleError> {
+	load_shader_module_in(&shaders_dir(), name, consts)
+}
+
+fn load_shader_module_in(root: &std::path::Path, name: &str, consts: &ShaderConsts) -> Result<String, VoxleError> {
+	lazy_static! {
+		static ref RE: regex::Regex = regex::Regex::new(
+			r"/\*!const\(([\w_]+)\)\*/"
+		).unwrap();
+	}
+
+	let module_string = include_shader_module(root, name, &mut ShaderIncludes::default())?;
+
+	let mut missing = None;
+	let module_string = RE.replace_all(&module_string, |caps: &regex::Captures| match consts.get(&caps[1]) {
+		Some(value) => value.to_string(),
+		None => {
+			missing.get_or_insert_with(|| caps[1].to_owned());
+			String::new()
 		}
+	}).into_owned();
+
+	match missing {
+		Some(constant) => Err(VoxleError::MissingShaderConst { shader: name.to_owned(), constant }),
+		None => Ok(module_string),
 	}
+}
 
-	Ok(module_string)
+/// `name` and the modules it includes with `//!use`, recursively.
+#[cfg(feature = "hot-reload")]
+pub fn shader_dependencies(name: &str) -> Result<Vec<String>, VoxleError> {
+	let mut dependencies = vec![];
+	collect_shader_dependencies(&shaders_dir(), name, &mut dependencies)?;
+	Ok(dependencies)
+}
+
+#[cfg(feature = "hot-reload")]
+fn collect_shader_dependencies(root: &std::path::Path, name: &str, dependencies: &mut Vec<String>) -> Result<(), VoxleError> {
+	if dependencies.iter().any(|dependency| dependency == name) {
+		return Ok(());
+	}
+	dependencies.push(name.to_owned());
+
+	let module_path = shader_path(root, name);
+	let module_source = std::fs::read_to_string(&module_path).map_err(VoxleError::io(&module_path))?;
+	for include in shader_includes(&module_source) {
+		collect_shader_dependencies(root, include, dependencies)?;
+	}
+	Ok(())
 }
 
-pub fn load_shader(name: &str) -> Result<wgpu::ShaderModuleDescriptor, std::io::Error>  {
-	let shader_code = load_shader_module::<fn(&str) -> &str>(name, None)?;
+pub fn load_shader(name: &str) -> Result<wgpu::ShaderModuleDescriptor, VoxleError>  {
+	let shader_code = load_shader_module(name, &ShaderConsts::new())?;
 
 	Ok(wgpu::ShaderModuleDescriptor {
 		label: Some(name),
@@ -59,8 +167,8 @@ pub fn load_shader(name: &str) -> Result<wgpu::ShaderModuleDescriptor, std::io::
 	})
 }
 
-pub fn load_shader_consts<F: Fn(&str) -> &str>(name: &str, get_const: F) -> Result<wgpu::ShaderModuleDescriptor, std::io::Error>  {
-	let shader_code = load_shader_module(name, Some(&get_const))?;
+pub fn load_shader_consts(name: &'static str, consts: &ShaderConsts) -> Result<wgpu::ShaderModuleDescriptor<'static>, VoxleError> {
+	let shader_code = load_shader_module(name, consts)?;
 
 	Ok(wgpu::ShaderModuleDescriptor {
 		label: Some(name),
@@ -71,29 +179,92 @@ pub fn load_shader_consts<F: Fn(&str) -> &str>(name: &str, get_const: F) -> Resu
 pub struct GameRenderer {
 	pub chunk_renderer: chunk::ChunkRenderer,
 	pub ui_renderer: ui::UiRenderer,
+	pub sky_renderer: sky::SkyRenderer,
+	pub icon_renderer: icon::IconRenderer,
 	// uniform_buffer: wgpu::Buffer,
 	graph: graph::Graph<super::GameState>,
+	shadow_map_size: u32,
+	/// msaa sample count of the main pass, 1 if it's disabled.
+	samples: u32,
+	/// `None` if the watcher couldn't be started.
+	#[cfg(feature = "hot-reload")]
+	shader_watcher: Option<reload::ShaderWatcher>,
 }
 
 impl GameRenderer {
 	pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
-	pub const SAMPLES: u32 = 4;
+	pub const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+	pub const TEXTURE_FILTERING: chunk::TextureFiltering = chunk::TextureFiltering::Nearest;
 	pub const CLEAR_COLOR: wgpu::Color = wgpu::Color { r: 0.2, g: 0.3, b: 0.5, a: 1.0 };
+	/// size of the block icons, in pixels, see [`icon::IconRenderer`].
+	pub const ICON_SIZE: u32 = 64;
+
+	/// `samples` falls back to 1 (no msaa) if the adapter doesn't support it.
+	pub fn new(gfx: &gfx::Gfx, block_textures: &super::texture::LoadedTextures, shadow_map_size: u32, samples: u32) -> Result<Self, VoxleError> {
+		let samples = if gfx.supports_sample_count(gfx.config.format, samples) && gfx.supports_sample_count(Self::DEPTH_FORMAT, samples) {
+			samples
+		} else {
+			eprintln!("{}x msaa isn't supported, disabling it", samples);
+			1
+		};
+
+		let mut chunk_renderer = chunk::ChunkRenderer::new(gfx, block_textures, Self::TEXTURE_FILTERING, samples)?;
+		let sky_renderer = sky::SkyRenderer::new(gfx, &chunk_renderer.world_bind_group_layout, samples)?;
+		let graph = Self::build_graph(gfx, shadow_map_size, samples);
+		chunk_renderer.set_shadow_map(gfx, graph.attachment_texture("shadow").unwrap());
+		let mut ui_renderer = ui::UiRenderer::new(gfx, samples)?;
+		let icon_renderer = icon::IconRenderer::new(
+			gfx,
+			&chunk_renderer,
+			&mut ui_renderer,
+			graph.attachment_texture("icons").unwrap(),
+			Self::ICON_SIZE
+		);
+
+		Ok(Self {
+			chunk_renderer,
+			sky_renderer,
+			ui_renderer,
+			icon_renderer,
+			graph,
+			shadow_map_size,
+			samples,
+			#[cfg(feature = "hot-reload")]
+			shader_watcher: reload::ShaderWatcher::new()
+				.map_err(|error| eprintln!("couldn't watch the shaders: {}", error))
+				.ok(),
+		})
+	}
 
-	pub fn new(gfx: &gfx::Gfx, block_textures: &super::texture::LoadedTextures) -> Self {
+	fn build_graph(gfx: &gfx::Gfx, shadow_map_size: u32, samples: u32) -> graph::Graph<super::GameState> {
+		Self::with_graph_spec(gfx.config.format, shadow_map_size, samples, |graph_spec| {
+			graph_spec.build(gfx).unwrap_or_else(|error| panic!("invalid render graph: {}", error))
+		})
+	}
+
+	/// calls `f` with the spec of the render graph, the spec borrows from this function.
+	fn with_graph_spec<T>(
+		format: wgpu::TextureFormat,
+		shadow_map_size: u32,
+		samples: u32,
+		f: impl FnOnce(graph::GraphSpec<super::GameState>) -> T
+	) -> T {
+		// with msaa, the passes draw into "msaa-output", which is resolved into the output.
+		let color_target = if samples == 1 { "output" } else { "msaa-output" };
 		let graph_spec = graph::GraphSpec::<super::GameState> {
 			attachments: &[
 				Some(("output", graph::AttachmentSpec::Output(graph::OutputAttachmentSpec {
+					// only used without msaa, the resolve target isn't loaded.
 					ops: |_: &gfx::Gfx| wgpu::Operations {
-						load: wgpu::LoadOp::Clear(if Self::SAMPLES == 1 { Self::CLEAR_COLOR } else { wgpu::Color::BLACK }),
+						load: wgpu::LoadOp::Clear(Self::CLEAR_COLOR),
 						store: true
 					}
 				}))),
-				if Self::SAMPLES != 1 {
+				if samples != 1 {
 					Some(("msaa-output", graph::AttachmentSpec::Color(graph::ColorAttachmentSpec {
-						format: gfx.config.format,
+						format,
 						resolve: Some("output"),
-						samples: Self::SAMPLES,
+						samples,
 						size: graph::AttachmentSizeSpec::Output(1.0),
 						ops: |_: &gfx::Gfx| wgpu::Operations {
 							load: wgpu::LoadOp::Clear(Self::CLEAR_COLOR),
@@ -105,38 +276,150 @@ impl GameRenderer {
 					format: Self::DEPTH_FORMAT,
 					depth_ops: Some(|_: &gfx::Gfx| Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: true })),
 					stencil_ops: None,
-					samples: Self::SAMPLES // TODO: allow user to sample count
+					size: graph::AttachmentSizeSpec::Output(1.0),
+					samples
+				}))),
+				// sampled by the main pass, so it's stored.
+				Some(("shadow", graph::AttachmentSpec::DepthStencil(graph::DepthStencilAttachmentSpec {
+					format: Self::SHADOW_FORMAT,
+					depth_ops: Some(|_: &gfx::Gfx| Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: true })),
+					stencil_ops: None,
+					size: graph::AttachmentSizeSpec::Fixed(wgpu::Extent3d {
+						width: shadow_map_size,
+						height: shadow_map_size,
+						depth_or_array_layers: 1
+					}),
+					samples: 1
+				}))),
+				// the icons are only drawn once, so the atlas is kept between frames.
+				Some(("icons", graph::AttachmentSpec::Color(graph::ColorAttachmentSpec {
+					format,
+					resolve: None,
+					samples: 1,
+					size: graph::AttachmentSizeSpec::Fixed(icon::IconRenderer::atlas_size(Self::ICON_SIZE)),
+					ops: |_: &gfx::Gfx| wgpu::Operations {
+						load: wgpu::LoadOp::Load,
+						store: true
+					},
+				}))),
+				Some(("icons-depth", graph::AttachmentSpec::DepthStencil(graph::DepthStencilAttachmentSpec {
+					format: Self::DEPTH_FORMAT,
+					depth_ops: Some(|_: &gfx::Gfx| Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: false })),
+					stencil_ops: None,
+					size: graph::AttachmentSizeSpec::Fixed(icon::IconRenderer::atlas_size(Self::ICON_SIZE)),
+					samples: 1
 				}))),
 			],
 			nodes: &[
+				graph::NodeSpec {
+					id: "icons",
+					kind: graph::NodeKind::Render {
+						color_attachments: &["icons"],
+						depth_stencil_attachment: Some("icons-depth"),
+						render: |_gfx, render_pass, game| {
+							game.renderer.icon_renderer.render(&game.renderer.chunk_renderer, render_pass);
+						}
+					}
+				},
+				graph::NodeSpec {
+					id: "shadow",
+					kind: graph::NodeKind::Render {
+						color_attachments: &[],
+						depth_stencil_attachment: Some("shadow"),
+						render: |_gfx, render_pass, game| {
+							game.renderer.render_shadows(render_pass, game);
+						}
+					}
+				},
+				// the first pass clears the color attachment, so without the sky it's `CLEAR_COLOR`.
+				graph::NodeSpec {
+					id: "sky",
+					kind: graph::NodeKind::Render {
+						color_attachments: &[color_target],
+						depth_stencil_attachment: None,
+						render: |_gfx, render_pass, game| {
+							if game.render_sky.get() {
+								game.renderer.sky_renderer.render(render_pass, &game.renderer.chunk_renderer.uniform_bind_group);
+							}
+						}
+					}
+				},
+				// fills the depth of the opaque chunks, so that "main" only shades what's visible.
+				// it's the first pass that uses "depth", so it clears it even when disabled.
+				graph::NodeSpec {
+					id: "depth-prepass",
+					kind: graph::NodeKind::Render {
+						color_attachments: &[],
+						depth_stencil_attachment: Some("depth"),
+						render: |_gfx, render_pass, game| {
+							game.renderer.render_depth_prepass(render_pass, game);
+						}
+					}
+				},
 				graph::NodeSpec {
 					id: "main",
-					color_attachments: &[if Self::SAMPLES == 1 { "output" } else { "msaa-output" }],
-					depth_stencil_attachment: Some("depth"),
-					render: |gfx, render_pass, game| {
-						game.renderer.render_main(gfx, render_pass, game);
+					kind: graph::NodeKind::Render {
+						color_attachments: &[color_target],
+						depth_stencil_attachment: Some("depth"),
+						render: |_gfx, render_pass, game| {
+							game.renderer.render_main(render_pass, game);
+						}
 					}
 				}
 			],
 		};
-	
-		Self {
-			chunk_renderer: chunk::ChunkRenderer::new(gfx, block_textures),
-			ui_renderer: ui::UiRenderer::new(gfx),
-			graph: graph_spec.build(gfx),
-		}
+
+		f(graph_spec)
+	}
+
+	pub fn shadow_map_size(&self) -> u32 {
+		self.shadow_map_size
+	}
+
+	pub fn samples(&self) -> u32 {
+		self.samples
+	}
+
+	/// rebuilds the render graph with a new shadow map, the block icons are rendered again.
+	pub fn set_shadow_map_size(&mut self, gfx: &gfx::Gfx, shadow_map_size: u32) {
+		self.graph = Self::build_graph(gfx, shadow_map_size, self.samples);
+		self.chunk_renderer.set_shadow_map(gfx, self.graph.attachment_texture("shadow").unwrap());
+		self.icon_renderer.set_atlas(gfx, &mut self.ui_renderer, self.graph.attachment_texture("icons").unwrap());
+		self.shadow_map_size = shadow_map_size;
+	}
+
+	/// recreates the attachments that follow the window's size.
+	pub fn resize(&mut self, gfx: &gfx::Gfx) {
+		self.graph.resize(gfx);
 	}
 
 	pub fn render(&self, ctx: &mut gfx::RenderContext, game: &super::GameState) {
 		ctx.render_graph(&self.graph, game);
 	}
 
-	fn render_main<'ctx>(&'ctx self, gfx: &gfx::Gfx, render_pass: &mut wgpu::RenderPass<'ctx>, game: &'ctx super::GameState) {
-		game.on_render(gfx, &mut GameRenderContext { renderer: self, render_pass });
+	fn render_main<'ctx>(&'ctx self, render_pass: &mut wgpu::RenderPass<'ctx>, game: &'ctx super::GameState) {
+		game.on_render(&mut GameRenderContext { renderer: self, render_pass });
+	}
+
+	fn render_depth_prepass<'ctx>(&'ctx self, render_pass: &mut wgpu::RenderPass<'ctx>, game: &'ctx super::GameState) {
+		game.on_render_depth_prepass(&mut GameRenderContext { renderer: self, render_pass });
 	}
 
-	pub fn update(&mut self, gfx: &gfx::Gfx, ui_builder: UiBuilder) {
-		self.chunk_renderer.update();
+	fn render_shadows<'ctx>(&'ctx self, render_pass: &mut wgpu::RenderPass<'ctx>, game: &'ctx super::GameState) {
+		game.on_render_shadows(&mut GameRenderContext { renderer: self, render_pass });
+	}
+
+	pub fn update(&mut self, gfx: &gfx::Gfx, dt: f32, ui_builder: UiBuilder) {
+		#[cfg(feature = "hot-reload")]
+		if let Some(changed) = self.shader_watcher.as_ref().map(|watcher| watcher.changed()) {
+			if !changed.is_empty() {
+				self.chunk_renderer.reload_shaders(gfx, &changed);
+				self.sky_renderer.reload_shaders(gfx, &changed);
+				self.ui_renderer.reload_shaders(gfx, &changed);
+			}
+		}
+
+		self.chunk_renderer.update(gfx, dt);
 		self.ui_renderer.update(gfx, ui_builder);
 	}
 }
@@ -147,11 +430,263 @@ pub struct GameRenderContext<'a, 'b> {
 }
 
 impl<'a, 'b> GameRenderContext<'a, 'b> {
-	pub fn begin_chunk_context<'ctx>(&'ctx mut self, gfx: &gfx::Gfx) -> ChunkRenderContext<'a, 'ctx> {
-		ChunkRenderContext::begin(gfx, self.renderer, self.render_pass)
+	pub fn begin_chunk_context<'ctx>(&'ctx mut self) -> ChunkRenderContext<'a, 'ctx> {
+		ChunkRenderContext::begin(self.renderer, self.render_pass)
+	}
+
+	pub fn begin_ui_context<'ctx>(&'ctx mut self) -> UiRenderContext<'a, 'ctx> {
+		UiRenderContext::begin(self.renderer, self.render_pass)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::world::tests::TestDirectory;
+
+	fn write_shader(directory: &TestDirectory, name: &str, source: &str) {
+		let path = shader_path(&directory.0, name);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, source).unwrap();
+	}
+
+	fn node_ids(spec: &graph::GraphSpec<super::super::GameState>) -> Vec<String> {
+		spec.nodes.iter().map(|node| node.id.to_owned()).collect()
 	}
 
-	pub fn begin_ui_context<'ctx>(&'ctx mut self, gfx: &gfx::Gfx) -> UiRenderContext<'a, 'ctx> {
-		UiRenderContext::begin(gfx, self.renderer, self.render_pass)
+	/// the color and depth stencil attachments of the render node `id`.
+	fn attachments<'a>(spec: &graph::GraphSpec<'a, super::super::GameState>, id: &str) -> (&'a [&'a str], Option<&'a str>) {
+		match spec.nodes.iter().find(|node| node.id == id).unwrap().kind {
+			graph::NodeKind::Render { color_attachments, depth_stencil_attachment, .. } => (color_attachments, depth_stencil_attachment),
+			graph::NodeKind::Compute { .. } => panic!("{} isn't a render node", id),
+		}
+	}
+
+	#[test]
+	fn graph_draws_the_sky_first() {
+		for samples in [1, 4] {
+			GameRenderer::with_graph_spec(wgpu::TextureFormat::Bgra8UnormSrgb, 2048, samples, |spec| {
+				assert_eq!(spec.validate(), Ok(()));
+				let ids = node_ids(&spec);
+				let sky = ids.iter().position(|id| id == "sky").unwrap();
+				let main = ids.iter().position(|id| id == "main").unwrap();
+				assert!(sky < main);
+
+				// the sky clears the color target that "main" draws on.
+				assert_eq!(attachments(&spec, "sky").0, attachments(&spec, "main").0);
+				assert_eq!(attachments(&spec, "sky").0, [if samples == 1 { "output" } else { "msaa-output" }]);
+			});
+		}
+	}
+
+	#[test]
+	fn graph_renders_shadows_before_the_main_pass() {
+		GameRenderer::with_graph_spec(wgpu::TextureFormat::Bgra8UnormSrgb, 1024, 1, |spec| {
+			assert_eq!(spec.validate(), Ok(()));
+			let ids = node_ids(&spec);
+			let shadow = ids.iter().position(|id| id == "shadow").unwrap();
+			assert!(shadow < ids.iter().position(|id| id == "main").unwrap());
+			assert_eq!(attachments(&spec, "shadow"), (&[][..], Some("shadow")));
+
+			let shadow_map = spec.attachments.iter().flatten().find(|(name, _)| *name == "shadow").unwrap();
+			match shadow_map.1 {
+				graph::AttachmentSpec::DepthStencil(graph::DepthStencilAttachmentSpec { format, size: graph::AttachmentSizeSpec::Fixed(extent), .. }) => {
+					assert_eq!(format, GameRenderer::SHADOW_FORMAT);
+					assert_eq!((extent.width, extent.height), (1024, 1024));
+				},
+				other => panic!("the shadow map is {:?}", other),
+			}
+		});
+	}
+
+	#[test]
+	fn the_depth_prepass_fills_the_main_depth() {
+		for samples in [1, 4] {
+			GameRenderer::with_graph_spec(wgpu::TextureFormat::Bgra8UnormSrgb, 1024, samples, |spec| {
+				assert_eq!(spec.validate(), Ok(()));
+				let ids = node_ids(&spec);
+				let prepass = ids.iter().position(|id| id == "depth-prepass").unwrap();
+				let main = ids.iter().position(|id| id == "main").unwrap();
+				assert!(prepass < main);
+
+				// depth only, into the attachment that "main" tests against.
+				assert_eq!(attachments(&spec, "depth-prepass"), (&[][..], Some("depth")));
+				assert_eq!(attachments(&spec, "main").1, Some("depth"));
+				// so it's the pass that clears the depth, "main" loads it.
+				assert!(ids[..prepass].iter().all(|id| attachments(&spec, id).1 != Some("depth")));
+			});
+		}
+	}
+
+	#[test]
+	fn icons_are_rendered_at_the_icon_size() {
+		let atlas_size = icon::IconRenderer::atlas_size(GameRenderer::ICON_SIZE);
+		assert_eq!((atlas_size.width, atlas_size.height), (GameRenderer::ICON_SIZE * 8, GameRenderer::ICON_SIZE * 8));
+
+		GameRenderer::with_graph_spec(wgpu::TextureFormat::Bgra8UnormSrgb, 1024, 4, |spec| {
+			let size = |name: &str| match spec.attachments.iter().flatten().find(|(id, _)| *id == name).unwrap().1 {
+				graph::AttachmentSpec::Color(graph::ColorAttachmentSpec { size: graph::AttachmentSizeSpec::Fixed(extent), samples, .. })
+				| graph::AttachmentSpec::DepthStencil(graph::DepthStencilAttachmentSpec { size: graph::AttachmentSizeSpec::Fixed(extent), samples, .. }) => {
+					// no msaa, the icons are sampled directly.
+					assert_eq!(samples, 1);
+					extent
+				},
+				other => panic!("{} is {:?}", name, other),
+			};
+			assert_eq!(size("icons"), atlas_size);
+			assert_eq!(size("icons-depth"), atlas_size);
+
+			assert_eq!(attachments(&spec, "icons"), (&["icons"][..], Some("icons-depth")));
+		});
+	}
+
+	#[test]
+	fn reloaded_shaders_resolve_includes_again() {
+		let directory = TestDirectory::new("shader-reload");
+		write_shader(&directory, "lib", "fn f() -> f32 { return 1.0; }");
+		write_shader(&directory, "game/main", "//!use lib\nfn main() { f(); }");
+
+		let load = || load_shader_module_in(&directory.0, "game/main", &ShaderConsts::new()).unwrap();
+		let loaded = load();
+		assert_eq!(loaded, "fn f() -> f32 { return 1.0; }\n//!use lib\nfn main() { f(); }\n");
+		assert_eq!(load(), loaded);
+
+		// only the included module changed.
+		write_shader(&directory, "lib", "fn f() -> f32 { return 2.0; }");
+		assert_eq!(load(), loaded.replace("1.0", "2.0"));
+	}
+
+	#[test]
+	fn missing_shaders_are_not_found() {
+		let directory = TestDirectory::new("shader-missing");
+		write_shader(&directory, "game/main", "//!use lib\nfn main() {}");
+
+		let load = |name| load_shader_module_in(&directory.0, name, &ShaderConsts::new());
+		assert!(matches!(load("game/other"), Err(VoxleError::ShaderNotFound(path)) if path == shader_path(&directory.0, "game/other")));
+		// or one of its includes.
+		assert!(matches!(load("game/main"), Err(VoxleError::ShaderNotFound(path)) if path == shader_path(&directory.0, "lib")));
+	}
+
+	#[test]
+	fn diamond_includes_are_included_once() {
+		let directory = TestDirectory::new("shader-diamond");
+		write_shader(&directory, "base", "const BASE = 1;");
+		write_shader(&directory, "left", "//!use base\nconst LEFT = BASE;");
+		write_shader(&directory, "right", "//!use base\nconst RIGHT = BASE;");
+		write_shader(&directory, "game/main", "//!use left right\nfn main() {}");
+
+		let loaded = load_shader_module_in(&directory.0, "game/main", &ShaderConsts::new()).unwrap();
+		assert_eq!(loaded.matches("const BASE").count(), 1);
+		assert_eq!(loaded, concat!(
+			"const BASE = 1;\n",
+			"//!use base\nconst LEFT = BASE;\n",
+			"//!use base\nconst RIGHT = BASE;\n",
+			"//!use left right\nfn main() {}\n",
+		));
+	}
+
+	#[test]
+	fn include_cycles_are_errors() {
+		let directory = TestDirectory::new("shader-cycle");
+		write_shader(&directory, "a", "//!use b");
+		write_shader(&directory, "b", "//!use a");
+		write_shader(&directory, "self", "//!use self");
+		write_shader(&directory, "game/main", "//!use a");
+
+		let load = |name| load_shader_module_in(&directory.0, name, &ShaderConsts::new());
+		assert!(matches!(load("game/main"), Err(VoxleError::ShaderIncludeCycle(cycle)) if cycle == ["game/main", "a", "b", "a"]));
+		assert!(matches!(load("self"), Err(VoxleError::ShaderIncludeCycle(cycle)) if cycle == ["self", "self"]));
+	}
+
+	#[test]
+	fn includes_are_spliced_where_they_are() {
+		let directory = TestDirectory::new("shader-include-lines");
+		write_shader(&directory, "consts", "const N = 4;");
+		write_shader(&directory, "lib", "fn f() -> i32 { return N; }");
+		write_shader(&directory, "game/main", "//!use consts\nstruct S { n: i32 }\n//!use lib\nfn main() { f(); }");
+
+		let loaded = load_shader_module_in(&directory.0, "game/main", &ShaderConsts::new()).unwrap();
+		assert_eq!(loaded, concat!(
+			"const N = 4;\n",
+			"//!use consts\n",
+			"struct S { n: i32 }\n",
+			"fn f() -> i32 { return N; }\n",
+			"//!use lib\n",
+			"fn main() { f(); }\n",
+		));
+	}
+
+	#[test]
+	fn consts_are_wgsl_literals() {
+		let directory = TestDirectory::new("shader-consts");
+		write_shader(&directory, "lib", "const SIZE = /*!const(size)*/;");
+		write_shader(&directory, "game/main", "//!use lib\nconst A = /*!const(a)*/; const B = /*!const(b)*/; const C = /*!const(c)*/;");
+
+		let consts = ShaderConsts::from([
+			("size".to_owned(), 32u32.into()),
+			("a".to_owned(), true.into()),
+			("b".to_owned(), (-2i32).into()),
+			("c".to_owned(), 1.0f32.into()),
+		]);
+		let loaded = load_shader_module_in(&directory.0, "game/main", &consts).unwrap();
+		assert_eq!(loaded, "const SIZE = 32u;\n//!use lib\nconst A = true; const B = -2i; const C = 1.0f;\n");
+	}
+
+	#[test]
+	fn missing_consts_are_errors() {
+		let directory = TestDirectory::new("shader-missing-const");
+		write_shader(&directory, "lib", "const SIZE = /*!const(size)*/;");
+		write_shader(&directory, "game/main", "//!use lib\nconst A = /*!const(a)*/;");
+
+		let missing = |name: &str| match load_shader_module_in(&directory.0, "game/main", &ShaderConsts::from([(name.to_owned(), 1u32.into())])) {
+			Err(VoxleError::MissingShaderConst { shader, constant }) => (shader, constant),
+			other => panic!("loaded with only {}: {:?}", name, other),
+		};
+		// the constant is reported even if it's in an included module.
+		assert_eq!(missing("a"), ("game/main".to_owned(), "size".to_owned()));
+		assert_eq!(missing("size"), ("game/main".to_owned(), "a".to_owned()));
+	}
+
+	#[cfg(feature = "hot-reload")]
+	#[test]
+	fn shaders_depend_on_their_includes() {
+		let directory = TestDirectory::new("shader-dependencies");
+		write_shader(&directory, "lib", "");
+		write_shader(&directory, "util", "//!use lib");
+		write_shader(&directory, "game/main", "//!use util lib");
+
+		let mut dependencies = Vec::new();
+		collect_shader_dependencies(&directory.0, "game/main", &mut dependencies).unwrap();
+		assert_eq!(dependencies, ["game/main", "util", "lib"]);
+	}
+
+	#[test]
+	fn shipped_shaders_load() {
+		let consts = ShaderConsts::from([("is_black".to_owned(), false.into())]);
+		for name in ["game/block", "game/depth", "game/outline", "game/overlay", "game/shadow", "game/sky", "ui", "ui/quad"] {
+			let source = load_shader_module_in(std::path::Path::new("data/shaders"), name, &consts).unwrap();
+			assert!(!source.contains("/*!const"), "{}", name);
+		}
+	}
+
+	#[test]
+	fn msaa_output_is_only_there_with_msaa() {
+		for samples in [1, 4] {
+			GameRenderer::with_graph_spec(wgpu::TextureFormat::Bgra8UnormSrgb, 2048, samples, |spec| {
+				let attachment = |name: &str| spec.attachments.iter().flatten().find(|(other, _)| *other == name).map(|(_, spec)| *spec);
+				match attachment("msaa-output") {
+					None => assert_eq!(samples, 1),
+					Some(graph::AttachmentSpec::Color(color)) => {
+						assert_eq!(color.samples, samples);
+						assert_eq!(color.resolve, Some("output"));
+					},
+					Some(other) => panic!("msaa-output is {:?}", other),
+				}
+				match attachment("depth") {
+					Some(graph::AttachmentSpec::DepthStencil(depth)) => assert_eq!(depth.samples, samples),
+					other => panic!("depth is {:?}", other),
+				}
+			});
+		}
 	}
 }
@@ -0,0 +1,106 @@
+use std::sync::mpsc;
+
+use notify::Watcher;
+
+use crate::gfx;
+
+/// watches a directory (recursively) for changed files.
+pub struct DirectoryWatcher {
+	_watcher: notify::RecommendedWatcher,
+	events: mpsc::Receiver<notify::Result<notify::Event>>,
+	root: std::path::PathBuf,
+}
+
+impl DirectoryWatcher {
+	pub fn new(root: &std::path::Path) -> notify::Result<Self> {
+		let root = root.canonicalize()?;
+		let (sender, events) = mpsc::channel();
+		let mut watcher = notify::recommended_watcher(sender)?;
+		watcher.watch(&root, notify::RecursiveMode::Recursive)?;
+		Ok(Self { _watcher: watcher, events, root })
+	}
+
+	/// the files that were modified or created since the last call, relative to the root.
+	pub fn changed(&self) -> Vec<std::path::PathBuf> {
+		let mut changed = Vec::new();
+		for event in self.events.try_iter() {
+			let event = match event {
+				Ok(event) => event,
+				Err(error) => {
+					eprintln!("file watcher error: {}", error);
+					continue;
+				}
+			};
+
+			if !(event.kind.is_modify() || event.kind.is_create()) {
+				continue;
+			}
+
+			for path in event.paths {
+				if let Ok(relative) = path.strip_prefix(&self.root) {
+					let relative = relative.to_path_buf();
+					if !changed.contains(&relative) {
+						changed.push(relative);
+					}
+				}
+			}
+		}
+		changed
+	}
+}
+
+/// watches [`super::shaders_dir`] for changes, see [`super::GameRenderer::update`].
+pub struct ShaderWatcher(DirectoryWatcher);
+
+impl ShaderWatcher {
+	pub fn new() -> notify::Result<Self> {
+		DirectoryWatcher::new(&super::shaders_dir()).map(Self)
+	}
+
+	/// the names of the shader modules (e.g. `game/block`) that changed since the last call.
+	pub fn changed(&self) -> Vec<String> {
+		self.0.changed().into_iter()
+			.filter(|path| path.extension().is_some_and(|extension| extension == "wgsl"))
+			.map(|path| path.with_extension("").to_string_lossy().replace('\\', "/"))
+			.collect()
+	}
+}
+
+/// whether the shader module `name`, or one of its includes, is in `changed`.
+pub fn depends_on(name: &str, changed: &[String]) -> bool {
+	match super::shader_dependencies(name) {
+		Ok(dependencies) => dependencies.iter().any(|dependency| changed.contains(dependency)),
+		Err(error) => {
+			eprintln!("couldn't read shader {}: {}", name, error);
+			false
+		}
+	}
+}
+
+/// runs `f`, returning `None` (and printing the error) if it caused a validation error,
+/// so that a broken shader doesn't crash the game, and the old pipeline can be kept.
+pub fn validated<T>(gfx: &gfx::Gfx, f: impl FnOnce() -> T) -> Option<T> {
+	gfx.device.push_error_scope(wgpu::ErrorFilter::Validation);
+	let result = f();
+	match pollster::block_on(gfx.device.pop_error_scope()) {
+		Some(error) => {
+			eprintln!("shader reload failed: {}", error);
+			None
+		},
+		None => Some(result),
+	}
+}
+
+/// loads and compiles a shader module, see [`validated`].
+pub fn create_shader_module(
+	gfx: &gfx::Gfx,
+	descriptor: Result<wgpu::ShaderModuleDescriptor, crate::error::VoxleError>
+) -> Option<wgpu::ShaderModule> {
+	match descriptor {
+		Ok(descriptor) => validated(gfx, || gfx.device.create_shader_module(descriptor)),
+		Err(error) => {
+			eprintln!("couldn't load shader: {}", error);
+			None
+		}
+	}
+}
@@ -0,0 +1,93 @@
+use crate::{gfx, error::VoxleError};
+
+/// draws a gradient sky with a sun glow, behind everything else.
+pub struct SkyRenderer {
+	pipeline: wgpu::RenderPipeline,
+	/// kept to rebuild the pipeline when the shader is reloaded.
+	#[cfg(feature = "hot-reload")]
+	layout: wgpu::PipelineLayout,
+	#[cfg(feature = "hot-reload")]
+	samples: u32,
+}
+
+impl SkyRenderer {
+	pub fn new(gfx: &gfx::Gfx, world_bind_group_layout: &wgpu::BindGroupLayout, samples: u32) -> Result<Self, VoxleError> {
+		let layout = gfx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Sky Pipeline Layout"),
+			bind_group_layouts: &[world_bind_group_layout],
+			push_constant_ranges: &[]
+		});
+
+		let shader = gfx.device.create_shader_module(super::load_shader("game/sky")?);
+		let pipeline = Self::create_pipeline(gfx, &layout, &shader, samples);
+
+		Ok(Self {
+			pipeline,
+			#[cfg(feature = "hot-reload")]
+			layout,
+			#[cfg(feature = "hot-reload")]
+			samples
+		})
+	}
+
+	/// see [`super::chunk::ChunkRenderer::reload_shaders`].
+	#[cfg(feature = "hot-reload")]
+	pub fn reload_shaders(&mut self, gfx: &gfx::Gfx, changed: &[String]) {
+		use super::reload;
+
+		if reload::depends_on("game/sky", changed) {
+			if let Some(shader) = reload::create_shader_module(gfx, super::load_shader("game/sky")) {
+				if let Some(pipeline) = reload::validated(gfx, || Self::create_pipeline(gfx, &self.layout, &shader, self.samples)) {
+					self.pipeline = pipeline;
+				}
+			}
+		}
+	}
+
+	fn create_pipeline(gfx: &gfx::Gfx, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, samples: u32) -> wgpu::RenderPipeline {
+		gfx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Sky Pipeline"),
+			layout: Some(layout),
+			vertex: wgpu::VertexState {
+				module: shader,
+				entry_point: "vs_main",
+				// a single fullscreen triangle, generated from the vertex index.
+				buffers: &[]
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: shader,
+				entry_point: "fs_main",
+				targets: &[
+					Some(wgpu::ColorTargetState {
+						format: gfx.config.format,
+						blend: None,
+						write_mask: wgpu::ColorWrites::ALL
+					})
+				]
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				strip_index_format: None,
+				front_face: wgpu::FrontFace::Ccw,
+				cull_mode: None,
+				unclipped_depth: false,
+				polygon_mode: wgpu::PolygonMode::Fill,
+				conservative: false
+			},
+			// the sky pass doesn't have a depth attachment.
+			depth_stencil: None,
+			multisample: wgpu::MultisampleState {
+				count: samples,
+				mask: !0,
+				alpha_to_coverage_enabled: false
+			},
+			multiview: None
+		})
+	}
+
+	pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, world_bind_group: &'a wgpu::BindGroup) {
+		render_pass.set_pipeline(&self.pipeline);
+		render_pass.set_bind_group(0, world_bind_group, &[]);
+		render_pass.draw(0..3, 0..1);
+	}
+}
@@ -1,19 +1,23 @@
+use std::rc::Rc;
+
 use wgpu::util::DeviceExt;
 
-use crate::{gfx, math::*};
+use crate::{gfx, math::*, error::VoxleError, imgui::Font};
 
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct UiVertex {
 	pub position: [f32; 2],
 	pub texcoord: [f32; 2],
+	pub rgba: [u8; 4],
 }
 
 fn create_pipeline(
 	gfx: &gfx::Gfx,
 	layout: &wgpu::PipelineLayout,
 	shader: &wgpu::ShaderModule,
+	samples: u32,
 ) -> wgpu::RenderPipeline {
 	gfx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
 		label: None,
@@ -36,6 +40,12 @@ fn create_pipeline(
 						format: wgpu::VertexFormat::Float32x2,
 						offset: 4 * 2,
 						shader_location: 1
+					},
+					// rgba
+					wgpu::VertexAttribute {
+						format: wgpu::VertexFormat::Unorm8x4,
+						offset: 4 * 4,
+						shader_location: 2
 					}
 				],
 			}]
@@ -75,7 +85,7 @@ fn create_pipeline(
 			bias: wgpu::DepthBiasState::default(),
 		}),
 		multisample: wgpu::MultisampleState {
-			count: super::GameRenderer::SAMPLES,
+			count: samples,
 			mask: !0,
 			alpha_to_coverage_enabled: false
 		},
@@ -93,87 +103,281 @@ struct ViewUniform {
 // 	x: 
 // }
 
+/// a texture the ui can draw from, see [`UiRenderer::add_texture`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UiTexture(usize);
+
+impl UiTexture {
+	/// `ui_spritesheet.png`, used by [`UiBuilder::add_rect`].
+	pub const SPRITES: Self = Self(0);
+	/// the font's atlas, used by [`UiBuilder::add_text`].
+	pub const FONT: Self = Self(1);
+}
+
+/// a scissor rect, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ClipRect {
+	x: u32,
+	y: u32,
+	w: u32,
+	h: u32,
+}
+
+impl ClipRect {
+	/// the ui is laid out in physical pixels already, but the scissor rect has to be inside of the screen.
+	fn from_ui(rect: &Rect<i32>, screen_size: Vec2u32) -> Self {
+		let (width, height) = (screen_size.x as i32, screen_size.y as i32);
+		let (x1, y1) = (rect.x1().clamp(0, width), rect.y1().clamp(0, height));
+		let (x2, y2) = (rect.x2().clamp(x1, width), rect.y2().clamp(y1, height));
+		Self { x: x1 as u32, y: y1 as u32, w: (x2 - x1) as u32, h: (y2 - y1) as u32 }
+	}
+
+	fn is_empty(&self) -> bool {
+		self.w == 0 || self.h == 0
+	}
+}
+
 pub struct UiPrimitive {
 	offset: u32,
 	count: u32,
+	texture: UiTexture,
+	/// `None` draws on the whole screen.
+	clip: Option<ClipRect>,
 }
 
 pub struct UiBuilder {
 	vertices: Vec<UiVertex>,
-	indices: Vec<u32>,
-	primitives: Vec<UiPrimitive>,
+	/// the texture and clip rect of each quad, which has 4 of the vertices, see [`UiBuilder::batch`].
+	quads: Vec<(UiTexture, Option<ClipRect>)>,
 	screen_size: Vec2u32,
 	texture_size: Vec2u32,
+	font: Rc<Font>,
+	/// applied to the quads that are added, see [`UiBuilder::set_clip_rect`].
+	clip: Option<ClipRect>,
 }
 
 impl UiBuilder {
-	pub fn new(screen_size: Vec2u32, texture_size: Vec2u32) -> Self {
+	/// `texture_size` and `font` come from the [`UiRenderer`].
+	pub fn new(screen_size: Vec2u32, texture_size: Vec2u32, font: Rc<Font>) -> Self {
 		Self {
 			vertices: vec![],
-			indices: vec![],
-			primitives: vec![],
+			quads: vec![],
 			screen_size,
-			texture_size
+			texture_size,
+			font,
+			clip: None
 		}
 	}
 
+	/// the quads added after this are cut off outside of `rect`, `None` stops clipping.
+	pub fn set_clip_rect(&mut self, rect: Option<Rect<i32>>) {
+		self.clip = rect.map(|rect| ClipRect::from_ui(&rect, self.screen_size));
+	}
+
+	pub fn font(&self) -> &Font {
+		&self.font
+	}
+
+	/// `rect` is in pixels, from the top-left corner of the screen.
+	/// the sprite is multiplied by `rgba`, white leaves it as is.
 	pub fn add_rect(
 		&mut self,
 		rect: Rect<i32>,
 		uvs: Rect<i32>,
+		rgba: [u8; 4],
 	) {
-		let vertex_offset = self.vertices.len() as u32;
-		let rect = {
-			let mut r = rect;
-			r.x -= self.screen_size.x as i32 / 2;
-			r.y -= self.screen_size.y as i32 / 2;
-			r
-		}.each_as::<f32>() / self.screen_size.each_as();
 		let uvs = uvs.each_as::<f32>() / self.texture_size.each_as();
+		self.add_quad(rect, uvs, rgba, UiTexture::SPRITES);
+	}
+
+	/// like [`UiBuilder::add_rect`], but from any of the renderer's textures, with normalized `uvs`.
+	pub fn add_image(
+		&mut self,
+		rect: Rect<i32>,
+		texture: UiTexture,
+		uvs: Rect<f32>,
+		rgba: [u8; 4],
+	) {
+		self.add_quad(rect, uvs, rgba, texture);
+	}
+
+	/// draws `text` starting at the top-left corner of `rect`,
+	/// the characters that don't fit into its width are cut off.
+	pub fn add_text(
+		&mut self,
+		rect: Rect<i32>,
+		text: &str,
+		rgba: [u8; 4],
+	) {
+		let font = self.font.clone();
+		let mut x = rect.x;
+		for c in text.chars() {
+			let ch = font.char(c);
+			let width = ch.scaled_width(&font) as i32;
+			if x + width > rect.x2() {
+				break;
+			}
+
+			let uvs = ch.uvs();
+			self.add_quad(
+				Rect { x, y: rect.y, w: width, h: ch.scaled_height(&font) as i32 },
+				Rect { x: uvs.x1(), y: uvs.y1(), w: uvs.x2() - uvs.x1(), h: uvs.y2() - uvs.y1() },
+				rgba,
+				UiTexture::FONT
+			);
+			x += ch.scaled_advance_x(&font) as i32;
+		}
+	}
+
+	/// `uvs` are normalized.
+	fn add_quad(
+		&mut self,
+		rect: Rect<i32>,
+		uvs: Rect<f32>,
+		rgba: [u8; 4],
+		texture: UiTexture,
+	) {
+		// to clip space, where y points up.
+		let rect = rect.each_as::<f32>() / self.screen_size.each_as();
+		let (x1, x2) = (rect.x1() * 2.0 - 1.0, rect.x2() * 2.0 - 1.0);
+		let (y1, y2) = (1.0 - rect.y1() * 2.0, 1.0 - rect.y2() * 2.0);
 
-		// note: uvs flipped on y
 		self.vertices.extend_from_slice(&[
-			UiVertex { position: [rect.x1(), rect.y1()], texcoord: [uvs.x1(), uvs.y2()] },
-			UiVertex { position: [rect.x2(), rect.y1()], texcoord: [uvs.x2(), uvs.y2()] },
-			UiVertex { position: [rect.x2(), rect.y2()], texcoord: [uvs.x2(), uvs.y1()] },
-			UiVertex { position: [rect.x1(), rect.y2()], texcoord: [uvs.x1(), uvs.y1()] },
+			UiVertex { position: [x1, y1], texcoord: [uvs.x1(), uvs.y1()], rgba },
+			UiVertex { position: [x2, y1], texcoord: [uvs.x2(), uvs.y1()], rgba },
+			UiVertex { position: [x2, y2], texcoord: [uvs.x2(), uvs.y2()], rgba },
+			UiVertex { position: [x1, y2], texcoord: [uvs.x1(), uvs.y2()], rgba },
 		]);
 
-		/*
-		    #0                #1
-			(x1,y1) --------- (x2,y1)
-			   | `-._            |
-				 |      `-._       |
-				 |           `-._  |
-			(x1,y2) --------- (x2,y2)
-		    #3                #2
-		*/
-
-		let index_offset = self.indices.len() as u32;
-		self.indices.extend_from_slice(&[
-			vertex_offset + 0,
-			vertex_offset + 1,
-			vertex_offset + 2,
-			vertex_offset + 0,
-			vertex_offset + 2,
-			vertex_offset + 3,
-		]);
+		self.quads.push((texture, self.clip));
+	}
+
+	/// the indices of the quads, grouped by texture in each run of quads with the same clip rect,
+	/// and a primitive for each group. the quads are drawn in order, except that quads with
+	/// different textures under the same clip rect can end up over each other in the other order.
+	fn batch(&self) -> (Vec<u32>, Vec<UiPrimitive>) {
+		let mut indices = Vec::with_capacity(self.quads.len() * 6);
+		let mut primitives = Vec::new();
+		let mut run_start = 0;
+		while run_start < self.quads.len() {
+			let clip = self.quads[run_start].1;
+			let run_end = self.quads[run_start..].iter()
+				.position(|&(_, quad_clip)| quad_clip != clip)
+				.map_or(self.quads.len(), |length| run_start + length);
+
+			// in the order that the textures are first used in.
+			let mut textures = Vec::new();
+			for &(texture, _) in &self.quads[run_start..run_end] {
+				if !textures.contains(&texture) {
+					textures.push(texture);
+				}
+			}
+
+			for texture in textures {
+				let offset = indices.len() as u32;
+				for quad in run_start..run_end {
+					if self.quads[quad].0 != texture {
+						continue;
+					}
 
-		self.primitives.push(UiPrimitive { offset: index_offset, count: 6 })
+					/*
+					    #0                #1
+						(x1,y1) --------- (x2,y1)
+						   | `-._            |
+							 |      `-._       |
+							 |           `-._  |
+						(x1,y2) --------- (x2,y2)
+					    #3                #2
+					*/
+					let vertex_offset = quad as u32 * 4;
+					indices.extend_from_slice(&[
+						vertex_offset + 0,
+						vertex_offset + 1,
+						vertex_offset + 2,
+						vertex_offset + 0,
+						vertex_offset + 2,
+						vertex_offset + 3,
+					]);
+				}
+				primitives.push(UiPrimitive { offset, count: indices.len() as u32 - offset, texture, clip });
+			}
+			run_start = run_end;
+		}
+		(indices, primitives)
 	}
 }
 
 pub struct UiRenderer {
 	quad_render_pipeline: wgpu::RenderPipeline,
-	uniform_bind_group: wgpu::BindGroup,
+	/// kept to rebuild the pipeline when the shader is reloaded.
+	#[cfg(feature = "hot-reload")]
+	quad_pipeline_layout: wgpu::PipelineLayout,
+	#[cfg(feature = "hot-reload")]
+	samples: u32,
+	bind_group_layout: wgpu::BindGroupLayout,
 	view_uniform_buffer: wgpu::Buffer,
-	texture: gfx::Texture,
+	/// indexed by [`UiTexture`], each with its own bind group.
+	/// `None` for the attachments, which are owned by the render graph, see [`UiRenderer::add_attachment`].
+	textures: Vec<(Option<gfx::Texture>, wgpu::BindGroup)>,
+	font: Rc<Font>,
 	mesh: gfx::Mesh<UiVertex>,
-	primitives: Vec<UiPrimitive>
+	/// what's in `mesh`, the ui usually doesn't change between frames, so it's only uploaded when it does.
+	vertices: Vec<UiVertex>,
+	indices: Vec<u32>,
+	primitives: Vec<UiPrimitive>,
+	/// to reset the scissor rect after the clipped primitives.
+	screen_size: Vec2u32,
+	/// the size that the view uniform was last written for.
+	view_size: Option<Vec2u32>,
+}
+
+/// `path` is relative to the data dir, see [`crate::assets::asset_path`].
+fn load_image(path: &str) -> Result<image::RgbaImage, VoxleError> {
+	let path = crate::assets::asset_path(path);
+	gfx::Texture::load_image(&path).map_err(VoxleError::io(&path))
+}
+
+/// like [`load_image`], straight into a texture.
+fn load_texture(gfx: &gfx::Gfx, path: &str) -> Result<gfx::Texture, VoxleError> {
+	let path = crate::assets::asset_path(path);
+	gfx::Texture::from_path(gfx, &path, wgpu::TextureFormat::Rgba8UnormSrgb).map_err(VoxleError::io(&path))
+}
+
+fn create_texture(gfx: &gfx::Gfx, image: &image::RgbaImage) -> gfx::Texture {
+	gfx::Texture::from_rgba(gfx, image, wgpu::TextureFormat::Rgba8UnormSrgb)
+}
+
+fn create_bind_group(
+	gfx: &gfx::Gfx,
+	layout: &wgpu::BindGroupLayout,
+	view_uniform_buffer: &wgpu::Buffer,
+	texture: &gfx::Texture,
+) -> wgpu::BindGroup {
+	gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+		label: None,
+		layout,
+		entries: &[
+			wgpu::BindGroupEntry {
+				binding: 0,
+				resource: view_uniform_buffer.as_entire_binding()
+			},
+			wgpu::BindGroupEntry {
+				binding: 1,
+				resource: wgpu::BindingResource::TextureView(&texture.view),
+			},
+			wgpu::BindGroupEntry {
+				binding: 2,
+				resource: wgpu::BindingResource::Sampler(texture.sampler.as_ref().unwrap()),
+			}
+		]
+	})
 }
 
 impl UiRenderer {
-	pub fn new(gfx: &gfx::Gfx) -> Self {
+	/// the font's atlas is tiny, so it's drawn at twice its size.
+	const FONT_SCALE: f32 = 2.0;
+
+	pub fn new(gfx: &gfx::Gfx, samples: u32) -> Result<Self, VoxleError> {
 		let bind_group_layout = gfx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
 			label: None,
 			entries: &[
@@ -212,32 +416,11 @@ impl UiRenderer {
 			push_constant_ranges: &[]
 		});
 		
-		let quad_shader = gfx.device.create_shader_module(super::load_shader("ui/quad").unwrap());
-		let quad_render_pipeline = create_pipeline(gfx, &quad_pipeline_layout, &quad_shader);
-
-		let texture = {
-			let bytes = image::load(std::io::BufReader::new(std::fs::File::open("data/textures/ui_spritesheet.png").unwrap()), image::ImageFormat::Png).unwrap();
-			let rgba8 = bytes.to_rgba8();
-			let texture = gfx::Texture::create_binding_texture(
-				gfx,
-				wgpu::TextureFormat::Rgba8UnormSrgb,
-				wgpu::Extent3d {
-					width: rgba8.width(),
-					height: rgba8.height(),
-					depth_or_array_layers: 1,
-				}
-			);
-			gfx.queue.write_texture(
-				texture.texture.as_image_copy(),
-				&rgba8,
-				wgpu::ImageDataLayout {
-					offset: 0,
-					bytes_per_row: Some(4 * texture.size().width),
-					rows_per_image: Some(texture.size().height),
-				}, texture.size()
-			);
-			texture
-		};
+		let quad_shader = gfx.device.create_shader_module(super::load_shader("ui/quad")?);
+		let quad_render_pipeline = create_pipeline(gfx, &quad_pipeline_layout, &quad_shader, samples);
+
+		let font_atlas = load_image("textures/font.png")?;
+		let font = Rc::new(Font::from_atlas(&font_atlas, Self::FONT_SCALE));
 
 		let view_uniform_buffer = gfx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
 			label: None,
@@ -245,50 +428,99 @@ impl UiRenderer {
 			contents: bytemuck::bytes_of(&ViewUniform { proj: [[0.0; 4]; 4] })
 		});
 
-		let uniform_bind_group = gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
-			label: None,
-			layout: &bind_group_layout,
-			entries: &[
-				wgpu::BindGroupEntry {
-					binding: 0,
-					resource: view_uniform_buffer.as_entire_binding()
-				},
-				wgpu::BindGroupEntry {
-					binding: 1,
-					resource: wgpu::BindingResource::TextureView(&texture.view),
-				},
-				wgpu::BindGroupEntry {
-					binding: 2,
-					resource: wgpu::BindingResource::Sampler(texture.sampler.as_ref().unwrap()),
-				}
-			]
-		});
-
-		Self {
+		let mut renderer = Self {
 			quad_render_pipeline,
-			texture,
-			uniform_bind_group,
+			#[cfg(feature = "hot-reload")]
+			quad_pipeline_layout,
+			#[cfg(feature = "hot-reload")]
+			samples,
+			bind_group_layout,
 			view_uniform_buffer,
+			textures: vec![],
+			font,
 			mesh: gfx::Mesh::new(gfx, &[], &[], Some("UI Mesh")),
-			primitives: vec![]
+			vertices: vec![],
+			indices: vec![],
+			primitives: vec![],
+			screen_size: vec2(gfx.config.width, gfx.config.height),
+			view_size: None,
+		};
+
+		// in the order of the `UiTexture` constants.
+		renderer.add_texture(gfx, load_texture(gfx, "textures/ui_spritesheet.png")?);
+		renderer.add_texture(gfx, create_texture(gfx, &font_atlas));
+		Ok(renderer)
+	}
+
+	/// `texture` needs a sampler, and can then be drawn with [`UiBuilder::add_image`].
+	pub fn add_texture(&mut self, gfx: &gfx::Gfx, texture: gfx::Texture) -> UiTexture {
+		let bind_group = create_bind_group(gfx, &self.bind_group_layout, &self.view_uniform_buffer, &texture);
+		self.textures.push((Some(texture), bind_group));
+		UiTexture(self.textures.len() - 1)
+	}
+
+	/// like [`UiRenderer::add_texture`], for an attachment of the render graph.
+	/// it has to be set again with [`UiRenderer::set_attachment`] whenever the graph is rebuilt.
+	pub fn add_attachment(&mut self, gfx: &gfx::Gfx, attachment: &gfx::Texture) -> UiTexture {
+		let bind_group = create_bind_group(gfx, &self.bind_group_layout, &self.view_uniform_buffer, attachment);
+		self.textures.push((None, bind_group));
+		UiTexture(self.textures.len() - 1)
+	}
+
+	pub fn set_attachment(&mut self, gfx: &gfx::Gfx, texture: UiTexture, attachment: &gfx::Texture) {
+		self.textures[texture.0] = (None, create_bind_group(gfx, &self.bind_group_layout, &self.view_uniform_buffer, attachment));
+	}
+
+	/// see [`super::chunk::ChunkRenderer::reload_shaders`].
+	#[cfg(feature = "hot-reload")]
+	pub fn reload_shaders(&mut self, gfx: &gfx::Gfx, changed: &[String]) {
+		use super::reload;
+
+		if reload::depends_on("ui/quad", changed) {
+			if let Some(shader) = reload::create_shader_module(gfx, super::load_shader("ui/quad")) {
+				if let Some(pipeline) = reload::validated(gfx, || create_pipeline(gfx, &self.quad_pipeline_layout, &shader, self.samples)) {
+					self.quad_render_pipeline = pipeline;
+				}
+			}
 		}
 	}
 
+	/// of [`UiTexture::SPRITES`].
 	pub fn texture_size(&self) -> Vec2u32 {
-		let size = self.texture.size();
+		let size = self.textures[UiTexture::SPRITES.0].0.as_ref().unwrap().size();
 		vec2(size.width, size.height)
 	}
 
+	pub fn font(&self) -> Rc<Font> {
+		self.font.clone()
+	}
+
+	/// whether the builder's mesh differs from the uploaded one.
+	fn mesh_changed(&self, vertices: &[UiVertex], indices: &[u32]) -> bool {
+		vertices != self.vertices || indices != self.indices
+	}
+
 	pub fn update(&mut self, gfx: &gfx::Gfx, builder: UiBuilder) {
-		self.mesh.update(gfx, &builder.vertices, &builder.indices);
-		self.primitives = builder.primitives;
-		gfx.queue.write_buffer(
-			&self.view_uniform_buffer,
-			0,
-			bytemuck::bytes_of(&ViewUniform {
-				proj: ortho_matrix(0.0, gfx.config.width as f32, gfx.config.height as f32, 0.0),
-			})
-		);
+		let (indices, primitives) = builder.batch();
+		if self.mesh_changed(&builder.vertices, &indices) {
+			self.mesh.update(gfx, &builder.vertices, &indices);
+			self.vertices = builder.vertices;
+			self.indices = indices;
+		}
+		self.primitives = primitives;
+		self.screen_size = builder.screen_size;
+
+		let view_size = vec2(gfx.config.width, gfx.config.height);
+		if self.view_size != Some(view_size) {
+			gfx.queue.write_buffer(
+				&self.view_uniform_buffer,
+				0,
+				bytemuck::bytes_of(&ViewUniform {
+					proj: ortho_matrix(0.0, view_size.x as f32, view_size.y as f32, 0.0),
+				})
+			);
+			self.view_size = Some(view_size);
+		}
 	}
 }
 
@@ -299,7 +531,6 @@ pub struct UiRenderContext<'a, 'b> {
 
 impl<'a, 'b> UiRenderContext<'a, 'b> {
 	pub(super) fn begin(
-		_gfx: &gfx::Gfx,
 		renderer: &'a super::GameRenderer,
 		render_pass: &'b mut wgpu::RenderPass<'a>
 	) -> UiRenderContext<'a, 'b> {
@@ -311,11 +542,113 @@ impl<'a, 'b> UiRenderContext<'a, 'b> {
 	
 	pub fn render(&mut self) {
 		self.render_pass.set_pipeline(&self.renderer.ui_renderer.quad_render_pipeline);
-		self.render_pass.set_bind_group(0, &self.renderer.ui_renderer.uniform_bind_group, &[]);
 		self.render_pass.set_vertex_buffer(0, self.renderer.ui_renderer.mesh.buffers.vertex_buffer.slice(..));
-		self.render_pass.set_index_buffer(self.renderer.ui_renderer.mesh.buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+		self.render_pass.set_index_buffer(self.renderer.ui_renderer.mesh.buffers.index_buffer.slice(..), self.renderer.ui_renderer.mesh.buffers.index_format);
+		let screen_size = self.renderer.ui_renderer.screen_size;
+		let mut clipped = false;
+		let mut bound_texture = None;
 		for primitive in &self.renderer.ui_renderer.primitives {
-			self.render_pass.draw_indexed(primitive.offset..primitive.count, 0, 0..1);
+			match primitive.clip {
+				Some(clip) if clip.is_empty() => continue,
+				Some(clip) => {
+					self.render_pass.set_scissor_rect(clip.x, clip.y, clip.w, clip.h);
+					clipped = true;
+				},
+				None if clipped => {
+					self.render_pass.set_scissor_rect(0, 0, screen_size.x, screen_size.y);
+					clipped = false;
+				},
+				None => {},
+			}
+			if bound_texture != Some(primitive.texture) {
+				self.render_pass.set_bind_group(0, &self.renderer.ui_renderer.textures[primitive.texture.0].1, &[]);
+				bound_texture = Some(primitive.texture);
+			}
+			self.render_pass.draw_indexed(primitive.offset..primitive.offset + primitive.count, 0, 0..1);
+		}
+		if clipped {
+			self.render_pass.set_scissor_rect(0, 0, screen_size.x, screen_size.y);
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// a 200x100 screen, with a 32x32 sprite sheet and an empty font.
+	fn builder() -> UiBuilder {
+		let font = Font::from_atlas(&image::RgbaImage::new(16, 6), 1.0);
+		UiBuilder::new(vec2(200, 100), vec2(32, 32), Rc::new(font))
+	}
+
+	#[test]
+	fn rects_are_tinted() {
+		let mut ui = builder();
+		let rgba = [255, 128, 0, 64];
+		ui.add_rect(Rect { x: 0, y: 0, w: 100, h: 50 }, Rect { x: 0, y: 0, w: 16, h: 16 }, rgba);
+
+		assert_eq!(ui.vertices.len(), 4);
+		assert!(ui.vertices.iter().all(|vertex| vertex.rgba == rgba));
+		// the top-left quarter of the screen, and of the sprite sheet.
+		assert_eq!(ui.vertices[0].position, [-1.0, 1.0]);
+		assert_eq!(ui.vertices[2].position, [0.0, 0.0]);
+		assert_eq!(ui.vertices[2].texcoord, [0.5, 0.5]);
+		assert_eq!(ui.batch().0.len(), 6);
+	}
+
+	#[test]
+	fn clip_rects_stay_on_the_screen() {
+		let screen_size = vec2(200, 100);
+		let clip = |x, y, w, h| ClipRect::from_ui(&Rect { x, y, w, h }, screen_size);
+		assert_eq!(clip(10, 20, 30, 40), ClipRect { x: 10, y: 20, w: 30, h: 40 });
+		assert_eq!(clip(-10, -20, 30, 40), ClipRect { x: 0, y: 0, w: 20, h: 20 });
+		assert_eq!(clip(190, 90, 30, 40), ClipRect { x: 190, y: 90, w: 10, h: 10 });
+		assert!(clip(300, 20, 30, 40).is_empty());
+
+		// the quads after `set_clip_rect` are drawn separately, with the clip rect.
+		let mut ui = builder();
+		let uvs = Rect { x: 0, y: 0, w: 16, h: 16 };
+		ui.add_rect(Rect { x: 0, y: 0, w: 10, h: 10 }, uvs, [255; 4]);
+		ui.set_clip_rect(Some(Rect { x: 5, y: 5, w: 10, h: 10 }));
+		ui.add_rect(Rect { x: 0, y: 0, w: 10, h: 10 }, uvs, [255; 4]);
+		ui.add_rect(Rect { x: 10, y: 0, w: 10, h: 10 }, uvs, [255; 4]);
+		ui.set_clip_rect(None);
+		ui.add_rect(Rect { x: 0, y: 0, w: 10, h: 10 }, uvs, [255; 4]);
+		let clips: Vec<_> = ui.batch().1.iter().map(|primitive| (primitive.clip, primitive.count)).collect();
+		assert_eq!(clips, [(None, 6), (Some(ClipRect { x: 5, y: 5, w: 10, h: 10 }), 12), (None, 6)]);
+	}
+
+	#[test]
+	fn quads_are_batched_by_texture() {
+		let mut ui = builder();
+		let rect = Rect { x: 0, y: 0, w: 10, h: 10 };
+		let uvs = Rect { x: 0.0, y: 0.0, w: 1.0, h: 1.0 };
+		// text and sprites over each other, like the hotbar's counts over its slots.
+		for texture in [UiTexture::SPRITES, UiTexture::FONT, UiTexture::SPRITES, UiTexture::FONT] {
+			ui.add_image(rect, texture, uvs, [255; 4]);
+		}
+		ui.set_clip_rect(Some(Rect { x: 5, y: 5, w: 10, h: 10 }));
+		for texture in [UiTexture::FONT, UiTexture::SPRITES, UiTexture::FONT] {
+			ui.add_image(rect, texture, uvs, [255; 4]);
+		}
+		ui.set_clip_rect(None);
+		ui.add_image(rect, UiTexture::FONT, uvs, [255; 4]);
+
+		let (indices, primitives) = ui.batch();
+		let clip = Some(ClipRect { x: 5, y: 5, w: 10, h: 10 });
+		let batches: Vec<_> = primitives.iter().map(|primitive| (primitive.clip, primitive.texture, primitive.count)).collect();
+		assert_eq!(batches, [
+			(None, UiTexture::SPRITES, 12),
+			(None, UiTexture::FONT, 12),
+			(clip, UiTexture::FONT, 12),
+			(clip, UiTexture::SPRITES, 6),
+			(None, UiTexture::FONT, 6),
+		]);
+
+		// the first batch draws the first and third quads.
+		assert_eq!(indices[..12], [0, 1, 2, 0, 2, 3, 8, 9, 10, 8, 10, 11]);
+		assert_eq!(indices.len(), 8 * 6);
+		assert!(primitives.windows(2).all(|pair| pair[0].offset + pair[0].count == pair[1].offset));
+	}
+}
@@ -1,12 +1,13 @@
 use std::collections::HashMap;
 
-use crate::math::*;
+use crate::{math::*, error::VoxleError};
 
-use super::{Dir, chunk::BlockId};
+use super::Dir;
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub struct TextureId(pub u32);
 
+#[derive(Clone, Copy)]
 pub struct BlockSides<T: Copy + Clone> {
 	pub top: T,
 	pub bottom: T,
@@ -21,7 +22,6 @@ impl<T: Copy + Clone> BlockSides<T> {
 		Self { top: t, bottom: t, left: t, right: t, front: t, back: t }
 	}
 
-	#[allow(dead_code)]
 	pub fn cylinder(top: T, bottom: T, side: T) -> Self {
 		Self { top, bottom, left: side, right: side, front: side, back: side }
 	}
@@ -45,108 +45,217 @@ impl<T: Copy + Clone> BlockSides<T> {
 
 pub type BlockTextures = BlockSides<TextureId>;
 
+/// the number of mip levels of a texture of `size`, down to 1x1.
+pub fn mip_level_count(size: Vec2u32) -> u32 {
+	u32::BITS - size.x.max(size.y).max(1).leading_zeros()
+}
+
+/// the mip levels after the first one, each half the size of the previous.
+pub fn generate_mips(image: &image::RgbaImage) -> Vec<image::RgbaImage> {
+	let count = mip_level_count(vec2(image.width(), image.height()));
+	let mut mips: Vec<image::RgbaImage> = Vec::with_capacity(count as usize - 1);
+	for _ in 1..count {
+		let previous = mips.last().unwrap_or(image);
+		mips.push(image::imageops::resize(
+			previous,
+			(previous.width() / 2).max(1),
+			(previous.height() / 2).max(1),
+			image::imageops::FilterType::Triangle
+		));
+	}
+	mips
+}
+
+/// a texture with its frames stacked vertically in its image,
+/// the frames are stored as consecutive layers starting at `first`.
+#[derive(Debug, Clone, Copy)]
+pub struct TextureAnimation {
+	pub first: TextureId,
+	pub frame_count: u32,
+	/// in seconds.
+	pub frame_duration: f32,
+}
+
+impl TextureAnimation {
+	/// the frame that is shown `time` seconds after the start, the animation loops.
+	pub fn frame(&self, time: f32) -> u32 {
+		(time / self.frame_duration).floor().rem_euclid(self.frame_count as f32) as u32
+	}
+}
+
+/// a texture in a block manifest, see [`super::registry::load_block_registry`].
+#[derive(Debug, Clone, Copy)]
+pub struct TextureDesc<'a> {
+	pub path: &'a str,
+	pub frame_count: u32,
+	/// in seconds, unused if there is only one frame.
+	pub frame_duration: f32,
+}
+
 pub struct TextureSource {
 	pub id: TextureId,
 	pub data: Option<image::RgbaImage>
 }
 
+/// the textures are uploaded as the layers of an array texture,
+/// so all of them (and all of their frames) have to be the same size.
 pub struct LoadedTextures {
-	pub blocks: HashMap<BlockId, BlockTextures>,
 	pub textures: Vec<TextureSource>,
-	pub size: Vec2u32
+	pub size: Vec2u32,
+	pub animations: Vec<TextureAnimation>,
+	paths: HashMap<std::path::PathBuf, TextureId>,
+	/// textures with the same pixels (and frames) are only stored once.
+	contents: HashMap<(Vec<u8>, u32), TextureId>,
 }
 
-pub fn load_block_textures(json_path: &str) -> Result<LoadedTextures, std::io::Error> {
-	let mut r = LoadedTextures {
-		blocks: HashMap::new(),
-		textures: Vec::new(),
-		size: vec2(0, 0)
-	};
+impl LoadedTextures {
+	pub fn new() -> Self {
+		Self {
+			textures: Vec::new(),
+			size: vec2(0, 0),
+			animations: Vec::new(),
+			paths: HashMap::new(),
+			contents: HashMap::new(),
+		}
+	}
 
-	let mut texture_paths = HashMap::<std::path::PathBuf, TextureId>::new();
+	/// loads the texture at `path`, unless it was already loaded.
+	/// the image is split into `frame_count` frames from top to bottom, see [`TextureAnimation`].
+	pub fn load_texture(&mut self, path: &std::path::Path, frame_count: u32, frame_duration: f32) -> Result<TextureId, VoxleError> {
+		let path = path.canonicalize().map_err(VoxleError::io(path))?;
 
-	let manifest = json::parse(&std::fs::read_to_string(json_path)?)
-		.expect("block texture manifest should be correct json");
+		if let Some(id) = self.paths.get(&path) {
+			return Ok(*id);
+		}
 
-	if !manifest.is_array() {
-		panic!("block texture manifest should be an array");
-	}
+		let id = TextureId(self.textures.len() as u32);
+
+		let image_data = crate::gfx::Texture::load_image(&path).map_err(VoxleError::io(&path))?;
+
+		if frame_count == 0 || image_data.height() % frame_count != 0 {
+			return Err(VoxleError::invalid_texture(path, format!("can't be split into {} frames", frame_count)));
+		}
+		let frame_height = image_data.height() / frame_count;
+		let frame_size = vec2(image_data.width(), frame_height);
 
-	// TODO: generate one large image buffer for this.
+		if self.size == vec2(0, 0) {
+			self.size = frame_size;
+		} else if frame_size != self.size {
+			return Err(VoxleError::invalid_texture(path, format!(
+				"it's {}x{}, but the other textures are {}x{}",
+				frame_size.x, frame_size.y, self.size.x, self.size.y
+			)));
+		}
+
+		let key = (image_data.as_raw().clone(), frame_count);
+		if let Some(id) = self.contents.get(&key) {
+			let id = *id;
+			self.paths.insert(path, id);
+			return Ok(id);
+		}
 
-	let root_dir = std::path::Path::new(json_path).parent().expect("json manifest should have parent dir");
-	for entry in manifest.members() {
-		if !entry.is_array() {
-			panic!("block texture manifest entries should be arrays");
+		for frame in 0..frame_count {
+			let frame_data = image::imageops::crop_imm(&image_data, 0, frame * frame_height, image_data.width(), frame_height).to_image();
+			self.textures.push(TextureSource {
+				id: TextureId(id.0 + frame),
+				data: Some(frame_data)
+			});
 		}
 
-		let name = entry[0].as_str().expect("block texture manifest entry's first element should be the block name");
-		let mut texs = BlockTextures::same(TextureId(u32::MAX));
-		for (index, texture_path) in entry.members().skip(1).enumerate() {
-			let path = texture_path.as_str().expect("block texture manifest entry's elements must be strings");
-			let path = root_dir.join(path).canonicalize().expect("texture path should be a valid path");
-			
-			let id = if !texture_paths.contains_key(&path) {
-				let id = TextureId(r.textures.len() as u32);
-
-				let image_data = image::load(
-					std::io::BufReader::new(std::fs::File::open(&path).unwrap()),
-					image::ImageFormat::from_path(&path).unwrap()
-				).unwrap().to_rgba8();
-
-				if r.size == vec2(0, 0) {
-					r.size = vec2(image_data.width(), image_data.height());
-				}
-
-				r.textures.push(TextureSource {
-					id,
-					data: Some(image_data)
-				});
-
-				texture_paths.insert(path, id);
-
-				id
-			} else {
-				texture_paths[&path]
-			};
-
-			// this is an optimization, so that we don't
-			// have to create another vector with TextureIds.
-			match entry.len() - 1 {
-				1 => texs = BlockTextures::same(id), // the only time we iterate
-				3 => match index { // depends on the current index
-					0 => texs.top = id,
-					1 => texs.bottom = id,
-					2 => (texs.left, texs.right, texs.front, texs.back) = (id, id, id, id),
-					_ => unreachable!()
-				},
-				6 => match index {
-					0 => texs.back = id,
-					1 => texs.bottom = id,
-					2 => texs.left = id,
-					3 => texs.right = id,
-					4 => texs.front = id,
-					5 => texs.back = id,
-					_ => unreachable!()
-				},
-				_ => panic!("block texture manifest entry's can only provide 1, 3 or 6 textures.")
-			}
+		if frame_count > 1 {
+			self.animations.push(TextureAnimation { first: id, frame_count, frame_duration });
 		}
 
-		let found_block_id = match name {
-			"stone" => Some(BlockId::Stone),
-			"grass" => Some(BlockId::Grass),
-			"dirt" => Some(BlockId::Dirt),
-			"snow" => Some(BlockId::Snow),
-			"snow_grass" => Some(BlockId::SnowGrass),
-			_ => None
-		};
-		if let Some(block_id) = found_block_id {
-			r.blocks.insert(block_id, texs);
-		} else {
-			eprintln!("unknown block id: {}", name);
+		self.paths.insert(path, id);
+		self.contents.insert(key, id);
+
+		Ok(id)
+	}
+
+	/// `paths` can contain 1 (all sides), 3 (top, bottom, sides) or
+	/// 6 (top, bottom, left, right, front, back) texture paths.
+	pub fn load_block_textures(&mut self, root_dir: &std::path::Path, paths: &[TextureDesc]) -> Result<BlockTextures, VoxleError> {
+		let mut ids = Vec::with_capacity(paths.len());
+		for desc in paths {
+			ids.push(self.load_texture(&root_dir.join(desc.path), desc.frame_count, desc.frame_duration)?);
 		}
+
+		Ok(match ids[..] {
+			[all] => BlockTextures::same(all),
+			[top, bottom, side] => BlockTextures::cylinder(top, bottom, side),
+			[top, bottom, left, right, front, back] => BlockTextures { top, bottom, left, right, front, back },
+			_ => return Err(VoxleError::invalid_manifest(root_dir, "block textures can only provide 1, 3 or 6 textures")),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::world::tests::TestDirectory;
+
+	#[test]
+	fn mips_go_down_to_one_pixel() {
+		assert_eq!(mip_level_count(vec2(1, 1)), 1);
+		assert_eq!(mip_level_count(vec2(16, 16)), 5);
+		assert_eq!(mip_level_count(vec2(17, 16)), 5);
+		assert_eq!(mip_level_count(vec2(16, 64)), 7);
+		assert_eq!(mip_level_count(vec2(0, 0)), 1);
+
+		let mips = generate_mips(&image::RgbaImage::new(16, 4));
+		let sizes: Vec<(u32, u32)> = mips.iter().map(|mip| mip.dimensions()).collect();
+		assert_eq!(sizes, [(8, 2), (4, 1), (2, 1), (1, 1)]);
+	}
+
+	#[test]
+	fn animations_loop_through_their_frames() {
+		let animation = TextureAnimation { first: TextureId(3), frame_count: 4, frame_duration: 0.25 };
+		assert_eq!(animation.frame(0.0), 0);
+		assert_eq!(animation.frame(0.24), 0);
+		assert_eq!(animation.frame(0.25), 1);
+		assert_eq!(animation.frame(0.9), 3);
+		assert_eq!(animation.frame(1.0), 0);
+		assert_eq!(animation.frame(10.6), 2);
+
+		let still = TextureAnimation { frame_count: 1, ..animation };
+		assert_eq!(still.frame(123.4), 0);
+	}
+
+	/// a `width`x`height` image filled with `color`, in `directory`.
+	fn image(directory: &TestDirectory, name: &str, width: u32, height: u32, color: [u8; 4]) -> std::path::PathBuf {
+		let path = directory.0.join(name);
+		image::RgbaImage::from_pixel(width, height, image::Rgba(color)).save(&path).unwrap();
+		path
 	}
 
-	Ok(r)
+	#[test]
+	fn textures_have_to_be_the_same_size() {
+		let directory = TestDirectory::new("texture-sizes");
+		let mut textures = LoadedTextures::new();
+		textures.load_texture(&image(&directory, "a.png", 16, 16, [255, 0, 0, 255]), 1, 0.0).unwrap();
+		// the frames are the same size, so that's fine.
+		textures.load_texture(&image(&directory, "animated.png", 16, 64, [0, 255, 0, 255]), 4, 0.1).unwrap();
+
+		let error = textures.load_texture(&image(&directory, "b.png", 32, 32, [0, 0, 255, 255]), 1, 0.0).unwrap_err();
+		assert!(matches!(&error, VoxleError::InvalidTexture { path, .. } if path.ends_with("b.png")), "{}", error);
+		let error = textures.load_texture(&image(&directory, "c.png", 16, 48, [0, 0, 255, 255]), 2, 0.1).unwrap_err();
+		assert!(matches!(error, VoxleError::InvalidTexture { .. }), "{}", error);
+		assert_eq!(textures.textures.len(), 5);
+	}
+
+	#[test]
+	fn identical_textures_are_stored_once() {
+		let directory = TestDirectory::new("texture-contents");
+		let mut textures = LoadedTextures::new();
+		let a = textures.load_texture(&image(&directory, "a.png", 16, 16, [255, 0, 0, 255]), 1, 0.0).unwrap();
+		let copy = textures.load_texture(&image(&directory, "copy.png", 16, 16, [255, 0, 0, 255]), 1, 0.0).unwrap();
+		let again = textures.load_texture(&directory.0.join("a.png"), 1, 0.0).unwrap();
+		let other = textures.load_texture(&image(&directory, "b.png", 16, 16, [0, 0, 255, 255]), 1, 0.0).unwrap();
+		// the same pixels, but split into frames, is another texture.
+		let animated = textures.load_texture(&image(&directory, "animated.png", 16, 32, [255, 0, 0, 255]), 2, 0.1).unwrap();
+
+		assert_eq!((a.0, copy.0, again.0, other.0, animated.0), (0, 0, 0, 1, 2));
+		assert_eq!(textures.textures.len(), 4);
+		assert_eq!(textures.animations.len(), 1);
+	}
 }
@@ -0,0 +1,89 @@
+use std::f32::consts::TAU;
+
+use crate::math::*;
+
+/// how far the sun's arc is tilted away from the x/y plane, in radians.
+const SUN_TILT: f32 = 0.5;
+const NOON_SUN_COLOR: Vec3f32 = Vector([1.0, 0.95, 0.9]);
+const SUNSET_SUN_COLOR: Vec3f32 = Vector([1.0, 0.55, 0.3]);
+const SUNSET_SKY_COLOR: Vec3f32 = Vector([0.95, 0.55, 0.35]);
+
+/// the time of day, drives the sun's direction and the sky colors.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeOfDay {
+	/// fraction of the day, 0 is midnight, 0.25 sunrise, 0.5 noon and 0.75 sunset.
+	time: f32,
+	/// length of a day, in seconds.
+	day_length: f32,
+}
+
+impl TimeOfDay {
+	pub fn new(time: f32, day_length: f32) -> Self {
+		Self { time: time.rem_euclid(1.0), day_length }
+	}
+
+	pub fn time(&self) -> f32 {
+		self.time
+	}
+
+	pub fn advance(&mut self, dt: f32) {
+		self.time = (self.time + dt / self.day_length).rem_euclid(1.0);
+	}
+
+	/// the direction towards the sun, normalized.
+	pub fn to_sun(&self) -> Vec3f32 {
+		let angle = (self.time - 0.25) * TAU;
+		let (sin, cos) = angle.sin_cos();
+		vec3(cos, sin * SUN_TILT.cos(), sin * SUN_TILT.sin())
+	}
+
+	/// the direction the sunlight travels in, see [`super::renderer::chunk::ChunkRenderer::set_sun_direction`].
+	pub fn sun_direction(&self) -> Vec4f32 {
+		let dir = -self.to_sun();
+		vec4(dir.x, dir.y, dir.z, 0.0)
+	}
+
+	/// 0 when the sun is high, 1 when it's at the horizon.
+	fn sunset(&self) -> f32 {
+		1.0 - (self.to_sun().y.abs() * 3.0).min(1.0)
+	}
+
+	/// the sun gets redder towards the horizon, its intensity is handled by the shaders.
+	pub fn sun_color(&self) -> Vec3f32 {
+		NOON_SUN_COLOR.lerp(SUNSET_SUN_COLOR, self.sunset())
+	}
+
+	/// tints the horizon (and so the fog) at sunrise and sunset.
+	pub fn horizon_color(&self, day_color: Vec3f32) -> Vec3f32 {
+		day_color.lerp(SUNSET_SKY_COLOR, self.sunset() * 0.8)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sun_moves_smoothly_around_a_day() {
+		let day_length = 120.0;
+		let mut time = TimeOfDay::new(0.0, day_length);
+		let start = time.to_sun();
+		let steps = 1000;
+		let mut last = start;
+		for _ in 0..steps {
+			time.advance(day_length / steps as f32);
+			let sun = time.to_sun();
+			assert!((sun.mag() - 1.0).abs() < 1e-4);
+			// a step is an angle of `TAU / steps`.
+			assert!((sun - last).mag() < TAU / steps as f32 * 1.01, "{last} -> {sun}");
+			last = sun;
+		}
+		// back where it started.
+		assert!((last - start).mag() < 1e-3, "{start} != {last}");
+
+		assert!(TimeOfDay::new(0.5, day_length).to_sun().y > 0.8);
+		assert!(TimeOfDay::new(0.0, day_length).to_sun().y < -0.8);
+		assert!(TimeOfDay::new(0.25, day_length).to_sun().y.abs() < 1e-4);
+		assert!((TimeOfDay::new(1.3, day_length).time() - 0.3).abs() < 1e-6);
+	}
+}
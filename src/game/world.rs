@@ -0,0 +1,342 @@
+use std::{collections::HashMap, path::PathBuf, io::{self, Read, Write}, fs, sync::{Arc, Mutex, mpsc}, thread};
+
+use crate::math::*;
+
+use super::chunk::{Block, ChunkData, CHUNK_BLOCK_COUNT};
+
+/// regions are `REGION_SIZE`³ chunks stored in a single file.
+const REGION_SIZE: i32 = 8;
+const REGION_CHUNK_COUNT: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+const CHUNK_FORMAT_VERSION: u8 = 1;
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// chunks that were saved to disk, in a world directory.
+/// only the blocks are saved, light is recomputed on load.
+/// saves are queued with [`World::queue_save`] and written a region at a time by [`World::flush`],
+/// usually on the [`ChunkSaver`] thread.
+///
+/// region file layout (all little-endian):
+/// ```text
+/// REGION_CHUNK_COUNT * (offset: u32, length: u32) // offset 0 means the chunk isn't saved.
+/// chunk data...
+/// ```
+pub struct World {
+	directory: PathBuf,
+	/// queued chunks that haven't been written yet, they're loaded from here instead of the region files.
+	unsaved: Mutex<HashMap<Vec3i32, Arc<ChunkData>>>,
+}
+
+impl World {
+	pub fn new(directory: impl Into<PathBuf>) -> Self {
+		Self { directory: directory.into(), unsaved: Mutex::new(HashMap::new()) }
+	}
+
+	fn region_of(chunk_position: Vec3i32) -> (Vec3i32, usize) {
+		let region = chunk_position.map(|c| c.div_euclid(REGION_SIZE));
+		let Vector([x, y, z]) = chunk_position.map(|c| c.rem_euclid(REGION_SIZE));
+		(region, ((y * REGION_SIZE + z) * REGION_SIZE + x) as usize)
+	}
+
+	fn region_path(&self, region: Vec3i32) -> PathBuf {
+		self.directory.join(format!("r.{}.{}.{}.bin", region.x, region.y, region.z))
+	}
+
+	/// a missing region file is an empty region.
+	fn read_region(&self, region: Vec3i32) -> io::Result<Vec<Option<Vec<u8>>>> {
+		let mut bytes = Vec::new();
+		match fs::File::open(self.region_path(region)) {
+			Ok(mut file) => { file.read_to_end(&mut bytes)?; },
+			Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![None; REGION_CHUNK_COUNT]),
+			Err(error) => return Err(error),
+		}
+
+		let header_size = REGION_CHUNK_COUNT * 8;
+		if bytes.len() < header_size {
+			return Err(invalid_data("region file is too short"));
+		}
+
+		(0..REGION_CHUNK_COUNT).map(|i| {
+			let offset = u32::from_le_bytes(bytes[i * 8..i * 8 + 4].try_into().unwrap()) as usize;
+			let length = u32::from_le_bytes(bytes[i * 8 + 4..i * 8 + 8].try_into().unwrap()) as usize;
+			if offset == 0 {
+				Ok(None)
+			} else {
+				bytes.get(offset..offset + length)
+					.map(|chunk| Some(chunk.to_vec()))
+					.ok_or_else(|| invalid_data("chunk is outside of the region file"))
+			}
+		}).collect()
+	}
+
+	fn write_region(&self, region: Vec3i32, chunks: &[Option<Vec<u8>>]) -> io::Result<()> {
+		let mut header = Vec::with_capacity(REGION_CHUNK_COUNT * 8);
+		let mut offset = REGION_CHUNK_COUNT * 8;
+		for chunk in chunks {
+			let (chunk_offset, length) = match chunk {
+				Some(chunk) => (offset, chunk.len()),
+				None => (0, 0),
+			};
+			header.extend_from_slice(&(chunk_offset as u32).to_le_bytes());
+			header.extend_from_slice(&(length as u32).to_le_bytes());
+			offset += length;
+		}
+
+		fs::create_dir_all(&self.directory)?;
+		// written to a temporary file first, so that the worldgen threads never see a partial region.
+		let path = self.region_path(region);
+		let temporary_path = path.with_extension("tmp");
+		{
+			let mut file = io::BufWriter::new(fs::File::create(&temporary_path)?);
+			file.write_all(&header)?;
+			for chunk in chunks.iter().flatten() {
+				file.write_all(chunk)?;
+			}
+			file.flush()?;
+		}
+		fs::rename(temporary_path, path)
+	}
+
+	/// the chunk is written by the next [`World::flush`], replacing an older queued save.
+	pub fn queue_save(&self, chunk_position: Vec3i32, data: Arc<ChunkData>) {
+		self.unsaved.lock().unwrap().insert(chunk_position, data);
+	}
+
+	/// writes the queued chunks, reading and writing every region file once.
+	/// the chunks of the regions that failed stay queued, so that they're tried again (and loaded from memory).
+	pub fn flush(&self) -> io::Result<()> {
+		let unsaved = self.unsaved.lock().unwrap().clone();
+		let mut regions: HashMap<Vec3i32, Vec<(usize, Vec3i32, Arc<ChunkData>)>> = HashMap::new();
+		for (chunk_position, data) in unsaved {
+			let (region, index) = Self::region_of(chunk_position);
+			regions.entry(region).or_default().push((index, chunk_position, data));
+		}
+
+		let mut result = Ok(());
+		for (region, chunks) in regions {
+			let written = self.read_region(region).and_then(|mut region_chunks| {
+				for (index, _, data) in &chunks {
+					region_chunks[*index] = Some(encode_chunk(data));
+				}
+				self.write_region(region, &region_chunks)
+			});
+
+			match written {
+				Ok(()) => {
+					let mut unsaved = self.unsaved.lock().unwrap();
+					for (_, chunk_position, data) in chunks {
+						// unless it was queued again while this was written.
+						if unsaved.get(&chunk_position).is_some_and(|queued| Arc::ptr_eq(queued, &data)) {
+							unsaved.remove(&chunk_position);
+						}
+					}
+				},
+				Err(error) => result = Err(error),
+			}
+		}
+		result
+	}
+
+	/// `Ok(None)` if the chunk was never saved.
+	pub fn load_chunk(&self, chunk_position: Vec3i32) -> io::Result<Option<ChunkData>> {
+		if let Some(data) = self.unsaved.lock().unwrap().get(&chunk_position) {
+			return Ok(Some((**data).clone()));
+		}
+
+		let (region, index) = Self::region_of(chunk_position);
+		let mut chunks = self.read_region(region)?;
+		chunks[index].take().map(|bytes| decode_chunk(&bytes)).transpose()
+	}
+}
+
+/// writes the queued chunks of a [`World`] on a background thread, so that saving doesn't hitch the main thread.
+/// the saves made while it's writing are batched into the next [`World::flush`].
+pub struct ChunkSaver {
+	requests: Option<mpsc::Sender<()>>,
+	worker: Option<thread::JoinHandle<()>>,
+}
+
+impl ChunkSaver {
+	pub fn new(world: Arc<World>) -> Self {
+		let (requests, request_receiver) = mpsc::channel::<()>();
+		let worker = thread::Builder::new()
+			.name("chunk saver".to_owned())
+			.spawn(move || {
+				while request_receiver.recv().is_ok() {
+					// everything queued so far is written by this flush.
+					while request_receiver.try_recv().is_ok() {}
+					if let Err(error) = world.flush() {
+						eprintln!("failed to save chunks: {}", error);
+					}
+				}
+				// the saver was dropped, write what's left before exiting.
+				if let Err(error) = world.flush() {
+					eprintln!("failed to save chunks: {}", error);
+				}
+			})
+			.expect("failed to spawn the chunk saver thread");
+		Self { requests: Some(requests), worker: Some(worker) }
+	}
+
+	/// writes the chunks queued with [`World::queue_save`] in the background.
+	pub fn flush(&self) {
+		// the thread only stops when this is dropped.
+		let _ = self.requests.as_ref().unwrap().send(());
+	}
+}
+
+impl Drop for ChunkSaver {
+	fn drop(&mut self) {
+		// closing the channel makes the thread write the rest and stop.
+		self.requests = None;
+		if let Some(worker) = self.worker.take() {
+			let _ = worker.join();
+		}
+	}
+}
+
+/// chunk layout (all little-endian):
+/// ```text
+/// version: u8
+/// palette length: u16, palette length * (id: u16, state: u16)
+/// runs of (count: u16, palette index: u16) until all blocks are covered
+/// ```
+fn encode_chunk(data: &ChunkData) -> Vec<u8> {
+	let mut palette: Vec<(u16, u16)> = Vec::new();
+	let mut runs: Vec<(u16, u16)> = Vec::new();
+
+	for block in data.blocks.iter() {
+		let key = ({ block.id }, { block.state });
+		let index = match palette.iter().position(|entry| *entry == key) {
+			Some(index) => index,
+			None => {
+				palette.push(key);
+				palette.len() - 1
+			}
+		} as u16;
+
+		match runs.last_mut() {
+			Some((count, last)) if *last == index && *count < u16::MAX => *count += 1,
+			_ => runs.push((1, index)),
+		}
+	}
+
+	let mut bytes = Vec::with_capacity(3 + palette.len() * 4 + runs.len() * 4);
+	bytes.push(CHUNK_FORMAT_VERSION);
+	bytes.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+	for (id, state) in palette {
+		bytes.extend_from_slice(&id.to_le_bytes());
+		bytes.extend_from_slice(&state.to_le_bytes());
+	}
+	for (count, index) in runs {
+		bytes.extend_from_slice(&count.to_le_bytes());
+		bytes.extend_from_slice(&index.to_le_bytes());
+	}
+	bytes
+}
+
+fn decode_chunk(bytes: &[u8]) -> io::Result<ChunkData> {
+	let mut words = bytes.get(1..)
+		.ok_or_else(|| invalid_data("empty chunk"))?
+		.chunks_exact(2)
+		.map(|word| u16::from_le_bytes([word[0], word[1]]));
+
+	if bytes[0] != CHUNK_FORMAT_VERSION {
+		return Err(invalid_data("unsupported chunk format version"));
+	}
+
+	let mut next = || words.next().ok_or_else(|| invalid_data("chunk data is truncated"));
+
+	let palette_length = next()?;
+	let palette = (0..palette_length)
+		.map(|_| Ok(Block { id: next()?, state: next()? }))
+		.collect::<io::Result<Vec<Block>>>()?;
+
+	let mut data = ChunkData::new();
+	let mut offset = 0;
+	while offset < CHUNK_BLOCK_COUNT {
+		let count = next()? as usize;
+		let block = *palette.get(next()? as usize).ok_or_else(|| invalid_data("palette index out of range"))?;
+		let end = offset + count;
+		if end > CHUNK_BLOCK_COUNT {
+			return Err(invalid_data("chunk has too many blocks"));
+		}
+		data.blocks[offset..end].fill(block);
+		offset = end;
+	}
+
+	Ok(data)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::game::chunk::BlockId;
+
+	/// an empty directory for a test's files, removed when it's dropped.
+	pub struct TestDirectory(pub PathBuf);
+
+	impl TestDirectory {
+		pub fn new(name: &str) -> Self {
+			let path = std::env::temp_dir().join(format!("voxle-test-{}-{}", name, std::process::id()));
+			let _ = fs::remove_dir_all(&path);
+			fs::create_dir_all(&path).unwrap();
+			Self(path)
+		}
+	}
+
+	impl Drop for TestDirectory {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	fn edited_chunk() -> ChunkData {
+		let mut data = ChunkData::new();
+		data.set_block(vec3(0, 0, 0), Block { id: BlockId::Stone as u16, state: 0 });
+		data.set_block(vec3(5, 6, 7), Block { id: BlockId::Log as u16, state: 3 });
+		data.set_block(vec3(31, 31, 31), Block { id: BlockId::Glass as u16, state: 0 });
+		data
+	}
+
+	fn same_blocks(a: &ChunkData, b: &ChunkData) -> bool {
+		a.blocks.iter().zip(b.blocks.iter()).all(|(a, b)| a == b)
+	}
+
+	#[test]
+	fn saved_chunks_reload() {
+		let directory = TestDirectory::new("saved-chunks-reload");
+		let world = World::new(&directory.0);
+		let data = edited_chunk();
+		// in the same region, and in another one.
+		world.queue_save(vec3(1, 2, 3), Arc::new(data.clone()));
+		world.queue_save(vec3(0, 0, 0), Arc::new(ChunkData::new()));
+		world.queue_save(vec3(-1, 0, 0), Arc::new(data.clone()));
+		world.flush().unwrap();
+		assert!(world.unsaved.lock().unwrap().is_empty());
+
+		let reopened = World::new(&directory.0);
+		assert!(same_blocks(&reopened.load_chunk(vec3(1, 2, 3)).unwrap().unwrap(), &data));
+		assert!(same_blocks(&reopened.load_chunk(vec3(-1, 0, 0)).unwrap().unwrap(), &data));
+		assert!(same_blocks(&reopened.load_chunk(vec3(0, 0, 0)).unwrap().unwrap(), &ChunkData::new()));
+		assert!(reopened.load_chunk(vec3(2, 2, 3)).unwrap().is_none());
+	}
+
+	#[test]
+	fn queued_chunks_load_before_they_are_written() {
+		let directory = TestDirectory::new("queued-chunks-load");
+		let world = Arc::new(World::new(&directory.0));
+		let data = edited_chunk();
+		world.queue_save(vec3(0, 0, 0), Arc::new(data.clone()));
+		assert!(same_blocks(&world.load_chunk(vec3(0, 0, 0)).unwrap().unwrap(), &data));
+		assert!(fs::read_dir(&directory.0).unwrap().next().is_none());
+
+		// dropping the saver writes the rest.
+		let saver = ChunkSaver::new(world.clone());
+		saver.flush();
+		drop(saver);
+		assert!(same_blocks(&World::new(&directory.0).load_chunk(vec3(0, 0, 0)).unwrap().unwrap(), &data));
+	}
+}
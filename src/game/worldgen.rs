@@ -1,6 +1,8 @@
-use noise::NoiseFn;
+use std::{collections::HashSet, sync::{Arc, Mutex, Condvar, mpsc}, thread};
+
+use noise::{NoiseFn, MultiFractal};
 use crate::math::*;
-use super::chunk::{self, CHUNK_SIZE, Block, BlockId};
+use super::{chunk::{self, CHUNK_SIZE, Block, BlockId}, world::World};
 
 
 // Process:
@@ -13,63 +15,903 @@ use super::chunk::{self, CHUNK_SIZE, Block, BlockId};
 // 7. block
 
 
+/// generates the chunks that haven't been saved yet, on the worldgen threads (see [`WorldGenQueue`]).
+pub trait WorldGenerator: Send + Sync {
+	/// `None` if the chunk shouldn't exist.
+	fn generate_chunk(&self, chunk_pos: Vec3i32) -> Option<chunk::Chunk>;
+
+	/// shows information about the terrain around `eye` in the debug ui.
+	fn debug_ui(&self, _ui: &mut egui::Ui, _eye: Vec3f32) {}
+}
+
+/// numbers are used as they are, other seeds are hashed (with fnv-1a),
+/// so that the same text always gives the same world.
+pub fn seed_from_str(seed: &str) -> u32 {
+	seed.trim().parse().unwrap_or_else(|_| {
+		seed.trim().bytes().fold(0x811c9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+	})
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+	let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+	t * t * (3.0 - 2.0 * t)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Biome {
+	Plains,
+	Forest,
+	Desert,
+	Tundra,
+}
+
+impl Biome {
+	/// the top block of the terrain, below the snow line.
+	fn surface_block(self) -> BlockId {
+		match self {
+			Self::Plains | Self::Forest => BlockId::Grass,
+			Self::Desert => BlockId::Sand,
+			Self::Tundra => BlockId::SnowGrass,
+		}
+	}
+
+	/// the few blocks under the surface block.
+	fn subsurface_block(self) -> BlockId {
+		match self {
+			Self::Desert => BlockId::Sand,
+			_ => BlockId::Dirt,
+		}
+	}
+
+	/// on average, one in this many columns has a tree, `None` if trees don't grow here.
+	fn tree_chance(self) -> Option<u32> {
+		match self {
+			Self::Plains => Some(400),
+			Self::Forest => Some(40),
+			Self::Desert => None,
+			Self::Tundra => Some(150),
+		}
+	}
+
+	/// multiplies the terrain height, see [`Climate::height_scale`].
+	fn height_scale(self) -> f64 {
+		match self {
+			Self::Plains => 0.7,
+			Self::Forest => 1.0,
+			Self::Desert => 0.5,
+			Self::Tundra => 1.0,
+		}
+	}
+}
+
+/// the climate of a column, biomes are picked from it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Climate {
+	/// roughly -1..1.
+	pub temperature: f64,
+	/// roughly -1..1.
+	pub humidity: f64,
+}
+
+impl Climate {
+	const HOT: f64 = 0.25;
+	const COLD: f64 = -0.25;
+	const DRY: f64 = 0.0;
+	const WET: f64 = 0.15;
+	/// how far from the thresholds the biomes blend into each other.
+	const BLEND: f64 = 0.1;
+
+	/// how much each biome applies, the weights add up to 1.
+	fn weights(self) -> [(Biome, f64); 4] {
+		let hot = smoothstep(Self::HOT - Self::BLEND, Self::HOT + Self::BLEND, self.temperature);
+		let cold = 1.0 - smoothstep(Self::COLD - Self::BLEND, Self::COLD + Self::BLEND, self.temperature);
+		let dry = 1.0 - smoothstep(Self::DRY - Self::BLEND, Self::DRY + Self::BLEND, self.humidity);
+		let wet = smoothstep(Self::WET - Self::BLEND, Self::WET + Self::BLEND, self.humidity);
+
+		// `hot` and `cold` don't overlap, so these don't go over 1.
+		let desert = hot * dry;
+		let tundra = cold;
+		let forest = (1.0 - desert - tundra) * wet;
+		let plains = 1.0 - desert - tundra - forest;
+		[(Biome::Plains, plains), (Biome::Forest, forest), (Biome::Desert, desert), (Biome::Tundra, tundra)]
+	}
+
+	/// the biome that applies the most.
+	pub fn biome(self) -> Biome {
+		self.weights().into_iter()
+			.max_by(|(_, a), (_, b)| a.total_cmp(b))
+			.map(|(biome, _)| biome)
+			.unwrap()
+	}
+
+	/// the biomes' height scales, blended so that there are no cliffs at the borders.
+	fn height_scale(self) -> f64 {
+		self.weights().into_iter().map(|(biome, weight)| biome.height_scale() * weight).sum()
+	}
+}
+
+/// veins of an ore, placed where its noise is above `threshold`.
+#[derive(Debug, Clone)]
+pub struct OreSpec {
+	pub block: BlockId,
+	/// the world heights the ore can be found at.
+	pub depth: std::ops::Range<i32>,
+	/// of the noise, higher values make smaller veins.
+	pub frequency: f64,
+	/// 0..1, higher values make the ore rarer.
+	pub threshold: f64,
+}
+
+/// the parts of the world that aren't picked by the seed.
+#[derive(Debug, Clone)]
+pub struct WorldGenConfig {
+	/// air at or below this height is filled with water, unless it's in a cave.
+	pub sea_level: i32,
+	/// the earlier ores win where veins overlap.
+	pub ores: Vec<OreSpec>,
+}
+
+impl Default for WorldGenConfig {
+	fn default() -> Self {
+		Self {
+			sea_level: 0,
+			ores: WorldGen::default_ores(),
+		}
+	}
+}
+
+/// a tree growing out of the surface, see [`WorldGen::tree_at`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tree {
+	/// the lowest block of the trunk, in world coordinates.
+	pub base: Vec3i32,
+	pub trunk_height: i32,
+}
+
+impl Tree {
+	/// how far the canopy reaches from the trunk, horizontally.
+	const CANOPY_RADIUS: i32 = 2;
+	const MIN_TRUNK_HEIGHT: i32 = 4;
+	const MAX_TRUNK_HEIGHT: i32 = 6;
+
+	/// calls `place` with the world position of every block of the tree.
+	fn blocks(&self, mut place: impl FnMut(Vec3i32, BlockId)) {
+		let top = self.base.y + self.trunk_height - 1;
+		// two wide layers around the top of the trunk, and two narrow ones at the top.
+		for dy in -2..=1 {
+			let radius = if dy < 0 { Self::CANOPY_RADIUS } else { 1 };
+			for dz in -radius..=radius {
+				for dx in -radius..=radius {
+					// leave out the corners, so that the canopy is rounder.
+					if dx.abs() == radius && dz.abs() == radius {
+						continue;
+					}
+					place(vec3(self.base.x + dx, top + dy, self.base.z + dz), BlockId::Leaves);
+				}
+			}
+		}
+
+		for y in 0..self.trunk_height {
+			place(self.base + vec3(0, y, 0), BlockId::Log);
+		}
+	}
+}
+
 pub struct WorldGen {
+	seed: u32,
+	sea_level: i32,
 	noise: noise::Fbm<noise::Perlin>,
-	noise2: noise::RidgedMulti<noise::Perlin>
+	noise2: noise::RidgedMulti<noise::Perlin>,
+	caves: noise::Fbm<noise::Perlin>,
+	temperature: noise::Perlin,
+	humidity: noise::Perlin,
+	/// with their own noise each, see [`WorldGen::ore_at`].
+	ores: Vec<(OreSpec, noise::Perlin)>,
 }
 
 impl WorldGen {
+	/// caves are carved where the cave noise is above this.
+	const CAVE_THRESHOLD: f64 = 0.3;
+	/// caves stay at least this many blocks below the surface, so they don't open up the ground.
+	const CAVE_MIN_DEPTH: i32 = 6;
+
+	/// columns this close to the sea level (or below it) are covered in sand.
+	const BEACH_HEIGHT: i32 = 2;
+
+	pub fn default_ores() -> Vec<OreSpec> {
+		vec![
+			OreSpec { block: BlockId::CoalOre, depth: -64..48, frequency: 0.15, threshold: 0.55 },
+			OreSpec { block: BlockId::IronOre, depth: -128..16, frequency: 0.18, threshold: 0.65 },
+			OreSpec { block: BlockId::GoldOre, depth: -256..-32, frequency: 0.2, threshold: 0.72 },
+		]
+	}
+
 	pub fn new(seed: u32) -> Self {
+		Self::with_ores(seed, Self::default_ores())
+	}
+
+	/// the earlier ores win where veins overlap.
+	pub fn with_ores(seed: u32, ores: Vec<OreSpec>) -> Self {
+		Self::with_config(seed, WorldGenConfig { ores, ..Default::default() })
+	}
+
+	pub fn with_config(seed: u32, config: WorldGenConfig) -> Self {
 		Self {
+			sea_level: config.sea_level,
+			ores: config.ores.into_iter()
+				.enumerate()
+				.map(|(i, ore)| (ore, noise::Perlin::new(seed.wrapping_add(4 + i as u32))))
+				.collect(),
+			seed,
 			noise: noise::Fbm::new(seed),
 			noise2: noise::RidgedMulti::new(seed),
+			// a different seed than the height field, so that the caves don't follow the terrain.
+			caves: noise::Fbm::new(seed.wrapping_add(1)).set_octaves(3),
+			temperature: noise::Perlin::new(seed.wrapping_add(2)),
+			humidity: noise::Perlin::new(seed.wrapping_add(3)),
 		}
 	}
 
-	fn get_height(&self, world_pos: Vec2i32) -> i32 {
+	pub fn seed(&self) -> u32 {
+		self.seed
+	}
+
+	pub fn sea_level(&self) -> i32 {
+		self.sea_level
+	}
+
+	/// whether the block at `world_pos` is carved out, `height` is the terrain height of its column.
+	fn is_cave(&self, world_pos: Vec3i32, height: i32) -> bool {
+		if world_pos.y > height - Self::CAVE_MIN_DEPTH {
+			return false;
+		}
+		// squashed vertically, so that the caves are wider than they are tall.
+		self.caves.get([
+			world_pos.x as f64 * 0.02,
+			world_pos.y as f64 * 0.04,
+			world_pos.z as f64 * 0.02,
+		]) > Self::CAVE_THRESHOLD
+	}
+
+	/// the ore at `world_pos`, if it's inside of a vein. only used where the terrain is stone.
+	fn ore_at(&self, world_pos: Vec3i32) -> Option<BlockId> {
+		self.ores.iter()
+			.filter(|(ore, _)| ore.depth.contains(&world_pos.y))
+			.find(|(ore, noise)| noise.get([
+				world_pos.x as f64 * ore.frequency,
+				world_pos.y as f64 * ore.frequency,
+				world_pos.z as f64 * ore.frequency,
+			]) > ore.threshold)
+			.map(|(ore, _)| ore.block)
+	}
+
+	/// a hash of the seed and the column, so that features don't depend on the order chunks are generated in.
+	fn column_hash(&self, column: Vec2i32) -> u32 {
+		// murmur3's finalizer.
+		let mut hash = self.seed
+			^ (column.x as u32).wrapping_mul(0x9e3779b1)
+			^ (column.y as u32).wrapping_mul(0x85ebca77);
+		hash ^= hash >> 16;
+		hash = hash.wrapping_mul(0x85ebca6b);
+		hash ^= hash >> 13;
+		hash = hash.wrapping_mul(0xc2b2ae35);
+		hash ^ (hash >> 16)
+	}
+
+	/// the climate of the column at `column` (world x and z).
+	pub fn climate_at(&self, column: Vec2i32) -> Climate {
+		let position = (column.each_as() * 0.0015).0;
+		Climate {
+			temperature: self.temperature.get(position),
+			humidity: self.humidity.get(position),
+		}
+	}
+
+	pub fn biome_at(&self, column: Vec2i32) -> Biome {
+		self.climate_at(column).biome()
+	}
+
+	/// the tree growing in the column at `column` (world x and z), if there is one.
+	pub fn tree_at(&self, column: Vec2i32) -> Option<Tree> {
+		let hash = self.column_hash(column);
+		let climate = self.climate_at(column);
+		let biome = climate.biome();
+		if biome.tree_chance().map_or(true, |chance| hash % chance != 0) {
+			return None;
+		}
+
+		// not on the snow on top of the mountains.
+		let height = self.get_height(column, climate);
+		if self.get_top_layer_block(height, height, biome).id != biome.surface_block() as u16 {
+			return None;
+		}
+
+		Some(Tree {
+			base: vec3(column.x, height + 1, column.y),
+			trunk_height: Tree::MIN_TRUNK_HEIGHT + (hash >> 16) as i32 % (Tree::MAX_TRUNK_HEIGHT - Tree::MIN_TRUNK_HEIGHT + 1),
+		})
+	}
+
+	fn get_height(&self, world_pos: Vec2i32, climate: Climate) -> i32 {
 		let h = self.noise.get((world_pos.each_as() * 0.001).0) * 64.0;
 		let h2 = self.noise2.get((world_pos.each_as() * 0.0005).0) * 128.0;
-		(h + h2) as i32
+		((h + h2) * climate.height_scale()) as i32
 	}
 
-	fn get_top_layer_block(&self, y: i32, height: i32) -> Block {
-		if y > height - 5 {
-			if y > 85 {
-				Block { id: BlockId::Snow as u16, state: 0 }
+	fn get_top_layer_block(&self, y: i32, height: i32, biome: Biome) -> Block {
+		let id = if y > height - 5 {
+			// beaches and sea floors.
+			if height <= self.sea_level + Self::BEACH_HEIGHT && y > height - 3 {
+				BlockId::Sand
+			// deserts don't get snow on top.
+			} else if y > 85 && biome != Biome::Desert {
+				BlockId::Snow
 			} else if y == height {
-				if y > 64 {
-					Block { id: BlockId::SnowGrass as u16, state: 0 }
+				if y > 64 && biome != Biome::Desert {
+					BlockId::SnowGrass
 				} else {
-					Block { id: BlockId::Grass as u16, state: 0 }
+					biome.surface_block()
 				}
 			} else {
-				Block { id: BlockId::Dirt as u16, state: 0 }
+				biome.subsurface_block()
 			}
 		} else {
-			Block { id: BlockId::Stone as u16, state: 0 }
-		}
+			BlockId::Stone
+		};
+		Block { id: id as u16, state: 0 }
 	}
 
-	pub fn generate_chunk(&self, chunk_pos: Vec3i32) -> Option<chunk::Chunk> {
-		let mut chunk = chunk::Chunk::new(chunk_pos, chunk::ChunkData::new());
+}
+
+impl WorldGenerator for WorldGen {
+	fn generate_chunk(&self, chunk_pos: Vec3i32) -> Option<chunk::Chunk> {
+		let mut data = chunk::ChunkData::new();
+		let chunk_origin = chunk_pos * CHUNK_SIZE.each_as();
+
+		// the columns of this chunk, and the ones around it that trees can reach into this chunk from.
+		let padding = Tree::CANOPY_RADIUS;
+		let padded_size = CHUNK_SIZE.xz().each_as::<i32>() + padding * 2;
+		let mut columns = Vec::with_capacity((padded_size.x * padded_size.y) as usize);
+		for z in -padding..CHUNK_SIZE.z as i32 + padding {
+			for x in -padding..CHUNK_SIZE.x as i32 + padding {
+				let world_pos = chunk_origin.xz() + vec2(x, z);
+				let climate = self.climate_at(world_pos);
+				columns.push((climate, self.get_height(world_pos, climate)));
+			}
+		}
+		let column = |x: i32, z: i32| columns[((z + padding) * padded_size.x + x + padding) as usize];
 
 		for z in 0..CHUNK_SIZE.z as i32 {
 			for x in 0..CHUNK_SIZE.x as i32 {
 				let local_pos = vec2(x, z);
-				let world_pos = chunk.position.xz() * CHUNK_SIZE.xz().each_as() + local_pos;
-				let height = self.get_height(world_pos);
+				let world_pos = chunk_origin.xz() + local_pos;
+				let (climate, height) = column(x, z);
+				let biome = climate.biome();
 				for y in 0..CHUNK_SIZE.y as i32 {
 					let local_pos = vec3(x, y, z);
-					let world_y = local_pos.y + chunk.position.y * CHUNK_SIZE.y as i32;
+					let world_y = local_pos.y + chunk_pos.y * CHUNK_SIZE.y as i32;
 					if world_y > height {
-						break
+						if world_y > self.sea_level {
+							break
+						}
+						data.set_block(local_pos, Block { id: BlockId::Water as u16, state: 0 });
+						continue
 					}
-					chunk.data.set_block(local_pos, self.get_top_layer_block(world_y, height));
+					if self.is_cave(vec3(world_pos.x, world_y, world_pos.y), height) {
+						continue
+					}
+					let mut block = self.get_top_layer_block(world_y, height, biome);
+					if block.id == BlockId::Stone as u16 {
+						if let Some(ore) = self.ore_at(vec3(world_pos.x, world_y, world_pos.y)) {
+							block.id = ore as u16;
+						}
+					}
+					data.set_block(local_pos, block);
 				}
 			}
 		}
 
-		Some(chunk)
+		// trees are placed after the terrain, and canopies can reach into this chunk from trees
+		// growing in the neighboring chunks. since the trees only depend on the seed, the neighbors
+		// place the parts of the tree that land in them on their own, no matter which is generated first.
+		// the trees go from just above the terrain to one block above the trunk, chunks that are entirely
+		// above or below that (most of them) can't have any.
+		let lowest = columns.iter().map(|&(_, height)| height).min().unwrap();
+		let highest = columns.iter().map(|&(_, height)| height).max().unwrap();
+		if chunk_origin.y > highest + 1 + Tree::MAX_TRUNK_HEIGHT || chunk_origin.y + (CHUNK_SIZE.y as i32) <= lowest + 1 {
+			return Some(chunk::Chunk::new(chunk_pos, data));
+		}
+		for z in -padding..CHUNK_SIZE.z as i32 + padding {
+			for x in -padding..CHUNK_SIZE.x as i32 + padding {
+				if let Some(tree) = self.tree_at(chunk_origin.xz() + vec2(x, z)) {
+					tree.blocks(|position, id| {
+						let local_pos = position - chunk_origin;
+						// leaves don't replace the terrain or other trees' trunks.
+						if data.get_block(local_pos).is_some_and(|old| id == BlockId::Log || old.id == BlockId::Air as u16) {
+							data.set_block(local_pos, Block { id: id as u16, state: 0 });
+						}
+					});
+				}
+			}
+		}
+
+		Some(chunk::Chunk::new(chunk_pos, data))
 	}
+
+	fn debug_ui(&self, ui: &mut egui::Ui, eye: Vec3f32) {
+		ui.label(format!("seed: {}", self.seed()));
+		ui.label(format!("sea level: {}", self.sea_level()));
+		ui.label(format!("biome: {:?}", self.biome_at(vec2(eye.x.floor() as i32, eye.z.floor() as i32))));
+	}
+}
+
+/// a level world made of horizontal layers, without any noise.
+pub struct FlatWorldGen {
+	/// the bottom of the lowest layer, there's only air under it.
+	pub bottom: i32,
+	/// from the bottom up, with their thickness in blocks.
+	pub layers: Vec<(BlockId, i32)>,
 }
 
+impl Default for FlatWorldGen {
+	/// bedrock at 0, dirt from 1 to 3, and grass at 4.
+	fn default() -> Self {
+		Self {
+			bottom: 0,
+			layers: vec![(BlockId::Bedrock, 1), (BlockId::Dirt, 3), (BlockId::Grass, 1)],
+		}
+	}
+}
+
+impl FlatWorldGen {
+	/// the block of the layer at the world height `y`, `None` if it's outside of the layers.
+	pub fn layer_at(&self, y: i32) -> Option<BlockId> {
+		let mut layer_bottom = self.bottom;
+		for &(id, thickness) in &self.layers {
+			if (layer_bottom..layer_bottom + thickness).contains(&y) {
+				return Some(id);
+			}
+			layer_bottom += thickness;
+		}
+		None
+	}
+}
+
+impl WorldGenerator for FlatWorldGen {
+	fn generate_chunk(&self, chunk_pos: Vec3i32) -> Option<chunk::Chunk> {
+		let mut data = chunk::ChunkData::new();
+
+		for y in 0..CHUNK_SIZE.y as i32 {
+			let id = match self.layer_at(y + chunk_pos.y * CHUNK_SIZE.y as i32) {
+				Some(id) => id,
+				None => continue,
+			};
+
+			for z in 0..CHUNK_SIZE.z as i32 {
+				for x in 0..CHUNK_SIZE.x as i32 {
+					data.set_block(vec3(x, y, z), Block { id: id as u16, state: 0 });
+				}
+			}
+		}
+
+		Some(chunk::Chunk::new(chunk_pos, data))
+	}
+}
+
+struct QueueState {
+	/// positions waiting for a worker, not including the ones being generated.
+	queued: HashSet<Vec3i32>,
+	/// queued positions closest to this are generated first.
+	center: Vec3i32,
+	stopped: bool,
+}
+
+struct SharedQueue {
+	state: Mutex<QueueState>,
+	available: Condvar,
+}
+
+impl SharedQueue {
+	/// blocks until there's a position to generate, returns `None` once the queue is stopped.
+	fn pop(&self) -> Option<Vec3i32> {
+		let mut state = self.state.lock().unwrap();
+		loop {
+			if state.stopped { return None }
+			let center = state.center;
+			// a linear scan, since the center moves and the queue is small (bounded by the render distance).
+			let nearest = state.queued.iter()
+				.copied()
+				.min_by_key(|position| ((*position - center).mag_squared(), position.0));
+			if let Some(position) = nearest {
+				state.queued.remove(&position);
+				return Some(position);
+			}
+			state = self.available.wait(state).unwrap();
+		}
+	}
+}
+
+/// loads the saved chunk, or generates it if it wasn't saved (or couldn't be loaded).
+pub fn load_or_generate_chunk(world: &World, worldgen: &dyn WorldGenerator, position: Vec3i32) -> Option<chunk::Chunk> {
+	match world.load_chunk(position) {
+		Ok(Some(data)) => Some(chunk::Chunk::new(position, data)),
+		Ok(None) => worldgen.generate_chunk(position),
+		Err(error) => {
+			eprintln!("failed to load chunk {:?}, regenerating it: {}", position.0, error);
+			worldgen.generate_chunk(position)
+		}
+	}
+}
+
+/// loads (or generates, if they weren't saved) chunks on background threads, nearest to the center first.
+/// the generated chunks are collected on the main thread with [`WorldGenQueue::receive`].
+pub struct WorldGenQueue {
+	shared: Arc<SharedQueue>,
+	results: mpsc::Receiver<(Vec3i32, Option<chunk::Chunk>)>,
+	/// queued, being generated or in `ready`, used to dedupe and to drop cancelled results.
+	pending: HashSet<Vec3i32>,
+	/// generated, but over the budget of [`WorldGenQueue::receive`] so far.
+	ready: Vec<(Vec3i32, Option<chunk::Chunk>)>,
+	/// the same as the workers', to hand out the nearest `ready` chunks first.
+	center: Vec3i32,
+	workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl WorldGenQueue {
+	const WORKER_COUNT: usize = 2;
+	/// generated chunks that haven't been received yet, the workers wait when this is full.
+	const RESULT_CAPACITY: usize = 64;
+
+	pub fn new(worldgen: Arc<dyn WorldGenerator>, world: Arc<World>) -> Self {
+		let shared = Arc::new(SharedQueue {
+			state: Mutex::new(QueueState {
+				queued: HashSet::new(),
+				center: vec3(0, 0, 0),
+				stopped: false,
+			}),
+			available: Condvar::new(),
+		});
+		let (result_sender, results) = mpsc::sync_channel(Self::RESULT_CAPACITY);
+
+		let workers = (0..Self::WORKER_COUNT).map(|i| {
+			let shared = shared.clone();
+			let result_sender = result_sender.clone();
+			let worldgen = worldgen.clone();
+			let world = world.clone();
+			thread::Builder::new()
+				.name(format!("worldgen {}", i))
+				.spawn(move || {
+					while let Some(position) = shared.pop() {
+						let chunk = load_or_generate_chunk(&world, &*worldgen, position);
+						if result_sender.send((position, chunk)).is_err() {
+							break;
+						}
+					}
+				})
+				.expect("failed to spawn a worldgen thread")
+		}).collect();
+
+		Self { shared, results, pending: HashSet::new(), ready: Vec::new(), center: vec3(0, 0, 0), workers }
+	}
+
+	/// does nothing if the position is already queued or being generated.
+	pub fn push(&mut self, position: Vec3i32) {
+		if !self.pending.insert(position) { return }
+		self.shared.state.lock().unwrap().queued.insert(position);
+		self.shared.available.notify_one();
+	}
+
+	pub fn set_center(&mut self, center: Vec3i32) {
+		self.center = center;
+		self.shared.state.lock().unwrap().center = center;
+	}
+
+	/// cancels the positions for which `keep` returns false.
+	/// chunks that are already being generated are dropped in [`WorldGenQueue::receive`].
+	pub fn retain(&mut self, mut keep: impl FnMut(Vec3i32) -> bool) {
+		self.pending.retain(|position| keep(*position));
+		let pending = &self.pending;
+		self.ready.retain(|(position, _)| pending.contains(position));
+		self.shared.state.lock().unwrap().queued.retain(|position| pending.contains(position));
+	}
+
+	/// returns up to `budget` of the generated chunks that weren't cancelled, the nearest first, without blocking.
+	/// the rest are kept for the next calls, so that a lot of chunks finishing at once doesn't cause a spike.
+	pub fn receive(&mut self, budget: usize) -> Vec<(Vec3i32, Option<chunk::Chunk>)> {
+		for (position, chunk) in self.results.try_iter() {
+			if self.pending.contains(&position) {
+				self.ready.push((position, chunk));
+			}
+		}
+
+		let center = self.center;
+		// furthest first, so that the nearest are split off the end.
+		self.ready.sort_by_key(|(position, _)| std::cmp::Reverse(((*position - center).mag_squared(), position.0)));
+		let generated = self.ready.split_off(self.ready.len().saturating_sub(budget));
+		for (position, _) in &generated {
+			self.pending.remove(position);
+		}
+		generated
+	}
+
+	/// number of positions that are queued, being generated or waiting to be received.
+	pub fn pending(&self) -> usize {
+		self.pending.len()
+	}
+
+	/// number of generated chunks that are waiting to be received, see [`WorldGenQueue::receive`].
+	pub fn ready(&self) -> usize {
+		self.ready.len()
+	}
+}
+
+impl Drop for WorldGenQueue {
+	fn drop(&mut self) {
+		self.shared.state.lock().unwrap().stopped = true;
+		self.shared.available.notify_all();
+		// unblock workers that are waiting for space in the result channel.
+		while self.results.try_recv().is_ok() {}
+		for worker in self.workers.drain(..) {
+			// a worker may still be blocked on a full channel, keep draining.
+			while !worker.is_finished() {
+				while self.results.try_recv().is_ok() {}
+				thread::yield_now();
+			}
+			let _ = worker.join();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use super::*;
+	use crate::game::chunk::{block_global_to_chunk, block_global_to_block_local};
+
+	const SEED: u32 = 69;
+
+	/// the block at the world position `position`, generating its chunk into `chunks` if it wasn't yet.
+	fn block_at(worldgen: &dyn WorldGenerator, chunks: &mut HashMap<Vec3i32, chunk::Chunk>, position: Vec3i32) -> u16 {
+		let chunk_pos = block_global_to_chunk(position);
+		let chunk = chunks.entry(chunk_pos).or_insert_with(|| worldgen.generate_chunk(chunk_pos).unwrap());
+		chunk.data.get_block(block_global_to_block_local(position)).unwrap().id
+	}
+
+	fn same_blocks(a: &chunk::Chunk, b: &chunk::Chunk) -> bool {
+		a.data.blocks.iter().zip(b.data.blocks.iter()).all(|(a, b)| a == b)
+	}
+
+	fn shared_queue(center: Vec3i32, queued: &[Vec3i32]) -> SharedQueue {
+		SharedQueue {
+			state: Mutex::new(QueueState { queued: queued.iter().copied().collect(), center, stopped: false }),
+			available: Condvar::new(),
+		}
+	}
+
+	#[test]
+	fn queue_pops_the_nearest_first() {
+		let queue = shared_queue(vec3(4, 0, 0), &[vec3(0, 0, 0), vec3(4, 1, 0), vec3(9, 0, 0), vec3(4, 0, 0), vec3(6, 0, 0)]);
+		let order: Vec<Vec3i32> = std::iter::from_fn(|| {
+			let empty = queue.state.lock().unwrap().queued.is_empty();
+			if empty { None } else { queue.pop() }
+		}).collect();
+		assert_eq!(order, [vec3(4, 0, 0), vec3(4, 1, 0), vec3(6, 0, 0), vec3(0, 0, 0), vec3(9, 0, 0)]);
+
+		queue.state.lock().unwrap().stopped = true;
+		assert_eq!(queue.pop(), None);
+	}
+
+	#[test]
+	fn queue_dedupes_positions() {
+		// nothing is saved in it, so every chunk is generated.
+		let world = Arc::new(World::new(std::env::temp_dir().join("voxle-test-unsaved-world")));
+		let mut queue = WorldGenQueue::new(Arc::new(FlatWorldGen::default()), world);
+		queue.push(vec3(0, 0, 0));
+		queue.push(vec3(0, 0, 0));
+		queue.push(vec3(1, 0, 0));
+		assert_eq!(queue.pending(), 2);
+
+		let mut received = Vec::new();
+		let start = std::time::Instant::now();
+		while queue.pending() > 0 {
+			assert!(start.elapsed() < std::time::Duration::from_secs(10), "the worldgen threads didn't finish");
+			received.extend(queue.receive(usize::MAX).into_iter().map(|(position, _)| position));
+			thread::yield_now();
+		}
+		received.sort_by_key(|position| position.0);
+		assert_eq!(received, [vec3(0, 0, 0), vec3(1, 0, 0)]);
+	}
+
+	#[test]
+	fn trees_are_placed_across_chunk_borders() {
+		let worldgen = WorldGen::new(SEED);
+		// a tree on the last column of a chunk, so that its canopy reaches into the next one.
+		let tree = (-20..20)
+			.flat_map(|chunk_x| (0..256).map(move |z| vec2(chunk_x * CHUNK_SIZE.x as i32 + CHUNK_SIZE.x as i32 - 1, z)))
+			.find_map(|column| worldgen.tree_at(column))
+			.expect("no tree on a chunk border");
+		assert_eq!(WorldGen::new(SEED).tree_at(tree.base.xz()), Some(tree));
+
+		let mut chunks = HashMap::new();
+		assert_eq!(block_at(&worldgen, &mut chunks, tree.base), BlockId::Log as u16);
+		// the top of the canopy, it's still placed if it's in the chunk above the terrain.
+		assert_eq!(block_at(&worldgen, &mut chunks, tree.base + vec3(0, tree.trunk_height, 0)), BlockId::Leaves as u16);
+		let mut leaves = HashSet::new();
+		tree.blocks(|position, id| {
+			if id == BlockId::Leaves && block_at(&worldgen, &mut chunks, position) == BlockId::Leaves as u16 {
+				leaves.insert(block_global_to_chunk(position).x);
+			}
+		});
+		let chunk_x = block_global_to_chunk(tree.base).x;
+		assert_eq!(leaves, HashSet::from([chunk_x, chunk_x + 1]), "the canopy should be in both chunks");
+
+		// generated on their own, the chunks are the same.
+		for (position, chunk) in &chunks {
+			assert!(same_blocks(chunk, &WorldGen::new(SEED).generate_chunk(*position).unwrap()));
+		}
+	}
+
+	#[test]
+	fn caves_stay_under_the_surface() {
+		let worldgen = WorldGen::new(SEED);
+		let mut chunks = HashMap::new();
+		for z in 0..CHUNK_SIZE.z as i32 {
+			for x in 0..CHUNK_SIZE.x as i32 {
+				let column = vec2(x, z);
+				let height = worldgen.get_height(column, worldgen.climate_at(column));
+				for y in height - WorldGen::CAVE_MIN_DEPTH + 1..=height {
+					assert_ne!(block_at(&worldgen, &mut chunks, vec3(x, y, z)), BlockId::Air as u16, "a cave breached the surface at {:?}", (x, y, z));
+				}
+			}
+		}
+
+		// deep enough, there are caves.
+		let mut caves = 0;
+		for z in (0..512).step_by(16) {
+			for y in -128..-64 {
+				for x in (0..512).step_by(16) {
+					caves += worldgen.is_cave(vec3(x, y, z), 0) as usize;
+				}
+			}
+		}
+		assert!(caves > 0);
+
+		let again = WorldGen::new(SEED);
+		for (position, chunk) in &chunks {
+			assert!(same_blocks(chunk, &again.generate_chunk(*position).unwrap()));
+		}
+	}
+
+	#[test]
+	fn biomes_only_depend_on_the_seed_and_column() {
+		let (a, b) = (WorldGen::new(SEED), WorldGen::new(SEED));
+		let mut seen = HashSet::new();
+		for z in (-4096..4096).step_by(256) {
+			for x in (-4096..4096).step_by(256) {
+				let column = vec2(x, z);
+				let biome = a.biome_at(column);
+				assert_eq!(biome, b.biome_at(column));
+				// generating chunks in between doesn't change it.
+				assert_eq!(biome, a.biome_at(column));
+				seen.insert(format!("{:?}", biome));
+
+				let weights = a.climate_at(column).weights();
+				assert!((weights.iter().map(|(_, weight)| weight).sum::<f64>() - 1.0).abs() < 1e-9);
+				if biome == Biome::Desert {
+					assert_eq!(a.tree_at(column), None);
+				}
+			}
+			a.generate_chunk(vec3(z / 256, 0, 0));
+		}
+		assert!(seen.len() > 1, "the whole area is one biome");
+	}
+
+	#[test]
+	fn ores_replace_stone_in_their_depth_range() {
+		let config = || WorldGenConfig {
+			ores: vec![OreSpec { block: BlockId::IronOre, depth: -40..-8, frequency: 0.2, threshold: 0.3 }],
+			..Default::default()
+		};
+		let worldgen = WorldGen::with_config(SEED, config());
+		let again = WorldGen::with_config(SEED, config());
+
+		let mut ores = 0;
+		for chunk_y in -2..=0 {
+			let chunk = worldgen.generate_chunk(vec3(0, chunk_y, 0)).unwrap();
+			assert!(same_blocks(&chunk, &again.generate_chunk(vec3(0, chunk_y, 0)).unwrap()));
+			for z in 0..CHUNK_SIZE.z as i32 {
+				for x in 0..CHUNK_SIZE.x as i32 {
+					let column = vec2(x, z);
+					let climate = worldgen.climate_at(column);
+					let height = worldgen.get_height(column, climate);
+					for y in 0..CHUNK_SIZE.y as i32 {
+						if chunk.data.get_block(vec3(x, y, z)).unwrap().id != BlockId::IronOre as u16 {
+							continue;
+						}
+						ores += 1;
+						let world_pos = vec3(x, y + chunk_y * CHUNK_SIZE.y as i32, z);
+						assert!((-40..-8).contains(&world_pos.y), "ore outside of its depth range at {:?}", world_pos.0);
+						assert!(!worldgen.is_cave(world_pos, height));
+						assert!(worldgen.get_top_layer_block(world_pos.y, height, climate.biome()).id == BlockId::Stone as u16, "ore outside of stone at {:?}", world_pos.0);
+					}
+				}
+			}
+		}
+		assert!(ores > 0);
+	}
+
+	#[test]
+	fn text_seeds_are_hashed() {
+		assert_eq!(seed_from_str("1234"), 1234);
+		assert_eq!(seed_from_str(" 1234 "), 1234);
+		assert_eq!(seed_from_str("glacier"), seed_from_str("glacier"));
+		assert_eq!(seed_from_str("glacier"), seed_from_str(" glacier\n"));
+		assert_ne!(seed_from_str("glacier"), seed_from_str("Glacier"));
+
+		let first_chunk = |seed: &str| WorldGen::new(seed_from_str(seed)).generate_chunk(vec3(0, 0, 0)).unwrap();
+		assert!(same_blocks(&first_chunk("glacier"), &first_chunk("glacier")));
+		assert!(!same_blocks(&first_chunk("glacier"), &first_chunk("volcano")));
+	}
+
+	#[test]
+	fn water_fills_up_to_the_sea_level() {
+		// the terrain around the origin is a slope, partly above and partly below this.
+		let sea_level = 56;
+		let worldgen = WorldGen::with_config(SEED, WorldGenConfig { sea_level, ..Default::default() });
+		assert_eq!(worldgen.sea_level(), sea_level);
+
+		let chunk_y = sea_level.div_euclid(CHUNK_SIZE.y as i32);
+		let bottom = chunk_y * CHUNK_SIZE.y as i32;
+		let mut flooded = 0;
+		for chunk_x in 0..4 {
+			let chunk = worldgen.generate_chunk(vec3(chunk_x, chunk_y, 0)).unwrap();
+			for z in 0..CHUNK_SIZE.z as i32 {
+				for x in 0..CHUNK_SIZE.x as i32 {
+					let column = vec2(x + chunk_x * CHUNK_SIZE.x as i32, z);
+					let height = worldgen.get_height(column, worldgen.climate_at(column));
+					if height >= sea_level {
+						continue;
+					}
+					flooded += 1;
+					let block = |y: i32| chunk.data.get_block(vec3(x, y - bottom, z)).unwrap().id;
+					for y in height.max(bottom - 1) + 1..=sea_level {
+						assert_eq!(block(y), BlockId::Water as u16, "no water at {:?}", (column.x, y, column.y));
+					}
+					assert_eq!(block(sea_level + 1), BlockId::Air as u16, "water above the sea level at {:?}", column.0);
+				}
+			}
+		}
+		assert!(flooded > 0, "all of the terrain is above the sea level");
+	}
+
+	#[test]
+	fn flat_worlds_are_solid_below_the_surface() {
+		let worldgen: Box<dyn WorldGenerator> = Box::new(FlatWorldGen {
+			bottom: -96,
+			layers: vec![(BlockId::Stone, 96), (BlockId::Grass, 1)],
+		});
+		let all = |chunk_pos: Vec3i32, id: BlockId| {
+			worldgen.generate_chunk(chunk_pos).unwrap().data.blocks.iter().all(|block| block.id == id as u16)
+		};
+		for chunk_pos in [vec3(0, -1, 0), vec3(-3, -2, 7), vec3(5, -3, -5)] {
+			assert!(all(chunk_pos, BlockId::Stone), "{:?} isn't solid", chunk_pos.0);
+		}
+		assert!(all(vec3(0, -4, 0), BlockId::Air));
+		assert!(all(vec3(0, 1, 0), BlockId::Air));
+	}
+
+	#[test]
+	fn flat_worlds_are_level_across_chunks() {
+		let worldgen = FlatWorldGen::default();
+		let mut chunks = HashMap::new();
+		for z in -(CHUNK_SIZE.z as i32)..CHUNK_SIZE.z as i32 {
+			for x in -(CHUNK_SIZE.x as i32)..CHUNK_SIZE.x as i32 {
+				let column: Vec<u16> = (-1..=6).map(|y| block_at(&worldgen, &mut chunks, vec3(x, y, z))).collect();
+				assert_eq!(column, [
+					BlockId::Air, BlockId::Bedrock, BlockId::Dirt, BlockId::Dirt, BlockId::Dirt, BlockId::Grass, BlockId::Air, BlockId::Air,
+				].map(|id| id as u16), "the column at {:?} isn't level", (x, z));
+			}
+		}
+	}
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use gilrs::{Axis, Button, EventType, Gilrs};
+use winit::event::ElementState;
+
+use crate::{InputElementState, math::*};
+
+/// stick deflection below this is ignored, controllers rarely rest at exactly zero.
+const STICK_DEADZONE: f32 = 0.15;
+
+/// maps a raw stick position to a delta with a radial deadzone, rescaled so
+/// that the output still goes smoothly from 0 (at the deadzone) to 1 (at full deflection).
+pub fn stick_to_delta(stick: Vec2f32, deadzone: f32) -> Vec2f32 {
+	let magnitude = stick.mag();
+	if magnitude <= deadzone {
+		return vec2(0.0, 0.0);
+	}
+	let scaled = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0);
+	stick / magnitude * scaled
+}
+
+/// the state of all connected gamepads, merged together.
+/// if gilrs fails to initialize (or nothing is connected) everything just stays at rest.
+pub struct Gamepad {
+	gilrs: Option<Gilrs>,
+	buttons: HashMap<Button, InputElementState>,
+	/// raw stick positions, y is up.
+	left_stick: Vec2f32,
+	right_stick: Vec2f32,
+}
+
+impl Gamepad {
+	pub fn new() -> Self {
+		let gilrs = match Gilrs::new() {
+			Ok(gilrs) => Some(gilrs),
+			Err(error) => {
+				eprintln!("gamepad support is disabled: {}", error);
+				None
+			}
+		};
+
+		Self {
+			gilrs,
+			buttons: HashMap::new(),
+			left_stick: vec2(0.0, 0.0),
+			right_stick: vec2(0.0, 0.0),
+		}
+	}
+
+	/// processes the events since the last call, should be called once per frame.
+	pub fn poll(&mut self) {
+		let Some(gilrs) = &mut self.gilrs else { return };
+		while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+			match event {
+				EventType::ButtonPressed(button, _) => {
+					let old = self.buttons.get(&button).copied().unwrap_or(InputElementState::None);
+					self.buttons.insert(button, InputElementState::from_old_new(old, ElementState::Pressed));
+				},
+				EventType::ButtonReleased(button, _) => {
+					let old = self.buttons.get(&button).copied().unwrap_or(InputElementState::None);
+					self.buttons.insert(button, InputElementState::from_old_new(old, ElementState::Released));
+				},
+				EventType::AxisChanged(axis, value, _) => match axis {
+					Axis::LeftStickX => self.left_stick.0[0] = value,
+					Axis::LeftStickY => self.left_stick.0[1] = value,
+					Axis::RightStickX => self.right_stick.0[0] = value,
+					Axis::RightStickY => self.right_stick.0[1] = value,
+					_ => {}
+				},
+				EventType::Disconnected => {
+					// otherwise a stick or button could stay stuck.
+					self.buttons.clear();
+					self.left_stick = vec2(0.0, 0.0);
+					self.right_stick = vec2(0.0, 0.0);
+				},
+				_ => {}
+			}
+		}
+	}
+
+	pub fn reset_deltas(&mut self) {
+		for button in self.buttons.values_mut() {
+			*button = button.next_frame();
+		}
+	}
+
+	pub fn button(&self, button: Button) -> InputElementState {
+		self.buttons.get(&button).copied().unwrap_or(InputElementState::None)
+	}
+
+	/// x is right, y is forward, in -1..1.
+	pub fn movement(&self) -> Vec2f32 {
+		stick_to_delta(self.left_stick, STICK_DEADZONE)
+	}
+
+	/// x is right, y is up, in -1..1.
+	pub fn look(&self) -> Vec2f32 {
+		stick_to_delta(self.right_stick, STICK_DEADZONE)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_close(a: Vec2f32, b: Vec2f32) {
+		assert!((a - b).mag() < 1e-5, "{a} != {b}");
+	}
+
+	#[test]
+	fn sticks_map_to_deltas() {
+		assert_close(stick_to_delta(vec2(0.1, 0.1), 0.15), vec2(0.0, 0.0));
+		assert_close(stick_to_delta(vec2(0.0, -1.0), 0.15), vec2(0.0, -1.0));
+		// halfway between the deadzone and full deflection, in the same direction.
+		assert_close(stick_to_delta(vec2(0.6f32, 0.8) * 0.575, 0.15), vec2(0.6f32, 0.8) * 0.5);
+		// the corners of square gates go past 1.
+		assert_close(stick_to_delta(vec2(1.0, 1.0), 0.15), vec2(1.0, 1.0) / 2f32.sqrt());
+	}
+}
@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use std::{borrow::Cow, marker::PhantomData};
 
 use wgpu::util::DeviceExt;
 
@@ -8,10 +8,18 @@ use self::graph::GraphRenderContext;
 
 pub struct Gfx {
 	pub surface: wgpu::Surface,
+	pub adapter: wgpu::Adapter,
 	pub device: wgpu::Device,
 	pub queue: wgpu::Queue,
 	pub config: wgpu::SurfaceConfiguration,
 	pub size: winit::dpi::PhysicalSize<u32>,
+	/// the present modes the surface supports, see [`Gfx::set_present_mode`].
+	pub present_modes: Vec<wgpu::PresentMode>,
+	pub profiler: profiler::GpuProfiler,
+	/// where the next frame is saved, see [`Gfx::capture_frame`].
+	capture_path: Option<std::path::PathBuf>,
+	/// the frame that is being read back, it's saved in a later frame once it's mapped.
+	pending_capture: Option<PendingCapture>,
 	pub egui_renderpass: egui_wgpu_backend::RenderPass,
 	pub egui_platform: polyfill::winit_egui::Platform,
 
@@ -23,6 +31,59 @@ pub struct Gfx {
 	pub window: Window,
 }
 
+/// a frame that was copied into `buffer`, see [`Gfx::copy_capture`].
+struct PendingCapture {
+	path: std::path::PathBuf,
+	buffer: wgpu::Buffer,
+	padded_row_bytes: u32,
+	/// of the surface when the frame was rendered.
+	size: (u32, u32),
+	format: wgpu::TextureFormat,
+	/// set by the buffer's `map_async` callback.
+	mapped: std::sync::Arc<std::sync::Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>,
+}
+
+/// the frame in `padded` as an rgba image, the surface's `format` has to be 8 bit rgba or bgra.
+fn capture_image(padded: &[u8], padded_row_bytes: u32, (width, height): (u32, u32), format: wgpu::TextureFormat) -> image::ImageResult<image::RgbaImage> {
+	let swap_red_blue = match format {
+		wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+		wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+		format => {
+			return Err(image::ImageError::Unsupported(image::error::UnsupportedError::from_format_and_kind(
+				image::error::ImageFormatHint::Unknown,
+				image::error::UnsupportedErrorKind::GenericFeature(format!("surface format {:?}", format)),
+			)));
+		}
+	};
+
+	let mut pixels = remove_row_padding(padded, width as usize * 4, padded_row_bytes as usize, height as usize);
+	if swap_red_blue {
+		for pixel in pixels.chunks_mut(4) {
+			pixel.swap(0, 2);
+		}
+	}
+	Ok(image::RgbaImage::from_raw(width, height, pixels).unwrap())
+}
+
+/// copies `height` rows of `row_bytes` bytes out of `padded` rows of `padded_row_bytes`,
+/// since buffer copies need the rows aligned to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+fn remove_row_padding(padded: &[u8], row_bytes: usize, padded_row_bytes: usize, height: usize) -> Vec<u8> {
+	let mut tight = Vec::with_capacity(row_bytes * height);
+	for row in padded.chunks(padded_row_bytes).take(height) {
+		tight.extend_from_slice(&row[..row_bytes]);
+	}
+	tight
+}
+
+/// `Fifo` is always supported, so it's the fallback.
+fn choose_present_mode(preferred: wgpu::PresentMode, available: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+	if available.contains(&preferred) {
+		preferred
+	} else {
+		wgpu::PresentMode::Fifo
+	}
+}
+
 impl Gfx {
 	pub async fn new(window: Window) -> Self {
 		let size = window.window.inner_size();
@@ -45,7 +106,11 @@ impl Gfx {
 		
 		let (device, queue) = adapter.request_device(
 			&wgpu::DeviceDescriptor {
-				features: wgpu::Features::PUSH_CONSTANTS | wgpu::Features::POLYGON_MODE_LINE,
+				// timestamp queries are only used by the profiler, if they're supported.
+				features: wgpu::Features::PUSH_CONSTANTS | wgpu::Features::POLYGON_MODE_LINE
+					| (adapter.features() & wgpu::Features::TIMESTAMP_QUERY)
+					// the chunks are drawn with one indirect draw per pass when these are supported.
+					| (adapter.features() & (wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE)),
 				// WebGL doesn't support all of wgpu's features, so if
 				// we're building for the web we'll have to disable some.
 				limits: if cfg!(target_arch = "wasm32") {
@@ -69,11 +134,12 @@ impl Gfx {
 			.unwrap_or(surface_caps.formats[0]);
 		
 		let config = wgpu::SurfaceConfiguration {
-			usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+			// copying from the surface is only needed for screenshots, see `Gfx::capture_frame`.
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
 			format: surface_format,
 			width: size.width,
 			height: size.height,
-			present_mode: surface_caps.present_modes[0],
+			present_mode: choose_present_mode(wgpu::PresentMode::Fifo, &surface_caps.present_modes),
 			alpha_mode: surface_caps.alpha_modes[0],
 			view_formats: vec![],
 		};
@@ -89,6 +155,7 @@ impl Gfx {
 		});
 
 		let egui_renderpass = egui_wgpu_backend::RenderPass::new(&device, config.format, 1);
+		let profiler = profiler::GpuProfiler::new(&device, &queue);
 
 		// let font = wgpu_text::glyph_brush::ab_glyph::FontArc::try_from_slice(include_bytes!("Hack-Regular.ttf")).unwrap();
 		// let text_brush = wgpu_text::BrushBuilder::using_font(font).build(
@@ -101,10 +168,15 @@ impl Gfx {
 		Self {
 			window,
 			surface,
+			adapter,
 			device,
 			queue,
 			config,
 			size,
+			present_modes: surface_caps.present_modes,
+			profiler,
+			capture_path: None,
+			pending_capture: None,
 			// text_brush,
 			egui_platform,
 			egui_renderpass
@@ -120,7 +192,113 @@ impl Gfx {
 	}
 
 	pub fn size(&self) -> winit::dpi::PhysicalSize<u32> { self.size }
+
+	/// uses `preferred` if the surface supports it, and `Fifo` (vsync) otherwise.
+	/// the mode that was picked is kept when the surface is resized.
+	pub fn set_present_mode(&mut self, preferred: wgpu::PresentMode) -> wgpu::PresentMode {
+		let present_mode = choose_present_mode(preferred, &self.present_modes);
+		if present_mode != self.config.present_mode {
+			self.config.present_mode = present_mode;
+			self.surface.configure(&self.device, &self.config);
+		}
+		present_mode
+	}
+
+	/// whether textures of `format` can be rendered to with `samples` samples per pixel.
+	pub fn supports_sample_count(&self, format: wgpu::TextureFormat, samples: u32) -> bool {
+		let features = if self.device.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+			self.adapter.get_texture_format_features(format)
+		} else {
+			format.guaranteed_format_features(self.device.features())
+		};
+		features.flags.sample_count_supported(samples)
+	}
 	
+	/// saves the next rendered frame (with the debug ui) as a png at `path`.
+	pub fn capture_frame(&mut self, path: impl Into<std::path::PathBuf>) {
+		self.capture_path = Some(path.into());
+	}
+
+	/// copies `texture` into a buffer, to be mapped with [`Gfx::read_capture`] after submitting `encoder`.
+	/// `None` if the surface can't be copied from.
+	fn copy_capture(&self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture, path: std::path::PathBuf) -> Option<PendingCapture> {
+		if !self.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+			eprintln!("can't take a screenshot, the surface doesn't support copies");
+			return None;
+		}
+
+		let block_size = self.config.format.block_size(None).unwrap();
+		let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+		let padded_row_bytes = (self.config.width * block_size + alignment - 1) / alignment * alignment;
+		let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Capture Buffer"),
+			size: padded_row_bytes as u64 * self.config.height as u64,
+			usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		encoder.copy_texture_to_buffer(
+			texture.as_image_copy(),
+			wgpu::ImageCopyBuffer {
+				buffer: &buffer,
+				layout: wgpu::ImageDataLayout {
+					offset: 0,
+					bytes_per_row: Some(padded_row_bytes),
+					rows_per_image: None,
+				},
+			},
+			texture.size(),
+		);
+
+		Some(PendingCapture {
+			path,
+			buffer,
+			padded_row_bytes,
+			size: (self.config.width, self.config.height),
+			format: self.config.format,
+			mapped: Default::default(),
+		})
+	}
+
+	/// starts mapping the capture's buffer, it's saved by [`Gfx::save_capture`] once that's done.
+	fn read_capture(&mut self, capture: PendingCapture) {
+		let mapped = capture.mapped.clone();
+		capture.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+			*mapped.lock().unwrap() = Some(result);
+		});
+		self.pending_capture = Some(capture);
+	}
+
+	/// saves the pending capture if its buffer has been mapped, without waiting for it.
+	fn save_capture(&mut self) {
+		self.device.poll(wgpu::Maintain::Poll);
+		let result = match &self.pending_capture {
+			Some(capture) => capture.mapped.lock().unwrap().take(),
+			None => return,
+		};
+		let Some(result) = result else { return };
+		let capture = self.pending_capture.take().unwrap();
+
+		let saved = result
+			.map_err(|error| image::ImageError::IoError(std::io::Error::new(std::io::ErrorKind::Other, error)))
+			.and_then(|()| {
+				let image = capture_image(&capture.buffer.slice(..).get_mapped_range(), capture.padded_row_bytes, capture.size, capture.format);
+				capture.buffer.unmap();
+				image
+			})
+			.and_then(|image| {
+				if let Some(parent) = capture.path.parent() {
+					std::fs::create_dir_all(parent)?;
+				}
+				image.save(&capture.path)
+			});
+		match saved {
+			Ok(()) => println!("saved a screenshot to {}", capture.path.display()),
+			Err(error) => eprintln!("couldn't save a screenshot to {}: {}", capture.path.display(), error),
+		}
+	}
+
+	/// the render graphs have to be resized too, see [`graph::Graph::resize`] and [`crate::state::State::resize`].
 	pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
 		if new_size.width > 0 && new_size.height > 0 {
 			self.size = new_size;
@@ -132,6 +310,8 @@ impl Gfx {
 	
 	pub fn render(&mut self, state: &dyn State) -> Result<(), wgpu::SurfaceError> {
 		let output = self.surface.get_current_texture()?;
+		self.save_capture();
+		self.profiler.begin_frame(&self.device);
 		let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
 		let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
 		
@@ -166,7 +346,19 @@ impl Gfx {
 		self.egui_renderpass.update_buffers(&self.device, &self.queue, &paint_jobs, &screen_descriptor);
 		self.egui_renderpass.execute(&mut encoder, &view, &paint_jobs, &screen_descriptor, None).unwrap();
 		
+		// one capture at a time, the path is kept until the previous one is saved.
+		let capture = match self.pending_capture {
+			None => self.capture_path.take().and_then(|path| self.copy_capture(&mut encoder, &output.texture, path)),
+			Some(_) => None,
+		};
+
+		self.profiler.resolve(&mut encoder);
 		self.queue.submit(std::iter::once(encoder.finish()));
+		self.profiler.end_frame();
+
+		if let Some(capture) = capture {
+			self.read_capture(capture);
+		}
 		output.present();
 
 		self.egui_renderpass.remove_textures(full_output.textures_delta).unwrap();
@@ -176,6 +368,8 @@ impl Gfx {
 }
 
 pub mod graph;
+pub mod pool;
+pub mod profiler;
 
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -193,14 +387,9 @@ impl Texture {
 	pub fn create_depth_texture(
 		gfx: &Gfx,
 		format: wgpu::TextureFormat,
+		size: wgpu::Extent3d,
 		sample_count: u32
 	) -> Self {
-		let size = wgpu::Extent3d {
-			width: gfx.config.width,
-			height: gfx.config.height,
-			depth_or_array_layers: 1
-		};
-
 		let texture = gfx.device.create_texture(&wgpu::TextureDescriptor {
 			label: None,
 			size,
@@ -299,6 +488,46 @@ impl Texture {
 
 		Self { texture, view, sampler: Some(sampler) }
 	}
+
+	/// a binding texture with the contents of `image`.
+	pub fn from_rgba(gfx: &Gfx, image: &image::RgbaImage, format: wgpu::TextureFormat) -> Self {
+		let texture = Self::create_binding_texture(
+			gfx,
+			format,
+			wgpu::Extent3d {
+				width: image.width(),
+				height: image.height(),
+				depth_or_array_layers: 1,
+			}
+		);
+		gfx.queue.write_texture(
+			texture.texture.as_image_copy(),
+			image,
+			wgpu::ImageDataLayout {
+				offset: 0,
+				bytes_per_row: Some(4 * texture.size().width),
+				rows_per_image: Some(texture.size().height),
+			}, texture.size()
+		);
+		texture
+	}
+
+	/// a binding texture with the image at `path`, see [`Texture::load_image`].
+	pub fn from_path(gfx: &Gfx, path: impl AsRef<std::path::Path>, format: wgpu::TextureFormat) -> Result<Self, std::io::Error> {
+		Ok(Self::from_rgba(gfx, &Self::load_image(path)?, format))
+	}
+
+	/// decodes the image at `path`, with the format guessed from the extension.
+	pub fn load_image(path: impl AsRef<std::path::Path>) -> Result<image::RgbaImage, std::io::Error> {
+		let path = path.as_ref();
+		let invalid = |error: image::ImageError| std::io::Error::new(
+			std::io::ErrorKind::InvalidData,
+			format!("couldn't decode {:?}: {}", path, error)
+		);
+		let format = image::ImageFormat::from_path(path).map_err(invalid)?;
+		let file = std::fs::File::open(path)?;
+		Ok(image::load(std::io::BufReader::new(file), format).map_err(invalid)?.to_rgba8())
+	}
 }
 
 // pub struct UiContext {
@@ -318,6 +547,21 @@ pub struct RenderContext<'a> {
 }
 
 impl<'a> RenderContext<'a> {
+	/// clears the output, for states that only draw the ui.
+	pub fn clear(&mut self, color: wgpu::Color) {
+		if let Some(output) = self.output {
+			self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+				label: Some("Clear"),
+				color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+					view: output,
+					resolve_target: None,
+					ops: wgpu::Operations { load: wgpu::LoadOp::Clear(color), store: true },
+				})],
+				depth_stencil_attachment: None,
+			});
+		}
+	}
+
 	pub fn render_graph<R>(&mut self, graph: &graph::Graph<R>, renderer: &R) {
 		graph.render(&mut GraphRenderContext {
 			gfx: self.gfx,
@@ -336,44 +580,101 @@ pub struct Mesh<V: Vertex> {
 pub struct MeshBuffers<V: Vertex> {
 	pub index_count: usize,
 	pub vertex_count: usize,
+	/// the buffers can be bigger than the data, see [`MeshBuffers::buffer_size`].
 	pub vertex_buffer: wgpu::Buffer,
 	pub index_buffer: wgpu::Buffer,
+	/// `Uint16` if the vertices fit, see [`MeshBuffers::index_data`].
+	pub index_format: wgpu::IndexFormat,
 	pub name: Option<String>,
 	_pd: PhantomData<V>
 }
 
 impl<V: Vertex> MeshBuffers<V> {
+	/// a buffer is shrunk when the data takes up less than `1 / SHRINK_THRESHOLD` of it.
+	const SHRINK_THRESHOLD: u64 = 4;
+
 	fn new(gfx: &Gfx, vertices: &[V], indices: &[u32], name: Option<&str>) -> Self {
+		let (index_format, index_bytes) = Self::index_data(vertices.len(), indices);
 		Self {
 			name: name.map(|s| s.to_string()),
 			vertex_count: vertices.len(),
 			index_count: indices.len(),
 			vertex_buffer: Self::create_vertex_buffer(gfx, vertices, name),
-			index_buffer: Self::create_index_buffer(gfx, indices, name),
+			index_buffer: Self::create_index_buffer(gfx, &index_bytes, name),
+			index_format,
 			_pd: PhantomData
 		}
 	}
 
-	fn update_vertex_buffer(&mut self, gfx: &Gfx, vertices: &[V]) {
-		let bytes = bytemuck::cast_slice::<V, u8>(vertices);
+	/// the indices as u16 if every vertex can be indexed with one, which halves the index buffer.
+	/// padded to a multiple of 4 bytes, as the buffer writes have to be.
+	fn index_data(vertex_count: usize, indices: &[u32]) -> (wgpu::IndexFormat, Cow<'_, [u8]>) {
+		if vertex_count <= u16::MAX as usize + 1 {
+			let mut small: Vec<u16> = indices.iter().map(|&index| index as u16).collect();
+			if small.len() % 2 == 1 {
+				small.push(0);
+			}
+			(wgpu::IndexFormat::Uint16, Cow::Owned(bytemuck::cast_slice(&small).to_vec()))
+		} else {
+			(wgpu::IndexFormat::Uint32, Cow::Borrowed(bytemuck::cast_slice(indices)))
+		}
+	}
 
-		if bytes.len() as u64 == self.vertex_buffer.size() {
-			gfx.queue.write_buffer(&self.vertex_buffer, 0, bytes);
+	/// the size of the buffer that should hold `needed` bytes, if it's currently `size` bytes.
+	/// the same size if the data fits and doesn't waste too much space, otherwise grows geometrically.
+	fn buffer_size(size: u64, needed: u64) -> u64 {
+		let needed = wgpu::util::align_to(needed.max(wgpu::COPY_BUFFER_ALIGNMENT), wgpu::COPY_BUFFER_ALIGNMENT);
+		if needed <= size && needed * Self::SHRINK_THRESHOLD >= size {
+			size
 		} else {
-			self.vertex_count = vertices.len();
-			self.vertex_buffer = Self::create_vertex_buffer(gfx, vertices, self.name.as_ref().map(|s| s.as_str()));
+			needed.next_power_of_two()
+		}
+	}
+
+	fn update_buffer(gfx: &Gfx, buffer: &mut wgpu::Buffer, bytes: &[u8], usage: wgpu::BufferUsages, name: Option<&str>, kind: &str) {
+		let size = Self::buffer_size(buffer.size(), bytes.len() as u64);
+		if size != buffer.size() {
+			*buffer = Self::create_buffer(gfx, size, usage, name, kind);
+		}
+		if !bytes.is_empty() {
+			gfx.queue.write_buffer(buffer, 0, bytes);
 		}
 	}
 
+	fn update_vertex_buffer(&mut self, gfx: &Gfx, vertices: &[V]) {
+		self.vertex_count = vertices.len();
+		Self::update_buffer(
+			gfx,
+			&mut self.vertex_buffer,
+			bytemuck::cast_slice(vertices),
+			wgpu::BufferUsages::VERTEX,
+			self.name.as_deref(),
+			"Vertex"
+		);
+	}
+
+	/// NB: after `update_vertex_buffer`, the index format depends on the vertex count.
 	fn update_index_buffer(&mut self, gfx: &Gfx, indices: &[u32]) {
-		let bytes = bytemuck::cast_slice::<u32, u8>(indices);
+		let (index_format, index_bytes) = Self::index_data(self.vertex_count, indices);
+		self.index_count = indices.len();
+		self.index_format = index_format;
+		Self::update_buffer(
+			gfx,
+			&mut self.index_buffer,
+			&index_bytes,
+			wgpu::BufferUsages::INDEX,
+			self.name.as_deref(),
+			"Index"
+		);
+	}
 
-		if bytes.len() as u64 == self.index_buffer.size() {
-			gfx.queue.write_buffer(&self.index_buffer, 0, bytes);
-		} else {
-			self.index_count = indices.len();
-			self.index_buffer = Self::create_index_buffer(gfx, indices, self.name.as_ref().map(|s| s.as_str()));
-		}
+	fn create_buffer(gfx: &Gfx, size: u64, usage: wgpu::BufferUsages, name: Option<&str>, kind: &str) -> wgpu::Buffer {
+		gfx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: name.map(|name| format!("{} {} Buffer", name, kind)).as_ref().map(|s| s.as_str()),
+			usage: wgpu::BufferUsages::COPY_DST | usage,
+			size,
+			mapped_at_creation: false,
+		})
 	}
 
 	fn create_vertex_buffer(gfx: &Gfx, vertices: &[V], name: Option<&str>) -> wgpu::Buffer {
@@ -384,11 +685,11 @@ impl<V: Vertex> MeshBuffers<V> {
 		})
 	}
 
-	fn create_index_buffer(gfx: &Gfx, indices: &[u32], name: Option<&str>) -> wgpu::Buffer {
+	fn create_index_buffer(gfx: &Gfx, index_bytes: &[u8], name: Option<&str>) -> wgpu::Buffer {
 		gfx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
 			label: name.map(|name| format!("{} Index Buffer", name)).as_ref().map(|s| s.as_str()),
 			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::INDEX,
-			contents: bytemuck::cast_slice(indices),
+			contents: index_bytes,
 		})
 	}
 }
@@ -404,10 +705,90 @@ impl<V: Vertex> Mesh<V> {
 	}
 
 	pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+		self.render_instances(render_pass, 0..1);
+	}
+
+	/// `instances` are passed to the shader as the `instance_index`.
+	pub fn render_instances<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, instances: std::ops::Range<u32>) {
 		if self.buffers.index_count > 0 {
 			render_pass.set_vertex_buffer(0, self.buffers.vertex_buffer.slice(..));
-			render_pass.set_index_buffer(self.buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-			render_pass.draw_indexed(0..self.buffers.index_count as u32, 0, 0..1);
+			render_pass.set_index_buffer(self.buffers.index_buffer.slice(..), self.buffers.index_format);
+			render_pass.draw_indexed(0..self.buffers.index_count as u32, 0, instances);
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn unsupported_present_modes_fall_back_to_vsync() {
+		use wgpu::PresentMode::*;
+		assert_eq!(choose_present_mode(Mailbox, &[Fifo, Mailbox, Immediate]), Mailbox);
+		assert_eq!(choose_present_mode(Immediate, &[Immediate]), Immediate);
+		assert_eq!(choose_present_mode(Mailbox, &[Fifo, Immediate]), Fifo);
+		assert_eq!(choose_present_mode(Immediate, &[]), Fifo);
+	}
+
+	#[test]
+	fn captures_drop_the_row_padding() {
+		// 3 pixels wide, the rows are padded to 16 bytes.
+		let (width, height, padded_row_bytes) = (3u32, 2u32, 16u32);
+		let mut padded = vec![0xee; (padded_row_bytes * height) as usize];
+		for y in 0..height {
+			for x in 0..width {
+				let offset = (y * padded_row_bytes + x * 4) as usize;
+				padded[offset..offset + 4].copy_from_slice(&[x as u8, y as u8, 100, 255]);
+			}
+		}
+
+		let tight = remove_row_padding(&padded, (width * 4) as usize, padded_row_bytes as usize, height as usize);
+		assert_eq!(tight.len(), (width * height * 4) as usize);
+		assert!(!tight.contains(&0xee));
+
+		let image = capture_image(&padded, padded_row_bytes, (width, height), wgpu::TextureFormat::Rgba8UnormSrgb).unwrap();
+		assert_eq!(image.get_pixel(2, 1).0, [2, 1, 100, 255]);
+		let image = capture_image(&padded, padded_row_bytes, (width, height), wgpu::TextureFormat::Bgra8Unorm).unwrap();
+		assert_eq!(image.get_pixel(2, 1).0, [100, 1, 2, 255]);
+		assert!(capture_image(&padded, padded_row_bytes, (width, height), wgpu::TextureFormat::Rgba16Float).is_err());
+	}
+
+	#[test]
+	fn mesh_buffers_grow_geometrically() {
+		let size = MeshBuffers::<u32>::buffer_size;
+		assert_eq!(size(0, 0), wgpu::COPY_BUFFER_ALIGNMENT);
+		assert_eq!(size(0, 100), 128);
+		// updates that fit reuse the buffer.
+		assert_eq!(size(128, 120), 128);
+		assert_eq!(size(128, 40), 128);
+		// and the ones that don't grow it to the next power of two.
+		assert_eq!(size(128, 130), 256);
+		// it only shrinks once most of it is wasted.
+		assert_eq!(size(128, 20), 32);
+	}
+
+	#[test]
+	fn small_meshes_use_16_bit_indices() {
+		let indices = [0, 1, 2, 2, 3, 65535, 7];
+		let (format, bytes) = MeshBuffers::<u32>::index_data(65536, &indices);
+		assert_eq!(format, wgpu::IndexFormat::Uint16);
+		// padded with one index, which isn't drawn, only `index_count` of them are.
+		let small: Vec<u16> = bytes.chunks(2).map(|index| u16::from_ne_bytes([index[0], index[1]])).collect();
+		assert_eq!(small, [0, 1, 2, 2, 3, 65535, 7, 0]);
+
+		let (format, bytes) = MeshBuffers::<u32>::index_data(65537, &indices);
+		assert_eq!(format, wgpu::IndexFormat::Uint32);
+		assert_eq!(bytes.len(), indices.len() * 4);
+	}
+
+	#[test]
+	fn images_are_loaded_from_their_path() {
+		let image = Texture::load_image("data/textures/ui_spritesheet.png").unwrap();
+		assert_eq!(image.dimensions(), (32, 32));
+
+		assert_eq!(Texture::load_image("data/textures/missing.png").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+		// not an image.
+		assert_eq!(Texture::load_image("data/shaders/game/sky.wgsl").unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+	}
+}
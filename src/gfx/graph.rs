@@ -8,6 +8,20 @@ pub enum AttachmentSizeSpec {
 	Output(f32)
 }
 
+impl AttachmentSizeSpec {
+	/// `output_size` is the size of the surface, in pixels.
+	fn extent(self, output_size: (u32, u32)) -> wgpu::Extent3d {
+		match self {
+			Self::Fixed(extent) => extent,
+			Self::Output(scale) => wgpu::Extent3d {
+				width: ((output_size.0 as f32 * scale).round() as u32).max(1),
+				height: ((output_size.1 as f32 * scale).round() as u32).max(1),
+				depth_or_array_layers: 1
+			}
+		}
+	}
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct ColorAttachmentSpec<Id> {
 	pub format: wgpu::TextureFormat,
@@ -22,6 +36,7 @@ pub struct DepthStencilAttachmentSpec {
 	pub format: wgpu::TextureFormat,
 	pub depth_ops: Option<fn(&super::Gfx) -> Option<wgpu::Operations<f32>>>,
 	pub stencil_ops: Option<fn(&super::Gfx) -> Option<wgpu::Operations<u32>>>,
+	pub size: AttachmentSizeSpec,
 	pub samples: u32
 }
 
@@ -39,14 +54,56 @@ pub enum AttachmentSpec<Id> {
 	Output(OutputAttachmentSpec)
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum NodeKind<R: ?Sized, Id, Ids> {
+	Render {
+		color_attachments: Ids,
+		depth_stencil_attachment: Option<Id>,
+		render: for<'a> fn(gfx: &super::Gfx, render_pass: &mut wgpu::RenderPass<'a>, renderer: &'a R),
+	},
+	/// doesn't use any attachments, the pipelines and the resources they use are bound by `compute`.
+	#[allow(dead_code)]
+	Compute {
+		compute: for<'a> fn(gfx: &super::Gfx, compute_pass: &mut wgpu::ComputePass<'a>, renderer: &'a R),
+	},
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct NodeSpec<R: ?Sized, Id, Ids> {
 	pub id: Id,
-	pub color_attachments: Ids,
-	pub depth_stencil_attachment: Option<Id>,
-	pub render: for<'a> fn(gfx: &super::Gfx, render_pass: &mut wgpu::RenderPass<'a>, renderer: &'a R),
+	pub kind: NodeKind<R, Id, Ids>,
 }
 
+/// a misconfigured [`GraphSpec`], see [`GraphSpec::build`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphBuildError {
+	/// a node uses an attachment that isn't in the spec.
+	UnknownAttachment { node: String, attachment: String },
+	/// a color attachment resolves into an attachment that isn't in the spec.
+	UnknownResolveTarget { attachment: String, resolve: String },
+	/// a depth-stencil attachment is used as one of a node's color attachments.
+	NotColorAttachment { node: String, attachment: String },
+	/// a color or output attachment is used as a node's depth-stencil attachment.
+	NotDepthStencilAttachment { node: String, attachment: String },
+}
+
+impl std::fmt::Display for GraphBuildError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::UnknownAttachment { node, attachment } =>
+				write!(f, "node {} uses an unknown attachment: {}", node, attachment),
+			Self::UnknownResolveTarget { attachment, resolve } =>
+				write!(f, "attachment {} resolves into an unknown attachment: {}", attachment, resolve),
+			Self::NotColorAttachment { node, attachment } =>
+				write!(f, "node {} uses the depth-stencil attachment {} as a color attachment", node, attachment),
+			Self::NotDepthStencilAttachment { node, attachment } =>
+				write!(f, "node {} uses the color attachment {} as a depth-stencil attachment", node, attachment),
+		}
+	}
+}
+
+impl std::error::Error for GraphBuildError {}
+
 pub struct GraphSpec<'a, R> {
 	pub attachments: &'a [Option<(&'a str, AttachmentSpec<&'a str>)>],
 	pub nodes: &'a [NodeSpec<R, &'a str, &'a [&'a str]>]
@@ -77,20 +134,28 @@ impl Attachment {
 				AttachmentSpec::DepthStencil(info) => AttachmentSpec::DepthStencil(info),
 				AttachmentSpec::Output(info) => AttachmentSpec::Output(info),
 			},
-			texture: match spec {
-				AttachmentSpec::Color(ColorAttachmentSpec { format, size, samples, .. }) =>
-					Some(super::Texture::create_attachment_texture(gfx, format, match size {
-						AttachmentSizeSpec::Fixed(extent) => extent,
-						AttachmentSizeSpec::Output(_scale) => wgpu::Extent3d {
-							width: gfx.config.width,
-							height: gfx.config.height,
-							depth_or_array_layers: 1
-						}
-					}, samples)),
-				AttachmentSpec::DepthStencil(DepthStencilAttachmentSpec { format, samples, .. }) =>
-					Some(super::Texture::create_depth_texture(gfx, format, samples)),
-				AttachmentSpec::Output(_) => None
-			}
+			texture: Self::create_texture(gfx, &spec)
+		}
+	}
+
+	fn create_texture<Id>(gfx: &super::Gfx, spec: &AttachmentSpec<Id>) -> Option<super::Texture> {
+		let output_size = (gfx.config.width, gfx.config.height);
+		match *spec {
+			AttachmentSpec::Color(ColorAttachmentSpec { format, size, samples, .. }) =>
+				Some(super::Texture::create_attachment_texture(gfx, format, size.extent(output_size), samples)),
+			AttachmentSpec::DepthStencil(DepthStencilAttachmentSpec { format, size, samples, .. }) =>
+				Some(super::Texture::create_depth_texture(gfx, format, size.extent(output_size), samples)),
+			AttachmentSpec::Output(_) => None
+		}
+	}
+
+	/// whether the attachment's size depends on the output's.
+	fn follows_output(&self) -> bool {
+		match self.spec {
+			AttachmentSpec::Color(ColorAttachmentSpec { size, .. })
+				| AttachmentSpec::DepthStencil(DepthStencilAttachmentSpec { size, .. }) =>
+				matches!(size, AttachmentSizeSpec::Output(_)),
+			AttachmentSpec::Output(_) => false
 		}
 	}
 }
@@ -98,10 +163,62 @@ impl Attachment {
 pub struct Graph<R: ?Sized> {
 	attachments: HashMap<u32, Attachment>,
 	passes: Vec<NodeSpec<R, u32, Vec<u32>>>,
+	/// index of the first and last pass that uses each attachment.
+	/// the attachment's load op is only used in the first pass, and its store op in the last,
+	/// the passes in between load and store, so that they draw on top of each other.
+	first_use: HashMap<u32, usize>,
+	last_use: HashMap<u32, usize>,
+	/// the ids of the named attachments and nodes, see [`Graph::attachment_texture`].
+	names: HashMap<String, u32>,
 }
 
 impl<'a, R> GraphSpec<'a, R> {
-	pub fn build(self, gfx: &super::Gfx) -> Graph<R> {
+	/// checks that the nodes only use attachments of the right kind that are in the spec.
+	pub fn validate(&self) -> Result<(), GraphBuildError> {
+		let specs: HashMap<&str, &AttachmentSpec<&str>> = self.attachments.iter()
+			.filter_map(|x| x.as_ref())
+			.map(|(name, spec)| (*name, spec))
+			.collect();
+
+		for (name, spec) in &specs {
+			if let AttachmentSpec::Color(ColorAttachmentSpec { resolve: Some(resolve), .. }) = spec {
+				if !specs.contains_key(resolve) {
+					return Err(GraphBuildError::UnknownResolveTarget { attachment: name.to_string(), resolve: resolve.to_string() });
+				}
+			}
+		}
+
+		for node in self.nodes {
+			let (color_attachments, depth_stencil_attachment) = match node.kind {
+				NodeKind::Render { color_attachments, depth_stencil_attachment, .. } => (color_attachments, depth_stencil_attachment),
+				NodeKind::Compute { .. } => continue,
+			};
+
+			for &attachment in color_attachments {
+				match specs.get(attachment) {
+					None => return Err(GraphBuildError::UnknownAttachment { node: node.id.to_owned(), attachment: attachment.to_owned() }),
+					Some(AttachmentSpec::DepthStencil(_)) =>
+						return Err(GraphBuildError::NotColorAttachment { node: node.id.to_owned(), attachment: attachment.to_owned() }),
+					Some(_) => {}
+				}
+			}
+
+			if let Some(attachment) = depth_stencil_attachment {
+				match specs.get(attachment) {
+					None => return Err(GraphBuildError::UnknownAttachment { node: node.id.to_owned(), attachment: attachment.to_owned() }),
+					Some(AttachmentSpec::DepthStencil(_)) => {}
+					Some(_) =>
+						return Err(GraphBuildError::NotDepthStencilAttachment { node: node.id.to_owned(), attachment: attachment.to_owned() }),
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn build(self, gfx: &super::Gfx) -> Result<Graph<R>, GraphBuildError> {
+		self.validate()?;
+
 		let mut ids: HashMap<&'a str, u32> = HashMap::new();
 
 		let mut get_id = |id: &'a str| -> u32 {
@@ -113,17 +230,47 @@ impl<'a, R> GraphSpec<'a, R> {
 			}
 		};
 
-		Graph {
-			attachments: self.attachments.into_iter().filter_map(|x| *x).map(|(name, val)| 
-				(get_id(name), Attachment::create_from_spec(gfx, val, &mut get_id))
-			).collect(),
-			passes: self.nodes.into_iter().map(|spec| NodeSpec {
-				id: get_id(spec.id),
-				color_attachments: spec.color_attachments.into_iter().map(|id| get_id(*id)).collect(),
-				depth_stencil_attachment: spec.depth_stencil_attachment.and_then(|id| Some(get_id(id))),
-				render: spec.render
-			}).collect()
+		let attachments = self.attachments.into_iter().filter_map(|x| *x).map(|(name, val)| 
+			(get_id(name), Attachment::create_from_spec(gfx, val, &mut get_id))
+		).collect();
+		let (passes, first_use, last_use) = Self::compile_passes(self.nodes, &mut get_id);
+
+		let names = ids.into_iter().map(|(name, id)| (name.to_owned(), id)).collect();
+
+		Ok(Graph { attachments, passes, first_use, last_use, names })
+	}
+
+	/// the nodes with their attachments replaced by ids, in the order they're dispatched in (the spec's),
+	/// and the index of the first and last of them that uses each attachment, see [`Graph::first_use`].
+	fn compile_passes(
+		nodes: &'a [NodeSpec<R, &'a str, &'a [&'a str]>],
+		get_id: &mut impl FnMut(&'a str) -> u32
+	) -> (Vec<NodeSpec<R, u32, Vec<u32>>>, HashMap<u32, usize>, HashMap<u32, usize>) {
+		let passes: Vec<_> = nodes.iter().map(|spec| NodeSpec {
+			id: get_id(spec.id),
+			kind: match spec.kind {
+				NodeKind::Render { color_attachments, depth_stencil_attachment, render } => NodeKind::Render {
+					color_attachments: color_attachments.iter().map(|id| get_id(*id)).collect::<Vec<_>>(),
+					depth_stencil_attachment: depth_stencil_attachment.map(|id| get_id(id)),
+					render
+				},
+				NodeKind::Compute { compute } => NodeKind::Compute { compute },
+			}
+		}).collect();
+
+		let mut first_use = HashMap::new();
+		let mut last_use = HashMap::new();
+		for (index, pass) in passes.iter().enumerate() {
+			// compute nodes don't use attachments.
+			if let NodeKind::Render { color_attachments, depth_stencil_attachment, .. } = &pass.kind {
+				for id in color_attachments.iter().chain(depth_stencil_attachment.iter()) {
+					first_use.entry(*id).or_insert(index);
+					last_use.insert(*id, index);
+				}
+			}
 		}
+
+		(passes, first_use, last_use)
 	}
 }
 
@@ -134,20 +281,60 @@ pub struct GraphRenderContext<'a> {
 }
 
 impl<R> Graph<R> {
-	// pub fn resize(&mut self, gfx: &super::Gfx) {
+	/// recreates the attachments that are sized relative to the output,
+	/// should be called after the surface was resized, see [`super::Gfx::resize`].
+	pub fn resize(&mut self, gfx: &super::Gfx) {
+		for attachment in self.attachments.values_mut() {
+			if attachment.follows_output() {
+				attachment.texture = Attachment::create_texture(gfx, &attachment.spec);
+			}
+		}
+	}
+
+	/// the texture backing an attachment, e.g. to sample it in a later pass.
+	/// `None` for the output attachment.
+	pub fn attachment_texture(&self, name: &str) -> Option<&super::Texture> {
+		self.names.get(name)
+			.and_then(|id| self.attachments.get(id))
+			.and_then(|attachment| attachment.texture.as_ref())
+	}
 
-	// }
+	/// the name the node was given in the spec.
+	fn node_name(&self, id: u32) -> &str {
+		self.names.iter().find(|(_, other)| **other == id).map_or("", |(name, _)| name.as_str())
+	}
+
+	/// see [`Graph::first_use`].
+	fn pass_ops<V>(&self, index: usize, attachment: u32, ops: wgpu::Operations<V>) -> wgpu::Operations<V> {
+		wgpu::Operations {
+			load: if self.first_use[&attachment] == index { ops.load } else { wgpu::LoadOp::Load },
+			store: if self.last_use[&attachment] == index { ops.store } else { true },
+		}
+	}
 
 	pub fn render(&self, ctx: &mut GraphRenderContext, renderer: &R) {
-		for pass in &self.passes {
-			let color_attachments = Vec::from_iter(pass.color_attachments.iter().map(
+		for (index, pass) in self.passes.iter().enumerate() {
+			let (color_attachments, depth_stencil_attachment, render) = match &pass.kind {
+				NodeKind::Render { color_attachments, depth_stencil_attachment, render } =>
+					(color_attachments, depth_stencil_attachment, render),
+				NodeKind::Compute { compute } => {
+					let scope = ctx.gfx.profiler.begin_scope(ctx.encoder, self.node_name(pass.id));
+					compute(ctx.gfx, &mut ctx.encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+						label: Some(&pass.id.to_string()),
+					}), renderer);
+					ctx.gfx.profiler.end_scope(ctx.encoder, scope);
+					continue;
+				}
+			};
+
+			let color_attachments = Vec::from_iter(color_attachments.iter().map(
 				|a| Some(wgpu::RenderPassColorAttachment {
 					view: self.attachments[a].texture.as_ref().and_then(|t| Some(&t.view)).or_else(|| ctx.output).unwrap(),
-					ops: match self.attachments[a].spec {
+					ops: self.pass_ops(index, *a, match self.attachments[a].spec {
 						AttachmentSpec::Color(ColorAttachmentSpec { ops, .. }) => ops(ctx.gfx),
 						AttachmentSpec::Output(OutputAttachmentSpec { ops, .. }) => ops(ctx.gfx),
-						_ => panic!("depth-stencil attachment provided in color attachments.") // TODO: move this to the build phase.
-					},
+						_ => unreachable!("checked in `GraphSpec::build`")
+					}),
 					resolve_target: match self.attachments[a].spec {
 						AttachmentSpec::Color(ColorAttachmentSpec { resolve, .. }) =>
 							resolve.and_then(|id| self.attachments[&id].texture.as_ref().and_then(|t| Some(&t.view)).or_else(|| ctx.output)),
@@ -156,23 +343,157 @@ impl<R> Graph<R> {
 				})
 			));
 
-			let depth_stencil_attachment = pass.depth_stencil_attachment.and_then(|ref a| {
+			let depth_stencil_attachment = depth_stencil_attachment.and_then(|ref a| {
 				let info = match &self.attachments[a].spec {
 					AttachmentSpec::DepthStencil(info) => info,
-					_ => panic!("color attachment provided in depth-stencil attachment.") // TODO: move this to the build phase.
+					_ => unreachable!("checked in `GraphSpec::build`")
 				};
 				Some(wgpu::RenderPassDepthStencilAttachment {
 					view: &self.attachments[a].texture.as_ref().unwrap().view,
-					depth_ops: info.depth_ops.and_then(|f| f(ctx.gfx)),
-					stencil_ops: info.stencil_ops.and_then(|f| f(ctx.gfx))
+					depth_ops: info.depth_ops.and_then(|f| f(ctx.gfx)).map(|ops| self.pass_ops(index, *a, ops)),
+					stencil_ops: info.stencil_ops.and_then(|f| f(ctx.gfx)).map(|ops| self.pass_ops(index, *a, ops))
 				})
 			});
 
-			(pass.render)(ctx.gfx, &mut ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			let scope = ctx.gfx.profiler.begin_scope(ctx.encoder, self.node_name(pass.id));
+			render(ctx.gfx, &mut ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
 				label: Some(&pass.id.to_string()),
 				color_attachments: &color_attachments,
 				depth_stencil_attachment
 			}), renderer);
+			ctx.gfx.profiler.end_scope(ctx.encoder, scope);
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn clear(_: &super::super::Gfx) -> wgpu::Operations<wgpu::Color> {
+		wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: true }
+	}
+
+	fn depth(size: AttachmentSizeSpec) -> AttachmentSpec<u32> {
+		AttachmentSpec::DepthStencil(DepthStencilAttachmentSpec {
+			format: wgpu::TextureFormat::Depth32Float,
+			depth_ops: None,
+			stencil_ops: None,
+			size,
+			samples: 1,
+		})
+	}
+
+	#[test]
+	fn attachments_follow_the_output_size() {
+		let extent = |size: AttachmentSizeSpec, output_size| {
+			let extent = size.extent(output_size);
+			(extent.width, extent.height, extent.depth_or_array_layers)
+		};
+		assert_eq!(extent(AttachmentSizeSpec::Output(1.0), (800, 600)), (800, 600, 1));
+		assert_eq!(extent(AttachmentSizeSpec::Output(1.0), (1920, 1080)), (1920, 1080, 1));
+		assert_eq!(extent(AttachmentSizeSpec::Output(0.5), (1921, 1080)), (961, 540, 1));
+		// minimized windows have a zero size, textures can't.
+		assert_eq!(extent(AttachmentSizeSpec::Output(0.5), (0, 0)), (1, 1, 1));
+
+		let fixed = AttachmentSizeSpec::Fixed(wgpu::Extent3d { width: 2048, height: 2048, depth_or_array_layers: 1 });
+		assert_eq!(extent(fixed, (800, 600)), (2048, 2048, 1));
+
+		// only those are recreated on resize.
+		let attachment = |spec| Attachment { spec, texture: None };
+		assert!(attachment(depth(AttachmentSizeSpec::Output(1.0))).follows_output());
+		assert!(!attachment(depth(fixed)).follows_output());
+		assert!(!attachment(AttachmentSpec::Output(OutputAttachmentSpec { ops: clear })).follows_output());
+	}
+
+	fn color(resolve: Option<&str>) -> AttachmentSpec<&str> {
+		AttachmentSpec::Color(ColorAttachmentSpec {
+			format: wgpu::TextureFormat::Rgba8Unorm,
+			resolve,
+			size: AttachmentSizeSpec::Output(1.0),
+			ops: clear,
+			samples: 4,
+		})
+	}
+
+	fn node<'a>(id: &'a str, color_attachments: &'a [&'a str], depth_stencil_attachment: Option<&'a str>) -> NodeSpec<(), &'a str, &'a [&'a str]> {
+		NodeSpec { id, kind: NodeKind::Render { color_attachments, depth_stencil_attachment, render: |_, _, _| {} } }
+	}
+
+	#[test]
+	fn misconfigured_graphs_dont_validate() {
+		let depth = AttachmentSpec::DepthStencil(DepthStencilAttachmentSpec {
+			format: wgpu::TextureFormat::Depth32Float,
+			depth_ops: None,
+			stencil_ops: None,
+			size: AttachmentSizeSpec::Output(1.0),
+			samples: 4,
+		});
+		let attachments = [
+			Some(("output", AttachmentSpec::Output(OutputAttachmentSpec { ops: clear }))),
+			Some(("msaa", color(Some("output")))),
+			Some(("depth", depth)),
+			None,
+		];
+		let validate = |nodes: &[NodeSpec<(), &str, &[&str]>]| GraphSpec { attachments: &attachments, nodes }.validate();
+
+		assert_eq!(validate(&[node("main", &["msaa"], Some("depth")), node("ui", &["output"], None)]), Ok(()));
+		assert_eq!(
+			validate(&[node("main", &["msaa", "missing"], None)]),
+			Err(GraphBuildError::UnknownAttachment { node: "main".to_owned(), attachment: "missing".to_owned() })
+		);
+		assert_eq!(
+			validate(&[node("main", &["msaa"], Some("missing"))]),
+			Err(GraphBuildError::UnknownAttachment { node: "main".to_owned(), attachment: "missing".to_owned() })
+		);
+		assert_eq!(
+			validate(&[node("main", &["depth"], None)]),
+			Err(GraphBuildError::NotColorAttachment { node: "main".to_owned(), attachment: "depth".to_owned() })
+		);
+		assert_eq!(
+			validate(&[node("main", &["msaa"], Some("output"))]),
+			Err(GraphBuildError::NotDepthStencilAttachment { node: "main".to_owned(), attachment: "output".to_owned() })
+		);
+
+		let attachments = [Some(("msaa", color(Some("missing"))))];
+		assert_eq!(
+			GraphSpec { attachments: &attachments, nodes: &[node("main", &["msaa"], None)] }.validate(),
+			Err(GraphBuildError::UnknownResolveTarget { attachment: "msaa".to_owned(), resolve: "missing".to_owned() })
+		);
+	}
+
+	#[test]
+	fn compute_nodes_are_dispatched_in_order() {
+		let compute = NodeSpec { id: "cull", kind: NodeKind::Compute { compute: |_, _, _| {} } };
+		let nodes = [node("shadow", &[], Some("shadow")), compute, node("main", &["output"], Some("depth")), node("ui", &["output"], None)];
+		let depth = AttachmentSpec::DepthStencil(DepthStencilAttachmentSpec {
+			format: wgpu::TextureFormat::Depth32Float,
+			depth_ops: None,
+			stencil_ops: None,
+			size: AttachmentSizeSpec::Output(1.0),
+			samples: 1,
+		});
+		let attachments = [
+			Some(("output", AttachmentSpec::Output(OutputAttachmentSpec { ops: clear }))),
+			Some(("shadow", depth)),
+			Some(("depth", depth)),
+		];
+		let spec = GraphSpec { attachments: &attachments, nodes: &nodes };
+		assert_eq!(spec.validate(), Ok(()));
+
+		let mut ids: Vec<String> = Vec::new();
+		let mut get_id = |id: &str| match ids.iter().position(|other| other == id) {
+			Some(index) => index as u32,
+			None => { ids.push(id.to_owned()); ids.len() as u32 - 1 }
+		};
+		let (passes, first_use, last_use) = GraphSpec::compile_passes(spec.nodes, &mut get_id);
+
+		let order: Vec<_> = passes.iter().map(|pass| (ids[pass.id as usize].as_str(), matches!(pass.kind, NodeKind::Compute { .. }))).collect();
+		assert_eq!(order, [("shadow", false), ("cull", true), ("main", false), ("ui", false)]);
+		// the compute node doesn't count as a use, "main" still clears the output and "ui" stores it.
+		let id = |name: &str| ids.iter().position(|other| *other == name).unwrap() as u32;
+		assert_eq!((first_use[&id("shadow")], last_use[&id("shadow")]), (0, 0));
+		assert_eq!((first_use[&id("output")], last_use[&id("output")]), (2, 3));
+		assert!(!first_use.contains_key(&id("cull")));
+	}
+}
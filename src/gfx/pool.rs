@@ -0,0 +1,224 @@
+use std::{marker::PhantomData, ops::Range, sync::{Arc, Mutex}};
+
+use super::{Gfx, Vertex};
+
+/// hands out ranges of a buffer, in elements.
+#[derive(Debug, Clone, Default)]
+struct RangeAllocator {
+	/// sorted, the ranges that touch are merged.
+	free: Vec<Range<u32>>,
+	size: u32,
+}
+
+impl RangeAllocator {
+	fn new(size: u32) -> Self {
+		let mut allocator = Self::default();
+		allocator.grow(size);
+		allocator
+	}
+
+	/// the start of the first free range that's long enough, `None` if there isn't one.
+	fn allocate(&mut self, len: u32) -> Option<Range<u32>> {
+		if len == 0 {
+			return Some(0..0);
+		}
+		let index = self.free.iter().position(|range| range.len() >= len as usize)?;
+		let start = self.free[index].start;
+		self.free[index].start += len;
+		if self.free[index].is_empty() {
+			self.free.remove(index);
+		}
+		Some(start..start + len)
+	}
+
+	fn free(&mut self, range: Range<u32>) {
+		if range.is_empty() {
+			return;
+		}
+		let index = self.free.partition_point(|free| free.start < range.start);
+		let joins_previous = index > 0 && self.free[index - 1].end == range.start;
+		let joins_next = index < self.free.len() && self.free[index].start == range.end;
+		match (joins_previous, joins_next) {
+			(true, true) => {
+				self.free[index - 1].end = self.free[index].end;
+				self.free.remove(index);
+			},
+			(true, false) => self.free[index - 1].end = range.end,
+			(false, true) => self.free[index].start = range.start,
+			(false, false) => self.free.insert(index, range),
+		}
+	}
+
+	/// adds the elements from the old size to `size` at the end.
+	fn grow(&mut self, size: u32) {
+		let old_size = std::mem::replace(&mut self.size, size);
+		self.free(old_size..size);
+	}
+}
+
+/// the vertex and index ranges of the dropped [`PooledMesh`]es.
+type FreedRanges = Arc<Mutex<Vec<(Range<u32>, Range<u32>)>>>;
+
+/// a buffer of `T`s and the parts of it that are free.
+struct PoolBuffer<T> {
+	buffer: wgpu::Buffer,
+	allocator: RangeAllocator,
+	usage: wgpu::BufferUsages,
+	label: String,
+	_pd: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> PoolBuffer<T> {
+	fn new(gfx: &Gfx, capacity: u32, usage: wgpu::BufferUsages, label: String) -> Self {
+		Self {
+			buffer: Self::create_buffer(gfx, capacity, usage, &label),
+			allocator: RangeAllocator::new(capacity),
+			usage,
+			label,
+			_pd: PhantomData,
+		}
+	}
+
+	fn create_buffer(gfx: &Gfx, capacity: u32, usage: wgpu::BufferUsages, label: &str) -> wgpu::Buffer {
+		let size = Self::bytes(capacity);
+		gfx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some(label),
+			// copied from when the buffer grows.
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC | usage,
+			size: wgpu::util::align_to(size.max(wgpu::COPY_BUFFER_ALIGNMENT), wgpu::COPY_BUFFER_ALIGNMENT),
+			mapped_at_creation: false,
+		})
+	}
+
+	fn bytes(count: u32) -> u64 {
+		count as u64 * std::mem::size_of::<T>() as u64
+	}
+
+	/// copies `data` into a free range, the buffer is replaced with a bigger copy if it doesn't fit.
+	/// NB: the writes have to be aligned to 4 bytes, which they are for the vertices and `u32` indices.
+	fn upload(&mut self, gfx: &Gfx, data: &[T]) -> Range<u32> {
+		let len = data.len() as u32;
+		let range = self.allocator.allocate(len).unwrap_or_else(|| {
+			let size = (self.allocator.size.max(1) * 2).max(self.allocator.size + len).next_power_of_two();
+			let buffer = Self::create_buffer(gfx, size, self.usage, &self.label);
+			let mut encoder = gfx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some(&format!("{} Copy", self.label)),
+			});
+			encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, self.buffer.size());
+			gfx.queue.submit([encoder.finish()]);
+			self.buffer = buffer;
+			self.allocator.grow(size);
+			self.allocator.allocate(len).expect("the buffer should have grown enough")
+		});
+
+		if !data.is_empty() {
+			gfx.queue.write_buffer(&self.buffer, Self::bytes(range.start), bytemuck::cast_slice(data));
+		}
+		range
+	}
+}
+
+/// the vertices and indices of many meshes in one pair of buffers, so that they can all be drawn
+/// without binding other buffers in between, e.g. with [`wgpu::RenderPass::multi_draw_indexed_indirect`].
+/// the indices of each mesh start at 0, they're offset by [`PooledMesh::base_vertex`].
+pub struct MeshPool<V: Vertex> {
+	vertices: PoolBuffer<V>,
+	indices: PoolBuffer<u32>,
+	freed: FreedRanges,
+}
+
+/// a mesh in a [`MeshPool`], its space is given back when it's dropped.
+pub struct PooledMesh {
+	vertices: Range<u32>,
+	indices: Range<u32>,
+	freed: FreedRanges,
+}
+
+impl PooledMesh {
+	/// added to the indices, see [`MeshPool`].
+	pub fn base_vertex(&self) -> i32 {
+		self.vertices.start as i32
+	}
+
+	/// in the pool's index buffer, empty for empty meshes.
+	pub fn indices(&self) -> Range<u32> {
+		self.indices.clone()
+	}
+}
+
+impl Drop for PooledMesh {
+	fn drop(&mut self) {
+		// the pool isn't borrowed here, it takes the ranges back on the next upload.
+		self.freed.lock().unwrap().push((self.vertices.clone(), self.indices.clone()));
+	}
+}
+
+impl<V: Vertex> MeshPool<V> {
+	/// the buffers double when the meshes don't fit anymore.
+	pub fn new(gfx: &Gfx, vertex_capacity: u32, index_capacity: u32, name: &str) -> Self {
+		Self {
+			vertices: PoolBuffer::new(gfx, vertex_capacity, wgpu::BufferUsages::VERTEX, format!("{} Vertex Buffer", name)),
+			indices: PoolBuffer::new(gfx, index_capacity, wgpu::BufferUsages::INDEX, format!("{} Index Buffer", name)),
+			freed: FreedRanges::default(),
+		}
+	}
+
+	/// copies the mesh into the buffers, they grow if it doesn't fit.
+	pub fn upload(&mut self, gfx: &Gfx, vertices: &[V], indices: &[u32]) -> PooledMesh {
+		// gives the ranges of the dropped meshes back first.
+		for (vertices, indices) in self.freed.lock().unwrap().drain(..) {
+			self.vertices.allocator.free(vertices);
+			self.indices.allocator.free(indices);
+		}
+		PooledMesh {
+			vertices: self.vertices.upload(gfx, vertices),
+			indices: self.indices.upload(gfx, indices),
+			freed: self.freed.clone(),
+		}
+	}
+
+	/// the meshes are drawn with the [`PooledMesh::indices`] and [`PooledMesh::base_vertex`].
+	pub fn bind<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+		render_pass.set_vertex_buffer(0, self.vertices.buffer.slice(..));
+		render_pass.set_index_buffer(self.indices.buffer.slice(..), wgpu::IndexFormat::Uint32);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn freed_ranges_are_reused_and_merged() {
+		let mut allocator = RangeAllocator::new(100);
+		let a = allocator.allocate(10).unwrap();
+		let b = allocator.allocate(20).unwrap();
+		let c = allocator.allocate(30).unwrap();
+		assert_eq!((a.clone(), b.clone(), c.clone()), (0..10, 10..30, 30..60));
+		assert_eq!(allocator.allocate(50), None);
+
+		allocator.free(b);
+		// the first range that fits.
+		assert_eq!(allocator.allocate(5), Some(10..15));
+		allocator.free(10..15);
+		allocator.free(a);
+		assert_eq!(allocator.free, [0..30, 60..100]);
+		allocator.free(c);
+		assert_eq!(allocator.free, [0..100]);
+	}
+
+	#[test]
+	fn growing_adds_to_the_end() {
+		let mut allocator = RangeAllocator::new(16);
+		assert_eq!(allocator.allocate(12), Some(0..12));
+		assert_eq!(allocator.allocate(8), None);
+		allocator.grow(32);
+		// the rest of the old size joins the new space.
+		assert_eq!(allocator.allocate(8), Some(12..20));
+		assert_eq!(allocator.free, [20..32]);
+		// empty meshes don't take any space.
+		assert_eq!(allocator.allocate(0), Some(0..0));
+		allocator.free(0..0);
+		assert_eq!(allocator.free, [20..32]);
+	}
+}
@@ -0,0 +1,256 @@
+use std::{cell::RefCell, collections::VecDeque, sync::{Arc, Mutex}};
+
+/// the average of the last few samples, so that the timings don't flicker.
+#[derive(Debug, Clone)]
+pub struct RollingAverage {
+	samples: VecDeque<f32>,
+	capacity: usize,
+}
+
+impl RollingAverage {
+	pub fn new(capacity: usize) -> Self {
+		Self { samples: VecDeque::with_capacity(capacity), capacity }
+	}
+
+	/// drops the oldest sample once there are `capacity` of them.
+	pub fn push(&mut self, sample: f32) {
+		if self.samples.len() == self.capacity {
+			self.samples.pop_front();
+		}
+		self.samples.push_back(sample);
+	}
+
+	/// 0 if there are no samples yet.
+	pub fn average(&self) -> f32 {
+		if self.samples.is_empty() {
+			0.0
+		} else {
+			self.samples.iter().sum::<f32>() / self.samples.len() as f32
+		}
+	}
+}
+
+/// named timings in milliseconds, in the order they were first recorded.
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+	timings: Vec<(String, RollingAverage)>,
+}
+
+impl Timings {
+	/// how many frames the timings are averaged over.
+	const WINDOW: usize = 60;
+
+	pub fn record(&mut self, name: &str, milliseconds: f32) {
+		match self.timings.iter_mut().find(|(other, _)| other == name) {
+			Some((_, average)) => average.push(milliseconds),
+			None => {
+				let mut average = RollingAverage::new(Self::WINDOW);
+				average.push(milliseconds);
+				self.timings.push((name.to_owned(), average));
+			}
+		}
+	}
+
+	/// records the cpu time since `start`.
+	pub fn record_since(&mut self, name: &str, start: std::time::Instant) {
+		self.record(name, start.elapsed().as_secs_f32() * 1000.0);
+	}
+
+	/// the averaged timings, in milliseconds.
+	pub fn iter(&self) -> impl Iterator<Item = (&str, f32)> {
+		self.timings.iter().map(|(name, average)| (name.as_str(), average.average()))
+	}
+}
+
+struct TimestampQueries {
+	query_set: wgpu::QuerySet,
+	resolve_buffer: wgpu::Buffer,
+	readback_buffer: wgpu::Buffer,
+	/// nanoseconds per timestamp tick.
+	period: f32,
+}
+
+/// set by the `map_async` callback of the readback buffer, see [`GpuProfiler::end_frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Readback {
+	Pending,
+	Mapped,
+	Failed,
+}
+
+/// the scopes that are being read back, and how far along the readback is.
+type InFlight = Option<(Vec<String>, Arc<Mutex<Readback>>)>;
+
+/// the scopes whose timestamps are mapped, `None` if there aren't any yet.
+/// a failed readback is dropped, so that the next frame is measured again.
+fn take_mapped(in_flight: &mut InFlight) -> Option<Vec<String>> {
+	let readback = *in_flight.as_ref()?.1.lock().unwrap();
+	match readback {
+		Readback::Pending => None,
+		Readback::Mapped => in_flight.take().map(|(scopes, _)| scopes),
+		Readback::Failed => {
+			eprintln!("couldn't read back the gpu timestamps");
+			*in_flight = None;
+			None
+		}
+	}
+}
+
+/// times the render graph's nodes on the gpu with timestamp queries.
+/// the timestamps are read back a frame or more later, frames in between aren't measured.
+/// does nothing if the device doesn't support `Features::TIMESTAMP_QUERY`.
+pub struct GpuProfiler {
+	queries: Option<TimestampQueries>,
+	/// the scopes written this frame, in query order, see [`GpuProfiler::begin_scope`].
+	/// a `RefCell`, since the graph is rendered with a shared `Gfx`.
+	scopes: RefCell<Vec<String>>,
+	in_flight: InFlight,
+	pub timings: Timings,
+}
+
+impl GpuProfiler {
+	/// each scope uses two queries, one at the start and one at the end.
+	const MAX_SCOPES: u32 = 32;
+
+	pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+		let queries = device.features().contains(wgpu::Features::TIMESTAMP_QUERY).then(|| {
+			let size = (Self::MAX_SCOPES * 2) as u64 * std::mem::size_of::<u64>() as u64;
+			TimestampQueries {
+				query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+					label: Some("Profiler Queries"),
+					ty: wgpu::QueryType::Timestamp,
+					count: Self::MAX_SCOPES * 2,
+				}),
+				resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+					label: Some("Profiler Resolve Buffer"),
+					size,
+					usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+					mapped_at_creation: false,
+				}),
+				readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+					label: Some("Profiler Readback Buffer"),
+					size,
+					usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+					mapped_at_creation: false,
+				}),
+				period: queue.get_timestamp_period(),
+			}
+		});
+
+		Self {
+			queries,
+			scopes: RefCell::new(Vec::new()),
+			in_flight: None,
+			timings: Timings::default(),
+		}
+	}
+
+	pub fn is_supported(&self) -> bool {
+		self.queries.is_some()
+	}
+
+	/// reads back the timestamps of an earlier frame, if they're ready.
+	pub fn begin_frame(&mut self, device: &wgpu::Device) {
+		self.scopes.borrow_mut().clear();
+
+		let queries = match &self.queries {
+			Some(queries) => queries,
+			None => return,
+		};
+
+		device.poll(wgpu::Maintain::Poll);
+		let Some(scopes) = take_mapped(&mut self.in_flight) else { return };
+		{
+			let data = queries.readback_buffer.slice(..).get_mapped_range();
+			let timestamps: &[u64] = bytemuck::cast_slice(&data);
+			for (index, name) in scopes.iter().enumerate() {
+				let ticks = timestamps[index * 2 + 1].saturating_sub(timestamps[index * 2]);
+				self.timings.record(name, ticks as f32 * queries.period / 1_000_000.0);
+			}
+		}
+		queries.readback_buffer.unmap();
+	}
+
+	/// writes the starting timestamp of `name`, returns the scope to pass to [`GpuProfiler::end_scope`].
+	/// `None` while the previous timestamps are still being read back.
+	pub fn begin_scope(&self, encoder: &mut wgpu::CommandEncoder, name: &str) -> Option<u32> {
+		let queries = self.queries.as_ref()?;
+		let mut scopes = self.scopes.borrow_mut();
+		if self.in_flight.is_some() || scopes.len() as u32 >= Self::MAX_SCOPES {
+			return None;
+		}
+
+		let scope = scopes.len() as u32;
+		scopes.push(name.to_owned());
+		encoder.write_timestamp(&queries.query_set, scope * 2);
+		Some(scope)
+	}
+
+	pub fn end_scope(&self, encoder: &mut wgpu::CommandEncoder, scope: Option<u32>) {
+		if let (Some(queries), Some(scope)) = (&self.queries, scope) {
+			encoder.write_timestamp(&queries.query_set, scope * 2 + 1);
+		}
+	}
+
+	/// copies this frame's timestamps to the readback buffer, run before submitting `encoder`.
+	pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+		let count = self.scopes.borrow().len() as u32 * 2;
+		if let Some(queries) = self.queries.as_ref().filter(|_| count > 0) {
+			encoder.resolve_query_set(&queries.query_set, 0..count, &queries.resolve_buffer, 0);
+			encoder.copy_buffer_to_buffer(&queries.resolve_buffer, 0, &queries.readback_buffer, 0, count as u64 * 8);
+		}
+	}
+
+	/// starts reading back the timestamps, run after submitting the encoder passed to [`GpuProfiler::resolve`].
+	pub fn end_frame(&mut self) {
+		let scopes = std::mem::take(self.scopes.get_mut());
+		if let Some(queries) = self.queries.as_ref().filter(|_| !scopes.is_empty()) {
+			let readback = Arc::new(Mutex::new(Readback::Pending));
+			let callback_readback = readback.clone();
+			queries.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+				*callback_readback.lock().unwrap() = if result.is_ok() { Readback::Mapped } else { Readback::Failed };
+			});
+			self.in_flight = Some((scopes, readback));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn timings_average_the_last_frames() {
+		let mut average = RollingAverage::new(3);
+		assert_eq!(average.average(), 0.0);
+		average.push(1.0);
+		average.push(2.0);
+		assert_eq!(average.average(), 1.5);
+		average.push(3.0);
+		average.push(7.0);
+		assert_eq!(average.average(), 4.0);
+
+		let mut timings = Timings::default();
+		timings.record("b", 2.0);
+		timings.record("a", 1.0);
+		timings.record("b", 4.0);
+		assert_eq!(timings.iter().collect::<Vec<_>>(), [("b", 3.0), ("a", 1.0)]);
+	}
+
+	#[test]
+	fn failed_readbacks_are_dropped() {
+		let readback = Arc::new(Mutex::new(Readback::Pending));
+		let mut in_flight: InFlight = Some((vec!["main".to_owned()], readback.clone()));
+		assert_eq!(take_mapped(&mut in_flight), None);
+		assert!(in_flight.is_some());
+
+		*readback.lock().unwrap() = Readback::Failed;
+		assert_eq!(take_mapped(&mut in_flight), None);
+		assert!(in_flight.is_none(), "the next frame should be measured");
+
+		in_flight = Some((vec!["main".to_owned()], Arc::new(Mutex::new(Readback::Mapped))));
+		assert_eq!(take_mapped(&mut in_flight), Some(vec!["main".to_owned()]));
+		assert!(in_flight.is_none());
+		assert_eq!(take_mapped(&mut in_flight), None);
+	}
+}
@@ -9,6 +9,7 @@ pub struct ScreenRect {
 	height: i32
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct Rect {
 	x: f32,
 	y: f32,
@@ -47,10 +48,16 @@ pub struct GuiVertex {
 	rgba: [u8; 4]
 }
 
+/// a bitmap font, see [`Font::from_atlas`].
 pub struct Font {
-
+	chars: HashMap<char, FontChar>,
+	/// drawn for the characters that aren't in the atlas.
+	fallback: FontChar,
+	height: i32,
+	scale: f32,
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct FontChar {
 	uvs: Rect,
 	width: i32,
@@ -62,19 +69,63 @@ impl FontChar {
 	pub fn scaled_width(&self, font: &Font) -> f32 { (self.width as f32 * font.scale()).round() }
 	pub fn scaled_height(&self, font: &Font) -> f32 { (self.height as f32 * font.scale()).round() }
 	pub fn scaled_advance_x(&self, font: &Font) -> f32 { (self.advance_x as f32 * font.scale()).round() }
+	/// normalized to the atlas' size.
+	pub fn uvs(&self) -> Rect { self.uvs }
 }
 
 impl Font {
+	/// the first character in the atlas, the rest follow in ascii order.
+	const FIRST_CHAR: char = ' ';
+	const ATLAS_COLUMNS: u32 = 16;
+	const ATLAS_ROWS: u32 = 6;
+
+	/// `atlas` is a grid of the printable ascii characters, from `' '` to `'~'`, 16 per row.
+	/// the glyphs are left-aligned in their cells, and their widths are measured from the opaque pixels.
+	pub fn from_atlas(atlas: &image::RgbaImage, scale: f32) -> Self {
+		let cell_width = atlas.width() / Self::ATLAS_COLUMNS;
+		let cell_height = atlas.height() / Self::ATLAS_ROWS;
+
+		let mut chars = HashMap::new();
+		for index in 0..Self::ATLAS_COLUMNS * Self::ATLAS_ROWS - 1 {
+			let c = char::from_u32(Self::FIRST_CHAR as u32 + index).unwrap();
+			let (cell_x, cell_y) = ((index % Self::ATLAS_COLUMNS) * cell_width, (index / Self::ATLAS_COLUMNS) * cell_height);
+
+			let width = (0..cell_width).rev()
+				.find(|&x| (0..cell_height).any(|y| atlas.get_pixel(cell_x + x, cell_y + y)[3] != 0))
+				.map_or(cell_width / 2, |x| x + 1);
+
+			chars.insert(c, FontChar {
+				uvs: Rect {
+					x: cell_x as f32 / atlas.width() as f32,
+					y: cell_y as f32 / atlas.height() as f32,
+					width: width as f32 / atlas.width() as f32,
+					height: cell_height as f32 / atlas.height() as f32,
+				},
+				width: width as i32,
+				height: cell_height as i32,
+				// one pixel between the glyphs.
+				advance_x: width as i32 + 1,
+			});
+		}
+
+		Self {
+			fallback: chars[&'?'],
+			chars,
+			height: cell_height as i32,
+			scale,
+		}
+	}
+
 	pub fn scale(&self) -> f32 {
-		todo!()
+		self.scale
 	}
 
-	pub fn char(&self, _c: char) -> FontChar {
-		todo!()
+	pub fn char(&self, c: char) -> FontChar {
+		*self.chars.get(&c).unwrap_or(&self.fallback)
 	}
 
 	pub fn scaled_height(&self) -> i32 {
-		todo!()
+		(self.height as f32 * self.scale).round() as i32
 	}
 
 	pub fn scaled_text_width(&self, text: &str) -> i32 {
@@ -124,12 +175,12 @@ impl Builder {
 
 		for c in text.chars() {
 			let ch = font.char(c);
-			x += ch.scaled_advance_x(font) as i32;
 			self.rect(ScreenRect {
 				x, y,
 				width: ch.scaled_width(font) as i32,
 				height: ch.scaled_height(font) as i32,
 			}, ch.uvs, rgba);
+			x += ch.scaled_advance_x(font) as i32;
 			// TODO: kerning
 		}
 	}
@@ -254,3 +305,32 @@ impl Context {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn text_width_adds_up_the_advances() {
+		// 8x8 cells, 'A' is 3 pixels wide, 'i' is 1, the rest are empty.
+		let mut atlas = image::RgbaImage::new(16 * 8, 6 * 8);
+		let mut fill = |c: char, width: u32| {
+			let index = c as u32 - ' ' as u32;
+			let (x, y) = ((index % 16) * 8, (index / 16) * 8);
+			for dx in 0..width {
+				atlas.put_pixel(x + dx, y + 2, image::Rgba([255; 4]));
+			}
+		};
+		fill('A', 3);
+		fill('i', 1);
+
+		let font = Font::from_atlas(&atlas, 2.0);
+		assert_eq!(font.scaled_height(), 16);
+		assert_eq!(font.scaled_text_width(""), 0);
+		// the empty glyphs are half a cell wide, every glyph is followed by a pixel.
+		assert_eq!(font.scaled_text_width("A"), 8);
+		assert_eq!(font.scaled_text_width("Ai "), 8 + 4 + 10);
+		// unknown characters are drawn as '?'.
+		assert_eq!(font.scaled_text_width("é"), font.scaled_text_width("?"));
+	}
+}
@@ -2,6 +2,8 @@
 #![feature(const_trait_impl)]
 #![feature(new_uninit)]
 
+use std::collections::HashMap;
+
 use math::{Vec2f32, vec2};
 use state::State;
 use winit::{
@@ -16,6 +18,11 @@ mod game;
 mod polyfill;
 mod imgui;
 mod math;
+mod bindings;
+mod gamepad;
+mod menu;
+mod error;
+mod assets;
 
 extern crate nalgebra_glm as glm;
 
@@ -58,42 +65,72 @@ impl InputElementState {
 	pub fn just_released(&self) -> bool {
 		*self == Self::JustReleased
 	}
+
+	/// the state on the next frame, if nothing happens.
+	fn next_frame(self) -> Self {
+		match self {
+			Self::JustPressed => Self::Held,
+			Self::JustReleased => Self::None,
+			_ => self
+		}
+	}
 }
 
-#[derive(Clone, Copy)]
 pub struct Input {
-	keys: [InputElementState; 256],
+	/// keys that were never pressed aren't in the map.
+	keys: HashMap<KeyCode, InputElementState>,
 	buttons: [InputElementState; 256],
 	mouse_delta: Vec2f32,
+	/// in lines, positive is away from the user.
+	scroll_delta: f32,
 	close_requested: bool,
+	bindings: bindings::InputBindings,
+	gamepad: gamepad::Gamepad,
 }
 
 impl Input {
-	fn new() -> Self {
+	fn new(bindings: bindings::InputBindings) -> Self {
 		Self {
-			keys: [InputElementState::None; 256],
+			bindings,
+			gamepad: gamepad::Gamepad::new(),
+			keys: HashMap::new(),
 			buttons: [InputElementState::None; 256],
 			mouse_delta: vec2(0.0, 0.0),
+			scroll_delta: 0.0,
 			close_requested: false,
 		}
 	}
 
+	/// touchpads scroll in pixels, this converts them to (roughly) lines.
+	const PIXELS_PER_SCROLL_LINE: f32 = 20.0;
+
+	/// the key releases are missed while the window isn't focused, so nothing stays held.
+	fn release_all(&mut self) {
+		self.keys.clear();
+		self.buttons = [InputElementState::None; 256];
+	}
+
 	fn reset_deltas(&mut self) {
 		self.mouse_delta = vec2(0.0, 0.0);
-		for key in &mut self.keys {
-			*key = match *key {
-				InputElementState::JustPressed => InputElementState::Held,
-				InputElementState::JustReleased => InputElementState::None,
-				_ => *key
-			}
+		self.scroll_delta = 0.0;
+		for key in self.keys.values_mut() {
+			*key = key.next_frame();
 		}
 		for button in &mut self.buttons {
-			*button = match *button {
-				InputElementState::JustPressed => InputElementState::Held,
-				InputElementState::JustReleased => InputElementState::None,
-				_ => *button
-			}
+			*button = button.next_frame();
 		}
+		self.gamepad.reset_deltas();
+	}
+
+	/// mouse delta (in pixels) for a fully deflected look stick, per second.
+	const STICK_LOOK_SPEED: f32 = 720.0;
+
+	/// should be called once per frame, before the update, `dt` is the time since the last frame.
+	fn poll_gamepad(&mut self, dt: f32) {
+		self.gamepad.poll();
+		// the look stick goes through the same path as the mouse, which has y going down.
+		let look = self.gamepad.look();
+		self.mouse_delta += vec2(look.x, -look.y) * Self::STICK_LOOK_SPEED * dt;
 	}
 
 	fn process_event(&mut self, event: &winit::event::Event<()>) -> bool {
@@ -101,8 +138,9 @@ impl Input {
 			Event::DeviceEvent { event, .. } => {
 				match *event {
 					DeviceEvent::MouseMotion { delta } => {
-						let new_mouse_delta = vec2(delta.0, delta.1).each_as();
-						self.mouse_delta = self.mouse_delta.lerp(new_mouse_delta, 0.5);
+						// summed up, so that the total doesn't depend on how many events arrive per frame.
+						let new_mouse_delta: Vec2f32 = vec2(delta.0, delta.1).each_as();
+						self.mouse_delta += new_mouse_delta;
 						true
 					},
 					_ => false
@@ -119,6 +157,13 @@ impl Input {
 				// 	self.last_mouse_pos = cur_mouse_pos;
 				// 	true
 				// },
+				WindowEvent::MouseWheel { delta, .. } => {
+					self.scroll_delta += match *delta {
+						MouseScrollDelta::LineDelta(_, y) => y,
+						MouseScrollDelta::PixelDelta(position) => position.y as f32 / Self::PIXELS_PER_SCROLL_LINE,
+					};
+					true
+				},
 				WindowEvent::MouseInput { state, button, .. } => {
 					let index = match button {
 						MouseButton::Left => 0,
@@ -136,7 +181,7 @@ impl Input {
 					event: KeyEvent { physical_key: PhysicalKey::Code(key), state, repeat: false, .. },
 					..
 				} => {
-					self.keys[*key as usize] = InputElementState::from_old_new(self.keys[*key as usize], *state);
+					self.process_key(*key, *state);
 					true
 				}
 				_ => false
@@ -145,8 +190,27 @@ impl Input {
 		}
 	}
 
+	fn process_key(&mut self, key: KeyCode, state: ElementState) {
+		let old = self.key(key);
+		self.keys.insert(key, InputElementState::from_old_new(old, state));
+	}
+
 	fn key(&self, key: KeyCode) -> InputElementState {
-		self.keys[key as usize]
+		self.keys.get(&key).copied().unwrap_or(InputElementState::None)
+	}
+
+	/// the state of the key bound to `action`, `None` if it's unbound.
+	fn action(&self, action: bindings::Action) -> InputElementState {
+		self.bindings.key(action).map_or(InputElementState::None, |key| self.key(key))
+	}
+
+	fn gamepad_button(&self, button: gilrs::Button) -> InputElementState {
+		self.gamepad.button(button)
+	}
+
+	/// left stick, x is right, y is forward.
+	pub fn gamepad_movement(&self) -> Vec2f32 {
+		self.gamepad.movement()
 	}
 
 	fn button(&self, button: u32) -> InputElementState {
@@ -157,15 +221,26 @@ impl Input {
 		self.close_requested
 	}
 
+	/// accumulated over the frame, in pixels.
 	pub fn mouse_delta(&self) -> Vec2f32 {
 		self.mouse_delta
 	}
+
+	/// accumulated over the frame, in lines.
+	pub fn scroll_delta(&self) -> f32 {
+		self.scroll_delta
+	}
 }
 
 pub struct Window {
 	input: Box<Input>,
 	window: winit::window::Window,
-	capture_cursor: bool
+	capture_cursor: bool,
+	focused: bool,
+	/// whether to capture the cursor again once the window is focused, see [`Window::set_focused`].
+	recapture_cursor: bool,
+	/// the size to go back to when leaving fullscreen, `None` while windowed.
+	windowed_size: Option<winit::dpi::PhysicalSize<u32>>,
 }
 
 impl Window {
@@ -194,11 +269,91 @@ impl Window {
 
 		self.capture_cursor = capture;
 	}
+
+	/// switches between borderless fullscreen and the window's previous size.
+	/// the surface is resized when the `Resized` event arrives.
+	pub fn toggle_fullscreen(&mut self) {
+		match toggle_windowed_size(&mut self.windowed_size, self.window.inner_size()) {
+			Some(size) => {
+				self.window.set_fullscreen(None);
+				let _ = self.window.request_inner_size(size);
+			}
+			None => self.window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(None))),
+		}
+	}
+
+	pub fn focused(&self) -> bool {
+		self.focused
+	}
+
+	/// releases the cursor while the window isn't focused, and captures it again once it is.
+	fn set_focused(&mut self, focused: bool) {
+		if self.focused == focused {
+			return;
+		}
+
+		self.focused = focused;
+		let (capture, recapture) = focus_cursor_capture(focused, self.capture_cursor, self.recapture_cursor);
+		self.recapture_cursor = recapture;
+		self.capture_cursor(capture);
+		if !focused {
+			self.input.release_all();
+		}
+	}
+}
+
+/// remembers the window's `size` when going fullscreen, `windowed_size` is `None` while windowed.
+/// returns the size to go back to when leaving fullscreen, `None` when entering it.
+fn toggle_windowed_size(
+	windowed_size: &mut Option<winit::dpi::PhysicalSize<u32>>,
+	size: winit::dpi::PhysicalSize<u32>,
+) -> Option<winit::dpi::PhysicalSize<u32>> {
+	match windowed_size.take() {
+		Some(windowed_size) => Some(windowed_size),
+		None => {
+			*windowed_size = Some(size);
+			None
+		}
+	}
+}
+
+/// whether the cursor is captured after the window's focus changed to `focused`,
+/// and whether to capture it again once the window is focused, see [`Window::set_focused`].
+fn focus_cursor_capture(focused: bool, capture_cursor: bool, recapture_cursor: bool) -> (bool, bool) {
+	if focused {
+		(capture_cursor || recapture_cursor, recapture_cursor)
+	} else {
+		(false, capture_cursor)
+	}
+}
+
+/// in seconds. longer frames (e.g. after a stall or while dragging the window)
+/// are updated as if they were this long, so that nothing moves too far at once.
+const MAX_DT: f32 = 0.1;
+
+/// the time since the last frame that updates see, at most [`MAX_DT`].
+fn frame_dt(elapsed: std::time::Duration) -> f32 {
+	elapsed.as_secs_f32().min(MAX_DT)
+}
+
+/// in seconds, the step of [`State::fixed_update`].
+const FIXED_DT: f32 = 1.0 / 60.0;
+
+/// adds `dt` to the time that hasn't been simulated yet, and returns how many fixed steps to take for it.
+/// the rest is left in `fixed_time`.
+fn fixed_steps(fixed_time: &mut f32, dt: f32) -> u32 {
+	*fixed_time += dt;
+	let steps = (*fixed_time / FIXED_DT) as u32;
+	*fixed_time = (*fixed_time - steps as f32 * FIXED_DT).max(0.0);
+	steps
 }
 
 pub struct UpdateContext<'a> {
 	gfx: &'a mut gfx::Gfx,
 	pub dt: f32,
+	/// how far this update is from the last [`State::fixed_update`] to the next one, 0 to 1.
+	/// things moved in fixed updates are drawn this far between their last two positions.
+	pub fixed_alpha: f32,
 }
 
 impl UpdateContext<'_> {
@@ -221,25 +376,43 @@ async fn run() {
 	let event_loop = EventLoop::new().unwrap();
 	
 	let mut gfx = Box::new(gfx::Gfx::new(Window {
-		input: Box::new(Input::new()),
+		input: Box::new(Input::new(
+			bindings::InputBindings::load(&assets::asset_path("bindings.json"))
+				.unwrap_or_else(|error| panic!("couldn't load the bindings: {}", error))
+		)),
 		window: WindowBuilder::new()
 			.with_inner_size(winit::dpi::PhysicalSize::new(1280, 720))
 			.build(&event_loop)
 			.unwrap(),
-		capture_cursor: false
+		capture_cursor: false,
+		focused: true,
+		recapture_cursor: false,
+		windowed_size: None,
 	}).await);
 	
-	let mut state = game::GameState::new(&gfx);
+	// `--seed <seed>` and `--flat` fill in the menu.
+	let seed = std::env::args()
+		.skip_while(|arg| arg != "--seed")
+		.nth(1)
+		.unwrap_or_else(|| game::DEFAULT_SEED.to_string());
+	let world_type = if std::env::args().any(|arg| arg == "--flat") {
+		game::WorldType::Flat
+	} else {
+		game::WorldType::Default
+	};
+	let mut state = state::StateStack::new();
 
 	{
 		let mut context = LoadContext { gfx: &mut gfx };
-		state.load(&mut context);
+		state.push(Box::new(menu::MenuState::new(seed, world_type)), &mut context);
 	}
 
 	event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
 	
 	let event_loop_start = std::time::Instant::now();
 	let mut last_render_time = std::time::Instant::now();
+	// time that hasn't been simulated by `fixed_update` yet, less than `FIXED_DT`.
+	let mut fixed_time = 0.0;
 	event_loop.run(move |event, elwt| {
 		if !gfx.window.capture_cursor {
 			gfx.egui_platform.handle_event(&gfx.window.window, &event);
@@ -264,9 +437,14 @@ async fn run() {
 				match event {
 					WindowEvent::Resized(physical_size) => {
 						gfx.resize(*physical_size);
+						state.resize(&gfx);
+					}
+					WindowEvent::Focused(focused) => {
+						gfx.window_mut().set_focused(*focused);
 					}
 					WindowEvent::ScaleFactorChanged { .. } => {
 						gfx.resize(gfx.window.window.inner_size());
+						state.resize(&gfx);
 					}
 					WindowEvent::CursorMoved { .. } if gfx.window().capture_cursor => {
 						// TODO: do we need this?
@@ -282,16 +460,36 @@ async fn run() {
 				let now = std::time::Instant::now();
 				let dt = now - last_render_time;
 				last_render_time = now;
+
+				let dt = frame_dt(dt);
+				gfx.window_mut().input.poll_gamepad(dt);
 				
-				{
+				// the simulation is paused while the window isn't focused.
+				if gfx.window().focused() {
+					for _ in 0..fixed_steps(&mut fixed_time, dt) {
+						state.fixed_update(&mut UpdateContext { gfx: &mut gfx, dt: FIXED_DT, fixed_alpha: 0.0 });
+					}
+
 					let mut context = UpdateContext {
 						gfx: &mut gfx,
-						dt: dt.as_secs_f32()
+						dt,
+						fixed_alpha: fixed_time / FIXED_DT,
 					};
 					
 					state.update(&mut context);
 				}
 
+				if gfx.window().input().action(bindings::Action::ToggleFullscreen).just_pressed() {
+					gfx.window_mut().toggle_fullscreen();
+				}
+
+				if gfx.window().input().action(bindings::Action::Screenshot).just_pressed() {
+					let timestamp = std::time::SystemTime::now()
+						.duration_since(std::time::UNIX_EPOCH)
+						.map_or(0, |duration| duration.as_millis());
+					gfx.capture_frame(format!("screenshots/{}.png", timestamp));
+				}
+
 				gfx.egui_platform.update_time(event_loop_start.elapsed().as_secs_f64());
 				
 				match gfx.render(&state) {
@@ -314,3 +512,110 @@ fn main() {
 	env_logger::init();
 	pollster::block_on(run());
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn window_event(event: WindowEvent) -> Event<()> {
+		Event::WindowEvent { window_id: unsafe { winit::window::WindowId::dummy() }, event }
+	}
+
+	fn wheel(delta: MouseScrollDelta) -> Event<()> {
+		window_event(WindowEvent::MouseWheel { device_id: unsafe { DeviceId::dummy() }, delta, phase: TouchPhase::Moved })
+	}
+
+	#[test]
+	fn scrolling_adds_up_until_the_next_frame() {
+		let mut input = Input::new(bindings::InputBindings::default());
+		assert!(input.process_event(&wheel(MouseScrollDelta::LineDelta(0.0, 1.0))));
+		input.process_event(&wheel(MouseScrollDelta::LineDelta(0.0, 2.0)));
+		// a line down.
+		input.process_event(&wheel(MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition::new(0.0, -20.0))));
+		assert_eq!(input.scroll_delta(), 2.0);
+
+		input.reset_deltas();
+		assert_eq!(input.scroll_delta(), 0.0);
+	}
+
+	#[test]
+	fn focus_releases_and_recaptures_the_cursor() {
+		// captured by the game, then alt-tabbed away from and back to.
+		let (capture, recapture) = focus_cursor_capture(false, true, false);
+		assert_eq!((capture, recapture), (false, true));
+		assert_eq!(focus_cursor_capture(true, capture, recapture), (true, true));
+
+		// in the menu, the cursor stays free.
+		let (capture, recapture) = focus_cursor_capture(false, false, true);
+		assert_eq!((capture, recapture), (false, false));
+		assert_eq!(focus_cursor_capture(true, capture, recapture), (false, false));
+	}
+
+	#[test]
+	fn stalls_are_capped() {
+		use std::time::Duration;
+		assert_eq!(frame_dt(Duration::from_millis(16)), 0.016);
+		assert_eq!(frame_dt(Duration::from_secs(5)), MAX_DT);
+		assert_eq!(frame_dt(Duration::from_secs_f32(MAX_DT)), MAX_DT);
+	}
+
+	#[test]
+	fn a_second_takes_sixty_fixed_steps() {
+		for frame_rate in [30, 59, 60, 144, 1000] {
+			let mut fixed_time = 0.0;
+			let steps: u32 = (0..frame_rate).map(|_| fixed_steps(&mut fixed_time, 1.0 / frame_rate as f32)).sum();
+			assert!((59..=60).contains(&steps), "{} steps at {} fps", steps, frame_rate);
+			assert!((0.0..FIXED_DT).contains(&fixed_time));
+		}
+
+		// the rest is carried over to the next frame.
+		let mut fixed_time = 0.0;
+		assert_eq!(fixed_steps(&mut fixed_time, FIXED_DT * 0.75), 0);
+		assert_eq!(fixed_steps(&mut fixed_time, FIXED_DT * 0.75), 1);
+		assert!((fixed_time - FIXED_DT * 0.5).abs() < 1e-6);
+	}
+
+	#[test]
+	fn leaving_fullscreen_restores_the_window_size() {
+		use winit::dpi::PhysicalSize;
+		let mut windowed_size = None;
+		assert_eq!(toggle_windowed_size(&mut windowed_size, PhysicalSize::new(1280, 720)), None);
+		assert_eq!(windowed_size, Some(PhysicalSize::new(1280, 720)));
+
+		// the fullscreen size isn't remembered.
+		assert_eq!(toggle_windowed_size(&mut windowed_size, PhysicalSize::new(1920, 1080)), Some(PhysicalSize::new(1280, 720)));
+		assert_eq!(windowed_size, None);
+
+		assert_eq!(toggle_windowed_size(&mut windowed_size, PhysicalSize::new(800, 600)), None);
+		assert_eq!(toggle_windowed_size(&mut windowed_size, PhysicalSize::new(1920, 1080)), Some(PhysicalSize::new(800, 600)));
+	}
+
+	#[test]
+	fn high_key_codes_are_tracked() {
+		let mut input = Input::new(bindings::InputBindings::default());
+		// the last of the key codes.
+		let key = KeyCode::F35;
+		assert_eq!(input.key(key), InputElementState::None);
+
+		input.process_key(key, ElementState::Pressed);
+		assert_eq!(input.key(key), InputElementState::JustPressed);
+		input.reset_deltas();
+		assert_eq!(input.key(key), InputElementState::Held);
+		input.process_key(key, ElementState::Released);
+		assert_eq!(input.key(key), InputElementState::JustReleased);
+		assert_eq!(input.key(KeyCode::LaunchApp2), InputElementState::None);
+	}
+
+	#[test]
+	fn rebinding_changes_the_key() {
+		let mut input = Input::new(bindings::InputBindings::default());
+		input.bindings.bind(bindings::Action::ToggleWireframe, KeyCode::KeyH);
+		input.process_key(KeyCode::KeyG, ElementState::Pressed);
+		assert!(!input.action(bindings::Action::ToggleWireframe).held());
+		input.process_key(KeyCode::KeyH, ElementState::Pressed);
+		assert!(input.action(bindings::Action::ToggleWireframe).just_pressed());
+		// the other actions keep their defaults.
+		input.process_key(KeyCode::KeyW, ElementState::Pressed);
+		assert!(input.action(bindings::Action::MoveForward).just_pressed());
+	}
+}
@@ -33,6 +33,131 @@ impl<T: Scalar, const N: usize> Vector<T, N> {
 		// let other = other.into();
 		Vector::<U, N>::make(|i| f(self.0[i], other.0[i]))
 	}
+
+	pub fn as_slice(&self) -> &[T] {
+		&self.0
+	}
+
+	pub fn as_mut_slice(&mut self) -> &mut [T] {
+		&mut self.0
+	}
+
+	pub fn iter(&self) -> std::slice::Iter<'_, T> {
+		self.0.iter()
+	}
+
+	pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+		self.0.iter_mut()
+	}
+
+	/// takes the first `N` items, `None` if there are fewer.
+	pub fn try_from_iter<I: IntoIterator<Item = T>>(iter: I) -> Option<Self> {
+		let items: Vec<T> = iter.into_iter().take(N).collect();
+		items.try_into().ok().map(Vector)
+	}
+}
+
+impl<T: Scalar, const N: usize> IntoIterator for Vector<T, N> {
+	type Item = T;
+	type IntoIter = std::array::IntoIter<T, N>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.into_iter()
+	}
+}
+
+impl<'a, T: Scalar, const N: usize> IntoIterator for &'a Vector<T, N> {
+	type Item = &'a T;
+	type IntoIter = std::slice::Iter<'a, T>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.iter()
+	}
+}
+
+impl<'a, T: Scalar, const N: usize> IntoIterator for &'a mut Vector<T, N> {
+	type Item = &'a mut T;
+	type IntoIter = std::slice::IterMut<'a, T>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.iter_mut()
+	}
+}
+
+/// a slice of the wrong length was converted into a [`Vector`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VectorLengthError {
+	pub expected: usize,
+	pub found: usize,
+}
+
+impl std::fmt::Display for VectorLengthError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "expected {} components, found {}", self.expected, self.found)
+	}
+}
+
+impl std::error::Error for VectorLengthError {}
+
+impl<T: Scalar, const N: usize> TryFrom<&[T]> for Vector<T, N> {
+	type Error = VectorLengthError;
+
+	fn try_from(value: &[T]) -> Result<Self, Self::Error> {
+		<[T; N]>::try_from(value)
+			.map(Vector)
+			.map_err(|_| VectorLengthError { expected: N, found: value.len() })
+	}
+}
+
+impl<T: Scalar, const N: usize> From<Vector<T, N>> for [T; N] {
+	fn from(value: Vector<T, N>) -> Self {
+		value.0
+	}
+}
+
+/// as a tuple of `N` components, i.e. an array in most formats.
+#[cfg(feature = "serde")]
+impl<T: Scalar + serde::Serialize, const N: usize> serde::Serialize for Vector<T, N> {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		use serde::ser::SerializeTuple;
+		let mut tuple = serializer.serialize_tuple(N)?;
+		for c in self.iter() {
+			tuple.serialize_element(c)?;
+		}
+		tuple.end()
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Scalar + serde::Deserialize<'de>, const N: usize> serde::Deserialize<'de> for Vector<T, N> {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct VectorVisitor<T, const N: usize>(std::marker::PhantomData<T>);
+
+		impl<'de, T: Scalar + serde::Deserialize<'de>, const N: usize> serde::de::Visitor<'de> for VectorVisitor<T, N> {
+			type Value = Vector<T, N>;
+
+			fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+				write!(f, "a vector of {} components", N)
+			}
+
+			fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+				let mut components = Vec::with_capacity(N);
+				for i in 0..N {
+					components.push(seq.next_element()?.ok_or_else(|| serde::de::Error::invalid_length(i, &self))?);
+				}
+				Ok(Vector::try_from(components.as_slice()).unwrap())
+			}
+		}
+
+		deserializer.deserialize_tuple(N, VectorVisitor(std::marker::PhantomData))
+	}
+}
+
+/// panics if there are fewer than `N` items, see [`Vector::try_from_iter`].
+impl<T: Scalar, const N: usize> FromIterator<T> for Vector<T, N> {
+	fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+		Self::try_from_iter(iter).expect("not enough items for the vector")
+	}
 }
 
 type DotProductOutput<T> = <<T as Mul>::Output as Add>::Output;
@@ -79,6 +204,25 @@ macro_rules! mag_impl_for_vec {
 	};
 }
 
+macro_rules! reflect_impl_for_vec {
+	($T:ty) => {
+		impl<const N: usize> Vector<$T, N> {
+			/// mirrors the vector off a surface, `normal` should be normalized.
+			pub fn reflect(self, normal: Self) -> Self {
+				self - normal * (2.0 * self.dot(&normal))
+			}
+
+			/// the part of the vector that points along `other`, which can't be zero.
+			pub fn project_onto(self, other: Self) -> Self {
+				other * (self.dot(&other) / other.mag_squared())
+			}
+		}
+	};
+}
+
+reflect_impl_for_vec!(f32);
+reflect_impl_for_vec!(f64);
+
 mag_impl_for_vec!(f32, f32);
 mag_impl_for_vec!(f64, f64);
 mag_impl_for_vec!(i8, f32);
@@ -195,6 +339,12 @@ macro_rules! impl_deref_for_vec {
 				unsafe { &*(self.0.as_ptr() as *const Self::Target) }
 			}
 		}
+
+		impl<T: Scalar> DerefMut for Vector<T, $n> {
+			fn deref_mut(&mut self) -> &mut Self::Target {
+				unsafe { &mut *(self.0.as_mut_ptr() as *mut Self::Target) }
+			}
+		}
 	};
 }
 
@@ -308,11 +458,59 @@ macro_rules! impl_swizzles_3d_for_vec {
 	};
 }
 
+/// writes the components of `value` into the named ones, e.g. `set_xz` leaves `y` alone.
+macro_rules! impl_swizzle_setter_for_vec {
+	($n:literal <- $m:literal : $name:ident => $($fields:ident),+) => {
+		impl<T: Scalar> Vector<T, $n> {
+			pub fn $name(&mut self, value: Vector<T, $m>) {
+				let mut components = value.into_iter();
+				$(self.$fields = components.next().unwrap();)+
+			}
+		}
+	};
+}
+
+macro_rules! impl_swizzle_setters_for_vec {
+	(2) => {
+		impl_swizzle_setter_for_vec!(2 <- 2: set_xy => x, y);
+		impl_swizzle_setter_for_vec!(2 <- 2: set_yx => y, x);
+	};
+	(3) => {
+		impl_swizzle_setter_for_vec!(3 <- 2: set_xy => x, y);
+		impl_swizzle_setter_for_vec!(3 <- 2: set_yx => y, x);
+		impl_swizzle_setter_for_vec!(3 <- 2: set_xz => x, z);
+		impl_swizzle_setter_for_vec!(3 <- 2: set_zx => z, x);
+		impl_swizzle_setter_for_vec!(3 <- 2: set_yz => y, z);
+		impl_swizzle_setter_for_vec!(3 <- 2: set_zy => z, y);
+		impl_swizzle_setter_for_vec!(3 <- 3: set_xyz => x, y, z);
+		impl_swizzle_setter_for_vec!(3 <- 3: set_xzy => x, z, y);
+		impl_swizzle_setter_for_vec!(3 <- 3: set_yxz => y, x, z);
+		impl_swizzle_setter_for_vec!(3 <- 3: set_yzx => y, z, x);
+		impl_swizzle_setter_for_vec!(3 <- 3: set_zxy => z, x, y);
+		impl_swizzle_setter_for_vec!(3 <- 3: set_zyx => z, y, x);
+	};
+	(4) => {
+		impl_swizzle_setter_for_vec!(4 <- 2: set_xy => x, y);
+		impl_swizzle_setter_for_vec!(4 <- 2: set_xz => x, z);
+		impl_swizzle_setter_for_vec!(4 <- 2: set_xw => x, w);
+		impl_swizzle_setter_for_vec!(4 <- 2: set_yz => y, z);
+		impl_swizzle_setter_for_vec!(4 <- 2: set_yw => y, w);
+		impl_swizzle_setter_for_vec!(4 <- 2: set_zw => z, w);
+		impl_swizzle_setter_for_vec!(4 <- 3: set_xyz => x, y, z);
+		impl_swizzle_setter_for_vec!(4 <- 3: set_xyw => x, y, w);
+		impl_swizzle_setter_for_vec!(4 <- 3: set_xzw => x, z, w);
+		impl_swizzle_setter_for_vec!(4 <- 3: set_yzw => y, z, w);
+	};
+}
+
 impl_swizzles_2d_for_vec!(2, 2);
 impl_swizzles_2d_for_vec!(3, 3);
 impl_swizzles_2d_for_vec!(4, 4);
 impl_swizzles_3d_for_vec!(3, 3);
 impl_swizzles_3d_for_vec!(4, 4);
+impl_swizzle_setters_for_vec!(2);
+impl_swizzle_setters_for_vec!(3);
+impl_swizzle_setters_for_vec!(4);
 
 impl<T: Scalar, const N: usize> From<T> for Vector<T, N> {
 	fn from(value: T) -> Self {
@@ -443,6 +641,8 @@ impl<T: Scalar + Display, const N: usize> std::fmt::Display for Vector<T, N> {
 	}
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Serialize, serde_derive::Deserialize))]
 pub struct Rect<T: Scalar + Add<Output = T>> {
 	pub x: T,
 	pub y: T,
@@ -495,6 +695,52 @@ where
 
 // TODO: matrix types
 
+/// the six planes of a view frustum, as `ax + by + cz + d >= 0` for points inside.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+	pub planes: [Vec4f32; 6]
+}
+
+impl Frustum {
+	/// extracts the planes from a column-major view-projection matrix
+	/// with a 0..1 depth range (Gribb & Hartmann).
+	pub fn from_view_proj(m: &[[f32; 4]; 4]) -> Self {
+		let row = |i: usize| vec4(m[0][i], m[1][i], m[2][i], m[3][i]);
+		let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+		Self {
+			planes: [
+				r3 + r0, // left
+				r3 - r0, // right
+				r3 + r1, // bottom
+				r3 - r1, // top
+				r2,      // near
+				r3 - r2, // far
+			]
+		}
+	}
+
+	fn plane_distance(plane: Vec4f32, point: Vec3f32) -> f32 {
+		plane.xyz().dot(&point) + plane.w
+	}
+
+	pub fn contains_point(&self, point: Vec3f32) -> bool {
+		self.planes.iter().all(|plane| Self::plane_distance(*plane, point) >= 0.0)
+	}
+
+	/// conservative, may return true for boxes that are just outside of a corner.
+	pub fn intersects_aabb(&self, min: Vec3f32, max: Vec3f32) -> bool {
+		self.planes.iter().all(|plane| {
+			// the corner that's furthest along the plane normal.
+			let positive = vec3(
+				if plane.x >= 0.0 { max.x } else { min.x },
+				if plane.y >= 0.0 { max.y } else { min.y },
+				if plane.z >= 0.0 { max.z } else { min.z },
+			);
+			Self::plane_distance(*plane, positive) >= 0.0
+		})
+	}
+}
+
 pub fn ortho_matrix(
 	left: f32, right: f32,
 	bottom: f32, top: f32,
@@ -510,3 +756,93 @@ pub fn ortho_matrix(
 		],
 	]
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn frustum_planes() {
+		// moves everything 1 to the right, so the frustum is x in -2..0, y in -1..1 and z in 0..1.
+		let view_proj = [
+			[1.0, 0.0, 0.0, 0.0],
+			[0.0, 1.0, 0.0, 0.0],
+			[0.0, 0.0, 1.0, 0.0],
+			[1.0, 0.0, 0.0, 1.0],
+		];
+		let frustum = Frustum::from_view_proj(&view_proj);
+		assert_eq!(frustum.planes[0], vec4(1.0, 0.0, 0.0, 2.0));
+		assert_eq!(frustum.planes[1], vec4(-1.0, 0.0, 0.0, 0.0));
+		assert_eq!(frustum.planes[4], vec4(0.0, 0.0, 1.0, 0.0));
+		assert_eq!(frustum.planes[5], vec4(0.0, 0.0, -1.0, 1.0));
+
+		assert!(frustum.contains_point(vec3(-1.0, 0.0, 0.5)));
+		assert!(!frustum.contains_point(vec3(0.5, 0.0, 0.5)));
+		assert!(!frustum.contains_point(vec3(-1.0, 1.5, 0.5)));
+		assert!(!frustum.contains_point(vec3(-1.0, 0.0, -0.5)));
+
+		assert!(frustum.intersects_aabb(vec3(-0.5, -0.5, 0.25), vec3(3.0, 0.5, 0.75)));
+		assert!(!frustum.intersects_aabb(vec3(0.5, -0.5, 0.25), vec3(3.0, 0.5, 0.75)));
+	}
+
+	#[test]
+	fn vectors_are_iterable() {
+		let v = vec3(1, -2, 5);
+		assert_eq!(v.iter().sum::<i32>(), v.x + v.y + v.z);
+		assert_eq!(v.into_iter().collect::<Vec<_>>(), vec![1, -2, 5]);
+		assert_eq!(Vec3i32::try_from_iter(v), Some(v));
+		// extra items are ignored, missing ones aren't.
+		assert_eq!(Vec2::<i32>::try_from_iter(v), Some(vec2(1, -2)));
+		assert_eq!(Vec3i32::try_from_iter([1, 2]), None);
+
+		let mut v = v;
+		for c in v.iter_mut() { *c *= 2; }
+		assert_eq!(v, vec3(2, -4, 10));
+	}
+
+	#[test]
+	fn swizzle_setters_leave_the_other_components() {
+		let mut v = vec3(1, 2, 3);
+		v.set_xz(vec2(7, 9));
+		assert_eq!(v, vec3(7, 2, 9));
+		v.set_zx(vec2(4, 5));
+		assert_eq!(v, vec3(5, 2, 4));
+		v.set_zyx(vec3(1, 2, 3));
+		assert_eq!(v, vec3(3, 2, 1));
+	}
+
+	#[test]
+	fn reflecting_and_projecting() {
+		let v = vec3(1.0f32, -2.0, 3.0);
+		assert_eq!(v.reflect(vec3(0.0, 1.0, 0.0)), vec3(1.0, 2.0, 3.0));
+		assert_eq!(v.reflect(vec3(-1.0, 0.0, 0.0)), vec3(-1.0, -2.0, 3.0));
+		assert_eq!(v.project_onto(vec3(0.0, 0.0, 2.0)), vec3(0.0, 0.0, 3.0));
+		assert_eq!(v.project_onto(vec3(0.0, -1.0, 0.0)), vec3(0.0, -2.0, 0.0));
+	}
+
+	#[test]
+	fn slices_are_length_checked() {
+		let components = [1.0f32, 2.0, 3.0];
+		assert_eq!(Vec3f32::try_from(&components[..]), Ok(vec3(1.0, 2.0, 3.0)));
+		assert_eq!(Vec2::<f32>::try_from(&components[..]), Err(VectorLengthError { expected: 2, found: 3 }));
+		assert_eq!(Vec3f32::try_from(&components[..2]), Err(VectorLengthError { expected: 3, found: 2 }));
+
+		let mut v = vec3(1, 2, 3);
+		assert_eq!(v.as_slice(), &[1, 2, 3]);
+		v.as_mut_slice()[1] = 5;
+		assert_eq!(<[i32; 3]>::from(v), [1, 5, 3]);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn vectors_and_rects_round_trip_through_serde() {
+		let v = vec3(1.5f32, -2.0, 0.25);
+		let text = serde_json::to_string(&v).unwrap();
+		assert_eq!(text, "[1.5,-2.0,0.25]");
+		assert_eq!(serde_json::from_str::<Vec3f32>(&text).unwrap(), v);
+		assert!(serde_json::from_str::<Vec3f32>("[1.0,2.0]").is_err());
+
+		let rect = Rect { x: 1.0f32, y: 2.0, w: 3.0, h: 4.0 };
+		assert_eq!(serde_json::from_str::<Rect<f32>>(&serde_json::to_string(&rect).unwrap()).unwrap(), rect);
+	}
+}
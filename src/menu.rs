@@ -0,0 +1,163 @@
+use std::cell::{Cell, RefCell};
+
+use crate::{state::State, game, gfx, LoadContext, UpdateContext, error::VoxleError};
+
+/// the start screen, pushes a [`game::GameState`] when "play" is clicked.
+pub struct MenuState {
+	/// see [`game::seed_from_str`], empty for the default seed.
+	seed: RefCell<String>,
+	world_type: Cell<game::WorldType>,
+	/// horizontal, in chunks.
+	render_distance: Cell<i32>,
+	/// set by the ui, the game is created in `update`, since that has the `Gfx`.
+	play: Cell<bool>,
+	game: Option<Box<dyn State>>,
+	/// why the last game couldn't be created, shown under the "play" button.
+	error: Option<String>,
+}
+
+impl MenuState {
+	const CLEAR_COLOR: wgpu::Color = wgpu::Color { r: 0.1, g: 0.12, b: 0.15, a: 1.0 };
+
+	pub fn new(seed: String, world_type: game::WorldType) -> Self {
+		Self {
+			seed: RefCell::new(seed),
+			world_type: Cell::new(world_type),
+			render_distance: Cell::new(4),
+			play: Cell::new(false),
+			game: None,
+			error: None,
+		}
+	}
+
+	/// [`State::update`], with `create_game` called with the seed, world type and render distance
+	/// instead of creating a [`game::GameState`], since that needs a `Gfx`.
+	fn update_with(&mut self, create_game: impl FnOnce(u32, game::WorldType, i32) -> Result<Box<dyn State>, VoxleError>) {
+		if self.play.take() {
+			match create_game(self.seed(), self.world_type.get(), self.render_distance.get()) {
+				Ok(game) => {
+					self.game = Some(game);
+					self.error = None;
+				},
+				Err(error) => {
+					eprintln!("couldn't start the game: {}", error);
+					self.error = Some(error.to_string());
+				},
+			}
+		}
+	}
+
+	fn seed(&self) -> u32 {
+		let seed = self.seed.borrow();
+		if seed.trim().is_empty() {
+			game::DEFAULT_SEED
+		} else {
+			game::seed_from_str(&seed)
+		}
+	}
+}
+
+impl State for MenuState {
+	fn load(&mut self, context: &mut LoadContext) {
+		context.window_mut().capture_cursor(false);
+	}
+
+	fn resume(&mut self, context: &mut LoadContext) {
+		context.window_mut().capture_cursor(false);
+	}
+
+	fn update(&mut self, context: &mut UpdateContext) {
+		self.update_with(|seed, world_type, render_distance| {
+			let mut game = game::GameState::new(context.gfx, seed, world_type)?;
+			game.set_render_distance(render_distance);
+			Ok(Box::new(game))
+		});
+	}
+
+	fn next_state(&mut self) -> Option<Box<dyn State>> {
+		self.game.take()
+	}
+
+	fn render<'a>(&'a self, context: &mut gfx::RenderContext<'a>) {
+		context.clear(Self::CLEAR_COLOR);
+	}
+
+	fn ui(&self, ctx: &egui::Context) {
+		egui::Window::new("voxle")
+			.collapsible(false)
+			.resizable(false)
+			.anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+			.show(ctx, |ui| {
+				ui.horizontal(|ui| {
+					ui.label("seed");
+					ui.text_edit_singleline(&mut *self.seed.borrow_mut());
+				});
+
+				let mut world_type = self.world_type.get();
+				ui.horizontal(|ui| {
+					ui.selectable_value(&mut world_type, game::WorldType::Default, "default");
+					ui.selectable_value(&mut world_type, game::WorldType::Flat, "flat");
+					ui.label("world type");
+				});
+				self.world_type.set(world_type);
+
+				let mut render_distance = self.render_distance.get();
+				ui.add(egui::Slider::new(&mut render_distance, 1..=16).text("render distance"));
+				self.render_distance.set(render_distance);
+
+				if ui.button("play").clicked() {
+					self.play.set(true);
+				}
+				if let Some(error) = &self.error {
+					ui.colored_label(egui::Color32::RED, format!("couldn't start the game: {}", error));
+				}
+			});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// stands in for the game.
+	struct Game;
+
+	impl State for Game {}
+
+	#[test]
+	fn playing_pushes_the_game() {
+		let mut menu = MenuState::new("glacier".to_owned(), game::WorldType::Flat);
+		menu.render_distance.set(6);
+		menu.update_with(|_, _, _| panic!("the game was created before \"play\" was clicked"));
+		assert!(menu.next_state().is_none());
+
+		menu.play.set(true);
+		let mut created = None;
+		menu.update_with(|seed, world_type, render_distance| {
+			created = Some((seed, world_type, render_distance));
+			Ok(Box::new(Game))
+		});
+		assert_eq!(created, Some((game::seed_from_str("glacier"), game::WorldType::Flat, 6)));
+		assert!(menu.next_state().is_some());
+		// it's only pushed once.
+		assert!(menu.next_state().is_none());
+	}
+
+	#[test]
+	fn errors_keep_the_menu_open() {
+		let mut menu = MenuState::new(String::new(), game::WorldType::Default);
+		menu.play.set(true);
+		menu.update_with(|seed, _, _| {
+			assert_eq!(seed, game::DEFAULT_SEED);
+			Err(VoxleError::ShaderNotFound("shaders/chunk.wgsl".into()))
+		});
+		assert!(menu.next_state().is_none());
+		assert!(menu.error.as_ref().is_some_and(|error| error.contains("chunk.wgsl")));
+
+		// cleared once the game starts.
+		menu.play.set(true);
+		menu.update_with(|_, _, _| Ok(Box::new(Game)));
+		assert!(menu.next_state().is_some());
+		assert!(menu.error.is_none());
+	}
+}
@@ -3,28 +3,73 @@ use crate::{gfx, UpdateContext, LoadContext};
 pub trait State {
 	fn load(&mut self, _context: &mut LoadContext) {}
 	fn update(&mut self, _context: &mut UpdateContext) {}
+	/// called a whole number of times per frame, before `update`, with `context.dt` always [`crate::FIXED_DT`].
+	fn fixed_update(&mut self, _context: &mut UpdateContext) {}
 	fn render<'a>(&'a self, _context: &mut gfx::RenderContext<'a>) { }
+	/// called after the surface was resized.
+	fn resize(&mut self, _gfx: &gfx::Gfx) {}
+	/// called when another state is pushed on top of this one in a [`StateStack`].
+	fn pause(&mut self) {}
+	/// called when the state on top of this one was popped off of a [`StateStack`].
+	fn resume(&mut self, _context: &mut LoadContext) {}
+	/// a state to push on top of this one, checked by [`StateStack`] after every update.
+	fn next_state(&mut self) -> Option<Box<dyn State>> { None }
+	/// whether to pop this state off of its [`StateStack`], checked after every update.
+	fn done(&self) -> bool { false }
 	// fn ui(&self, _context: &mut gfx::UiContext) { }
 	fn ui(&self, _context: &egui::Context) { }
 }
 
+/// what happens to the top of a [`StateStack`] after it updated.
+enum Transition {
+	Push(Box<dyn State>),
+	Pop,
+}
+
 pub struct StateStack {
 	stack: Vec<Box<dyn State>>
 }
 
-#[allow(dead_code)] // XXX: use
 impl StateStack {
 	pub fn new() -> Self {
 		Self { stack: Vec::new() }
 	}
 
-	pub fn push(&mut self, state: Box<dyn State>, _context: &LoadContext) {
-		self.stack.push(state)
-		// TODO: self.stack.top.load()?
+	/// loads `state` and pauses the one it covers.
+	pub fn push(&mut self, state: Box<dyn State>, context: &mut LoadContext) {
+		self.push_with(state, |state| state.load(context));
+	}
+
+	/// [`StateStack::push`], with `load` called instead of [`State::load`].
+	fn push_with(&mut self, mut state: Box<dyn State>, load: impl FnOnce(&mut dyn State)) {
+		if let Some(top) = self.stack.last_mut() {
+			top.pause();
+		}
+		load(&mut *state);
+		self.stack.push(state);
 	}
 
-	pub fn pop(&mut self) -> Option<Box<dyn State>> {
-		self.stack.pop()
+	/// resumes the state under the popped one.
+	pub fn pop(&mut self, context: &mut LoadContext) -> Option<Box<dyn State>> {
+		self.pop_with(|state| state.resume(context))
+	}
+
+	/// [`StateStack::pop`], with `resume` called instead of [`State::resume`].
+	fn pop_with(&mut self, resume: impl FnOnce(&mut dyn State)) -> Option<Box<dyn State>> {
+		let state = self.stack.pop();
+		if let Some(top) = self.stack.last_mut() {
+			resume(&mut **top);
+		}
+		state
+	}
+
+	/// see [`State::done`] and [`State::next_state`].
+	fn transition(&mut self) -> Option<Transition> {
+		let top = self.stack.last_mut()?;
+		if top.done() {
+			return Some(Transition::Pop);
+		}
+		top.next_state().map(Transition::Push)
 	}
 }
 
@@ -37,7 +82,20 @@ impl State for StateStack {
 
 	fn update<'a>(&mut self, context: &mut UpdateContext<'a>) {
 		if let Some(top) = self.stack.last_mut() {
-			top.update(context)
+			top.update(context);
+		}
+
+		let context = &mut LoadContext { gfx: context.gfx };
+		match self.transition() {
+			Some(Transition::Push(next)) => self.push(next, context),
+			Some(Transition::Pop) => drop(self.pop(context)),
+			None => {}
+		}
+	}
+
+	fn fixed_update(&mut self, context: &mut UpdateContext) {
+		if let Some(top) = self.stack.last_mut() {
+			top.fixed_update(context)
 		}
 	}
 
@@ -47,9 +105,122 @@ impl State for StateStack {
 		}
 	}
 
+	fn resize(&mut self, gfx: &gfx::Gfx) {
+		if let Some(top) = self.stack.last_mut() {
+			top.resize(gfx)
+		}
+	}
+
+	fn pause(&mut self) {
+		if let Some(top) = self.stack.last_mut() {
+			top.pause()
+		}
+	}
+
+	fn resume(&mut self, context: &mut LoadContext) {
+		if let Some(top) = self.stack.last_mut() {
+			top.resume(context)
+		}
+	}
+
 	fn ui(&self, context: &egui::Context) {
 		if let Some(top) = self.stack.last() {
 			top.ui(context)
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use std::{cell::{Cell, RefCell}, rc::Rc};
+
+	use super::*;
+
+	type Log = Rc<RefCell<Vec<String>>>;
+
+	/// adds the hooks that are called on it to `log`, as "<name> <hook>".
+	struct Stub {
+		name: &'static str,
+		log: Log,
+		next: Option<Box<dyn State>>,
+		done: Rc<Cell<bool>>,
+	}
+
+	impl Stub {
+		fn new(name: &'static str, log: &Log) -> Box<Self> {
+			Box::new(Self { name, log: log.clone(), next: None, done: Default::default() })
+		}
+	}
+
+	/// stands in for the hooks that take a context, since that needs a `Gfx`.
+	fn hook<'a>(log: &'a Log, entry: &'a str) -> impl FnOnce(&mut dyn State) + 'a {
+		move |_| log.borrow_mut().push(entry.to_owned())
+	}
+
+	impl State for Stub {
+		fn pause(&mut self) {
+			self.log.borrow_mut().push(format!("{} pause", self.name));
+		}
+
+		fn next_state(&mut self) -> Option<Box<dyn State>> {
+			self.next.take()
+		}
+
+		fn done(&self) -> bool {
+			self.done.get()
+		}
+	}
+
+	#[test]
+	fn pushed_states_are_loaded() {
+		let log = Log::default();
+		let mut stack = StateStack::new();
+		stack.push_with(Stub::new("menu", &log), hook(&log, "menu load"));
+		assert_eq!(*log.borrow(), ["menu load"]);
+
+		stack.push_with(Stub::new("game", &log), hook(&log, "game load"));
+		// the menu is paused before the game is loaded.
+		assert_eq!(*log.borrow(), ["menu load", "menu pause", "game load"]);
+		assert_eq!(stack.stack.len(), 2);
+	}
+
+	#[test]
+	fn popping_resumes_the_state_below() {
+		let log = Log::default();
+		let mut stack = StateStack::new();
+		stack.push_with(Stub::new("menu", &log), hook(&log, "menu load"));
+		stack.push_with(Stub::new("game", &log), hook(&log, "game load"));
+		log.borrow_mut().clear();
+
+		assert!(stack.pop_with(hook(&log, "menu resume")).is_some());
+		assert_eq!(*log.borrow(), ["menu resume"]);
+		// nothing is left to resume.
+		assert!(stack.pop_with(hook(&log, "nothing resume")).is_some());
+		assert!(stack.pop_with(hook(&log, "nothing resume")).is_none());
+		assert_eq!(*log.borrow(), ["menu resume"]);
+	}
+
+	#[test]
+	fn playing_goes_from_the_menu_to_the_game_and_back() {
+		let log = Log::default();
+		let mut stack = StateStack::new();
+		let mut menu = Stub::new("menu", &log);
+		let game = Stub::new("game", &log);
+		let quit = game.done.clone();
+		assert!(matches!(stack.transition(), None));
+
+		menu.next = Some(game);
+		stack.push_with(menu, hook(&log, "menu load"));
+		// "play" was clicked.
+		let Some(Transition::Push(game)) = stack.transition() else { panic!("the menu didn't push the game") };
+		stack.push_with(game, hook(&log, "game load"));
+		assert!(matches!(stack.transition(), None));
+		assert_eq!(*log.borrow(), ["menu load", "menu pause", "game load"]);
+
+		quit.set(true);
+		assert!(matches!(stack.transition(), Some(Transition::Pop)));
+		stack.pop_with(hook(&log, "menu resume"));
+		assert_eq!(stack.stack.len(), 1);
+		assert_eq!(log.borrow().last().unwrap(), "menu resume");
+	}
+}